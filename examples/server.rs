@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use bluedroid::{
-    gatt_server::{Characteristic, Profile, Service, GLOBAL_GATT_SERVER},
+    gatt_server::{Characteristic, GattServer, Profile, Service},
     utilities::{AttributePermissions, CharacteristicProperties},
     uuid128,
 };
@@ -90,13 +90,15 @@ fn main() {
         .service(&service)
         .build();
 
-    GLOBAL_GATT_SERVER
+    GattServer::take()
+        .expect("Cannot take the GATT server.")
         .lock()
         .profile(profile)
         .device_name("ESP32-GATT-Server")
         .appearance(bluedroid::utilities::Appearance::WristWornPulseOximeter)
         .advertise_service(&service)
-        .start();
+        .start()
+        .expect("Cannot start the GATT server.");
 
     std::thread::spawn(move || {
         let mut counter = 0;
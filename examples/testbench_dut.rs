@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use bluedroid::{
-    gatt_server::{Characteristic, Profile, Service, GLOBAL_GATT_SERVER},
+    gatt_server::{Characteristic, GattServer, Profile, Service},
     utilities::{AttributePermissions, BleUuid, CharacteristicProperties},
 };
 
@@ -103,13 +103,15 @@ fn main() {
         .service(&another_service)
         .build();
 
-    GLOBAL_GATT_SERVER
+    GattServer::take()
+        .expect("Cannot take the GATT server.")
         .lock()
         .profile(profile)
         .device_name("BLUEDROID-DUT")
         .appearance(bluedroid::utilities::Appearance::GenericUnknown)
         .advertise_service(&advertised_service)
-        .start();
+        .start()
+        .expect("Cannot start the GATT server.");
 
     std::thread::spawn(move || {
         let mut counter = 0;
@@ -0,0 +1,62 @@
+//! Measures the lookup of the attributes of registration events by UUID.
+//!
+//! The registration events carry the UUID of the new attribute as an `esp_bt_uuid_t`,
+//! which is matched against the UUIDs of the declared attributes. This example times
+//! that lookup when every event UUID is first converted into a `BleUuid`, and when it is
+//! compared with the declared UUIDs directly.
+
+use std::{hint::black_box, time::Instant};
+
+use bluedroid::utilities::BleUuid;
+use esp_idf_sys::esp_bt_uuid_t;
+use log::info;
+
+/// The number of lookups of each measurement.
+const LOOKUPS: u32 = 100_000;
+
+fn main() {
+    esp_idf_sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // A GATT tree mixing standard and custom attributes.
+    let declared: Vec<BleUuid> = (0..8)
+        .map(|index| BleUuid::from_uuid16(0x2A00 + index))
+        .chain((0..8).map(|index| {
+            BleUuid::from_uuid128_string(format!("6482DF69-A273-4F69-BADC-18583BA9A5{index:02X}"))
+        }))
+        .collect();
+
+    // The events of the registration of every attribute, in declaration order.
+    let events: Vec<esp_bt_uuid_t> = declared.iter().map(|uuid| (*uuid).into()).collect();
+
+    let converted = measure(&events, |event| {
+        let event = BleUuid::from(*event);
+        declared.iter().position(|uuid| *uuid == event)
+    });
+    let direct = measure(&events, |event| {
+        declared.iter().position(|uuid| uuid == event)
+    });
+
+    info!(
+        "Lookup of {} attributes: {} ns with a conversion, {} ns with a direct comparison.",
+        declared.len(),
+        converted,
+        direct
+    );
+
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(10));
+    }
+}
+
+/// Returns the average duration of a lookup, in nanoseconds.
+fn measure(events: &[esp_bt_uuid_t], lookup: impl Fn(&esp_bt_uuid_t) -> Option<usize>) -> u128 {
+    let start = Instant::now();
+
+    for round in 0..LOOKUPS {
+        let event = &events[round as usize % events.len()];
+        black_box(lookup(black_box(event)));
+    }
+
+    start.elapsed().as_nanos() / u128::from(LOOKUPS)
+}
@@ -0,0 +1,278 @@
+//! The initialisation of the Bluetooth stack, shared by the GATT server, the GATT client
+//! and the scanner.
+
+use std::sync::Once;
+
+#[allow(clippy::wildcard_imports)]
+use esp_idf_sys::*;
+use log::{debug, info, warn};
+
+use crate::error::{esp_result, BluedroidError};
+use crate::utilities::log_targets::{GAP, NVS};
+#[cfg(any(feature = "server", feature = "scanner"))]
+use crate::utilities::AddressType;
+
+/// Initialises the NVS, the Bluetooth controller and Bluedroid, unless Bluedroid is already enabled.
+///
+/// The GATT server, the GATT client and the scanner share the Bluetooth stack: the first one
+/// to start initialises it. The GAP callback can only be registered once, so it is registered
+/// here, and dispatches the GAP events to their owner. The GATT callbacks are registered
+/// by the GATT server and the GATT client themselves.
+///
+/// Fails with the first function of the Bluetooth stack that fails. The stack is left
+/// as far as it was initialised, and a later call resumes the initialisation.
+#[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
+pub(crate) fn initialise() -> Result<(), BluedroidError> {
+    static CLASSIC_MEMORY_RELEASE: Once = Once::new();
+
+    if unsafe { esp_bluedroid_get_status() } == esp_bluedroid_status_t_ESP_BLUEDROID_STATUS_ENABLED
+    {
+        debug!(target: GAP, "BLE stack already initialised.");
+        return Ok(());
+    }
+
+    info!(target: GAP, "Initialising BLE stack.");
+
+    // NVS initialisation.
+    unsafe {
+        let result = nvs_flash_init();
+        if result == ESP_ERR_NVS_NO_FREE_PAGES || result == ESP_ERR_NVS_NEW_VERSION_FOUND {
+            warn!(target: NVS, "NVS initialisation failed. Erasing NVS.");
+            esp_result!(nvs_flash_erase())?;
+            esp_result!(nvs_flash_init())?;
+        }
+    }
+
+    #[cfg(esp32)]
+    let mut default_controller_configuration = esp_bt_controller_config_t {
+        controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as _,
+        controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as _,
+        hci_uart_no: BT_HCI_UART_NO_DEFAULT as _,
+        hci_uart_baudrate: BT_HCI_UART_BAUDRATE_DEFAULT,
+        scan_duplicate_mode: SCAN_DUPLICATE_MODE as _,
+        scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as _,
+        normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as _,
+        mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as _,
+        send_adv_reserved_size: SCAN_SEND_ADV_RESERVED_SIZE as _,
+        controller_debug_flag: CONTROLLER_ADV_LOST_DEBUG_BIT,
+        mode: esp_bt_mode_t_ESP_BT_MODE_BLE as _,
+        ble_max_conn: CONFIG_BTDM_CTRL_BLE_MAX_CONN_EFF as _,
+        bt_max_acl_conn: CONFIG_BTDM_CTRL_BR_EDR_MAX_ACL_CONN_EFF as _,
+        bt_sco_datapath: CONFIG_BTDM_CTRL_BR_EDR_SCO_DATA_PATH_EFF as _,
+        auto_latency: BTDM_CTRL_AUTO_LATENCY_EFF != 0,
+        bt_legacy_auth_vs_evt: BTDM_CTRL_LEGACY_AUTH_VENDOR_EVT_EFF != 0,
+        bt_max_sync_conn: CONFIG_BTDM_CTRL_BR_EDR_MAX_SYNC_CONN_EFF as _,
+        ble_sca: CONFIG_BTDM_BLE_SLEEP_CLOCK_ACCURACY_INDEX_EFF as _,
+        pcm_role: CONFIG_BTDM_CTRL_PCM_ROLE_EFF as _,
+        pcm_polar: CONFIG_BTDM_CTRL_PCM_POLAR_EFF as _,
+        hli: BTDM_CTRL_HLI != 0,
+        magic: ESP_BT_CONTROLLER_CONFIG_MAGIC_VAL,
+        #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
+        dup_list_refresh_period: SCAN_DUPL_CACHE_REFRESH_PERIOD as u16,
+    };
+
+    #[cfg(esp32c3)]
+    let mut default_controller_configuration = esp_bt_controller_config_t {
+        magic: ESP_BT_CTRL_CONFIG_MAGIC_VAL,
+        version: ESP_BT_CTRL_CONFIG_VERSION,
+        controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as u16,
+        controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as u8,
+        controller_task_run_cpu: CONFIG_BT_CTRL_PINNED_TO_CORE as u8,
+        bluetooth_mode: CONFIG_BT_CTRL_MODE_EFF as u8,
+        ble_max_act: CONFIG_BT_CTRL_BLE_MAX_ACT_EFF as u8,
+        sleep_mode: CONFIG_BT_CTRL_SLEEP_MODE_EFF as u8,
+        sleep_clock: CONFIG_BT_CTRL_SLEEP_CLOCK_EFF as u8,
+        ble_st_acl_tx_buf_nb: CONFIG_BT_CTRL_BLE_STATIC_ACL_TX_BUF_NB as u8,
+        ble_hw_cca_check: CONFIG_BT_CTRL_HW_CCA_EFF as u8,
+        ble_adv_dup_filt_max: CONFIG_BT_CTRL_ADV_DUP_FILT_MAX as u16,
+        coex_param_en: false,
+        ce_len_type: CONFIG_BT_CTRL_CE_LENGTH_TYPE_EFF as u8,
+        coex_use_hooks: false,
+        hci_tl_type: CONFIG_BT_CTRL_HCI_TL_EFF as u8,
+        hci_tl_funcs: std::ptr::null_mut(),
+        txant_dft: CONFIG_BT_CTRL_TX_ANTENNA_INDEX_EFF as u8,
+        rxant_dft: CONFIG_BT_CTRL_RX_ANTENNA_INDEX_EFF as u8,
+        txpwr_dft: CONFIG_BT_CTRL_DFT_TX_POWER_LEVEL_EFF as u8,
+        #[cfg(any(esp_idf_version = "5.1"))]
+        cfg_mask: CFG_MASK,
+        #[cfg(any(
+            esp_idf_version_full = "4.4.3",
+            esp_idf_version_full = "4.4.4",
+            esp_idf_version = "5.0"
+        ))]
+        cfg_mask: CFG_NASK,
+        scan_duplicate_mode: SCAN_DUPLICATE_MODE as u8,
+        scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as u8,
+        normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as u16,
+        mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as u16,
+        coex_phy_coded_tx_rx_time_limit: CONFIG_BT_CTRL_COEX_PHY_CODED_TX_RX_TLIM_EFF as u8,
+        #[cfg(any(
+            esp_idf_version_full = "4.4.3",
+            esp_idf_version_full = "4.4.4",
+            esp_idf_version = "5.0"
+        ))]
+        hw_target_code: BLE_HW_TARGET_CODE_ESP32C3_CHIP_ECO0,
+        #[cfg(any(esp_idf_version = "5.1"))]
+        hw_target_code: BLE_HW_TARGET_CODE_CHIP_ECO0,
+        slave_ce_len_min: SLAVE_CE_LEN_MIN_DEFAULT as u8,
+        hw_recorrect_en: AGC_RECORRECT_EN as u8,
+        cca_thresh: CONFIG_BT_CTRL_HW_CCA_VAL as u8,
+        #[cfg(any(
+            esp_idf_version_full = "4.4.4",
+            esp_idf_version = "5.0",
+            esp_idf_version = "5.1"
+        ))]
+        scan_backoff_upperlimitmax: BT_CTRL_SCAN_BACKOFF_UPPERLIMITMAX as u16,
+        #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
+        dup_list_refresh_period: DUPL_SCAN_CACHE_REFRESH_PERIOD as u16,
+        #[cfg(any(esp_idf_version = "5.1"))]
+        ble_50_feat_supp: BT_CTRL_50_FEATURE_SUPPORT != 0,
+    };
+
+    #[cfg(esp32s3)]
+    let mut default_controller_configuration = esp_bt_controller_config_t {
+        magic: ESP_BT_CTRL_CONFIG_MAGIC_VAL,
+        version: ESP_BT_CTRL_CONFIG_VERSION,
+        controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as u16,
+        controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as u8,
+        controller_task_run_cpu: CONFIG_BT_CTRL_PINNED_TO_CORE as u8,
+        bluetooth_mode: CONFIG_BT_CTRL_MODE_EFF as u8,
+        ble_max_act: CONFIG_BT_CTRL_BLE_MAX_ACT_EFF as u8,
+        sleep_mode: CONFIG_BT_CTRL_SLEEP_MODE_EFF as u8,
+        sleep_clock: CONFIG_BT_CTRL_SLEEP_CLOCK_EFF as u8,
+        ble_st_acl_tx_buf_nb: CONFIG_BT_CTRL_BLE_STATIC_ACL_TX_BUF_NB as u8,
+        ble_hw_cca_check: CONFIG_BT_CTRL_HW_CCA_EFF as u8,
+        ble_adv_dup_filt_max: CONFIG_BT_CTRL_ADV_DUP_FILT_MAX as u16,
+        coex_param_en: false,
+        ce_len_type: CONFIG_BT_CTRL_CE_LENGTH_TYPE_EFF as u8,
+        coex_use_hooks: false,
+        hci_tl_type: CONFIG_BT_CTRL_HCI_TL_EFF as u8,
+        hci_tl_funcs: std::ptr::null_mut(),
+        txant_dft: CONFIG_BT_CTRL_TX_ANTENNA_INDEX_EFF as u8,
+        rxant_dft: CONFIG_BT_CTRL_RX_ANTENNA_INDEX_EFF as u8,
+        txpwr_dft: CONFIG_BT_CTRL_DFT_TX_POWER_LEVEL_EFF as u8,
+        cfg_mask: CFG_MASK,
+        scan_duplicate_mode: SCAN_DUPLICATE_MODE as u8,
+        scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as u8,
+        normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as u16,
+        mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as u16,
+        coex_phy_coded_tx_rx_time_limit: CONFIG_BT_CTRL_COEX_PHY_CODED_TX_RX_TLIM_EFF as u8,
+
+        #[cfg(any(esp_idf_version = "4.4"))]
+        hw_target_code: BLE_HW_TARGET_CODE_ESP32S3_CHIP_ECO0,
+        #[cfg(esp_idf_version = "5.0")]
+        hw_target_code: BLE_HW_TARGET_CODE_CHIP_ECO0,
+        slave_ce_len_min: SLAVE_CE_LEN_MIN_DEFAULT as u8,
+        hw_recorrect_en: AGC_RECORRECT_EN as u8,
+        cca_thresh: CONFIG_BT_CTRL_HW_CCA_VAL as u8,
+        #[cfg(any(
+            esp_idf_version_full = "4.4.4",
+            esp_idf_version = "5.0",
+            esp_idf_version = "5.1"
+        ))]
+        scan_backoff_upperlimitmax: BT_CTRL_SCAN_BACKOFF_UPPERLIMITMAX as u16,
+        #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
+        dup_list_refresh_period: DUPL_SCAN_CACHE_REFRESH_PERIOD as u16,
+        #[cfg(any(esp_idf_version = "5.0"))]
+        ble_50_feat_supp: EXT_CSD_SEC_FEATURE_SUPPORT != 0,
+    };
+    // The classic Bluetooth memory can only be released once, even if the stack is initialised again.
+    let mut released = Ok(());
+    CLASSIC_MEMORY_RELEASE.call_once(|| unsafe {
+        released = esp_result!(esp_bt_controller_mem_release(
+            esp_bt_mode_t_ESP_BT_MODE_CLASSIC_BT
+        ));
+    });
+    released?;
+
+    // BLE controller initialisation. The controller reads its configuration during the call.
+    // The steps already completed by a failed initialisation are skipped.
+    unsafe {
+        if esp_bt_controller_get_status()
+            == esp_bt_controller_status_t_ESP_BT_CONTROLLER_STATUS_IDLE
+        {
+            esp_result!(esp_bt_controller_init(
+                &mut default_controller_configuration
+            ))?;
+        }
+        if esp_bt_controller_get_status()
+            == esp_bt_controller_status_t_ESP_BT_CONTROLLER_STATUS_INITED
+        {
+            esp_result!(esp_bt_controller_enable(esp_bt_mode_t_ESP_BT_MODE_BLE))?;
+        }
+        if esp_bluedroid_get_status() == esp_bluedroid_status_t_ESP_BLUEDROID_STATUS_UNINITIALIZED {
+            esp_result!(esp_bluedroid_init())?;
+        }
+        esp_result!(esp_bluedroid_enable())?;
+        esp_result!(esp_ble_gap_register_callback(Some(gap_callback)))
+    }
+}
+
+/// Adds a device to the whitelist of the controller, or removes it.
+///
+/// The whitelist is shared by the advertisements of the GATT server and the scanner.
+#[cfg(any(feature = "server", feature = "scanner"))]
+pub(crate) fn update_whitelist(
+    add: bool,
+    mut address: [u8; 6],
+    address_type: AddressType,
+) -> Result<(), BluedroidError> {
+    let address_type = match address_type {
+        AddressType::Public => esp_ble_wl_addr_type_t_BLE_WL_ADDR_TYPE_PUBLIC,
+        AddressType::Random => esp_ble_wl_addr_type_t_BLE_WL_ADDR_TYPE_RANDOM,
+    };
+
+    debug!(
+        target: GAP,
+        "{} {:02X?} the whitelist.",
+        if add { "Adding to" } else { "Removing from" },
+        address
+    );
+    unsafe {
+        esp_result!(esp_ble_gap_update_whitelist(
+            add,
+            address.as_mut_ptr(),
+            address_type
+        ))
+    }
+}
+
+/// Removes every device from the whitelist of the controller.
+#[cfg(any(feature = "server", feature = "scanner"))]
+pub(crate) fn clear_whitelist() -> Result<(), BluedroidError> {
+    debug!(target: GAP, "Clearing the whitelist.");
+    unsafe { esp_result!(esp_ble_gap_clear_whitelist()) }
+}
+
+/// Dispatches the GAP events to the scanner, then to the GATT server.
+#[cfg_attr(
+    not(any(feature = "scanner", feature = "server")),
+    allow(unused_variables)
+)]
+#[cfg_attr(not(feature = "server"), allow(clippy::needless_return))]
+extern "C" fn gap_callback(event: esp_gap_ble_cb_event_t, param: *mut esp_ble_gap_cb_param_t) {
+    // The whitelist is shared, so its events have no single owner.
+    #[cfg(any(feature = "server", feature = "scanner"))]
+    if event == esp_gap_ble_cb_event_t_ESP_GAP_BLE_UPDATE_WHITELIST_COMPLETE_EVT {
+        let param = unsafe { (*param).update_whitelist_cmpl };
+        if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+            debug!(target: GAP, "BLE GAP whitelist updated.");
+        } else {
+            warn!(
+                target: GAP,
+                "BLE GAP whitelist update failed with status 0x{:x}.", param.status
+            );
+        }
+        return;
+    }
+
+    #[cfg(feature = "scanner")]
+    if crate::gap::gap_event_handler(event, param) {
+        return;
+    }
+
+    #[cfg(feature = "server")]
+    crate::gatt_server::GLOBAL_GATT_SERVER
+        .lock()
+        .gap_event_handler(event, param);
+}
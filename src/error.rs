@@ -0,0 +1,93 @@
+//! The errors returned by the fallible operations of the crate.
+
+use esp_idf_sys::{esp_err_t, ESP_OK};
+
+#[cfg(feature = "client")]
+use crate::gatt_client::GattClientError;
+#[cfg(feature = "server")]
+use crate::gatt_server::GattServerError;
+
+/// Calls a Bluetooth stack function, turning its failure into a [`BluedroidError`].
+///
+/// Evaluates to a `Result<(), BluedroidError>`.
+macro_rules! esp_result {
+    ($function:ident($($argument:expr),* $(,)?)) => {
+        $crate::BluedroidError::check(stringify!($function), $function($($argument),*))
+    };
+}
+
+pub(crate) use esp_result;
+
+/// The error returned by all the fallible operations of the crate.
+///
+/// The failures of the Bluetooth stack are returned to the caller instead of aborting
+/// the firmware, so the application can recover from the transient ones, for example
+/// by starting the GATT server again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BluedroidError {
+    /// A function of the Bluetooth stack failed.
+    Stack {
+        /// The name of the function.
+        operation: &'static str,
+        /// The returned error code.
+        code: esp_err_t,
+    },
+    /// An operation of the GATT server failed.
+    #[cfg(feature = "server")]
+    Server(GattServerError),
+    /// An operation of the GATT client failed.
+    #[cfg(feature = "client")]
+    Client(GattClientError),
+}
+
+impl BluedroidError {
+    /// Turns the error code returned by a Bluetooth stack function into a result.
+    pub(crate) fn check(operation: &'static str, code: esp_err_t) -> Result<(), Self> {
+        if code == ESP_OK {
+            Ok(())
+        } else {
+            Err(Self::Stack { operation, code })
+        }
+    }
+}
+
+impl std::fmt::Display for BluedroidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stack { operation, code } => {
+                write!(f, "{operation} failed with error code 0x{code:x}")
+            }
+            #[cfg(feature = "server")]
+            Self::Server(error) => write!(f, "GATT server error: {error}"),
+            #[cfg(feature = "client")]
+            Self::Client(error) => write!(f, "GATT client error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BluedroidError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Stack { .. } => None,
+            #[cfg(feature = "server")]
+            Self::Server(error) => Some(error),
+            #[cfg(feature = "client")]
+            Self::Client(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<GattServerError> for BluedroidError {
+    fn from(error: GattServerError) -> Self {
+        Self::Server(error)
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<GattClientError> for BluedroidError {
+    fn from(error: GattClientError) -> Self {
+        Self::Client(error)
+    }
+}
@@ -0,0 +1,175 @@
+use esp_idf_sys::{
+    esp_ble_evt_type_t_ESP_BLE_EVT_CONN_ADV, esp_ble_evt_type_t_ESP_BLE_EVT_CONN_DIR_ADV,
+    esp_ble_gap_cb_param_t_ble_scan_result_evt_param,
+};
+
+use crate::utilities::{AddressType, BleUuid};
+
+/// The AD types read by the accessors of [`AdvertisementReport`].
+const AD_TYPE_UUID16_INCOMPLETE_LIST: u8 = 0x02;
+const AD_TYPE_UUID16_LIST: u8 = 0x03;
+const AD_TYPE_SHORTENED_NAME: u8 = 0x08;
+const AD_TYPE_COMPLETE_NAME: u8 = 0x09;
+const AD_TYPE_TX_POWER: u8 = 0x0A;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+
+/// An advertisement received by the [`Scanner`](crate::gap::Scanner).
+///
+/// With an active scan, the scan response of the advertiser is merged in the same report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisementReport {
+    address: [u8; 6],
+    address_type: AddressType,
+    rssi: i8,
+    connectable: bool,
+    advertisement: Vec<u8>,
+    scan_response: Vec<u8>,
+}
+
+impl AdvertisementReport {
+    /// Returns the device address of the advertiser.
+    #[must_use]
+    pub const fn address(&self) -> [u8; 6] {
+        self.address
+    }
+
+    /// Returns the type of the device address of the advertiser.
+    #[must_use]
+    pub const fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    /// Returns the signal strength of the advertisement, in dBm.
+    #[must_use]
+    pub const fn rssi(&self) -> i8 {
+        self.rssi
+    }
+
+    /// Returns whether the advertiser accepts connections.
+    #[must_use]
+    pub const fn is_connectable(&self) -> bool {
+        self.connectable
+    }
+
+    /// Returns the raw advertisement data.
+    #[must_use]
+    pub fn advertisement(&self) -> &[u8] {
+        &self.advertisement
+    }
+
+    /// Returns the raw scan response data, empty unless the scan is active.
+    #[must_use]
+    pub fn scan_response(&self) -> &[u8] {
+        &self.scan_response
+    }
+
+    /// Returns the AD structures of the advertisement, then the ones of the scan response,
+    /// as `(AD type, data)` pairs.
+    pub fn structures(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        AdStructures::new(&self.advertisement).chain(AdStructures::new(&self.scan_response))
+    }
+
+    /// Returns the data of the first AD structure of the given type.
+    #[must_use]
+    pub fn find(&self, ad_type: u8) -> Option<&[u8]> {
+        self.structures()
+            .find(|(structure_type, _)| *structure_type == ad_type)
+            .map(|(_, data)| data)
+    }
+
+    /// Returns the complete local name of the advertiser, or its shortened name.
+    #[must_use]
+    pub fn local_name(&self) -> Option<String> {
+        self.find(AD_TYPE_COMPLETE_NAME)
+            .or_else(|| self.find(AD_TYPE_SHORTENED_NAME))
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// Returns the advertised transmission power, in dBm.
+    #[must_use]
+    pub fn tx_power(&self) -> Option<i8> {
+        self.find(AD_TYPE_TX_POWER)
+            .and_then(|data| data.first())
+            .map(|power| i8::from_le_bytes([*power]))
+    }
+
+    /// Returns the manufacturer specific data: the company identifier, then the data.
+    #[must_use]
+    pub fn manufacturer_data(&self) -> Option<(u16, &[u8])> {
+        let data = self.find(AD_TYPE_MANUFACTURER_DATA)?;
+        let (company, data) = data.split_first_chunk::<2>()?;
+
+        Some((u16::from_le_bytes(*company), data))
+    }
+
+    /// Returns the 16-bit service identifiers listed by the advertiser.
+    #[must_use]
+    pub fn service_uuids(&self) -> Vec<BleUuid> {
+        self.structures()
+            .filter(|(ad_type, _)| {
+                *ad_type == AD_TYPE_UUID16_LIST || *ad_type == AD_TYPE_UUID16_INCOMPLETE_LIST
+            })
+            .flat_map(|(_, data)| data.chunks_exact(2))
+            .map(|uuid| BleUuid::from_uuid16(u16::from_le_bytes([uuid[0], uuid[1]])))
+            .collect()
+    }
+}
+
+impl From<esp_ble_gap_cb_param_t_ble_scan_result_evt_param> for AdvertisementReport {
+    #[allow(non_upper_case_globals)]
+    fn from(param: esp_ble_gap_cb_param_t_ble_scan_result_evt_param) -> Self {
+        let advertisement_length = usize::from(param.adv_data_len).min(param.ble_adv.len());
+        let scan_response_length =
+            usize::from(param.scan_rsp_len).min(param.ble_adv.len() - advertisement_length);
+        let (advertisement, rest) = param.ble_adv.split_at(advertisement_length);
+
+        Self {
+            address: param.bda,
+            address_type: param.ble_addr_type.into(),
+            rssi: i8::try_from(param.rssi).unwrap_or(i8::MIN),
+            connectable: matches!(
+                param.ble_evt_type,
+                esp_ble_evt_type_t_ESP_BLE_EVT_CONN_ADV
+                    | esp_ble_evt_type_t_ESP_BLE_EVT_CONN_DIR_ADV
+            ),
+            advertisement: advertisement.to_vec(),
+            scan_response: rest[..scan_response_length].to_vec(),
+        }
+    }
+}
+
+/// An iterator over the AD structures of an advertisement or scan response packet,
+/// as `(AD type, data)` pairs.
+///
+/// The iteration stops at the first empty or truncated structure.
+#[derive(Debug, Clone)]
+pub struct AdStructures<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> AdStructures<'a> {
+    /// Creates an iterator over the AD structures of raw advertisement data.
+    #[must_use]
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for AdStructures<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&length, rest) = self.data.split_first()?;
+        let length = usize::from(length);
+
+        if length == 0 || length > rest.len() {
+            self.data = &[];
+            return None;
+        }
+
+        let (structure, rest) = rest.split_at(length);
+        self.data = rest;
+
+        Some((structure[0], &structure[1..]))
+    }
+}
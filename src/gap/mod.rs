@@ -0,0 +1,15 @@
+//! The GAP roles that do not need a connection.
+//!
+//! For now, this is the observer role: the [`Scanner`] listens to the advertisements of the
+//! devices around, and delivers them as [`AdvertisementReport`]s.
+//!
+//! The GAP events of the Bluetooth stack go through this module first: the ones it does not
+//! handle are passed to the GATT server, if any.
+
+pub use advertisement_report::{AdStructures, AdvertisementReport};
+pub use scanner::Scanner;
+
+pub(crate) use scanner::gap_event_handler;
+
+mod advertisement_report;
+mod scanner;
@@ -0,0 +1,304 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC, esp_ble_gap_cb_param_t, esp_ble_gap_set_scan_params,
+    esp_ble_gap_start_scanning, esp_ble_gap_stop_scanning,
+    esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_DISABLE,
+    esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_ENABLE,
+    esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ALL,
+    esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ONLY_WLST, esp_ble_scan_params_t,
+    esp_ble_scan_type_t_BLE_SCAN_TYPE_ACTIVE, esp_ble_scan_type_t_BLE_SCAN_TYPE_PASSIVE,
+    esp_bt_status_t_ESP_BT_STATUS_SUCCESS, esp_gap_ble_cb_event_t,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_PARAM_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RESULT_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_START_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_STOP_COMPLETE_EVT,
+    esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_CMPL_EVT,
+    esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_RES_EVT,
+};
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+
+use crate::{
+    ble_stack,
+    error::{esp_result, BluedroidError},
+    gap::AdvertisementReport,
+    utilities::{log_targets::GAP, AddressType},
+};
+
+type ReportCallback = dyn Fn(&AdvertisementReport) + Send + Sync;
+
+/// The report callback of the running scan.
+static REPORT_CALLBACK: RwLock<Option<Arc<ReportCallback>>> = RwLock::new(None);
+
+/// The duration of the requested scan, in seconds, applied once its parameters are set.
+static DURATION: AtomicU32 = AtomicU32::new(0);
+
+static SCANNING: AtomicBool = AtomicBool::new(false);
+
+/// The default scan interval and window, in units of 0.625 ms: 50 ms and 30 ms.
+const DEFAULT_INTERVAL: u16 = 0x50;
+const DEFAULT_WINDOW: u16 = 0x30;
+
+/// A scanner of the advertisements of the devices around.
+///
+/// The scanner is configured with a builder, then started: the advertisements are delivered
+/// to the callback set with [`Scanner::on_report`] until the scan times out or is stopped.
+/// Only one scan runs at a time: starting a scan replaces the running one.
+///
+/// ```ignore
+/// Scanner::new()
+///     .active(true)
+///     .on_report(|report| info!("{:02X?}: {:?}", report.address(), report.local_name()))
+///     .start(Some(Duration::from_secs(10)))?;
+/// ```
+#[derive(Clone)]
+pub struct Scanner {
+    parameters: esp_ble_scan_params_t,
+    report_callback: Option<Arc<ReportCallback>>,
+}
+
+impl Scanner {
+    /// Creates a new passive [`Scanner`], scanning 30 ms every 50 ms and filtering
+    /// the duplicate advertisements.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            parameters: esp_ble_scan_params_t {
+                scan_type: esp_ble_scan_type_t_BLE_SCAN_TYPE_PASSIVE,
+                own_addr_type: esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+                scan_filter_policy: esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ALL,
+                scan_interval: DEFAULT_INTERVAL,
+                scan_window: DEFAULT_WINDOW,
+                scan_duplicate: esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_ENABLE,
+            },
+            report_callback: None,
+        }
+    }
+
+    /// Sets whether the scan is active: an active scan requests the scan response
+    /// of the advertisers, and delivers it with their advertisement.
+    pub fn active(&mut self, active: bool) -> &mut Self {
+        self.parameters.scan_type = if active {
+            esp_ble_scan_type_t_BLE_SCAN_TYPE_ACTIVE
+        } else {
+            esp_ble_scan_type_t_BLE_SCAN_TYPE_PASSIVE
+        };
+        self
+    }
+
+    /// Sets the interval between the starts of two scan windows,
+    /// between 2.5 ms and 10.24 s.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.parameters.scan_interval = scan_units(interval);
+        self
+    }
+
+    /// Sets the duration of each scan window, between 2.5 ms and the scan interval.
+    pub fn window(&mut self, window: Duration) -> &mut Self {
+        self.parameters.scan_window = scan_units(window);
+        self
+    }
+
+    /// Sets whether the controller filters the advertisements already reported during the scan.
+    pub fn filter_duplicates(&mut self, filter: bool) -> &mut Self {
+        self.parameters.scan_duplicate = if filter {
+            esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_ENABLE
+        } else {
+            esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_DISABLE
+        };
+        self
+    }
+
+    /// Sets whether only the advertisements of the devices of the whitelist are reported.
+    ///
+    /// The whitelist is managed with [`Scanner::add_to_whitelist`].
+    pub fn whitelist_only(&mut self, enabled: bool) -> &mut Self {
+        self.parameters.scan_filter_policy = if enabled {
+            esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ONLY_WLST
+        } else {
+            esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ALL
+        };
+        self
+    }
+
+    /// Sets the callback receiving the advertisement reports.
+    ///
+    /// The callback is called from the Bluetooth stack's context, and must not block.
+    pub fn on_report<C: Fn(&AdvertisementReport) + Send + Sync + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.report_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Starts scanning for the given duration, or until [`Scanner::stop`] if `None`,
+    /// initialising the Bluetooth stack if needed.
+    ///
+    /// The scan starts once the Bluetooth stack has applied its parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack cannot be initialised,
+    /// or rejects the scan parameters.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn start(&self, duration: Option<Duration>) -> Result<(), BluedroidError> {
+        ble_stack::initialise()?;
+
+        let mut parameters = self.parameters;
+        parameters.scan_window = parameters.scan_window.min(parameters.scan_interval);
+
+        REPORT_CALLBACK.write().clone_from(&self.report_callback);
+        DURATION.store(
+            duration.map_or(0, |duration| {
+                duration.as_secs().clamp(1, u64::from(u32::MAX)) as u32
+            }),
+            Ordering::Release,
+        );
+
+        debug!(
+            target: GAP,
+            "Setting the scan parameters: {:?}, for {:?}.", parameters, duration
+        );
+
+        unsafe { esp_result!(esp_ble_gap_set_scan_params(&mut parameters)) }
+    }
+
+    /// Stops the running scan.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack rejects the request.
+    pub fn stop() -> Result<(), BluedroidError> {
+        debug!(target: GAP, "Stopping the scan.");
+
+        unsafe { esp_result!(esp_ble_gap_stop_scanning()) }
+    }
+
+    /// Adds a device to the whitelist of the controller, initialising the Bluetooth stack
+    /// if needed.
+    ///
+    /// The whitelist is shared with the GATT server.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack cannot be initialised,
+    /// or rejects the request.
+    pub fn add_to_whitelist(
+        address: [u8; 6],
+        address_type: AddressType,
+    ) -> Result<(), BluedroidError> {
+        ble_stack::initialise()?;
+        ble_stack::update_whitelist(true, address, address_type)
+    }
+
+    /// Removes a device from the whitelist of the controller.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack rejects the request.
+    pub fn remove_from_whitelist(
+        address: [u8; 6],
+        address_type: AddressType,
+    ) -> Result<(), BluedroidError> {
+        ble_stack::update_whitelist(false, address, address_type)
+    }
+
+    /// Removes every device from the whitelist of the controller.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack rejects the request.
+    pub fn clear_whitelist() -> Result<(), BluedroidError> {
+        ble_stack::clear_whitelist()
+    }
+
+    /// Returns whether a scan is running.
+    #[must_use]
+    pub fn is_scanning() -> bool {
+        SCANNING.load(Ordering::Acquire)
+    }
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Scanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scanner")
+            .field("parameters", &self.parameters)
+            .field("report_callback", &self.report_callback.is_some())
+            .finish()
+    }
+}
+
+/// Converts a duration to units of 0.625 ms, in the range accepted by the controller.
+#[allow(clippy::cast_possible_truncation)]
+fn scan_units(duration: Duration) -> u16 {
+    (duration.as_micros() / 625).clamp(0x0004, 0x4000) as u16
+}
+
+/// Handles the scan events of the GAP, and returns whether the event was one of them.
+#[allow(non_upper_case_globals)]
+pub(crate) fn gap_event_handler(
+    event: esp_gap_ble_cb_event_t,
+    param: *mut esp_ble_gap_cb_param_t,
+) -> bool {
+    match event {
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_PARAM_SET_COMPLETE_EVT => {
+            let status = unsafe { (*param).scan_param_cmpl.status };
+            if status != esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                warn!(target: GAP, "Setting the scan parameters failed: {}.", status);
+                return true;
+            }
+
+            if let Err(error) =
+                unsafe { esp_result!(esp_ble_gap_start_scanning(DURATION.load(Ordering::Acquire))) }
+            {
+                warn!(target: GAP, "Starting the scan failed: {}.", error);
+            }
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_START_COMPLETE_EVT => {
+            let status = unsafe { (*param).scan_start_cmpl.status };
+            if status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                info!(target: GAP, "Scan started.");
+                SCANNING.store(true, Ordering::Release);
+            } else {
+                warn!(target: GAP, "Starting the scan failed: {}.", status);
+            }
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_STOP_COMPLETE_EVT => {
+            info!(target: GAP, "Scan stopped.");
+            SCANNING.store(false, Ordering::Release);
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RESULT_EVT => {
+            let param = unsafe { (*param).scan_rst };
+            match param.search_evt {
+                esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_RES_EVT => {
+                    let callback = REPORT_CALLBACK.read().clone();
+                    if let Some(callback) = callback {
+                        callback(&AdvertisementReport::from(param));
+                    }
+                }
+                esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_CMPL_EVT => {
+                    info!(target: GAP, "Scan complete.");
+                    SCANNING.store(false, Ordering::Release);
+                }
+                _ => {}
+            }
+        }
+        _ => return false,
+    }
+
+    true
+}
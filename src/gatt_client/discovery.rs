@@ -0,0 +1,111 @@
+use crate::BluedroidError;
+use esp_idf_sys::{
+    esp_ble_gattc_get_all_char, esp_ble_gattc_get_attr_count, esp_ble_gattc_search_service,
+    esp_bt_uuid_t, esp_gatt_db_attr_type_t_ESP_GATT_DB_CHARACTERISTIC, esp_gattc_char_elem_t,
+};
+use log::debug;
+
+use crate::{
+    gatt_client::{
+        error::esp_check, GattClient, GattClientError, RemoteCharacteristic, RemoteService,
+    },
+    utilities::{log_targets::GATTC, BleUuid, Connection},
+};
+
+impl GattClient {
+    /// Discovers the services of a remote server, or only the ones with the given identifier.
+    ///
+    /// The discovered services are delivered to the profile of the connection as a
+    /// [`GattClientEvent::ServicesDiscovered`](crate::gatt_client::GattClientEvent::ServicesDiscovered)
+    /// event, and kept until the next discovery: see [`GattClient::services`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn discover_services(
+        &mut self,
+        connection: Connection,
+        filter: Option<BleUuid>,
+    ) -> Result<(), BluedroidError> {
+        let remote = self
+            .connections
+            .get_mut(&connection.id())
+            .ok_or(GattClientError::NotConnected(connection.id()))?;
+        remote.services.clear();
+        let interface = remote.interface;
+
+        debug!(target: GATTC, "Discovering the services of {}.", connection);
+
+        let mut filter: Option<esp_bt_uuid_t> = filter.map(Into::into);
+        let filter = filter
+            .as_mut()
+            .map_or(std::ptr::null_mut(), std::ptr::from_mut);
+
+        unsafe {
+            esp_check!(esp_ble_gattc_search_service(
+                interface,
+                connection.id(),
+                filter
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the services of a remote server found by the latest discovery.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open.
+    pub fn services(&self, connection: Connection) -> Result<Vec<RemoteService>, BluedroidError> {
+        Ok(self.remote(connection)?.services.clone())
+    }
+
+    /// Returns the characteristics of a discovered service, from the attribute cache
+    /// of the Bluetooth stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Status`] if the attribute cache cannot be queried.
+    pub fn characteristics(
+        &self,
+        connection: Connection,
+        service: &RemoteService,
+    ) -> Result<Vec<RemoteCharacteristic>, BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        let mut count: u16 = 0;
+        GattClientError::check_status("esp_ble_gattc_get_attr_count", unsafe {
+            esp_ble_gattc_get_attr_count(
+                interface,
+                connection.id(),
+                esp_gatt_db_attr_type_t_ESP_GATT_DB_CHARACTERISTIC,
+                service.start_handle,
+                service.end_handle,
+                0,
+                &mut count,
+            )
+        })?;
+
+        let mut elements = vec![esp_gattc_char_elem_t::default(); usize::from(count)];
+        GattClientError::check_status("esp_ble_gattc_get_all_char", unsafe {
+            esp_ble_gattc_get_all_char(
+                interface,
+                connection.id(),
+                service.start_handle,
+                service.end_handle,
+                elements.as_mut_ptr(),
+                &mut count,
+                0,
+            )
+        })?;
+        elements.truncate(usize::from(count));
+
+        Ok(elements
+            .into_iter()
+            .map(RemoteCharacteristic::from)
+            .collect())
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use esp_idf_sys::{esp_err_t, esp_gatt_status_t, esp_gatt_status_t_ESP_GATT_OK, ESP_OK};
+use log::warn;
+use parking_lot::RwLock;
+
+use crate::gatt_client::GattClient;
+use crate::utilities::log_targets::GATTC;
+
+type ErrorCallback = dyn Fn(&GattClientError) + Send + Sync;
+
+/// The function to be called when the GATT client encounters an error in an event.
+static ERROR_CALLBACK: RwLock<Option<Arc<ErrorCallback>>> = RwLock::new(None);
+
+/// Calls a Bluetooth stack function, turning its failure into a [`GattClientError`].
+///
+/// Evaluates to a `Result<(), GattClientError>`.
+macro_rules! esp_check {
+    ($function:ident($($argument:expr),* $(,)?)) => {
+        $crate::gatt_client::GattClientError::check(stringify!($function), $function($($argument),*))
+    };
+}
+
+pub(crate) use esp_check;
+
+/// An error encountered by the GATT client.
+///
+/// The errors of the operations are returned to the caller wrapped in a [`BluedroidError`],
+/// while the failures reported later by the Bluetooth stack are logged and passed to the callback
+/// set with [`GattClient::on_error`].
+///
+/// [`BluedroidError`]: crate::BluedroidError
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GattClientError {
+    /// A function of the Bluetooth stack failed.
+    Stack {
+        /// The name of the function.
+        operation: &'static str,
+        /// The returned error code.
+        code: esp_err_t,
+    },
+    /// The Bluetooth stack reported a failure in an event, or in a query of its attribute cache.
+    Status {
+        /// The name of the event or of the query.
+        operation: &'static str,
+        /// The reported status code.
+        status: esp_gatt_status_t,
+    },
+    /// An event referred to a profile that is not part of the GATT client.
+    UnknownProfile(u16),
+    /// A profile was used before being registered in the Bluetooth stack.
+    NotRegistered(String),
+    /// An operation referred to a connection the GATT client does not know.
+    NotConnected(u16),
+}
+
+impl GattClientError {
+    /// Logs this error and passes it to the error callback.
+    pub(crate) fn report(self) {
+        warn!(target: GATTC, "GATT client error: {}.", self);
+
+        // Do not hold the lock while running the callback.
+        let callback = ERROR_CALLBACK.read().clone();
+        if let Some(callback) = callback {
+            callback(&self);
+        }
+    }
+
+    /// Turns the error code returned by a Bluetooth stack function into a result.
+    pub(crate) fn check(operation: &'static str, code: esp_err_t) -> Result<(), Self> {
+        if code == ESP_OK {
+            Ok(())
+        } else {
+            Err(Self::Stack { operation, code })
+        }
+    }
+
+    /// Turns the status returned by a query of the attribute cache, or carried by an event, into a result.
+    pub(crate) fn check_status(
+        operation: &'static str,
+        status: esp_gatt_status_t,
+    ) -> Result<(), Self> {
+        if status == esp_gatt_status_t_ESP_GATT_OK {
+            Ok(())
+        } else {
+            Err(Self::Status { operation, status })
+        }
+    }
+}
+
+impl std::fmt::Display for GattClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stack { operation, code } => {
+                write!(f, "{operation} failed with error code 0x{code:x}")
+            }
+            Self::Status { operation, status } => {
+                write!(f, "{operation} failed with status 0x{status:x}")
+            }
+            Self::UnknownProfile(identifier) => {
+                write!(f, "unknown profile with identifier 0x{identifier:04x}")
+            }
+            Self::NotRegistered(profile) => write!(f, "{profile} is not registered"),
+            Self::NotConnected(connection) => write!(f, "unknown connection {connection}"),
+        }
+    }
+}
+
+impl std::error::Error for GattClientError {}
+
+impl GattClient {
+    /// Sets the callback for the failures reported by the Bluetooth stack in its events,
+    /// such as a failed profile registration.
+    ///
+    /// # Notes
+    ///
+    /// The callback might be called from the Bluetooth stack's context, so it must not block.
+    pub fn on_error(
+        &mut self,
+        callback: impl Fn(&GattClientError) + Send + Sync + 'static,
+    ) -> &mut Self {
+        *ERROR_CALLBACK.write() = Some(Arc::new(callback));
+        self
+    }
+}
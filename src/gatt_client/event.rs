@@ -0,0 +1,66 @@
+use esp_idf_sys::esp_gatt_status_t;
+
+use crate::{gatt_client::RemoteService, utilities::Connection};
+
+/// An event of a connection of the GATT client, passed to the callback set with
+/// [`Profile::on_event`](crate::gatt_client::Profile::on_event).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum GattClientEvent {
+    /// A connection opened with [`GattClient::connect`](crate::gatt_client::GattClient::connect) is established.
+    Connected(Connection),
+    /// A connection could not be established.
+    ConnectionFailed {
+        /// The address of the remote device.
+        address: [u8; 6],
+        /// The status reported by the Bluetooth stack.
+        status: esp_gatt_status_t,
+    },
+    /// A connection was closed, by either side.
+    Disconnected(Connection),
+    /// A service discovery completed.
+    ServicesDiscovered {
+        /// The connection of the remote server.
+        connection: Connection,
+        /// The discovered services.
+        services: Vec<RemoteService>,
+    },
+    /// A read of a characteristic or descriptor completed.
+    Read {
+        /// The connection of the remote server.
+        connection: Connection,
+        /// The handle of the read attribute.
+        handle: u16,
+        /// The status reported by the remote server.
+        status: esp_gatt_status_t,
+        /// The read value, empty if the read failed.
+        value: Vec<u8>,
+    },
+    /// A write of a characteristic or descriptor completed.
+    Written {
+        /// The connection of the remote server.
+        connection: Connection,
+        /// The handle of the written attribute.
+        handle: u16,
+        /// The status reported by the remote server.
+        status: esp_gatt_status_t,
+    },
+    /// The remote server notified or indicated the value of a characteristic.
+    Notification {
+        /// The connection of the remote server.
+        connection: Connection,
+        /// The handle of the characteristic value.
+        handle: u16,
+        /// The notified value.
+        value: Vec<u8>,
+        /// Whether the value was indicated rather than notified.
+        indication: bool,
+    },
+    /// The MTU of a connection was negotiated.
+    MtuChanged {
+        /// The connection of the remote server.
+        connection: Connection,
+        /// The negotiated MTU, in bytes.
+        mtu: u16,
+    },
+}
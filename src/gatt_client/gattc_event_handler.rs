@@ -0,0 +1,232 @@
+#[allow(clippy::wildcard_imports)]
+use esp_idf_sys::*;
+use log::{debug, info};
+
+use crate::{
+    gatt_client::{
+        Dispatch, GattClient, GattClientError, GattClientEvent, RemoteConnection, RemoteService,
+    },
+    utilities::{forget_mtu, log_targets::GATTC, record_mtu, Connection},
+};
+
+impl GattClient {
+    /// The main GATT client event loop.
+    ///
+    /// Updates the state of the client, and returns the event to deliver to the profile
+    /// of the interface, if any.
+    pub(crate) fn gattc_event_handler(
+        &mut self,
+        event: esp_gattc_cb_event_t,
+        gattc_if: esp_gatt_if_t,
+        param: *mut esp_ble_gattc_cb_param_t,
+    ) -> Option<Dispatch> {
+        #[allow(non_upper_case_globals)]
+        let event = match event {
+            esp_gattc_cb_event_t_ESP_GATTC_REG_EVT => {
+                let param = unsafe { (*param).reg };
+                self.on_reg(gattc_if, param);
+                None
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_OPEN_EVT => {
+                let param = unsafe { (*param).open };
+                Some(self.on_open(gattc_if, param))
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_DISCONNECT_EVT => {
+                let param = unsafe { (*param).disconnect };
+                self.on_disconnect(gattc_if, param)
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_SEARCH_RES_EVT => {
+                let param = unsafe { (*param).search_res };
+                if let Some(remote) = self.connections.get_mut(&param.conn_id) {
+                    remote.services.push(RemoteService {
+                        uuid: param.srvc_id.uuid.into(),
+                        start_handle: param.start_handle,
+                        end_handle: param.end_handle,
+                        primary: param.is_primary,
+                    });
+                }
+                None
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_SEARCH_CMPL_EVT => {
+                let param = unsafe { (*param).search_cmpl };
+                self.on_search_complete(param)
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_READ_CHAR_EVT
+            | esp_gattc_cb_event_t_ESP_GATTC_READ_DESCR_EVT => {
+                let param = unsafe { (*param).read };
+                let value = if param.status == esp_gatt_status_t_ESP_GATT_OK {
+                    unsafe { std::slice::from_raw_parts(param.value, usize::from(param.value_len)) }
+                        .to_vec()
+                } else {
+                    Vec::new()
+                };
+
+                self.connection(param.conn_id)
+                    .map(|connection| GattClientEvent::Read {
+                        connection,
+                        handle: param.handle,
+                        status: param.status,
+                        value,
+                    })
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_WRITE_CHAR_EVT
+            | esp_gattc_cb_event_t_ESP_GATTC_WRITE_DESCR_EVT => {
+                let param = unsafe { (*param).write };
+                self.connection(param.conn_id)
+                    .map(|connection| GattClientEvent::Written {
+                        connection,
+                        handle: param.handle,
+                        status: param.status,
+                    })
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_NOTIFY_EVT => {
+                let param = unsafe { (*param).notify };
+                let value = unsafe {
+                    std::slice::from_raw_parts(param.value, usize::from(param.value_len))
+                }
+                .to_vec();
+
+                self.connection(param.conn_id)
+                    .map(|connection| GattClientEvent::Notification {
+                        connection,
+                        handle: param.handle,
+                        value,
+                        indication: !param.is_notify,
+                    })
+            }
+            esp_gattc_cb_event_t_ESP_GATTC_CFG_MTU_EVT => {
+                let param = unsafe { (*param).cfg_mtu };
+                if let Err(error) =
+                    GattClientError::check_status("ESP_GATTC_CFG_MTU_EVT", param.status)
+                {
+                    error.report();
+                    return None;
+                }
+
+                let remote = self.connections.get_mut(&param.conn_id)?;
+                record_mtu(param.conn_id, param.mtu);
+                remote.connection.mtu = param.mtu;
+
+                Some(GattClientEvent::MtuChanged {
+                    connection: remote.connection,
+                    mtu: param.mtu,
+                })
+            }
+            _ => {
+                debug!(target: GATTC, "Unhandled GATT client event: {}.", event);
+                None
+            }
+        }?;
+
+        let callback = self.get_profile(gattc_if)?.read().event_callback.clone()?;
+
+        Some((callback, event))
+    }
+
+    fn on_reg(
+        &mut self,
+        gattc_if: esp_gatt_if_t,
+        param: esp_ble_gattc_cb_param_t_gattc_reg_evt_param,
+    ) {
+        let Some(profile) = self
+            .profiles
+            .iter()
+            .find(|profile| profile.read().identifier == param.app_id)
+        else {
+            GattClientError::UnknownProfile(param.app_id).report();
+            return;
+        };
+
+        if let Err(error) = GattClientError::check_status("ESP_GATTC_REG_EVT", param.status) {
+            error.report();
+            return;
+        }
+
+        debug!(target: GATTC, "{} registered on interface {}.", profile.read(), gattc_if);
+        profile.write().interface = Some(gattc_if);
+    }
+
+    fn on_open(
+        &mut self,
+        gattc_if: esp_gatt_if_t,
+        param: esp_ble_gattc_cb_param_t_gattc_open_evt_param,
+    ) -> GattClientEvent {
+        if param.status != esp_gatt_status_t_ESP_GATT_OK {
+            info!(
+                target: GATTC,
+                "Connection to {:02X?} failed with status 0x{:x}.",
+                param.remote_bda, param.status
+            );
+
+            return GattClientEvent::ConnectionFailed {
+                address: param.remote_bda,
+                status: param.status,
+            };
+        }
+
+        let connection = Connection::from(param);
+        info!(target: GATTC, "Connected to {}.", connection);
+
+        self.connections.insert(
+            param.conn_id,
+            RemoteConnection {
+                connection,
+                interface: gattc_if,
+                services: Vec::new(),
+            },
+        );
+
+        GattClientEvent::Connected(connection)
+    }
+
+    fn on_disconnect(
+        &mut self,
+        gattc_if: esp_gatt_if_t,
+        param: esp_ble_gattc_cb_param_t_gattc_disconnect_evt_param,
+    ) -> Option<GattClientEvent> {
+        // The event is sent to every interface: only the one that opened the connection handles it.
+        if self.connections.get(&param.conn_id)?.interface != gattc_if {
+            return None;
+        }
+
+        let remote = self.connections.remove(&param.conn_id)?;
+        forget_mtu(param.conn_id);
+        info!(
+            target: GATTC,
+            "Disconnected from {} (reason 0x{:x}).",
+            remote.connection, param.reason
+        );
+
+        Some(GattClientEvent::Disconnected(remote.connection))
+    }
+
+    fn on_search_complete(
+        &self,
+        param: esp_ble_gattc_cb_param_t_gattc_search_cmpl_evt_param,
+    ) -> Option<GattClientEvent> {
+        if let Err(error) = GattClientError::check_status("ESP_GATTC_SEARCH_CMPL_EVT", param.status)
+        {
+            error.report();
+            return None;
+        }
+
+        let remote = self.connections.get(&param.conn_id)?;
+        debug!(
+            target: GATTC,
+            "Discovered {} services on {}.",
+            remote.services.len(),
+            remote.connection
+        );
+
+        Some(GattClientEvent::ServicesDiscovered {
+            connection: remote.connection,
+            services: remote.services.clone(),
+        })
+    }
+
+    fn connection(&self, conn_id: u16) -> Option<Connection> {
+        self.connections
+            .get(&conn_id)
+            .map(|remote| remote.connection)
+    }
+}
@@ -0,0 +1,228 @@
+//! The GATT client.
+//!
+//! The GATT client connects to remote GATT servers, discovers their services and characteristics,
+//! reads and writes their values, and subscribes to their notifications.
+//!
+//! Like the GATT server, it is a singleton, organised in profiles:
+//! each [`Profile`] is registered as an application in the Bluetooth stack, and receives
+//! the events of the connections it opened. The operations complete asynchronously:
+//! their results are delivered as [`GattClientEvent`]s.
+
+use std::collections::HashMap;
+
+#[allow(clippy::wildcard_imports)]
+use esp_idf_sys::*;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::{
+    ble_stack,
+    error::esp_result,
+    utilities::{log_targets::GATTC, AddressType, Connection},
+    BluedroidError,
+};
+
+pub use error::GattClientError;
+pub use event::GattClientEvent;
+pub use profile::{LockedProfile, Profile};
+pub use remote::{RemoteCharacteristic, RemoteService};
+
+use error::esp_check;
+use profile::EventCallback;
+
+mod discovery;
+mod error;
+mod event;
+mod gattc_event_handler;
+mod operations;
+mod profile;
+mod remote;
+
+lazy_static! {
+    /// The GATT client singleton.
+    pub static ref GLOBAL_GATT_CLIENT: Mutex<GattClient> = Mutex::new(GattClient {
+        profiles: Vec::new(),
+        started: false,
+        connections: HashMap::new(),
+    });
+}
+
+/// Represents a GATT client.
+///
+/// This is a singleton, and can be accessed via the [`GLOBAL_GATT_CLIENT`] static.
+///
+/// # Notes
+///
+/// The GATT client and the GATT server share the Bluetooth stack: whichever starts first
+/// initialises it. The supervisor of the GATT server only restarts the server,
+/// so the GATT client must be started again after a recovery.
+pub struct GattClient {
+    profiles: Vec<LockedProfile>,
+    started: bool,
+    /// The open connections, by connection identifier.
+    connections: HashMap<u16, RemoteConnection>,
+}
+
+/// An open connection of the GATT client.
+struct RemoteConnection {
+    connection: Connection,
+    /// The interface of the profile that opened the connection.
+    interface: u8,
+    /// The services found by the latest discovery.
+    services: Vec<RemoteService>,
+}
+
+unsafe impl Send for GattClient {}
+
+impl GattClient {
+    /// Starts the [`GattClient`], registering its profiles in the Bluetooth stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the Bluetooth stack cannot be initialised.
+    /// The client is not started then, and can be started again.
+    pub fn start(&mut self) -> Result<(), BluedroidError> {
+        if self.started {
+            warn!(target: GATTC, "GATT client already started.");
+            return Ok(());
+        }
+
+        ble_stack::initialise()?;
+        unsafe {
+            esp_result!(esp_ble_gattc_register_callback(Some(
+                Self::default_gattc_callback
+            )))?;
+        }
+
+        self.started = true;
+        for profile in &self.profiles {
+            Self::register_profile(&profile.read());
+        }
+
+        Ok(())
+    }
+
+    /// Adds a [`Profile`] to the [`GattClient`].
+    ///
+    /// If the client is already started, the profile is registered right away.
+    pub fn profile(&mut self, profile: &LockedProfile) -> &mut Self {
+        if self.started {
+            Self::register_profile(&profile.read());
+        }
+
+        self.profiles.push(profile.clone());
+        self
+    }
+
+    /// Opens a connection to the remote device with the given address, on behalf of a profile.
+    ///
+    /// The outcome is delivered to the profile as a [`GattClientEvent::Connected`]
+    /// or a [`GattClientEvent::ConnectionFailed`] event.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotRegistered`] if the profile is not registered yet,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn connect(
+        &mut self,
+        profile: &LockedProfile,
+        mut address: [u8; 6],
+        address_type: AddressType,
+    ) -> Result<(), BluedroidError> {
+        let profile = profile.read();
+        let Some(interface) = profile.interface else {
+            return Err(GattClientError::NotRegistered(profile.to_string()).into());
+        };
+
+        debug!(
+            target: GATTC,
+            "Connecting to {:02X?} on behalf of {}.",
+            address, profile
+        );
+
+        unsafe {
+            esp_check!(esp_ble_gattc_open(
+                interface,
+                address.as_mut_ptr(),
+                address_type.into(),
+                true
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes a connection of the GATT client.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn disconnect(&mut self, connection: Connection) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        unsafe { esp_check!(esp_ble_gattc_close(interface, connection.id()))? };
+
+        Ok(())
+    }
+
+    /// Returns the open connections of the GATT client.
+    #[must_use]
+    pub fn connections(&self) -> Vec<Connection> {
+        self.connections
+            .values()
+            .map(|remote| remote.connection)
+            .collect()
+    }
+
+    fn register_profile(profile: &Profile) {
+        debug!(target: GATTC, "Registering {}.", profile);
+
+        if let Err(error) = unsafe { esp_check!(esp_ble_gattc_app_register(profile.identifier)) } {
+            error.report();
+        }
+    }
+
+    fn remote(&self, connection: Connection) -> Result<&RemoteConnection, GattClientError> {
+        self.connections
+            .get(&connection.id())
+            .ok_or(GattClientError::NotConnected(connection.id()))
+    }
+
+    fn get_profile(&self, interface: u8) -> Option<LockedProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.read().interface == Some(interface))
+            .cloned()
+    }
+
+    /// Calls the global client's GATT event callback, then the event callback of the profile,
+    /// once the client is unlocked.
+    extern "C" fn default_gattc_callback(
+        event: esp_gattc_cb_event_t,
+        gattc_if: esp_gatt_if_t,
+        param: *mut esp_ble_gattc_cb_param_t,
+    ) {
+        let dispatched = GLOBAL_GATT_CLIENT
+            .lock()
+            .gattc_event_handler(event, gattc_if, param);
+
+        if let Some((callback, event)) = dispatched {
+            callback(&event);
+        }
+    }
+}
+
+impl std::fmt::Debug for GattClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GattClient")
+            .field("profiles", &self.profiles)
+            .field("started", &self.started)
+            .field("connections", &self.connections())
+            .finish()
+    }
+}
+
+/// The profile event callback to call once the GATT client is unlocked, with its event.
+type Dispatch = (std::sync::Arc<EventCallback>, GattClientEvent);
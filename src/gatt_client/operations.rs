@@ -0,0 +1,248 @@
+use crate::BluedroidError;
+use esp_idf_sys::{
+    esp_ble_gattc_get_descr_by_char_handle, esp_ble_gattc_read_char, esp_ble_gattc_read_char_descr,
+    esp_ble_gattc_register_for_notify, esp_ble_gattc_send_mtu_req,
+    esp_ble_gattc_unregister_for_notify, esp_ble_gattc_write_char, esp_ble_gattc_write_char_descr,
+    esp_gatt_auth_req_t_ESP_GATT_AUTH_REQ_NONE, esp_gatt_write_type_t_ESP_GATT_WRITE_TYPE_NO_RSP,
+    esp_gatt_write_type_t_ESP_GATT_WRITE_TYPE_RSP, esp_gattc_descr_elem_t,
+};
+use log::debug;
+
+use crate::{
+    gatt_client::{error::esp_check, GattClient, GattClientError, RemoteCharacteristic},
+    utilities::{log_targets::GATTC, sig::descriptors, Connection},
+};
+
+impl GattClient {
+    /// Reads the value of a characteristic of a remote server.
+    ///
+    /// The value is delivered to the profile of the connection as a
+    /// [`GattClientEvent::Read`](crate::gatt_client::GattClientEvent::Read) event.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn read(&self, connection: Connection, handle: u16) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        unsafe {
+            esp_check!(esp_ble_gattc_read_char(
+                interface,
+                connection.id(),
+                handle,
+                esp_gatt_auth_req_t_ESP_GATT_AUTH_REQ_NONE
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the value of a descriptor of a remote server.
+    ///
+    /// The value is delivered like the one of a characteristic, see [`GattClient::read`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn read_descriptor(
+        &self,
+        connection: Connection,
+        handle: u16,
+    ) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        unsafe {
+            esp_check!(esp_ble_gattc_read_char_descr(
+                interface,
+                connection.id(),
+                handle,
+                esp_gatt_auth_req_t_ESP_GATT_AUTH_REQ_NONE
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the value of a characteristic of a remote server, with a write request
+    /// or a write command.
+    ///
+    /// The outcome of a write request is delivered to the profile of the connection as a
+    /// [`GattClientEvent::Written`](crate::gatt_client::GattClientEvent::Written) event.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write(
+        &self,
+        connection: Connection,
+        handle: u16,
+        value: &[u8],
+        with_response: bool,
+    ) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+        let write_type = if with_response {
+            esp_gatt_write_type_t_ESP_GATT_WRITE_TYPE_RSP
+        } else {
+            esp_gatt_write_type_t_ESP_GATT_WRITE_TYPE_NO_RSP
+        };
+
+        // The stack copies the value before returning.
+        let mut value = value.to_vec();
+        unsafe {
+            esp_check!(esp_ble_gattc_write_char(
+                interface,
+                connection.id(),
+                handle,
+                value.len() as u16,
+                value.as_mut_ptr(),
+                write_type,
+                esp_gatt_auth_req_t_ESP_GATT_AUTH_REQ_NONE
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the value of a descriptor of a remote server, with a write request.
+    ///
+    /// The outcome is delivered like the one of a characteristic, see [`GattClient::write`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_descriptor(
+        &self,
+        connection: Connection,
+        handle: u16,
+        value: &[u8],
+    ) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        let mut value = value.to_vec();
+        unsafe {
+            esp_check!(esp_ble_gattc_write_char_descr(
+                interface,
+                connection.id(),
+                handle,
+                value.len() as u16,
+                value.as_mut_ptr(),
+                esp_gatt_write_type_t_ESP_GATT_WRITE_TYPE_RSP,
+                esp_gatt_auth_req_t_ESP_GATT_AUTH_REQ_NONE
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Subscribes to the notifications of a characteristic of a remote server,
+    /// or to its indications if it does not notify.
+    ///
+    /// The characteristic's CCCD is written on the remote server, and the values are delivered
+    /// to the profile of the connection as
+    /// [`GattClientEvent::Notification`](crate::gatt_client::GattClientEvent::Notification) events.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// a [`GattClientError::Status`] if the characteristic has no CCCD,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn subscribe(
+        &self,
+        connection: Connection,
+        characteristic: &RemoteCharacteristic,
+    ) -> Result<(), BluedroidError> {
+        let value: u16 = if characteristic.can_notify() {
+            0x0001
+        } else {
+            0x0002
+        };
+        self.configure_subscription(connection, characteristic, value)
+    }
+
+    /// Unsubscribes from the notifications and indications of a characteristic of a remote server.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// a [`GattClientError::Status`] if the characteristic has no CCCD,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn unsubscribe(
+        &self,
+        connection: Connection,
+        characteristic: &RemoteCharacteristic,
+    ) -> Result<(), BluedroidError> {
+        self.configure_subscription(connection, characteristic, 0x0000)
+    }
+
+    /// Asks the remote server to negotiate the MTU of a connection.
+    ///
+    /// The negotiated MTU is delivered to the profile of the connection as a
+    /// [`GattClientEvent::MtuChanged`](crate::gatt_client::GattClientEvent::MtuChanged) event.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattClientError::NotConnected`] if the connection is not open,
+    /// or a [`GattClientError::Stack`] if the Bluetooth stack rejects the request.
+    pub fn request_mtu(&self, connection: Connection) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+
+        unsafe { esp_check!(esp_ble_gattc_send_mtu_req(interface, connection.id()))? };
+
+        Ok(())
+    }
+
+    /// Registers the characteristic for notifications in the Bluetooth stack,
+    /// then writes its CCCD on the remote server.
+    fn configure_subscription(
+        &self,
+        connection: Connection,
+        characteristic: &RemoteCharacteristic,
+        value: u16,
+    ) -> Result<(), BluedroidError> {
+        let interface = self.remote(connection)?.interface;
+        let mut address = connection.remote_bda();
+
+        debug!(
+            target: GATTC,
+            "Setting the CCCD of {} on {} to {:04X}.",
+            characteristic.uuid, connection, value
+        );
+
+        let mut cccd = esp_gattc_descr_elem_t::default();
+        let mut count: u16 = 1;
+        GattClientError::check_status("esp_ble_gattc_get_descr_by_char_handle", unsafe {
+            esp_ble_gattc_get_descr_by_char_handle(
+                interface,
+                connection.id(),
+                characteristic.handle,
+                descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION.into(),
+                &mut cccd,
+                &mut count,
+            )
+        })?;
+
+        unsafe {
+            if value == 0 {
+                esp_check!(esp_ble_gattc_unregister_for_notify(
+                    interface,
+                    address.as_mut_ptr(),
+                    characteristic.handle
+                ))?;
+            } else {
+                esp_check!(esp_ble_gattc_register_for_notify(
+                    interface,
+                    address.as_mut_ptr(),
+                    characteristic.handle
+                ))?;
+            }
+        }
+
+        self.write_descriptor(connection, cccd.handle, &value.to_le_bytes())
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::gatt_client::GattClientEvent;
+
+/// Shorthand for our locked profiles that are returned everywhere
+pub type LockedProfile = Arc<RwLock<Profile>>;
+
+pub(crate) type EventCallback = dyn Fn(&GattClientEvent) + Send + Sync;
+
+/// Represents a GATT client profile.
+///
+/// Each profile is registered as a separate application in the Bluetooth stack,
+/// with its own interface: the connections it opens, and their events, belong to it.
+#[derive(Clone)]
+pub struct Profile {
+    pub(crate) name: Option<String>,
+    pub(crate) identifier: u16,
+    pub(crate) interface: Option<u8>,
+    pub(crate) event_callback: Option<Arc<EventCallback>>,
+}
+
+impl Profile {
+    /// Creates a new [`Profile`].
+    #[must_use]
+    pub const fn new(identifier: u16) -> Self {
+        Self {
+            name: None,
+            identifier,
+            interface: None,
+            event_callback: None,
+        }
+    }
+
+    /// Sets the name of the [`Profile`].
+    ///
+    /// This name is only used for debugging purposes.
+    pub fn name<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the callback receiving the events of the connections of this [`Profile`].
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, so it must not block.
+    /// The GATT client is not locked during the call: the callback can start new operations,
+    /// such as discovering the services of a new connection.
+    pub fn on_event(
+        &mut self,
+        callback: impl Fn(&GattClientEvent) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.event_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns the application identifier of the [`Profile`].
+    #[must_use]
+    pub const fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    /// Returns the GATT interface assigned to the [`Profile`], once registered in the Bluetooth stack.
+    #[must_use]
+    pub const fn interface(&self) -> Option<u8> {
+        self.interface
+    }
+
+    /// Returns a reference to the built [`Profile`] behind an `Arc` and an `RwLock`.
+    ///
+    /// The returned value can be passed to any function of this crate that expects a [`Profile`].
+    /// It can be used in different threads, because it is protected by an `RwLock`.
+    #[must_use]
+    pub fn build(&self) -> LockedProfile {
+        Arc::new(RwLock::new(self.clone()))
+    }
+}
+
+impl std::fmt::Debug for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profile")
+            .field("name", &self.name)
+            .field("identifier", &self.identifier)
+            .field("interface", &self.interface)
+            .field("event_callback", &self.event_callback.is_some())
+            .finish()
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (0x{:04x})",
+            self.name
+                .clone()
+                .unwrap_or_else(|| "Unnamed profile".to_string()),
+            self.identifier,
+        )
+    }
+}
@@ -0,0 +1,109 @@
+use esp_idf_sys::{
+    esp_gatt_char_prop_t, esp_gattc_char_elem_t, ESP_GATT_CHAR_PROP_BIT_INDICATE,
+    ESP_GATT_CHAR_PROP_BIT_NOTIFY, ESP_GATT_CHAR_PROP_BIT_READ, ESP_GATT_CHAR_PROP_BIT_WRITE,
+    ESP_GATT_CHAR_PROP_BIT_WRITE_NR,
+};
+
+use crate::utilities::BleUuid;
+
+/// A service discovered on a remote GATT server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteService {
+    pub(crate) uuid: BleUuid,
+    pub(crate) start_handle: u16,
+    pub(crate) end_handle: u16,
+    pub(crate) primary: bool,
+}
+
+impl RemoteService {
+    /// Returns the identifier of the service.
+    #[must_use]
+    pub const fn uuid(&self) -> BleUuid {
+        self.uuid
+    }
+
+    /// Returns the handle of the service declaration, the first of the service.
+    #[must_use]
+    pub const fn start_handle(&self) -> u16 {
+        self.start_handle
+    }
+
+    /// Returns the last handle of the service.
+    #[must_use]
+    pub const fn end_handle(&self) -> u16 {
+        self.end_handle
+    }
+
+    /// Returns whether the service is a primary service.
+    #[must_use]
+    pub const fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// A characteristic of a [`RemoteService`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteCharacteristic {
+    pub(crate) uuid: BleUuid,
+    pub(crate) handle: u16,
+    pub(crate) properties: esp_gatt_char_prop_t,
+}
+
+impl RemoteCharacteristic {
+    /// Returns the identifier of the characteristic.
+    #[must_use]
+    pub const fn uuid(&self) -> BleUuid {
+        self.uuid
+    }
+
+    /// Returns the handle of the characteristic value, used to read and write it.
+    #[must_use]
+    pub const fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    /// Returns whether the characteristic announces the "read" property.
+    #[must_use]
+    pub const fn can_read(&self) -> bool {
+        self.has_property(ESP_GATT_CHAR_PROP_BIT_READ)
+    }
+
+    /// Returns whether the characteristic announces the "write" property.
+    #[must_use]
+    pub const fn can_write(&self) -> bool {
+        self.has_property(ESP_GATT_CHAR_PROP_BIT_WRITE)
+    }
+
+    /// Returns whether the characteristic announces the "write without response" property.
+    #[must_use]
+    pub const fn can_write_without_response(&self) -> bool {
+        self.has_property(ESP_GATT_CHAR_PROP_BIT_WRITE_NR)
+    }
+
+    /// Returns whether the characteristic announces the "notify" property.
+    #[must_use]
+    pub const fn can_notify(&self) -> bool {
+        self.has_property(ESP_GATT_CHAR_PROP_BIT_NOTIFY)
+    }
+
+    /// Returns whether the characteristic announces the "indicate" property.
+    #[must_use]
+    pub const fn can_indicate(&self) -> bool {
+        self.has_property(ESP_GATT_CHAR_PROP_BIT_INDICATE)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    const fn has_property(&self, property: u32) -> bool {
+        self.properties & property as esp_gatt_char_prop_t != 0
+    }
+}
+
+impl From<esp_gattc_char_elem_t> for RemoteCharacteristic {
+    fn from(element: esp_gattc_char_elem_t) -> Self {
+        Self {
+            uuid: element.uuid.into(),
+            handle: element.char_handle,
+            properties: element.properties,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use esp_idf_sys::{esp_ble_gap_start_advertising, esp_ble_gap_stop_advertising};
+use log::info;
+
+use crate::gatt_server::{error::esp_report, worker, GattServer, GLOBAL_GATT_SERVER};
+use crate::utilities::log_targets::GAP;
+
+/// How often the idle timeout of the policy is checked.
+const CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// The bounds of the advertising interval, in units of 0.625 ms.
+const MIN_INTERVAL: u64 = 0x0020;
+const MAX_INTERVAL: u64 = 0x4000;
+
+/// Identifies the running policy: the checks of replaced policies stop once it changes.
+static POLICY: AtomicU32 = AtomicU32::new(0);
+
+/// A policy adapting the advertising of the GATT server to its state and its battery,
+/// set with [`GattServer::adaptive_advertising`].
+///
+/// The server advertises at the fast interval, then widens to the slow interval once it went
+/// `idle_timeout` without connections. It goes back to the fast interval when a client
+/// disconnects, or when the application calls [`GattServer::wake_advertising`],
+/// for example after a button press or when the charger is attached.
+///
+/// Advertising can also pause while the battery level reported with
+/// [`GattServer::battery_level`] is below a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvertisingPolicy {
+    fast_interval: Duration,
+    slow_interval: Duration,
+    idle_timeout: Duration,
+    pause_below: Option<u8>,
+}
+
+impl AdvertisingPolicy {
+    /// Creates a new [`AdvertisingPolicy`].
+    ///
+    /// The intervals are clamped between 20 ms and 10.24 s, the bounds of the specification.
+    #[must_use]
+    pub const fn new(
+        fast_interval: Duration,
+        slow_interval: Duration,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self {
+            fast_interval,
+            slow_interval,
+            idle_timeout,
+            pause_below: None,
+        }
+    }
+
+    /// Pauses advertising while the battery level is below `percent`.
+    #[must_use]
+    pub const fn pause_below(mut self, percent: u8) -> Self {
+        self.pause_below = Some(percent);
+        self
+    }
+}
+
+/// The advertising mode selected by an [`AdvertisingPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AdvertisingMode {
+    Fast,
+    Slow,
+    Paused,
+}
+
+/// The state of the adaptive advertising of the GATT server.
+#[derive(Debug)]
+pub(crate) struct AdaptiveAdvertising {
+    policy: AdvertisingPolicy,
+    mode: AdvertisingMode,
+    last_activity: Instant,
+}
+
+impl GattServer {
+    /// Adapts the advertising of the GATT server with the given [`AdvertisingPolicy`],
+    /// replacing the previous one, if any.
+    ///
+    /// The policy is evaluated on the worker thread of the GATT server.
+    pub fn adaptive_advertising(&mut self, policy: AdvertisingPolicy) -> &mut Self {
+        let generation = POLICY.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+
+        self.adaptive_advertising = Some(AdaptiveAdvertising {
+            policy,
+            mode: AdvertisingMode::Fast,
+            last_activity: Instant::now(),
+        });
+        self.apply_advertising_mode(AdvertisingMode::Fast);
+
+        worker::schedule_periodic(CHECK_PERIOD, move || {
+            if POLICY.load(Ordering::Relaxed) != generation {
+                return false;
+            }
+
+            GLOBAL_GATT_SERVER.lock().check_idle_timeout();
+            true
+        });
+
+        self
+    }
+
+    /// Narrows the advertising interval back to the fast interval of the [`AdvertisingPolicy`],
+    /// for example after a button press or when the charger is attached.
+    pub fn wake_advertising(&mut self) -> &mut Self {
+        let Some(state) = self.adaptive_advertising.as_mut() else {
+            return self;
+        };
+
+        state.last_activity = Instant::now();
+        if state.mode == AdvertisingMode::Slow {
+            self.apply_advertising_mode(AdvertisingMode::Fast);
+        }
+
+        self
+    }
+
+    /// Reports the battery level, in percent, to the [`AdvertisingPolicy`].
+    ///
+    /// Advertising pauses while the level is below the threshold of the policy,
+    /// and resumes at the fast interval once it is back above.
+    pub fn battery_level(&mut self, percent: u8) -> &mut Self {
+        let Some(state) = self.adaptive_advertising.as_mut() else {
+            return self;
+        };
+        let Some(threshold) = state.policy.pause_below else {
+            return self;
+        };
+
+        if percent < threshold && state.mode != AdvertisingMode::Paused {
+            info!(
+                target: GAP,
+                "Battery level {}% below {}%, pausing advertising.",
+                percent,
+                threshold
+            );
+            self.apply_advertising_mode(AdvertisingMode::Paused);
+        } else if percent >= threshold && state.mode == AdvertisingMode::Paused {
+            info!(
+                target: GAP,
+                "Battery level {}% back above {}%, resuming advertising.",
+                percent,
+                threshold
+            );
+            state.last_activity = Instant::now();
+            self.apply_advertising_mode(AdvertisingMode::Fast);
+        }
+
+        self
+    }
+
+    /// Returns whether the [`AdvertisingPolicy`] paused advertising.
+    pub(crate) fn advertising_paused(&self) -> bool {
+        self.adaptive_advertising
+            .as_ref()
+            .is_some_and(|state| state.mode == AdvertisingMode::Paused)
+    }
+
+    /// Goes back to the fast interval when a client disconnects, before advertising restarts.
+    pub(crate) fn on_advertising_activity(&mut self) {
+        let Some(state) = self.adaptive_advertising.as_mut() else {
+            return;
+        };
+
+        state.last_activity = Instant::now();
+        if state.mode == AdvertisingMode::Slow {
+            state.mode = AdvertisingMode::Fast;
+            let interval = state.policy.fast_interval;
+            self.set_advertising_interval(interval);
+        }
+    }
+
+    /// Widens the advertising interval once the server went idle for long enough.
+    fn check_idle_timeout(&mut self) {
+        let Some(state) = self.adaptive_advertising.as_ref() else {
+            return;
+        };
+
+        if state.mode == AdvertisingMode::Fast
+            && self.active_connections.is_empty()
+            && state.last_activity.elapsed() >= state.policy.idle_timeout
+        {
+            info!(
+                target: GAP,
+                "No connection for {:?}, slowing advertising down.",
+                state.policy.idle_timeout
+            );
+            self.apply_advertising_mode(AdvertisingMode::Slow);
+        }
+    }
+
+    /// Switches to an advertising mode, restarting advertising if it is running.
+    fn apply_advertising_mode(&mut self, mode: AdvertisingMode) {
+        let Some(state) = self.adaptive_advertising.as_mut() else {
+            return;
+        };
+
+        state.mode = mode;
+        let policy = state.policy;
+        match mode {
+            AdvertisingMode::Fast => self.set_advertising_interval(policy.fast_interval),
+            AdvertisingMode::Slow => self.set_advertising_interval(policy.slow_interval),
+            AdvertisingMode::Paused => {}
+        }
+
+        // Advertising starts with the registration, and stops while a client is connected.
+        if !self.advertisement_configured || !self.active_connections.is_empty() {
+            return;
+        }
+
+        unsafe {
+            esp_report!(esp_ble_gap_stop_advertising());
+            if mode != AdvertisingMode::Paused {
+                esp_report!(esp_ble_gap_start_advertising(
+                    &mut self.advertisement_parameters
+                ));
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn set_advertising_interval(&mut self, interval: Duration) {
+        // The interval is counted in units of 0.625 ms.
+        let units =
+            (interval.as_micros() / 625).clamp(u128::from(MIN_INTERVAL), u128::from(MAX_INTERVAL));
+
+        self.advertisement_parameters.adv_int_min = units as u16;
+        self.advertisement_parameters.adv_int_max = units as u16;
+    }
+}
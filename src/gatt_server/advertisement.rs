@@ -0,0 +1,468 @@
+use crate::BluedroidError;
+use esp_idf_sys::{
+    esp_ble_adv_data_t, esp_ble_gap_config_adv_data, esp_ble_gap_config_adv_data_raw,
+    esp_ble_gap_config_local_icon, esp_ble_gap_config_scan_rsp_data_raw, esp_ble_gap_set_rand_addr,
+};
+use log::{debug, info, warn};
+
+use crate::gatt_server::{error::esp_report, GattServer, GattServerError};
+use crate::utilities::log_targets::GAP;
+
+/// The maximum length of a legacy advertisement or scan response packet.
+const MAX_PACKET_LENGTH: usize = 31;
+
+/// The length and type bytes preceding the data of every AD structure.
+const AD_HEADER_LENGTH: usize = 2;
+
+/// The shortest shortened name worth advertising, in bytes.
+const MIN_SHORTENED_NAME_LENGTH: usize = 4;
+
+/// The AD types of the fields of the advertisement and scan response packets.
+const AD_TYPE_FLAGS: u8 = 0x01;
+const AD_TYPE_UUID16_LIST: u8 = 0x03;
+const AD_TYPE_UUID32_LIST: u8 = 0x05;
+const AD_TYPE_UUID128_LIST: u8 = 0x07;
+const AD_TYPE_SHORTENED_NAME: u8 = 0x08;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+pub(crate) const AD_TYPE_COMPLETE_NAME: u8 = 0x09;
+const AD_TYPE_TX_POWER: u8 = 0x0A;
+const AD_TYPE_CONNECTION_INTERVAL: u8 = 0x12;
+const AD_TYPE_SERVICE_DATA: u8 = 0x16;
+const AD_TYPE_APPEARANCE: u8 = 0x19;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+
+/// The Bluetooth base UUID, in little-endian order, without its 32-bit prefix.
+const BASE_UUID: [u8; 12] = [
+    0xfb, 0x34, 0x9b, 0x5f, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00,
+];
+
+impl GattServer {
+    /// Checks that the advertisement and the scan response data fit in their packets.
+    ///
+    /// The Bluetooth stack silently truncates the packets that are too long,
+    /// so this check is also done every time the data is handed to the stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::AdvertisementTooLong`] naming the first field that does not fit.
+    pub fn check_advertisement(&self) -> Result<(), BluedroidError> {
+        check_packet(&self.advertisement_data, &self.device_name)?;
+        Ok(check_packet(&self.scan_response_data, &self.device_name)?)
+    }
+
+    /// Hands the advertisement data to the Bluetooth stack, unless it does not fit in its packet.
+    pub(crate) fn configure_advertisement_data(&mut self) {
+        // The extended advertisement carries the fields of the advertisement data.
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        if self.extended_advertising.is_some() {
+            self.configure_extended_data(super::extended_advertising::SERVER_INSTANCE);
+            return;
+        }
+
+        match shortened_name_length(&self.advertisement_data, &self.device_name) {
+            Ok(None) => unsafe {
+                esp_report!(esp_ble_gap_config_adv_data(&mut self.advertisement_data));
+            },
+            Ok(Some(name_length)) => {
+                let mut packet = self.encode_packet(&self.advertisement_data, name_length);
+                unsafe {
+                    esp_report!(esp_ble_gap_config_adv_data_raw(
+                        packet.as_mut_ptr(),
+                        packet.len() as u32
+                    ));
+                }
+            }
+            Err(error) => error.report(),
+        }
+    }
+
+    /// Hands the scan response data to the Bluetooth stack, unless it does not fit in its packet.
+    pub(crate) fn configure_scan_response_data(&mut self) {
+        // The extended advertisement carries the fields of the scan response data too.
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        if self.extended_advertising.is_some() {
+            return;
+        }
+
+        match shortened_name_length(&self.scan_response_data, &self.device_name) {
+            Ok(None) => unsafe {
+                esp_report!(esp_ble_gap_config_adv_data(&mut self.scan_response_data));
+            },
+            Ok(Some(name_length)) => {
+                let mut packet = self.encode_packet(&self.scan_response_data, name_length);
+                unsafe {
+                    esp_report!(esp_ble_gap_config_scan_rsp_data_raw(
+                        packet.as_mut_ptr(),
+                        packet.len() as u32
+                    ));
+                }
+            }
+            Err(error) => error.report(),
+        }
+    }
+
+    /// Encodes a packet with the device name shortened to `name_length` bytes.
+    ///
+    /// The Bluetooth stack would shorten the name to all the space left after the preceding fields,
+    /// dropping the following ones: the packet is encoded here instead, in the same field order.
+    fn encode_packet(&self, data: &esp_ble_adv_data_t, name_length: usize) -> Vec<u8> {
+        let name = &self.device_name.trim_end_matches('\0')[..name_length];
+        info!(
+            target: GAP,
+            "The device name does not fit in the {}, shortening it to \"{}\".",
+            packet_name(data),
+            name
+        );
+
+        encode_fields(&self.packet_fields(data, AD_TYPE_SHORTENED_NAME, name))
+    }
+
+    /// Returns the AD structures of a packet, as `(AD type, data)` pairs,
+    /// in the field order of the Bluetooth stack.
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn packet_fields(
+        &self,
+        data: &esp_ble_adv_data_t,
+        name_type: u8,
+        name: &str,
+    ) -> Vec<(u8, Vec<u8>)> {
+        let mut fields = Vec::new();
+        let mut push = |ad_type: u8, value: &[u8]| fields.push((ad_type, value.to_vec()));
+
+        if !data.set_scan_rsp && data.flag != 0 {
+            push(AD_TYPE_FLAGS, &[data.flag]);
+        }
+        if data.appearance != 0 {
+            push(AD_TYPE_APPEARANCE, &(data.appearance as u16).to_le_bytes());
+        }
+        if data.include_name {
+            push(name_type, name.as_bytes());
+        }
+        if data.manufacturer_len > 0 {
+            push(AD_TYPE_MANUFACTURER_DATA, unsafe {
+                std::slice::from_raw_parts(data.p_manufacturer_data, data.manufacturer_len as usize)
+            });
+        }
+        if data.include_txpower {
+            push(AD_TYPE_TX_POWER, &self.tx_power().to_le_bytes());
+        }
+        for (ad_type, uuids) in service_uuid_lists(data) {
+            if !uuids.is_empty() {
+                push(ad_type, &uuids);
+            }
+        }
+        if data.min_interval > 0 && data.max_interval > 0 {
+            let mut interval = (data.min_interval as u16).to_le_bytes().to_vec();
+            interval.extend_from_slice(&(data.max_interval as u16).to_le_bytes());
+            push(AD_TYPE_CONNECTION_INTERVAL, &interval);
+        }
+        if data.service_data_len > 0 {
+            push(AD_TYPE_SERVICE_DATA, unsafe {
+                std::slice::from_raw_parts(data.p_service_data, data.service_data_len as usize)
+            });
+        }
+
+        fields
+    }
+
+    /// Returns the transmission power of the advertisements, in dBm.
+    fn tx_power(&self) -> i8 {
+        // The power levels go up by 3 dBm from the lowest one, which depends on the chip.
+        #[cfg(esp32)]
+        let lowest = -12;
+        #[cfg(not(esp32))]
+        let lowest = -24;
+
+        i8::try_from(lowest + 3 * i64::from(self.power_level)).unwrap_or(i8::MAX)
+    }
+
+    /// Sets the manufacturer data of the advertisement, updating it if it is already configured.
+    ///
+    /// The manufacturer data must start with the company identifier, in little-endian order.
+    #[cfg(feature = "standard-services")]
+    pub(crate) fn set_manufacturer_data(&mut self, manufacturer_data: Vec<u8>) {
+        if !self.store_manufacturer_data(manufacturer_data) {
+            return;
+        }
+
+        // Before the registration, the advertisement data is configured with the new value anyway.
+        if self.advertisement_configured {
+            self.configure_advertisement_data();
+        }
+    }
+
+    /// Stores the manufacturer data of the advertisement, without configuring it.
+    ///
+    /// Returns `false` if the manufacturer data did not change.
+    #[cfg(feature = "standard-services")]
+    pub(crate) fn store_manufacturer_data(&mut self, manufacturer_data: Vec<u8>) -> bool {
+        if manufacturer_data == self.manufacturer_data {
+            return false;
+        }
+
+        self.manufacturer_data = manufacturer_data;
+        if self.manufacturer_data.is_empty() {
+            self.advertisement_data.p_manufacturer_data = std::ptr::null_mut();
+            self.advertisement_data.manufacturer_len = 0;
+        } else {
+            self.advertisement_data.p_manufacturer_data = self.manufacturer_data.as_mut_ptr();
+            self.advertisement_data.manufacturer_len = self.manufacturer_data.len() as u16;
+        }
+
+        true
+    }
+
+    /// Places the identifiers of the primary services in the advertisement,
+    /// or in the scan response if they do not fit.
+    pub(crate) fn place_primary_service_uuids(&mut self) {
+        let mut uuids: Vec<u8> = Vec::new();
+        for profile in &self.profiles {
+            for service in &profile.read().services {
+                let service = service.read();
+                let uuid = service.uuid.as_uuid128_array();
+
+                if service.primary && !uuids.chunks_exact(16).any(|existing| existing == uuid) {
+                    uuids.extend_from_slice(&uuid);
+                }
+            }
+        }
+
+        self.service_uuids = uuids;
+        for data in [&mut self.advertisement_data, &mut self.scan_response_data] {
+            data.p_service_uuid = std::ptr::null_mut();
+            data.service_uuid_len = 0;
+        }
+
+        if self.service_uuids.is_empty() {
+            return;
+        }
+
+        let pointer = self.service_uuids.as_mut_ptr();
+        let length = self.service_uuids.len() as u16;
+
+        self.advertisement_data.p_service_uuid = pointer;
+        self.advertisement_data.service_uuid_len = length;
+        if check_packet(&self.advertisement_data, &self.device_name).is_ok() {
+            debug!(target: GAP, "Advertising the primary services.");
+            return;
+        }
+        self.advertisement_data.p_service_uuid = std::ptr::null_mut();
+        self.advertisement_data.service_uuid_len = 0;
+
+        self.scan_response_data.p_service_uuid = pointer;
+        self.scan_response_data.service_uuid_len = length;
+        if check_packet(&self.scan_response_data, &self.device_name).is_ok() {
+            debug!(target: GAP, "Advertising the primary services in the scan response.");
+            return;
+        }
+        self.scan_response_data.p_service_uuid = std::ptr::null_mut();
+        self.scan_response_data.service_uuid_len = 0;
+
+        warn!(
+            target: GAP,
+            "The primary services do not fit in the advertisement or the scan response."
+        );
+    }
+
+    /// Hands the appearance to the GAP service of the Bluetooth stack.
+    pub(crate) fn configure_appearance(&self) {
+        let appearance = u16::try_from(self.advertisement_data.appearance).unwrap_or_default();
+
+        unsafe {
+            esp_report!(esp_ble_gap_config_local_icon(appearance));
+        }
+    }
+
+    /// Hands the random address of the advertisement to the Bluetooth stack, if one is set.
+    pub(crate) fn configure_random_address(&mut self) {
+        if let Some(address) = self.random_address.as_mut() {
+            unsafe {
+                esp_report!(esp_ble_gap_set_rand_addr(address.as_mut_ptr()));
+            }
+        }
+    }
+}
+
+/// Encodes AD structures, given as `(AD type, data)` pairs, in a packet.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn encode_fields(fields: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(MAX_PACKET_LENGTH);
+    for (ad_type, value) in fields {
+        packet.push(value.len() as u8 + 1);
+        packet.push(*ad_type);
+        packet.extend_from_slice(value);
+    }
+
+    packet
+}
+
+/// Checks that the packet fits, possibly with the device name shortened.
+pub(crate) fn check_packet(
+    data: &esp_ble_adv_data_t,
+    device_name: &str,
+) -> Result<(), GattServerError> {
+    shortened_name_length(data, device_name).map(|_| ())
+}
+
+/// Returns the length to which the device name must be shortened for the packet to fit,
+/// or `None` if the complete name fits.
+///
+/// # Errors
+///
+/// Returns a [`GattServerError::AdvertisementTooLong`] if the packet does not fit,
+/// even with a name of [`MIN_SHORTENED_NAME_LENGTH`] bytes.
+fn shortened_name_length(
+    data: &esp_ble_adv_data_t,
+    device_name: &str,
+) -> Result<Option<usize>, GattServerError> {
+    let name = device_name.trim_end_matches('\0');
+
+    let complete = check_fields(data, name.len());
+    if complete.is_ok() || !data.include_name {
+        return complete.map(|()| None);
+    }
+
+    // The space left for the name once all the other fields are placed.
+    let others: usize = fields(data, 0)
+        .iter()
+        .filter(|(field, _)| *field != NAME_FIELD)
+        .map(|(_, length)| AD_HEADER_LENGTH + length)
+        .sum();
+    let room = MAX_PACKET_LENGTH.saturating_sub(others + AD_HEADER_LENGTH);
+
+    // Do not cut a character in half.
+    let length = (0..=room.min(name.len()))
+        .rev()
+        .find(|length| name.is_char_boundary(*length))
+        .unwrap_or_default();
+
+    if length < MIN_SHORTENED_NAME_LENGTH {
+        check_fields(data, MIN_SHORTENED_NAME_LENGTH)?;
+    }
+
+    Ok(Some(length))
+}
+
+/// The name of the device name field.
+const NAME_FIELD: &str = "device name";
+
+/// Returns the name of the packet described by the given data.
+const fn packet_name(data: &esp_ble_adv_data_t) -> &'static str {
+    if data.set_scan_rsp {
+        "scan response"
+    } else {
+        "advertisement"
+    }
+}
+
+/// Returns the fields of the packet with their lengths, in the order of the Bluetooth stack.
+fn fields(data: &esp_ble_adv_data_t, name_length: usize) -> Vec<(&'static str, usize)> {
+    let mut fields: Vec<(&'static str, usize)> = Vec::new();
+
+    // The flags are only sent in advertisements.
+    if !data.set_scan_rsp && data.flag != 0 {
+        fields.push(("flags", 1));
+    }
+    if data.appearance != 0 {
+        fields.push(("appearance", 2));
+    }
+    if data.include_name {
+        fields.push((NAME_FIELD, name_length));
+    }
+    if data.manufacturer_len > 0 {
+        fields.push(("manufacturer data", data.manufacturer_len as usize));
+    }
+    if data.include_txpower {
+        fields.push(("TX power level", 1));
+    }
+    for (field, length) in service_uuid_lengths(data) {
+        if length > 0 {
+            fields.push((field, length));
+        }
+    }
+    if data.min_interval > 0 && data.max_interval > 0 {
+        fields.push(("connection interval range", 4));
+    }
+    if data.service_data_len > 0 {
+        fields.push(("service data", data.service_data_len as usize));
+    }
+
+    fields
+}
+
+/// Computes the encoded length of the packet, field by field, with a name of the given length.
+fn check_fields(data: &esp_ble_adv_data_t, name_length: usize) -> Result<(), GattServerError> {
+    let mut length = 0;
+    for (field, field_length) in fields(data, name_length) {
+        length += AD_HEADER_LENGTH + field_length;
+
+        if length > MAX_PACKET_LENGTH {
+            return Err(GattServerError::AdvertisementTooLong {
+                packet: packet_name(data),
+                field,
+                length,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the AD types and contents of the 16-bit, 32-bit and 128-bit service UUID lists.
+fn service_uuid_lists(data: &esp_ble_adv_data_t) -> [(u8, Vec<u8>); 3] {
+    let mut lists = [
+        (AD_TYPE_UUID16_LIST, Vec::new()),
+        (AD_TYPE_UUID32_LIST, Vec::new()),
+        (AD_TYPE_UUID128_LIST, Vec::new()),
+    ];
+
+    if data.p_service_uuid.is_null() {
+        return lists;
+    }
+
+    let uuids =
+        unsafe { std::slice::from_raw_parts(data.p_service_uuid, data.service_uuid_len as usize) };
+
+    for uuid in uuids.chunks_exact(16) {
+        if uuid[..12] != BASE_UUID {
+            lists[2].1.extend_from_slice(uuid);
+        } else if uuid[14..] == [0, 0] {
+            lists[0].1.extend_from_slice(&uuid[12..14]);
+        } else {
+            lists[1].1.extend_from_slice(&uuid[12..]);
+        }
+    }
+
+    lists
+}
+
+/// Returns the lengths of the 16-bit, 32-bit and 128-bit service UUID lists.
+///
+/// The service UUIDs are stored as 128-bit UUIDs, but the Bluetooth stack
+/// advertises the ones derived from the base UUID in their shortest form.
+fn service_uuid_lengths(data: &esp_ble_adv_data_t) -> [(&'static str, usize); 3] {
+    let mut lengths = [
+        ("16-bit service UUIDs", 0),
+        ("32-bit service UUIDs", 0),
+        ("128-bit service UUIDs", 0),
+    ];
+
+    if data.p_service_uuid.is_null() {
+        return lengths;
+    }
+
+    let uuids =
+        unsafe { std::slice::from_raw_parts(data.p_service_uuid, data.service_uuid_len as usize) };
+
+    for uuid in uuids.chunks_exact(16) {
+        if uuid[..12] != BASE_UUID {
+            lengths[2].1 += 16;
+        } else if uuid[14..] == [0, 0] {
+            lengths[0].1 += 2;
+        } else {
+            lengths[1].1 += 4;
+        }
+    }
+
+    lengths
+}
@@ -0,0 +1,227 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp_ble_adv_type_t, esp_ble_adv_type_t_ADV_TYPE_NONCONN_IND, esp_ble_gap_config_adv_data_raw,
+    esp_ble_gap_stop_advertising, ESP_BLE_ADV_FLAG_BREDR_NOT_SPT, ESP_BLE_ADV_FLAG_GEN_DISC,
+};
+use log::debug;
+
+use crate::gatt_server::{
+    error::esp_report, worker, GattServer, GattServerError, GLOBAL_GATT_SERVER,
+};
+use crate::utilities::log_targets::GAP;
+
+/// The maximum length of a legacy advertisement packet.
+const MAX_PACKET_LENGTH: usize = 31;
+
+/// The AD types of the fields added by [`AdvertisingRotation`].
+const AD_TYPE_FLAGS: u8 = 0x01;
+const AD_TYPE_SERVICE_DATA: u8 = 0x16;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+
+/// Identifies the running rotation: rotations stop once it changes.
+static ROTATION: AtomicU32 = AtomicU32::new(0);
+
+/// An advertisement of an [`AdvertisingRotation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AdvertisingPayload {
+    /// The advertisement configured on the GATT server.
+    Server,
+    /// A non-connectable advertisement packet, made of AD structures.
+    Raw(Vec<u8>),
+}
+
+/// A set of advertisements, advertised one after another, for devices that must speak
+/// several advertising "languages": for example the connectable advertisement of the GATT server,
+/// a BTHome frame and an iBeacon.
+///
+/// Start the rotation with [`GattServer::rotate_advertising`].
+///
+/// # Notes
+///
+/// Only the advertisement of the GATT server is connectable: the other advertisements are
+/// non-connectable, and are not answered with the scan response data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisingRotation {
+    interval: Duration,
+    payloads: Vec<AdvertisingPayload>,
+}
+
+impl AdvertisingRotation {
+    /// Creates a new [`AdvertisingRotation`], advertising each of its advertisements
+    /// for `interval` before moving to the next one.
+    #[must_use]
+    pub const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            payloads: Vec::new(),
+        }
+    }
+
+    /// Adds the advertisement configured on the GATT server to the rotation.
+    pub fn server_advertisement(&mut self) -> &mut Self {
+        self.payloads.push(AdvertisingPayload::Server);
+        self
+    }
+
+    /// Adds a raw advertisement packet, made of AD structures, to the rotation.
+    ///
+    /// Packets longer than 31 bytes are reported as [`GattServerError::AdvertisementTooLong`]
+    /// and ignored.
+    pub fn raw(&mut self, packet: &[u8]) -> &mut Self {
+        if packet.len() > MAX_PACKET_LENGTH {
+            GattServerError::AdvertisementTooLong {
+                packet: "advertisement",
+                field: "raw data",
+                length: packet.len(),
+            }
+            .report();
+            return self;
+        }
+
+        self.payloads.push(AdvertisingPayload::Raw(packet.to_vec()));
+        self
+    }
+
+    /// Adds an advertisement carrying service data, such as a BTHome frame
+    /// encoded with `BtHome::encode`, to the rotation.
+    ///
+    /// The service data must start with the 16-bit service identifier, in little-endian order.
+    pub fn service_data(&mut self, service_data: &[u8]) -> &mut Self {
+        match packet(AD_TYPE_SERVICE_DATA, "service data", service_data) {
+            Ok(packet) => self.raw(&packet),
+            Err(error) => {
+                error.report();
+                self
+            }
+        }
+    }
+
+    /// Adds an advertisement carrying manufacturer data, such as an iBeacon, to the rotation.
+    ///
+    /// The manufacturer data must start with the company identifier, in little-endian order.
+    pub fn manufacturer_data(&mut self, manufacturer_data: &[u8]) -> &mut Self {
+        match packet(
+            AD_TYPE_MANUFACTURER_DATA,
+            "manufacturer data",
+            manufacturer_data,
+        ) {
+            Ok(packet) => self.raw(&packet),
+            Err(error) => {
+                error.report();
+                self
+            }
+        }
+    }
+}
+
+impl GattServer {
+    /// Cycles through the advertisements of an [`AdvertisingRotation`], replacing the
+    /// rotation that is already running, if any.
+    ///
+    /// The rotation runs on the worker thread of the GATT server, and updates the advertisement
+    /// data at runtime: advertising only stops when switching between connectable
+    /// and non-connectable advertisements.
+    pub fn rotate_advertising(&mut self, rotation: &AdvertisingRotation) -> &mut Self {
+        let generation = ROTATION.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        if rotation.payloads.is_empty() {
+            return self;
+        }
+
+        let server_type = *self
+            .rotated_advertisement_type
+            .get_or_insert(self.advertisement_parameters.adv_type);
+        let payloads = rotation.payloads.clone();
+        self.advertise_payload(&payloads[0], server_type);
+
+        let mut index = 0;
+        worker::schedule_periodic(rotation.interval, move || {
+            if ROTATION.load(Ordering::Relaxed) != generation {
+                return false;
+            }
+
+            index = (index + 1) % payloads.len();
+            GLOBAL_GATT_SERVER
+                .lock()
+                .advertise_payload(&payloads[index], server_type);
+            true
+        });
+
+        self
+    }
+
+    /// Stops the running [`AdvertisingRotation`], and advertises the advertisement
+    /// of the GATT server again.
+    pub fn stop_advertising_rotation(&mut self) -> &mut Self {
+        ROTATION.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(server_type) = self.rotated_advertisement_type.take() {
+            self.advertise_payload(&AdvertisingPayload::Server, server_type);
+        }
+
+        self
+    }
+
+    /// Hands an advertisement of a rotation to the Bluetooth stack.
+    fn advertise_payload(&mut self, payload: &AdvertisingPayload, server_type: esp_ble_adv_type_t) {
+        // Before the registration, advertising starts with the advertisement of the server.
+        if !self.advertisement_configured {
+            return;
+        }
+
+        let adv_type = match payload {
+            AdvertisingPayload::Server => server_type,
+            AdvertisingPayload::Raw(_) => esp_ble_adv_type_t_ADV_TYPE_NONCONN_IND,
+        };
+        if adv_type != self.advertisement_parameters.adv_type {
+            // The advertisement type cannot change while advertising.
+            unsafe {
+                esp_report!(esp_ble_gap_stop_advertising());
+            }
+            self.advertisement_parameters.adv_type = adv_type;
+        }
+
+        debug!(target: GAP, "Rotating to the {:?} advertisement.", payload);
+
+        // Advertising restarts once the data is set.
+        match payload {
+            AdvertisingPayload::Server => self.configure_advertisement_data(),
+            AdvertisingPayload::Raw(packet) => {
+                let mut packet = packet.clone();
+                #[allow(clippy::cast_possible_truncation)]
+                unsafe {
+                    esp_report!(esp_ble_gap_config_adv_data_raw(
+                        packet.as_mut_ptr(),
+                        packet.len() as u32
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Encodes a non-connectable advertisement packet, with its flags and a single AD structure.
+///
+/// Fails with a [`GattServerError::AdvertisementTooLong`] if the value does not fit
+/// in the length byte of its AD structure.
+#[allow(clippy::cast_possible_truncation)]
+fn packet(ad_type: u8, field: &'static str, value: &[u8]) -> Result<Vec<u8>, GattServerError> {
+    let flags = (ESP_BLE_ADV_FLAG_GEN_DISC | ESP_BLE_ADV_FLAG_BREDR_NOT_SPT) as u8;
+
+    // The length counts the AD type along with the value.
+    let length =
+        u8::try_from(value.len() + 1).map_err(|_| GattServerError::AdvertisementTooLong {
+            packet: "advertisement",
+            field,
+            length: value.len() + 5,
+        })?;
+
+    let mut packet = vec![2, AD_TYPE_FLAGS, flags];
+    packet.push(length);
+    packet.push(ad_type);
+    packet.extend_from_slice(value);
+    Ok(packet)
+}
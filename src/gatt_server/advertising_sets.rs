@@ -0,0 +1,133 @@
+use esp_idf_sys::esp_ble_gap_ext_adv_set_remove;
+use log::{debug, warn};
+
+use crate::gatt_server::{
+    error::esp_report, extended_advertising::SERVER_INSTANCE, periodic_advertising,
+    ExtendedAdvertising, GattServer,
+};
+use crate::utilities::log_targets::GAP;
+
+/// The number of advertising sets supported by the Bluetooth stack,
+/// including the one of the extended advertisement of the GATT server.
+pub const MAX_ADVERTISING_SETS: u8 = 10;
+
+impl GattServer {
+    /// Adds an advertising set, sent next to the extended advertisement of the GATT server
+    /// with its own parameters and data, or replaces the set with the same instance.
+    ///
+    /// The instance identifies the set, from 1 to [`MAX_ADVERTISING_SETS`] excluded:
+    /// the instance 0 is the extended advertisement of the server.
+    /// A set without data sends the data of the server, encoded when the set is configured.
+    ///
+    /// The controller does not mix legacy and extended advertising, so the advertising sets are
+    /// only sent when the server advertises with [`GattServer::extended_advertising`].
+    /// They are configured when the server starts, or right away if it is already advertising.
+    ///
+    /// ```ignore
+    /// server
+    ///     .extended_advertising(ExtendedAdvertising::new())
+    ///     .advertising_set(1, ExtendedAdvertising::new().connectable(false).data(ibeacon));
+    /// ```
+    pub fn advertising_set(&mut self, instance: u8, advertising: ExtendedAdvertising) -> &mut Self {
+        if instance == SERVER_INSTANCE || instance >= MAX_ADVERTISING_SETS {
+            warn!(
+                target: GAP,
+                "Invalid advertising set instance {}: expected 1 to {}.",
+                instance,
+                MAX_ADVERTISING_SETS - 1
+            );
+            return self;
+        }
+
+        self.advertising_sets.insert(instance, advertising);
+        if self.advertising_sets_configured() {
+            self.configure_extended_advertising(instance);
+        }
+
+        self
+    }
+
+    /// Replaces the raw data of an advertising set, updating it if it is already configured.
+    pub fn advertising_set_data(&mut self, instance: u8, data: impl Into<Vec<u8>>) -> &mut Self {
+        let Some(advertising) = self.advertising_set_configuration_mut(instance) else {
+            warn!(target: GAP, "No advertising set {} to update.", instance);
+            return self;
+        };
+
+        advertising.data = Some(data.into());
+        if self.advertising_sets_configured() {
+            self.configure_extended_data(instance);
+        }
+
+        self
+    }
+
+    /// Replaces the data of the periodic advertisement of an advertising set,
+    /// updating it if it is running.
+    pub fn advertising_set_periodic_data(
+        &mut self,
+        instance: u8,
+        data: impl Into<Vec<u8>>,
+    ) -> &mut Self {
+        let Some(periodic) = self
+            .advertising_set_configuration_mut(instance)
+            .and_then(|advertising| advertising.periodic.as_mut())
+        else {
+            warn!(
+                target: GAP,
+                "No periodic advertisement in advertising set {} to update.", instance
+            );
+            return self;
+        };
+
+        periodic.data = data.into();
+        if periodic_advertising::is_running(instance) {
+            self.configure_periodic_data(instance);
+        }
+
+        self
+    }
+
+    /// Stops and removes an advertising set added with [`GattServer::advertising_set`].
+    pub fn remove_advertising_set(&mut self, instance: u8) -> &mut Self {
+        if self.advertising_sets.remove(&instance).is_none() {
+            warn!(target: GAP, "No advertising set {} to remove.", instance);
+            return self;
+        }
+
+        debug!(target: GAP, "Removing advertising set {}.", instance);
+        if self.advertising_sets_configured() {
+            unsafe {
+                esp_report!(esp_ble_gap_ext_adv_set_remove(instance));
+            }
+        }
+
+        self
+    }
+
+    /// Returns the instances of the advertising sets, the one of the server first.
+    pub(crate) fn advertising_set_instances(&self) -> Vec<u8> {
+        self.extended_advertising
+            .as_ref()
+            .map(|_| SERVER_INSTANCE)
+            .into_iter()
+            .chain(self.advertising_sets.keys().copied())
+            .collect()
+    }
+
+    /// Returns whether the advertising sets were handed to the Bluetooth stack.
+    fn advertising_sets_configured(&self) -> bool {
+        self.advertisement_configured && self.extended_advertising.is_some()
+    }
+
+    fn advertising_set_configuration_mut(
+        &mut self,
+        instance: u8,
+    ) -> Option<&mut ExtendedAdvertising> {
+        if instance == SERVER_INSTANCE {
+            self.extended_advertising.as_mut()
+        } else {
+            self.advertising_sets.get_mut(&instance)
+        }
+    }
+}
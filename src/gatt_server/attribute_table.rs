@@ -0,0 +1,122 @@
+use esp_idf_sys::{
+    esp_attr_control_t, esp_attr_desc_t, esp_ble_gatts_create_attr_tab, esp_gatt_perm_t,
+    esp_gatts_attr_db_t, ESP_GATT_AUTO_RSP, ESP_GATT_PERM_READ, ESP_GATT_UUID_CHAR_DECLARE,
+    ESP_GATT_UUID_PRI_SERVICE, ESP_GATT_UUID_SEC_SERVICE,
+};
+
+use crate::gatt_server::{error::esp_check, GattServerError};
+use crate::utilities::BleUuid;
+
+/// The buffers the entries of an attribute table point to.
+///
+/// The Bluetooth stack reads them after the registration call returns,
+/// so they must be kept until the attribute table event arrives.
+pub(crate) type TableBuffers = Vec<Vec<u8>>;
+
+/// A whole service, described as the attribute table registered by `esp_ble_gatts_create_attr_tab`.
+///
+/// The attributes are listed in the order of the handles returned by the attribute table event:
+/// the service declaration, then for each characteristic its declaration, its value
+/// and its descriptors.
+pub(crate) struct AttributeTable {
+    entries: Vec<esp_gatts_attr_db_t>,
+    buffers: TableBuffers,
+}
+
+impl AttributeTable {
+    /// Creates an attribute table, starting with the declaration of a service.
+    pub(crate) fn new(uuid: BleUuid, primary: bool) -> Self {
+        let mut table = Self {
+            entries: Vec::new(),
+            buffers: Vec::new(),
+        };
+
+        let declaration = if primary {
+            ESP_GATT_UUID_PRI_SERVICE
+        } else {
+            ESP_GATT_UUID_SEC_SERVICE
+        };
+        table.declare(declaration, &uuid.as_bytes());
+
+        table
+    }
+
+    /// Adds the declaration of a characteristic, with its properties.
+    pub(crate) fn characteristic(&mut self, properties: u8) {
+        self.declare(ESP_GATT_UUID_CHAR_DECLARE, &[properties]);
+    }
+
+    /// Adds the value of a characteristic, or a descriptor.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn attribute(
+        &mut self,
+        uuid: BleUuid,
+        permissions: esp_gatt_perm_t,
+        control: esp_attr_control_t,
+        max_length: u16,
+        value: &[u8],
+    ) {
+        let mut uuid = uuid.as_bytes();
+        let mut value = value.to_vec();
+
+        self.entries.push(esp_gatts_attr_db_t {
+            attr_control: control,
+            att_desc: esp_attr_desc_t {
+                uuid_length: uuid.len() as u16,
+                uuid_p: uuid.as_mut_ptr(),
+                perm: permissions,
+                max_length: max_length.max(value.len() as u16),
+                length: value.len() as u16,
+                value: value.as_mut_ptr(),
+            },
+        });
+
+        // Moving the buffers does not move their contents.
+        self.buffers.push(uuid);
+        self.buffers.push(value);
+    }
+
+    /// Adds a read-only declaration, answered by the Bluetooth stack.
+    #[allow(clippy::cast_possible_truncation)]
+    fn declare(&mut self, declaration: u32, value: &[u8]) {
+        let control = esp_attr_control_t {
+            auto_rsp: ESP_GATT_AUTO_RSP as u8,
+        };
+
+        self.attribute(
+            BleUuid::from_uuid16(declaration as u16),
+            ESP_GATT_PERM_READ as esp_gatt_perm_t,
+            control,
+            0,
+            value,
+        );
+    }
+
+    /// Returns the number of attributes in the table.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Registers the attribute table on a GATT interface.
+    ///
+    /// Returns the buffers to keep until the attribute table event arrives.
+    pub(crate) fn create(self, interface: u8) -> Result<TableBuffers, GattServerError> {
+        let Ok(length) = u8::try_from(self.entries.len()) else {
+            return Err(GattServerError::CapacityExceeded {
+                collection: "attributes",
+                capacity: u8::MAX.into(),
+            });
+        };
+
+        unsafe {
+            esp_check!(esp_ble_gatts_create_attr_tab(
+                self.entries.as_ptr(),
+                interface,
+                length,
+                0,
+            ))?;
+        }
+
+        Ok(self.buffers)
+    }
+}
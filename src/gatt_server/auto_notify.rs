@@ -0,0 +1,107 @@
+use std::{
+    ffi::c_void,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp_timer_create, esp_timer_create_args_t, esp_timer_dispatch_t_ESP_TIMER_TASK,
+    esp_timer_handle_t, esp_timer_start_periodic,
+};
+use parking_lot::RwLock;
+
+use crate::gatt_server::{
+    error::esp_report, Characteristic, LockedCharacteristic, GLOBAL_GATT_SERVER,
+};
+
+pub(crate) type ValueProducer = dyn Fn() -> Vec<u8> + Send + Sync;
+
+/// Periodically sets a fresh value to a characteristic, while clients are subscribed to it.
+#[derive(Clone)]
+pub(crate) struct AutoNotify {
+    period: Duration,
+    producer: Arc<ValueProducer>,
+    /// Whether the timer is running. It keeps running if the characteristic is registered again.
+    started: bool,
+}
+
+/// The argument of the timer callback.
+struct AutoNotifyContext {
+    characteristic: Weak<RwLock<Characteristic>>,
+    producer: Arc<ValueProducer>,
+}
+
+impl AutoNotify {
+    pub(crate) fn new(period: Duration, producer: Arc<ValueProducer>) -> Self {
+        Self {
+            period,
+            producer,
+            started: false,
+        }
+    }
+
+    /// Starts the timer of the given characteristic, unless it is already running.
+    pub(crate) fn start(&mut self, characteristic: &LockedCharacteristic) {
+        if self.started {
+            return;
+        }
+
+        // The context lives as long as the timer, which is never deleted:
+        // it is allocated once per characteristic, not per tick.
+        let context = Box::into_raw(Box::new(AutoNotifyContext {
+            characteristic: Arc::downgrade(characteristic),
+            producer: self.producer.clone(),
+        }));
+
+        let arguments = esp_timer_create_args_t {
+            callback: Some(Self::on_tick),
+            arg: context.cast::<c_void>(),
+            dispatch_method: esp_timer_dispatch_t_ESP_TIMER_TASK,
+            name: b"auto_notify\0".as_ptr().cast(),
+            skip_unhandled_events: true,
+        };
+
+        let mut timer: esp_timer_handle_t = std::ptr::null_mut();
+        unsafe {
+            if !esp_report!(esp_timer_create(&arguments, &mut timer)) {
+                return;
+            }
+
+            #[allow(clippy::cast_possible_truncation)]
+            if !esp_report!(esp_timer_start_periodic(
+                timer,
+                self.period.as_micros() as u64
+            )) {
+                return;
+            }
+        }
+
+        self.started = true;
+    }
+
+    unsafe extern "C" fn on_tick(argument: *mut c_void) {
+        let context = &*argument.cast::<AutoNotifyContext>();
+
+        let Some(characteristic) = context.characteristic.upgrade() else {
+            return;
+        };
+
+        // Do not produce values nobody is going to receive.
+        let connections = GLOBAL_GATT_SERVER.lock().active_connections.clone();
+        if characteristic.read().recipients(&connections).is_empty() {
+            return;
+        }
+
+        let value = (context.producer)();
+        characteristic.write().set_value(value);
+    }
+}
+
+impl std::fmt::Debug for AutoNotify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AutoNotify")
+            .field("period", &self.period)
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
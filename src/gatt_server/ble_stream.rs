@@ -0,0 +1,379 @@
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind, Read, Write},
+    sync::Arc,
+    task::Waker,
+    time::Duration,
+};
+
+#[cfg(feature = "embedded-io-async")]
+use std::task::{Context, Poll};
+
+use log::warn;
+use parking_lot::{Condvar, Mutex};
+
+use crate::utilities::log_targets::NOTIFY;
+use crate::{
+    gatt_server::{LockedCharacteristic, GLOBAL_GATT_SERVER},
+    utilities::{Connection, DeliveryOutcome},
+};
+
+/// The default size of the values sent on the TX characteristic, fitting the default ATT MTU of 23 bytes.
+const DEFAULT_CHUNK_SIZE: usize = 20;
+
+/// The default capacity of the receive buffer.
+const DEFAULT_RX_CAPACITY: usize = 1024;
+
+/// The default number of values that can be waiting for delivery before writes block.
+const DEFAULT_MAX_IN_FLIGHT: usize = 8;
+
+#[derive(Default)]
+struct StreamState {
+    rx_buffer: VecDeque<u8>,
+    in_flight: usize,
+    /// The tasks waiting for received bytes or for deliveries, with the async traits.
+    wakers: Vec<Waker>,
+}
+
+#[derive(Default)]
+struct SharedState {
+    state: Mutex<StreamState>,
+    condvar: Condvar,
+}
+
+impl SharedState {
+    /// Wakes up the blocked readers and writers, and the waiting tasks.
+    fn notify(&self, state: &mut StreamState) {
+        self.condvar.notify_all();
+        state.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+/// A byte stream over a pair of characteristics, implementing [`std::io::Read`] and [`std::io::Write`].
+///
+/// With the `embedded-io` and `embedded-io-async` features, it also implements the blocking
+/// and async traits of `embedded-io`, so drivers and protocols written against them
+/// can run over a BLE link unmodified.
+///
+/// Reads return the bytes that clients write to the RX characteristic, and writes
+/// are sent as notifications or indications of the TX characteristic to the clients
+/// that would receive its value changes, according to its [`NotificationMode`].
+///
+/// Received bytes are buffered up to a fixed capacity, and writes block while
+/// too many values are waiting to be delivered, so that a fast writer cannot flood slow clients.
+///
+/// [`NotificationMode`]: crate::utilities::NotificationMode
+#[derive(Clone)]
+pub struct BleStream {
+    tx: LockedCharacteristic,
+    shared: Arc<SharedState>,
+    chunk_size: usize,
+    max_in_flight: usize,
+    read_timeout: Option<Duration>,
+}
+
+impl BleStream {
+    /// Creates a new [`BleStream`] over the given RX and TX characteristics.
+    ///
+    /// This replaces the write callback of the RX characteristic.
+    #[must_use]
+    pub fn new(rx: &LockedCharacteristic, tx: &LockedCharacteristic) -> Self {
+        Self::with_rx_capacity(rx, tx, DEFAULT_RX_CAPACITY)
+    }
+
+    /// Creates a new [`BleStream`] over the given RX and TX characteristics,
+    /// buffering at most `rx_capacity` received bytes.
+    ///
+    /// Bytes received when the buffer is full are discarded.
+    /// This replaces the write callback of the RX characteristic.
+    #[must_use]
+    pub fn with_rx_capacity(
+        rx: &LockedCharacteristic,
+        tx: &LockedCharacteristic,
+        rx_capacity: usize,
+    ) -> Self {
+        let shared = Arc::new(SharedState::default());
+
+        let rx_shared = shared.clone();
+        rx.write().on_write(move |value, _| {
+            let mut state = rx_shared.state.lock();
+            let available = rx_capacity.saturating_sub(state.rx_buffer.len());
+
+            if value.len() > available {
+                warn!(
+                    target: NOTIFY,
+                    "BLE stream receive buffer full, discarding {} bytes.",
+                    value.len() - available
+                );
+            }
+
+            state
+                .rx_buffer
+                .extend(value.iter().take(available).copied());
+            rx_shared.notify(&mut state);
+        });
+
+        Self {
+            tx: tx.clone(),
+            shared,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            read_timeout: None,
+        }
+    }
+
+    /// Sets the size of the values sent on the TX characteristic. The default value is 20 bytes.
+    ///
+    /// The chunk size should not exceed the ATT MTU of the connections minus three bytes.
+    pub fn chunk_size(&mut self, chunk_size: usize) -> &mut Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sets how many values can be waiting for delivery before writes block. The default value is 8.
+    pub fn max_in_flight(&mut self, max_in_flight: usize) -> &mut Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Sets how long reads wait for data. By default, reads wait indefinitely.
+    ///
+    /// When the timeout expires, reads fail with [`ErrorKind::TimedOut`].
+    pub fn read_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Returns the number of received bytes that are ready to be read.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.shared.state.lock().rx_buffer.len()
+    }
+
+    fn on_delivery(shared: &SharedState, connection: Connection, outcome: DeliveryOutcome) {
+        if matches!(outcome, DeliveryOutcome::Failed | DeliveryOutcome::Dropped) {
+            warn!(target: NOTIFY, "BLE stream data could not be delivered to {}.", connection);
+        }
+
+        let mut state = shared.state.lock();
+        state.in_flight -= 1;
+        shared.notify(&mut state);
+    }
+
+    /// Returns the clients that receive the stream.
+    fn recipients(&self) -> std::io::Result<Vec<Connection>> {
+        let connections = GLOBAL_GATT_SERVER.lock().active_connections.clone();
+        let recipients = self.tx.read().recipients(&connections);
+
+        if recipients.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                "No client is receiving the BLE stream",
+            ));
+        }
+
+        Ok(recipients
+            .into_iter()
+            .map(|(connection, _)| connection)
+            .collect())
+    }
+
+    /// Queues a chunk for delivery to a client, once a delivery slot was reserved for it.
+    fn queue(&self, connection: Connection, chunk: &[u8]) -> std::io::Result<()> {
+        let shared = self.shared.clone();
+        let queued = self.tx.read().queue_value(
+            connection,
+            chunk.to_vec(),
+            Some(Arc::new(move |connection, outcome| {
+                Self::on_delivery(&shared, connection, outcome);
+            })),
+        );
+
+        if queued {
+            Ok(())
+        } else {
+            self.shared.state.lock().in_flight -= 1;
+            Err(Error::new(ErrorKind::Other, "Cannot send BLE stream data"))
+        }
+    }
+
+    /// Moves received bytes into `buf`, returning their number, or `None` if there are none yet.
+    fn take_received(state: &mut StreamState, buf: &mut [u8]) -> Option<usize> {
+        if state.rx_buffer.is_empty() {
+            return None;
+        }
+
+        let length = buf.len().min(state.rx_buffer.len());
+        for (byte, received) in buf.iter_mut().zip(state.rx_buffer.drain(..length)) {
+            *byte = received;
+        }
+
+        Some(length)
+    }
+
+    #[cfg(feature = "embedded-io-async")]
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<usize> {
+        let mut state = self.shared.state.lock();
+        if let Some(length) = Self::take_received(&mut state, buf) {
+            Poll::Ready(length)
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Reserves a delivery slot, once fewer than the maximum number of values are in flight.
+    #[cfg(feature = "embedded-io-async")]
+    fn poll_reserve(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.shared.state.lock();
+        if state.in_flight < self.max_in_flight {
+            state.in_flight += 1;
+            Poll::Ready(())
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[cfg(feature = "embedded-io-async")]
+    fn poll_flush(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.shared.state.lock();
+        if state.in_flight == 0 {
+            Poll::Ready(())
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Read for BleStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.shared.state.lock();
+
+        loop {
+            if let Some(length) = Self::take_received(&mut state, buf) {
+                return Ok(length);
+            }
+
+            match self.read_timeout {
+                Some(timeout) => {
+                    if self
+                        .shared
+                        .condvar
+                        .wait_for(&mut state, timeout)
+                        .timed_out()
+                        && state.rx_buffer.is_empty()
+                    {
+                        return Err(Error::new(ErrorKind::TimedOut, "BLE stream read timed out"));
+                    }
+                }
+                None => self.shared.condvar.wait(&mut state),
+            }
+        }
+    }
+}
+
+impl Write for BleStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let recipients = self.recipients()?;
+        let length = buf.len().min(self.chunk_size);
+
+        for connection in recipients {
+            // Backpressure: wait for previously sent values to be delivered.
+            let mut state = self.shared.state.lock();
+            while state.in_flight >= self.max_in_flight {
+                self.shared.condvar.wait(&mut state);
+            }
+            state.in_flight += 1;
+            drop(state);
+
+            self.queue(connection, &buf[..length])?;
+        }
+
+        Ok(length)
+    }
+
+    /// Waits until all the written data has been delivered.
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut state = self.shared.state.lock();
+        while state.in_flight > 0 {
+            self.shared.condvar.wait(&mut state);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::ErrorType for BleStream {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Read for BleStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Read::read(self, buf)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Write for BleStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Write::write(self, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Write::flush(self)
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Read for BleStream {
+    /// Waits for received bytes. The read timeout does not apply: use the timeouts of the executor.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(std::future::poll_fn(|cx| self.poll_read(cx, buf)).await)
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Write for BleStream {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let recipients = self.recipients()?;
+        let length = buf.len().min(self.chunk_size);
+
+        for connection in recipients {
+            // Backpressure: wait for previously sent values to be delivered.
+            std::future::poll_fn(|cx| self.poll_reserve(cx)).await;
+            self.queue(connection, &buf[..length])?;
+        }
+
+        Ok(length)
+    }
+
+    /// Waits until all the written data has been delivered.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        std::future::poll_fn(|cx| self.poll_flush(cx)).await;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for BleStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BleStream")
+            .field("tx", &self.tx.read().to_string())
+            .field("available", &self.available())
+            .field("chunk_size", &self.chunk_size)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("read_timeout", &self.read_timeout)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,493 @@
+use crate::BluedroidError;
+use std::ffi::CString;
+
+use esp_idf_sys::{
+    esp_err_t, esp_fill_random, mbedtls_ccm_auth_decrypt, mbedtls_ccm_context,
+    mbedtls_ccm_encrypt_and_tag, mbedtls_ccm_free, mbedtls_ccm_init, mbedtls_ccm_setkey,
+    mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES, nvs_close, nvs_commit, nvs_flash_init, nvs_get_blob,
+    nvs_handle_t, nvs_open, nvs_open_mode_t, nvs_open_mode_t_NVS_READONLY,
+    nvs_open_mode_t_NVS_READWRITE, nvs_set_blob, ESP_ERR_NVS_NOT_FOUND, ESP_OK,
+};
+use log::{debug, warn};
+
+use crate::gatt_server::{
+    custom_attributes::{cccd_key, STORAGE},
+    resolving_list::bonded_devices,
+    DescriptorKey, GattServer, GattServerError,
+};
+use crate::utilities::{log_targets::NVS, sig::descriptors};
+
+/// The NVS namespace in which Bluedroid keeps its configuration, bonds included.
+const STACK_NAMESPACE: &str = "bt_config.conf";
+
+/// The key of the configuration blobs of Bluedroid: its total length, followed by its
+/// numbered chunks.
+const STACK_KEY: &str = "bt_cfg_key";
+
+/// The maximum length of an NVS key.
+const MAX_KEY_LENGTH: usize = 15;
+
+const MAGIC: &[u8; 4] = b"BLBK";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 6;
+
+/// The flag of the encrypted backups.
+const ENCRYPTED: u8 = 0x01;
+
+const NONCE_LENGTH: usize = 13;
+const TAG_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordKind {
+    /// A configuration blob of the Bluetooth stack.
+    Stack = 0,
+    /// The CCCD value of a bonded client, under its NVS key.
+    ///
+    /// Only found in the backups exported before [`RecordKind::Descriptor`].
+    Cccd = 1,
+    /// The descriptor value of a bonded client, under its serialised [`DescriptorKey`].
+    Descriptor = 2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    kind: RecordKind,
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Record {
+    /// Returns the key of a record kept in the NVS.
+    fn nvs_key(&self) -> Result<&str, GattServerError> {
+        std::str::from_utf8(&self.key).map_err(|_| GattServerError::BondBackup("invalid key"))
+    }
+}
+
+/// A backup of the bonds of the GATT server, and of the CCCD values of the bonded clients.
+///
+/// A backup exported with [`GattServer::export_bonds`] can be imported on a replacement unit
+/// with [`GattServer::import_bonds`], so that the clients do not have to pair again.
+/// It can be serialised in clear, or encrypted and authenticated with AES-CCM.
+///
+/// # Notes
+///
+/// The clients recognise a bonded device by its identity address, so the replacement unit
+/// must use the same address as the original one, for example a random static address.
+/// The CCCD values are restored by client and characteristic: both units must declare
+/// the same services and characteristics.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BondBackup {
+    records: Vec<Record>,
+}
+
+impl BondBackup {
+    /// Returns whether the backup holds no bonds.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Serialises the backup, in clear.
+    ///
+    /// The backup holds the long-term keys of the bonds: store it securely,
+    /// or use [`BondBackup::to_encrypted_bytes`].
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = header(0).to_vec();
+        bytes.extend_from_slice(&self.payload());
+        bytes
+    }
+
+    /// Serialises the backup, encrypted and authenticated with AES-128-CCM under the given key.
+    ///
+    /// Returns `None` if the encryption failed.
+    #[must_use]
+    pub fn to_encrypted_bytes(&self, key: &[u8; 16]) -> Option<Vec<u8>> {
+        let header = header(ENCRYPTED);
+        let payload = self.payload();
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        unsafe { esp_fill_random(nonce.as_mut_ptr().cast(), NONCE_LENGTH) };
+
+        let mut ciphertext = vec![0u8; payload.len()];
+        let mut tag = [0u8; TAG_LENGTH];
+        let mut context = mbedtls_ccm_context::default();
+
+        let result = unsafe {
+            mbedtls_ccm_init(&mut context);
+
+            let mut result = mbedtls_ccm_setkey(
+                &mut context,
+                mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+                key.as_ptr(),
+                128,
+            );
+            if result == 0 {
+                result = mbedtls_ccm_encrypt_and_tag(
+                    &mut context,
+                    payload.len(),
+                    nonce.as_ptr(),
+                    NONCE_LENGTH,
+                    header.as_ptr(),
+                    HEADER_LENGTH,
+                    payload.as_ptr(),
+                    ciphertext.as_mut_ptr(),
+                    tag.as_mut_ptr(),
+                    TAG_LENGTH,
+                );
+            }
+
+            mbedtls_ccm_free(&mut context);
+            result
+        };
+
+        if result != 0 {
+            warn!(target: NVS, "Bond backup encryption failed with error code {}.", result);
+            return None;
+        }
+
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        bytes.extend_from_slice(&tag);
+        Some(bytes)
+    }
+
+    /// Parses a backup serialised with [`BondBackup::to_bytes`]
+    /// or [`BondBackup::to_encrypted_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::BondBackup`] if the bytes are not a valid backup,
+    /// or if the backup is encrypted and the key is missing or wrong.
+    pub fn from_bytes(bytes: &[u8], key: Option<&[u8; 16]>) -> Result<Self, BluedroidError> {
+        if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC {
+            return Err(GattServerError::BondBackup("not a bond backup").into());
+        }
+        if bytes[4] != VERSION {
+            return Err(GattServerError::BondBackup("unsupported version").into());
+        }
+
+        let (header, body) = bytes.split_at(HEADER_LENGTH);
+        if header[5] & ENCRYPTED == 0 {
+            return Ok(parse(body)?);
+        }
+
+        let Some(key) = key else {
+            return Err(GattServerError::BondBackup("the backup is encrypted").into());
+        };
+        if body.len() < NONCE_LENGTH + TAG_LENGTH {
+            return Err(GattServerError::BondBackup("truncated").into());
+        }
+
+        let (nonce, body) = body.split_at(NONCE_LENGTH);
+        let (ciphertext, tag) = body.split_at(body.len() - TAG_LENGTH);
+        let mut payload = vec![0u8; ciphertext.len()];
+        let mut context = mbedtls_ccm_context::default();
+
+        let result = unsafe {
+            mbedtls_ccm_init(&mut context);
+
+            let mut result = mbedtls_ccm_setkey(
+                &mut context,
+                mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+                key.as_ptr(),
+                128,
+            );
+            if result == 0 {
+                result = mbedtls_ccm_auth_decrypt(
+                    &mut context,
+                    ciphertext.len(),
+                    nonce.as_ptr(),
+                    NONCE_LENGTH,
+                    header.as_ptr(),
+                    HEADER_LENGTH,
+                    ciphertext.as_ptr(),
+                    payload.as_mut_ptr(),
+                    tag.as_ptr(),
+                    TAG_LENGTH,
+                );
+            }
+
+            mbedtls_ccm_free(&mut context);
+            result
+        };
+
+        if result != 0 {
+            return Err(GattServerError::BondBackup(
+                "the backup cannot be decrypted with this key",
+            )
+            .into());
+        }
+
+        Ok(parse(&payload)?)
+    }
+
+    /// Encodes the records: kind, key length, key, value length on two bytes, value.
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+
+        for record in &self.records {
+            payload.push(record.kind as u8);
+            #[allow(clippy::cast_possible_truncation)]
+            payload.push(record.key.len() as u8);
+            payload.extend_from_slice(&record.key);
+            #[allow(clippy::cast_possible_truncation)]
+            payload.extend_from_slice(&(record.value.len() as u16).to_le_bytes());
+            payload.extend_from_slice(&record.value);
+        }
+
+        payload
+    }
+}
+
+impl GattServer {
+    /// Exports the bonds of the GATT server, and the CCCD values of the bonded clients.
+    ///
+    /// The server must be started, so that the bonds are loaded and the CCCD handles known.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::BondBackup`] if the server is not started,
+    /// or a [`GattServerError::Storage`] or [`GattServerError::DescriptorStore`] if the values
+    /// cannot be read.
+    pub fn export_bonds(&self) -> Result<BondBackup, BluedroidError> {
+        if !self.started {
+            return Err(GattServerError::BondBackup(
+                "the server must be started to export its bonds",
+            )
+            .into());
+        }
+
+        let mut records = Vec::new();
+
+        if let Some(namespace) = StackNamespace::open(nvs_open_mode_t_NVS_READONLY)? {
+            let keys = std::iter::once(STACK_KEY.to_string())
+                .chain((0..).map(|index| format!("{STACK_KEY}{index}")));
+            for key in keys {
+                let Some(value) = namespace.get(&key)? else {
+                    break;
+                };
+                records.push(Record {
+                    kind: RecordKind::Stack,
+                    key: key.into_bytes(),
+                    value,
+                });
+            }
+        }
+
+        let handles = self.cccd_handles();
+        for device in bonded_devices() {
+            for handle in &handles {
+                let Some(key) = cccd_key(device.bd_addr, *handle) else {
+                    continue;
+                };
+                if let Some(value) = STORAGE.load_descriptor(&key)? {
+                    records.push(Record {
+                        kind: RecordKind::Descriptor,
+                        key: key.to_bytes(),
+                        value,
+                    });
+                }
+            }
+        }
+
+        debug!(target: NVS, "Exported {} bond backup records.", records.len());
+
+        Ok(BondBackup { records })
+    }
+
+    /// Imports a [`BondBackup`] exported by another unit.
+    ///
+    /// The bonds are loaded by the Bluetooth stack when it is initialised,
+    /// so they must be imported before starting the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::BondBackup`] if the server is already started,
+    /// or a [`GattServerError::Storage`] or [`GattServerError::DescriptorStore`] if the values
+    /// cannot be written.
+    pub fn import_bonds(&mut self, backup: &BondBackup) -> Result<&mut Self, BluedroidError> {
+        if self.started {
+            return Err(GattServerError::BondBackup(
+                "bonds must be imported before starting the server",
+            )
+            .into());
+        }
+
+        let result = unsafe { nvs_flash_init() };
+        if result != ESP_OK {
+            return Err(GattServerError::Storage(result).into());
+        }
+
+        let stack_records = backup
+            .records
+            .iter()
+            .filter(|record| record.kind == RecordKind::Stack);
+        if let Some(namespace) = StackNamespace::open(nvs_open_mode_t_NVS_READWRITE)? {
+            for record in stack_records {
+                namespace.set(record.nvs_key()?, &record.value)?;
+            }
+            namespace.commit()?;
+        }
+
+        for record in &backup.records {
+            match record.kind {
+                RecordKind::Stack => {}
+                RecordKind::Cccd => STORAGE
+                    .store(record.nvs_key()?, &record.value)
+                    .map_err(|error| GattServerError::Storage(error.code()))?,
+                RecordKind::Descriptor => {
+                    let key = DescriptorKey::from_bytes(&record.key)
+                        .ok_or(GattServerError::BondBackup("invalid key"))?;
+                    STORAGE.store_descriptor(&key, &record.value)?;
+                }
+            }
+        }
+
+        debug!(
+            target: NVS,
+            "Imported {} bond backup records.",
+            backup.records.len()
+        );
+
+        Ok(self)
+    }
+
+    /// Returns the attribute handles of the registered CCCDs.
+    fn cccd_handles(&self) -> Vec<u16> {
+        let mut handles = Vec::new();
+
+        for service in self.services() {
+            for characteristic in &service.read().characteristics {
+                for descriptor in &characteristic.read().descriptors {
+                    let descriptor = descriptor.read();
+                    if descriptor.uuid == descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION {
+                        handles.extend(descriptor.attribute_handle);
+                    }
+                }
+            }
+        }
+
+        handles
+    }
+}
+
+/// The NVS namespace of the Bluetooth stack, closed when dropped.
+struct StackNamespace(nvs_handle_t);
+
+impl StackNamespace {
+    /// Opens the namespace, or returns `None` if it does not exist yet, when read only.
+    fn open(mode: nvs_open_mode_t) -> Result<Option<Self>, GattServerError> {
+        let name = CString::new(STACK_NAMESPACE).expect("The namespace has no nul character");
+        let mut handle: nvs_handle_t = 0;
+
+        match unsafe { nvs_open(name.as_ptr(), mode, &mut handle) } {
+            ESP_OK => Ok(Some(Self(handle))),
+            ESP_ERR_NVS_NOT_FOUND => Ok(None),
+            error => Err(GattServerError::Storage(error)),
+        }
+    }
+
+    /// Reads a blob, or returns `None` if it does not exist.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, GattServerError> {
+        let key = CString::new(key).map_err(|_| GattServerError::BondBackup("invalid key"))?;
+
+        let mut length = 0;
+        let result =
+            unsafe { nvs_get_blob(self.0, key.as_ptr(), std::ptr::null_mut(), &mut length) };
+        match result {
+            ESP_OK => {}
+            ESP_ERR_NVS_NOT_FOUND => return Ok(None),
+            error => return Err(GattServerError::Storage(error)),
+        }
+
+        let mut value = vec![0u8; length];
+        check(unsafe {
+            nvs_get_blob(self.0, key.as_ptr(), value.as_mut_ptr().cast(), &mut length)
+        })?;
+        value.truncate(length);
+
+        Ok(Some(value))
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), GattServerError> {
+        let key = CString::new(key).map_err(|_| GattServerError::BondBackup("invalid key"))?;
+
+        check(unsafe { nvs_set_blob(self.0, key.as_ptr(), value.as_ptr().cast(), value.len()) })
+    }
+
+    fn commit(&self) -> Result<(), GattServerError> {
+        check(unsafe { nvs_commit(self.0) })
+    }
+}
+
+impl Drop for StackNamespace {
+    fn drop(&mut self) {
+        unsafe { nvs_close(self.0) };
+    }
+}
+
+fn check(result: esp_err_t) -> Result<(), GattServerError> {
+    if result == ESP_OK {
+        Ok(())
+    } else {
+        Err(GattServerError::Storage(result))
+    }
+}
+
+fn header(flags: u8) -> [u8; HEADER_LENGTH] {
+    [MAGIC[0], MAGIC[1], MAGIC[2], MAGIC[3], VERSION, flags]
+}
+
+/// Decodes the records of a backup, checking their keys.
+fn parse(mut payload: &[u8]) -> Result<BondBackup, GattServerError> {
+    let truncated = GattServerError::BondBackup("truncated");
+    let mut records = Vec::new();
+
+    while let [kind, key_length, rest @ ..] = payload {
+        let kind = match kind {
+            0 => RecordKind::Stack,
+            1 => RecordKind::Cccd,
+            2 => RecordKind::Descriptor,
+            _ => return Err(GattServerError::BondBackup("unknown record")),
+        };
+
+        let key_length = usize::from(*key_length);
+        if rest.len() < key_length + 2 {
+            return Err(truncated);
+        }
+        let (key, rest) = rest.split_at(key_length);
+        let valid_key = match (kind, std::str::from_utf8(key)) {
+            (RecordKind::Stack, Ok(key)) => {
+                key.starts_with(STACK_KEY) && key.len() <= MAX_KEY_LENGTH
+            }
+            (RecordKind::Cccd, Ok(key)) => !key.is_empty() && key.len() <= MAX_KEY_LENGTH,
+            (RecordKind::Descriptor, _) => DescriptorKey::from_bytes(key).is_some(),
+            (_, Err(_)) => false,
+        };
+        if !valid_key {
+            return Err(GattServerError::BondBackup("invalid key"));
+        }
+
+        let value_length = usize::from(u16::from_le_bytes([rest[0], rest[1]]));
+        let rest = &rest[2..];
+        if rest.len() < value_length {
+            return Err(truncated);
+        }
+        let (value, rest) = rest.split_at(value_length);
+
+        records.push(Record {
+            kind,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+        payload = rest;
+    }
+
+    if !payload.is_empty() {
+        return Err(truncated);
+    }
+
+    Ok(BondBackup { records })
+}
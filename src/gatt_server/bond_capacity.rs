@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Arc};
+
+use esp_idf_sys::{esp_ble_remove_bond_device, CONFIG_BT_SMP_MAX_BONDS};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+
+use crate::gatt_server::{
+    custom_attributes::{forget_cccds, STORAGE},
+    error::esp_report,
+    resolving_list::bonded_devices,
+    GattServer, GattServerError,
+};
+use crate::utilities::log_targets::GAP;
+
+/// The storage key of the last connection counter.
+const SEQUENCE_KEY: &str = "bond-seq";
+
+lazy_static! {
+    /// The bond to evict once the pairing of a peer succeeds, by address of the peer.
+    static ref PENDING_EVICTIONS: Mutex<HashMap<[u8; 6], [u8; 6]>> = Mutex::new(HashMap::new());
+}
+
+pub(crate) type EvictionCallback = dyn Fn(&[[u8; 6]]) -> Option<[u8; 6]> + Send + Sync;
+
+impl GattServer {
+    /// Sets a callback choosing the bond to evict when the bond table is full.
+    ///
+    /// When a new peer requests pairing while the Bluetooth stack already stores
+    /// `CONFIG_BT_SMP_MAX_BONDS` bonds, the callback receives the addresses of the bonded peers,
+    /// from the least to the most recently connected, and returns the bond to remove,
+    /// or `None` to reject the pairing.
+    ///
+    /// Without a callback, the least recently connected bond is evicted.
+    /// The bond is only removed once the pairing succeeds, along with the CCCD values
+    /// stored for its peer, so that a failed pairing costs no bond.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, while the GATT server
+    /// is locked: it must not block, nor lock the GATT server.
+    pub fn on_bond_table_full(
+        &mut self,
+        callback: impl Fn(&[[u8; 6]]) -> Option<[u8; 6]> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.eviction_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Picks the bond to evict for a new peer if the bond table is full.
+    ///
+    /// The bond is evicted by [`evict_bond`] once the pairing succeeds.
+    /// Returns `false` if no bond could be evicted, in which case the pairing must be rejected.
+    pub(crate) fn make_room_for_bond(&self, address: [u8; 6]) -> bool {
+        PENDING_EVICTIONS.lock().remove(&address);
+
+        let devices = bonded_devices();
+        if devices.len() < CONFIG_BT_SMP_MAX_BONDS as usize
+            || devices.iter().any(|device| device.bd_addr == address)
+        {
+            return true;
+        }
+
+        let mut peers: Vec<[u8; 6]> = devices.iter().map(|device| device.bd_addr).collect();
+        peers.sort_by_key(|peer| last_seen(*peer));
+
+        let evicted = match &self.eviction_callback {
+            Some(callback) => callback(&peers),
+            None => peers.first().copied(),
+        };
+        let Some(evicted) = evicted else {
+            warn!(
+                target: GAP,
+                "Bond table full, rejecting the pairing of {:02X?}.",
+                address
+            );
+            return false;
+        };
+
+        debug!(
+            target: GAP,
+            "Bond table full, evicting {:02X?} once {:02X?} is paired.",
+            evicted,
+            address
+        );
+        PENDING_EVICTIONS.lock().insert(address, evicted);
+
+        true
+    }
+}
+
+/// Evicts the bond picked for a peer by [`GattServer::make_room_for_bond`],
+/// once the pairing of the peer completed.
+pub(crate) fn evict_bond(address: [u8; 6], paired: bool) {
+    let Some(mut evicted) = PENDING_EVICTIONS.lock().remove(&address) else {
+        return;
+    };
+
+    if !paired
+        || !bonded_devices()
+            .iter()
+            .any(|device| device.bd_addr == evicted)
+    {
+        return;
+    }
+
+    info!(
+        target: GAP,
+        "Bond table full, evicting {:02X?} for {:02X?}.",
+        evicted,
+        address
+    );
+
+    // The CCCD values are keyed by the identity address, which is resolved with the bond.
+    forget_cccds(evicted);
+    if let Err(error) = STORAGE.remove(&last_seen_key(evicted)) {
+        GattServerError::Storage(error.code()).report();
+    }
+
+    unsafe {
+        esp_report!(esp_ble_remove_bond_device(evicted.as_mut_ptr()));
+    }
+}
+
+/// Records a connection of a bonded peer, to find the least recently connected bond.
+pub(crate) fn record_connection(address: [u8; 6]) {
+    let sequence = load_counter(SEQUENCE_KEY).wrapping_add(1);
+    debug!(
+        target: GAP,
+        "Recording connection {} of {:02X?}.",
+        sequence,
+        address
+    );
+
+    for key in [SEQUENCE_KEY, &last_seen_key(address)] {
+        if let Err(error) = STORAGE.store(key, &sequence.to_le_bytes()) {
+            GattServerError::Storage(error.code()).report();
+        }
+    }
+}
+
+/// Returns the counter value of the last connection of a peer, or 0 if it was never recorded.
+fn last_seen(address: [u8; 6]) -> u32 {
+    load_counter(&last_seen_key(address))
+}
+
+fn load_counter(key: &str) -> u32 {
+    match STORAGE.load(key) {
+        Ok(Some(value)) => value.try_into().map_or(0, u32::from_le_bytes),
+        Ok(None) => 0,
+        Err(error) => {
+            GattServerError::Storage(error.code()).report();
+            0
+        }
+    }
+}
+
+/// Returns the storage key of the last connection of a peer.
+///
+/// NVS keys are limited to 15 characters, so only the last four bytes of the address are used.
+fn last_seen_key(address: [u8; 6]) -> String {
+    format!(
+        "{:02X}{:02X}{:02X}{:02X}-seen",
+        address[2], address[3], address[4], address[5]
+    )
+}
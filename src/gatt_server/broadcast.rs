@@ -0,0 +1,99 @@
+use log::{debug, warn};
+
+use crate::gatt_server::GattServer;
+use crate::utilities::log_targets::GAP;
+
+impl GattServer {
+    /// Mirrors the value of the broadcasting characteristic into the service data of the advertisement.
+    ///
+    /// A characteristic is broadcast if it has the "broadcast" property and a client
+    /// enabled it through its SCCD. The advertisement has room for a single service data field,
+    /// so only the first broadcasting characteristic is advertised, and none of them
+    /// while a BTHome advertisement is set.
+    pub(crate) fn update_broadcast_data(&mut self) {
+        let mut broadcasts = Vec::new();
+
+        for profile in &self.profiles {
+            for service in &profile.read().services {
+                let service = service.read();
+
+                for characteristic in &service.characteristics {
+                    let characteristic = characteristic.read();
+
+                    if characteristic.properties.broadcast && characteristic.broadcast_enabled {
+                        broadcasts.push((service.uuid, characteristic.internal_value.clone()));
+                    }
+                }
+            }
+        }
+
+        if !self.bthome_data.is_empty() {
+            if !broadcasts.is_empty() {
+                warn!(
+                    target: GAP,
+                    "{} characteristics are broadcasting, but the BTHome advertisement is advertised instead.",
+                    broadcasts.len()
+                );
+            }
+
+            self.set_service_data(self.bthome_data.clone());
+            return;
+        }
+
+        if broadcasts.len() > 1 {
+            warn!(
+                target: GAP,
+                "{} characteristics are broadcasting, only the first one is advertised.",
+                broadcasts.len()
+            );
+        }
+
+        let broadcast_data = match broadcasts
+            .first()
+            .map(|(uuid, value)| (uuid, uuid.as_uuid16(), value))
+        {
+            Some((_, Some(uuid), value)) => {
+                // The service data starts with the 16-bit service identifier.
+                let mut data = uuid.to_le_bytes().to_vec();
+                data.extend_from_slice(value);
+                data
+            }
+            Some((uuid, None, _)) => {
+                warn!(
+                    target: GAP,
+                    "Cannot broadcast a characteristic of service {}: only 16-bit service identifiers are supported.",
+                    uuid
+                );
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        self.set_service_data(broadcast_data);
+    }
+
+    /// Sets the service data of the advertisement, updating it if it is already configured.
+    ///
+    /// The service data must start with the 16-bit service identifier, in little-endian order.
+    pub(crate) fn set_service_data(&mut self, service_data: Vec<u8>) {
+        if service_data == self.broadcast_data {
+            return;
+        }
+
+        debug!(target: GAP, "Updating service data to {:02X?}.", service_data);
+
+        self.broadcast_data = service_data;
+        if self.broadcast_data.is_empty() {
+            self.advertisement_data.p_service_data = std::ptr::null_mut();
+            self.advertisement_data.service_data_len = 0;
+        } else {
+            self.advertisement_data.p_service_data = self.broadcast_data.as_mut_ptr();
+            self.advertisement_data.service_data_len = self.broadcast_data.len() as u16;
+        }
+
+        // Before the registration, the advertisement data is configured with the new value anyway.
+        if self.advertisement_configured {
+            self.configure_advertisement_data();
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use esp_idf_sys::{
+    esp_mac_type_t_ESP_MAC_BT, esp_read_mac, mbedtls_ccm_context, mbedtls_ccm_encrypt_and_tag,
+    mbedtls_ccm_free, mbedtls_ccm_init, mbedtls_ccm_setkey,
+    mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+};
+use log::warn;
+
+use crate::gatt_server::{error::esp_report, GattServer};
+use crate::utilities::log_targets::GAP;
+
+/// The 16-bit service identifier of BTHome advertisements.
+const BTHOME_UUID: u16 = 0xFCD2;
+
+/// The device information flag of encrypted advertisements.
+const FLAG_ENCRYPTED: u8 = 0x01;
+
+/// The device information flag of advertisements sent on state changes only.
+const FLAG_TRIGGER_BASED: u8 = 0x04;
+
+/// The BTHome version, in the upper bits of the device information.
+const VERSION_2: u8 = 0x40;
+
+/// The length of the message integrity check of encrypted advertisements.
+const MIC_LENGTH: usize = 4;
+
+/// An encoder of sensor measurements into [BTHome v2](https://bthome.io/format/) advertisements,
+/// picked up by Home Assistant without a connection.
+///
+/// Measurements are added with the builder methods, then advertised with
+/// [`GattServer::advertise_bthome`]. The encoder keeps its measurements, so a single value
+/// can be updated before advertising again.
+///
+/// With an encryption key, the measurements are encrypted with AES-CCM, as specified by BTHome.
+/// The nonce includes the Bluetooth MAC address of the device, so the advertisement must use
+/// the public address.
+#[derive(Clone, Default)]
+pub struct BtHome {
+    key: Option<[u8; 16]>,
+    counter: u32,
+    trigger_based: bool,
+    measurements: Vec<(u8, Vec<u8>)>,
+}
+
+impl BtHome {
+    /// Creates a new [`BtHome`] encoder, sending the measurements in plain text.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`BtHome`] encoder, encrypting the measurements with the given key.
+    #[must_use]
+    pub fn encrypted(key: [u8; 16]) -> Self {
+        Self {
+            key: Some(key),
+            ..Self::default()
+        }
+    }
+
+    /// Flags the advertisements as sent on state changes only, instead of periodically.
+    pub fn trigger_based(&mut self) -> &mut Self {
+        self.trigger_based = true;
+        self
+    }
+
+    /// Sets the raw value of a measurement, given its BTHome object identifier.
+    ///
+    /// The value must be encoded as specified for the object, in little-endian order.
+    pub fn measurement(&mut self, object_id: u8, value: &[u8]) -> &mut Self {
+        match self
+            .measurements
+            .iter_mut()
+            .find(|(id, _)| *id == object_id)
+        {
+            Some((_, stored)) => *stored = value.to_vec(),
+            None => self.measurements.push((object_id, value.to_vec())),
+        }
+
+        self
+    }
+
+    /// Sets the packet identifier, letting receivers discard duplicate advertisements.
+    pub fn packet_id(&mut self, packet_id: u8) -> &mut Self {
+        self.measurement(0x00, &[packet_id])
+    }
+
+    /// Sets the battery level, in percent.
+    pub fn battery(&mut self, percent: u8) -> &mut Self {
+        self.measurement(0x01, &[percent])
+    }
+
+    /// Sets the temperature, in degrees Celsius, with a resolution of 0.01 °C.
+    pub fn temperature(&mut self, celsius: f32) -> &mut Self {
+        let value = scaled(celsius, 100.0).clamp(i64::from(i16::MIN), i64::from(i16::MAX));
+        #[allow(clippy::cast_possible_truncation)]
+        self.measurement(0x02, &(value as i16).to_le_bytes())
+    }
+
+    /// Sets the relative humidity, in percent, with a resolution of 0.01 %.
+    pub fn humidity(&mut self, percent: f32) -> &mut Self {
+        self.measurement(0x03, &unsigned(scaled(percent, 100.0), 2))
+    }
+
+    /// Sets the pressure, in hectopascals, with a resolution of 0.01 hPa.
+    pub fn pressure(&mut self, hectopascals: f32) -> &mut Self {
+        self.measurement(0x04, &unsigned(scaled(hectopascals, 100.0), 3))
+    }
+
+    /// Sets the illuminance, in lux, with a resolution of 0.01 lx.
+    pub fn illuminance(&mut self, lux: f32) -> &mut Self {
+        self.measurement(0x05, &unsigned(scaled(lux, 100.0), 3))
+    }
+
+    /// Sets the energy, in kilowatt-hours, with a resolution of 0.001 kWh.
+    pub fn energy(&mut self, kilowatt_hours: f32) -> &mut Self {
+        self.measurement(0x0A, &unsigned(scaled(kilowatt_hours, 1000.0), 3))
+    }
+
+    /// Sets the power, in watts, with a resolution of 0.01 W.
+    pub fn power(&mut self, watts: f32) -> &mut Self {
+        self.measurement(0x0B, &unsigned(scaled(watts, 100.0), 3))
+    }
+
+    /// Sets the voltage, in volts, with a resolution of 0.001 V.
+    pub fn voltage(&mut self, volts: f32) -> &mut Self {
+        self.measurement(0x0C, &unsigned(scaled(volts, 1000.0), 2))
+    }
+
+    /// Sets the carbon dioxide concentration, in parts per million.
+    pub fn co2(&mut self, ppm: u16) -> &mut Self {
+        self.measurement(0x12, &ppm.to_le_bytes())
+    }
+
+    /// Sets the soil moisture, in percent, with a resolution of 0.01 %.
+    pub fn moisture(&mut self, percent: f32) -> &mut Self {
+        self.measurement(0x14, &unsigned(scaled(percent, 100.0), 2))
+    }
+
+    /// Sets whether an opening (door, window) is open.
+    pub fn opening(&mut self, open: bool) -> &mut Self {
+        self.measurement(0x11, &[u8::from(open)])
+    }
+
+    /// Sets whether motion is detected.
+    pub fn motion(&mut self, detected: bool) -> &mut Self {
+        self.measurement(0x21, &[u8::from(detected)])
+    }
+
+    /// Sets a count.
+    pub fn count(&mut self, count: u32) -> &mut Self {
+        self.measurement(0x3E, &count.to_le_bytes())
+    }
+
+    /// Removes all the measurements.
+    pub fn clear(&mut self) -> &mut Self {
+        self.measurements.clear();
+        self
+    }
+
+    /// Encodes the measurements into service data, starting with the BTHome service identifier.
+    ///
+    /// Encrypted advertisements increment the replay counter on every call.
+    /// Returns `None` if the encryption failed.
+    pub fn encode(&mut self) -> Option<Vec<u8>> {
+        let mut device_information = VERSION_2;
+        if self.trigger_based {
+            device_information |= FLAG_TRIGGER_BASED;
+        }
+        if self.key.is_some() {
+            device_information |= FLAG_ENCRYPTED;
+        }
+
+        // Receivers expect the objects in ascending order of identifiers.
+        let mut measurements = self.measurements.clone();
+        measurements.sort_by_key(|(object_id, _)| *object_id);
+
+        let mut payload = Vec::new();
+        for (object_id, value) in measurements {
+            payload.push(object_id);
+            payload.extend_from_slice(&value);
+        }
+
+        let mut data = BTHOME_UUID.to_le_bytes().to_vec();
+        data.push(device_information);
+
+        match self.key {
+            Some(key) => {
+                let counter = self.counter;
+                self.counter = self.counter.wrapping_add(1);
+
+                let (ciphertext, mic) = encrypt(&key, device_information, counter, &payload)?;
+                data.extend_from_slice(&ciphertext);
+                data.extend_from_slice(&counter.to_le_bytes());
+                data.extend_from_slice(&mic);
+            }
+            None => data.extend_from_slice(&payload),
+        }
+
+        Some(data)
+    }
+}
+
+impl std::fmt::Debug for BtHome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BtHome")
+            .field("encrypted", &self.key.is_some())
+            .field("trigger_based", &self.trigger_based)
+            .field("measurements", &self.measurements)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GattServer {
+    /// Advertises the measurements of a [`BtHome`] encoder in the service data of the advertisement.
+    ///
+    /// The advertisement is updated immediately if it is already configured.
+    /// It takes the place of the service data of the broadcasting characteristics,
+    /// as the advertisement has room for a single service data field.
+    pub fn advertise_bthome(&mut self, bthome: &mut BtHome) -> &mut Self {
+        match bthome.encode() {
+            Some(service_data) => {
+                self.bthome_data = service_data;
+                self.update_broadcast_data();
+            }
+            None => warn!(target: GAP, "Cannot encode the BTHome advertisement."),
+        }
+
+        self
+    }
+}
+
+/// Encrypts a BTHome payload with AES-CCM, returning the ciphertext and the message integrity check.
+fn encrypt(
+    key: &[u8; 16],
+    device_information: u8,
+    counter: u32,
+    payload: &[u8],
+) -> Option<(Vec<u8>, [u8; MIC_LENGTH])> {
+    let mut mac = [0u8; 6];
+    if !unsafe { esp_report!(esp_read_mac(mac.as_mut_ptr(), esp_mac_type_t_ESP_MAC_BT)) } {
+        return None;
+    }
+
+    let mut nonce = mac.to_vec();
+    nonce.extend_from_slice(&BTHOME_UUID.to_le_bytes());
+    nonce.push(device_information);
+    nonce.extend_from_slice(&counter.to_le_bytes());
+
+    let mut ciphertext = vec![0u8; payload.len()];
+    let mut mic = [0u8; MIC_LENGTH];
+    let mut context = mbedtls_ccm_context::default();
+
+    let result = unsafe {
+        mbedtls_ccm_init(&mut context);
+
+        let mut result = mbedtls_ccm_setkey(
+            &mut context,
+            mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+            key.as_ptr(),
+            128,
+        );
+        if result == 0 {
+            result = mbedtls_ccm_encrypt_and_tag(
+                &mut context,
+                payload.len(),
+                nonce.as_ptr(),
+                nonce.len(),
+                std::ptr::null(),
+                0,
+                payload.as_ptr(),
+                ciphertext.as_mut_ptr(),
+                mic.as_mut_ptr(),
+                MIC_LENGTH,
+            );
+        }
+
+        mbedtls_ccm_free(&mut context);
+        result
+    };
+
+    if result != 0 {
+        warn!(target: GAP, "BTHome encryption failed with error code {}.", result);
+        return None;
+    }
+
+    Some((ciphertext, mic))
+}
+
+/// Scales a measurement to the integer unit of its object.
+#[allow(clippy::cast_possible_truncation)]
+fn scaled(value: f32, factor: f32) -> i64 {
+    (f64::from(value) * f64::from(factor)).round() as i64
+}
+
+/// Encodes an unsigned value on the given number of little-endian bytes, saturating it.
+#[allow(clippy::cast_sign_loss)]
+fn unsigned(value: i64, length: usize) -> Vec<u8> {
+    let maximum = (1i64 << (8 * length)) - 1;
+    let value = value.clamp(0, maximum) as u64;
+    value.to_le_bytes()[..length].to_vec()
+}
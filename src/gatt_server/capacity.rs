@@ -0,0 +1,134 @@
+//! Bounded storage of the GATT tree, for constrained builds, and buffer sizes.
+//!
+//! With the `heapless` feature, the profiles, services, characteristics and descriptors
+//! are stored in fixed-capacity vectors, and the values in fixed-size buffers, so the memory
+//! used by the GATT tree does not grow at runtime. Otherwise, they are stored in [`Vec`]s.
+
+use std::ops::Deref;
+
+use esp_idf_sys::ESP_GATT_MAX_ATTR_LEN;
+
+#[cfg(feature = "heapless")]
+use crate::gatt_server::GattServerError;
+use crate::gatt_server::{LockedCharacteristic, LockedDescriptor, LockedProfile, LockedService};
+
+/// The maximum number of profiles of the GATT server, with the `heapless` feature.
+pub const MAX_PROFILES: usize = 4;
+
+/// The maximum number of services of a profile, with the `heapless` feature.
+pub const MAX_SERVICES: usize = 8;
+
+/// The maximum number of characteristics of a service, with the `heapless` feature.
+pub const MAX_CHARACTERISTICS: usize = 16;
+
+/// The maximum number of descriptors of a characteristic, with the `heapless` feature.
+pub const MAX_DESCRIPTORS: usize = 4;
+
+/// The maximum length of a characteristic or descriptor value, with the `heapless` feature.
+pub const MAX_VALUE_LENGTH: usize = 64;
+
+/// The length of the responses sent by the application, fixed by the Bluetooth stack.
+pub(crate) const RESPONSE_LENGTH: usize = ESP_GATT_MAX_ATTR_LEN as usize;
+
+#[cfg(feature = "heapless")]
+pub(crate) type Profiles = heapless::Vec<LockedProfile, MAX_PROFILES>;
+#[cfg(feature = "heapless")]
+pub(crate) type Services = heapless::Vec<LockedService, MAX_SERVICES>;
+#[cfg(feature = "heapless")]
+pub(crate) type Characteristics = heapless::Vec<LockedCharacteristic, MAX_CHARACTERISTICS>;
+#[cfg(feature = "heapless")]
+pub(crate) type Descriptors = heapless::Vec<LockedDescriptor, MAX_DESCRIPTORS>;
+#[cfg(feature = "heapless")]
+pub(crate) type Buffer = heapless::Vec<u8, MAX_VALUE_LENGTH>;
+
+#[cfg(not(feature = "heapless"))]
+pub(crate) type Profiles = Vec<LockedProfile>;
+#[cfg(not(feature = "heapless"))]
+pub(crate) type Services = Vec<LockedService>;
+#[cfg(not(feature = "heapless"))]
+pub(crate) type Characteristics = Vec<LockedCharacteristic>;
+#[cfg(not(feature = "heapless"))]
+pub(crate) type Descriptors = Vec<LockedDescriptor>;
+#[cfg(not(feature = "heapless"))]
+pub(crate) type Buffer = Vec<u8>;
+
+/// The value of a characteristic or a descriptor.
+///
+/// The values of the constant declarations are borrowed from them, and stay in flash
+/// until they first change.
+#[derive(Clone)]
+pub(crate) enum Value {
+    /// A value borrowed from a constant declaration.
+    Static(&'static [u8]),
+    /// A value set at runtime.
+    Owned(Buffer),
+}
+
+impl Value {
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Static(bytes) => bytes,
+            Self::Owned(buffer) => buffer.as_slice(),
+        }
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::Owned(Buffer::new())
+    }
+}
+
+impl Deref for Value {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+/// Appends an attribute to a collection of the GATT tree.
+///
+/// If the collection is full, a [`GattServerError::CapacityExceeded`] is reported
+/// and the attribute is dropped.
+#[cfg(feature = "heapless")]
+pub(crate) fn push<T, const N: usize>(
+    collection: &mut heapless::Vec<T, N>,
+    item: T,
+    name: &'static str,
+) {
+    if collection.push(item).is_err() {
+        GattServerError::CapacityExceeded {
+            collection: name,
+            capacity: N,
+        }
+        .report();
+    }
+}
+
+/// Appends an attribute to a collection of the GATT tree.
+#[cfg(not(feature = "heapless"))]
+pub(crate) fn push<T>(collection: &mut Vec<T>, item: T, _name: &'static str) {
+    collection.push(item);
+}
+
+/// Copies bytes into a value buffer.
+///
+/// Returns `None` if the bytes do not fit in [`MAX_VALUE_LENGTH`].
+#[cfg(feature = "heapless")]
+pub(crate) fn value(bytes: &[u8]) -> Option<Value> {
+    Buffer::from_slice(bytes).ok().map(Value::Owned)
+}
+
+/// Copies bytes into a value buffer.
+#[cfg(not(feature = "heapless"))]
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn value(bytes: &[u8]) -> Option<Value> {
+    Some(Value::Owned(bytes.to_vec()))
+}
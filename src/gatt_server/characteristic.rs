@@ -1,50 +1,104 @@
+use crate::utilities::log_targets::{GATTS, NOTIFY};
 use crate::{
+    gatt_server::attribute_table::AttributeTable,
+    gatt_server::auto_notify::AutoNotify,
+    gatt_server::capacity::{self, Descriptors, Value, MAX_VALUE_LENGTH},
+    gatt_server::delivery::{DeliveryCallback, QueuedValue, ReliableDelivery, DELIVERY_QUEUE},
     gatt_server::descriptor::Descriptor,
     gatt_server::descriptor::LockedDescriptor,
-    leaky_box_raw,
-    utilities::{AttributeControl, AttributePermissions, BleUuid, CharacteristicProperties},
+    gatt_server::encryption_policy::registered_permissions,
+    gatt_server::error::{esp_check, esp_report},
+    gatt_server::indication::PENDING_INDICATIONS,
+    gatt_server::read_cache::ReadCache,
+    gatt_server::registration::RegistrationRetries,
+    gatt_server::GattServerError,
+    utilities::{
+        sig::descriptors, AttributeControl, AttributeOperation, AttributePermissions, BleUuid,
+        CharacteristicProperties, Connection, DeliveryOutcome, FromGattValue, NotificationMode,
+        PayloadCodec, PrivilegeLevel, ToGattValue,
+    },
+    BluedroidError,
 };
 
 use esp_idf_sys::{
     esp_attr_control_t, esp_attr_value_t, esp_ble_gatts_add_char,
     esp_ble_gatts_cb_param_t_gatts_read_evt_param, esp_ble_gatts_cb_param_t_gatts_write_evt_param,
-    esp_ble_gatts_set_attr_value, esp_nofail,
+    esp_ble_gatts_get_attr_value, esp_ble_gatts_send_indicate, esp_ble_gatts_set_attr_value,
+    esp_gatt_status_t, esp_gatt_status_t_ESP_GATT_OK, ESP_ERR_INVALID_SIZE,
 };
 use log::{debug, warn};
 use parking_lot::RwLock;
-use std::{fmt::Formatter, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt::Formatter,
+    sync::{mpsc::Receiver, Arc},
+    time::{Duration, Instant},
+};
 
 /// Shorthand for our locked characteristics that are returned everywhere
 pub type LockedCharacteristic = Arc<RwLock<Characteristic>>;
 type WriteCallback = dyn Fn(Vec<u8>, esp_ble_gatts_cb_param_t_gatts_write_evt_param) + Send + Sync;
+type AccessPolicy = dyn Fn(&Connection, AttributeOperation) -> bool + Send + Sync;
 
 /// Represents a GATT characteristic.
 #[derive(Clone)]
 pub struct Characteristic {
     /// The name of the characteristic, for debugging purposes.
-    name: Option<String>,
+    pub(crate) name: Option<Cow<'static, str>>,
     /// The characteristic identifier.
     pub(crate) uuid: BleUuid,
     /// The function to be called when a write happens. This functions receives the written value in the first parameter, a `Vec<u8>`.
     pub(crate) write_callback: Option<Arc<WriteCallback>>,
+    /// The function that decides whether a connection can access this characteristic.
+    access_policy: Option<Arc<AccessPolicy>>,
+    /// The privilege level a connection needs to access this characteristic.
+    required_privilege: PrivilegeLevel,
     /// A list of descriptors for this characteristic.
-    pub(crate) descriptors: Vec<LockedDescriptor>,
+    pub(crate) descriptors: Descriptors,
     /// The handle that the Bluetooth stack assigned to this characteristic.
     pub(crate) attribute_handle: Option<u16>,
     /// The handle of the containing service.
-    service_handle: Option<u16>,
+    pub(crate) service_handle: Option<u16>,
+    /// The interface of the profile this characteristic is registered in.
+    pub(crate) interface: Option<u8>,
     /// The access permissions for this characteristic.
-    permissions: AttributePermissions,
+    pub(crate) permissions: AttributePermissions,
     /// The properties that are announced for this characteristic.
     pub(crate) properties: CharacteristicProperties,
+    /// The clients to be notified when the value of this characteristic changes.
+    pub(crate) notification_mode: NotificationMode,
+    /// The minimum time between two notifications or indications of this characteristic.
+    pub(crate) min_notify_interval: Option<Duration>,
+    /// The time of the latest notification or indication of this characteristic.
+    pub(crate) last_notification: Option<Instant>,
+    /// Whether a coalesced notification is waiting for the interval to elapse.
+    pub(crate) notification_pending: bool,
+    /// The number of value change events whose notifications are sent by a transaction instead.
+    pub(crate) deferred_notifications: u32,
+    /// Whether a client enabled the broadcast of this characteristic through its SCCD.
+    pub(crate) broadcast_enabled: bool,
+    /// The retry settings of the delivery queue, if not the default ones.
+    reliable_delivery: Option<ReliableDelivery>,
+    /// The function to be called with the outcome of every delivery.
+    delivery_callback: Option<Arc<DeliveryCallback>>,
     /// The way this characteristic is read.
     pub(crate) control: AttributeControl,
+    /// Whether the written values are stored by the crate instead of the Bluetooth stack,
+    /// for the access checks to apply to them.
+    pub(crate) stores_writes: bool,
+    /// The cache of the values returned by the read callback, if enabled.
+    pub(crate) read_cache: Option<ReadCache>,
     /// A buffer for keeping in memory the actual value of this characteristic.
-    pub(crate) internal_value: Vec<u8>,
+    pub(crate) internal_value: Value,
     /// The maximum length of the characteristic value.
     max_value_length: Option<u16>,
     /// A copy of the `control` property, in the `esp_attr_control_t` type, passed directly to the Bluetooth stack.
     internal_control: esp_attr_control_t,
+    /// The failed registration attempts of this characteristic.
+    pub(crate) registration: RegistrationRetries,
+    /// The periodic value updates of this characteristic.
+    pub(crate) auto_notify: Option<AutoNotify>,
 }
 
 impl Characteristic {
@@ -54,22 +108,37 @@ impl Characteristic {
         Self {
             name: None,
             uuid,
-            internal_value: vec![0],
+            internal_value: capacity::value(&[0]).unwrap_or_default(),
             write_callback: None,
-            descriptors: Vec::new(),
+            access_policy: None,
+            required_privilege: PrivilegeLevel::Anonymous,
+            descriptors: Descriptors::new(),
             attribute_handle: None,
             service_handle: None,
+            interface: None,
             permissions: AttributePermissions::default(),
             properties: CharacteristicProperties::default(),
+            notification_mode: NotificationMode::default(),
+            min_notify_interval: None,
+            last_notification: None,
+            notification_pending: false,
+            deferred_notifications: 0,
+            broadcast_enabled: false,
+            reliable_delivery: None,
+            delivery_callback: None,
             control: AttributeControl::AutomaticResponse(vec![0]),
+            stores_writes: false,
+            read_cache: None,
             internal_control: AttributeControl::AutomaticResponse(vec![0]).into(),
             max_value_length: None,
+            registration: RegistrationRetries::new(),
+            auto_notify: None,
         }
     }
 
     /// Adds a [`Descriptor`] to the [`Characteristic`].
     pub fn descriptor(&mut self, descriptor: &LockedDescriptor) -> &mut Self {
-        self.descriptors.push(descriptor.clone());
+        capacity::push(&mut self.descriptors, descriptor.clone(), "descriptors");
         self
     }
 
@@ -77,7 +146,7 @@ impl Characteristic {
     ///
     /// This name is only used for debugging purposes.
     pub fn name<S: Into<String>>(&mut self, name: S) -> &mut Self {
-        self.name = Some(name.into());
+        self.name = Some(Cow::Owned(name.into()));
         self
     }
 
@@ -89,10 +158,98 @@ impl Characteristic {
 
     /// Sets the properties for this [`Characteristic`].
     pub fn properties(&mut self, properties: CharacteristicProperties) -> &mut Self {
+        if let Some(ignored) = properties.ignored {
+            warn!(
+                target: GATTS,
+                "Cannot set notify and indicate at the same time. Ignoring {ignored}."
+            );
+        }
+
         self.properties = properties;
         self
     }
 
+    /// Sets which clients are notified when the value of this [`Characteristic`] changes.
+    ///
+    /// By default, only the clients that subscribed through the CCCD are notified.
+    /// See [`NotificationMode`] for the available modes.
+    pub fn notification_mode(&mut self, mode: NotificationMode) -> &mut Self {
+        self.notification_mode = mode;
+        self
+    }
+
+    /// Sets the minimum time between two notifications or indications of this [`Characteristic`].
+    ///
+    /// Value changes arriving faster than this are coalesced: only the latest value
+    /// is sent to the clients, once the interval has elapsed.
+    /// This protects slow clients from being flooded by frequently updated values.
+    pub fn min_notify_interval(&mut self, interval: Duration) -> &mut Self {
+        self.min_notify_interval = Some(interval);
+        self
+    }
+
+    /// Sets the retry settings of the delivery queue for the value changes of this [`Characteristic`].
+    ///
+    /// Notifications and indications are queued per connection and sent in order,
+    /// waiting for the congestion of the connection to clear.
+    /// A value change that is rejected or dropped by the Bluetooth stack, for example because
+    /// the connection is congested, or an indication that is not confirmed within
+    /// `confirmation_timeout`, is retried up to `retries` times, backing off between attempts.
+    /// By default, value changes are retried 3 times, and indications are confirmed within 5 seconds.
+    ///
+    /// The outcome of every delivery is reported to the [`Self::on_delivery`] callback.
+    pub fn reliable_delivery(&mut self, retries: u8, confirmation_timeout: Duration) -> &mut Self {
+        self.reliable_delivery = Some(ReliableDelivery {
+            retries,
+            confirmation_timeout,
+        });
+        self
+    }
+
+    /// Sets the delivery callback for this characteristic.
+    /// The callback will be called with the outcome of every value change sent through
+    /// the delivery queue, see [`Self::reliable_delivery`].
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the worker thread of the GATT server,
+    /// or from the Bluetooth stack's context, so it must not block.
+    pub fn on_delivery(
+        &mut self,
+        callback: impl Fn(Connection, DeliveryOutcome) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.delivery_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Periodically sets the value returned by `producer` to this characteristic,
+    /// notifying or indicating it to the subscribed clients.
+    ///
+    /// The timer starts when the characteristic is registered. On every tick, `producer` is only called
+    /// if at least one client is subscribed to this characteristic.
+    ///
+    /// # Notes
+    ///
+    /// The producer is called from the `esp_timer` task, so it must not block.
+    pub fn auto_notify_every(
+        &mut self,
+        period: Duration,
+        producer: impl Fn() -> Vec<u8> + Send + Sync + 'static,
+    ) -> &mut Self {
+        if !self.properties.notify && !self.properties.indicate {
+            warn!(
+                target: GATTS,
+                "Characteristic {} does not have the notify or indicate property. Ignoring automatic notifications.",
+                self
+            );
+
+            return self;
+        }
+
+        self.auto_notify = Some(AutoNotify::new(period, Arc::new(producer)));
+        self
+    }
+
     /// Sets the maximum length for the content of this characteristic. The default value is 8 bytes.
     pub fn max_value_length(&mut self, length: u16) -> &mut Self {
         self.max_value_length = Some(length);
@@ -104,6 +261,9 @@ impl Characteristic {
     ///
     /// The callback must return a `Vec<u8>` containing the value to be put into the response to the read request.
     ///
+    /// Values longer than the MTU are read in several parts: the callback is called for the first one,
+    /// and the blob reads that follow are answered from the value it returned.
+    ///
     /// # Notes
     ///
     /// The callback will be called from the Bluetooth stack's context, so it must not block.
@@ -113,8 +273,9 @@ impl Characteristic {
         &mut self,
         callback: C,
     ) -> &mut Self {
-        if !self.properties.read || !self.permissions.read_access {
+        if !self.is_readable() {
             warn!(
+                target: GATTS,
                 "Characteristic {} does not have read permissions. Ignoring read callback.",
                 self
             );
@@ -124,10 +285,37 @@ impl Characteristic {
 
         self.control = AttributeControl::ResponseByApp(Arc::new(callback));
         self.internal_control = self.control.clone().into();
+        self.invalidate();
 
         self
     }
 
+    /// Caches the values returned by the read callback for the given duration.
+    ///
+    /// Reads arriving while the cached value is fresh, from any client, are answered
+    /// without calling the read callback. This spares expensive callbacks, such as sensor reads,
+    /// from bursts of reads. Use [`Self::invalidate`] to discard the cached value
+    /// before it expires.
+    ///
+    /// # Notes
+    ///
+    /// The cached value is shared by all the clients, so the read callback must not depend
+    /// on the connection it is called for.
+    pub fn cache_reads(&mut self, ttl: Duration) -> &mut Self {
+        self.read_cache = Some(ReadCache::new(ttl));
+        self
+    }
+
+    /// Discards the cached value of this [`Characteristic`], so that the next read
+    /// calls the read callback again.
+    ///
+    /// Does nothing if [read caching](Self::cache_reads) is not enabled.
+    pub fn invalidate(&self) {
+        if let Some(cache) = &self.read_cache {
+            cache.invalidate();
+        }
+    }
+
     /// Sets the write callback for this characteristic.
     /// The callback will be called when a client writes to this characteristic.
     ///
@@ -140,10 +328,9 @@ impl Characteristic {
             + Sync
             + 'static,
     ) -> &mut Self {
-        if !((self.properties.write || self.properties.write_without_response)
-            && self.permissions.write_access)
-        {
+        if !self.is_writable() {
             warn!(
+                target: GATTS,
                 "Characteristic {} does not have write permissions. Ignoring write callback.",
                 self
             );
@@ -155,6 +342,42 @@ impl Characteristic {
         self
     }
 
+    /// Sets the access policy for this characteristic.
+    ///
+    /// The policy is evaluated before invoking the read and write callbacks, and receives
+    /// the requesting [`Connection`] and the requested [`AttributeOperation`].
+    /// If it returns `false`, the callback is not invoked and the request is rejected
+    /// with an "insufficient authorization" error.
+    ///
+    /// # Notes
+    ///
+    /// The Bluetooth stack cannot evaluate the policy, so a characteristic without a read callback
+    /// is registered to be answered by the crate instead: its reads are answered with the stored value,
+    /// and its writes are stored once the policy allows them.
+    pub fn access_policy(
+        &mut self,
+        policy: impl Fn(&Connection, AttributeOperation) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.access_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Sets the privilege level a connection needs to read and write this characteristic.
+    ///
+    /// The application grants levels to the connections with [`Connection::grant_privilege`].
+    /// Requests of connections with a lower level are rejected with an "insufficient authorization"
+    /// error, before the access policy and the callbacks are evaluated.
+    ///
+    /// # Notes
+    ///
+    /// The Bluetooth stack cannot evaluate the privilege level, so a characteristic without a read
+    /// callback that requires more than [`PrivilegeLevel::Anonymous`] is registered to be answered
+    /// by the crate instead, as with an [access policy](Characteristic::access_policy).
+    pub fn required_privilege(&mut self, level: PrivilegeLevel) -> &mut Self {
+        self.required_privilege = level;
+        self
+    }
+
     /// Creates a new "User description" descriptor for this characteristic
     /// that contains the name of the characteristic.
     pub fn show_name(&mut self) -> &mut Self {
@@ -162,8 +385,8 @@ impl Characteristic {
             self.descriptor(&Arc::new(RwLock::new(Descriptor::user_description(name))));
         }
 
-        if let BleUuid::Uuid16(_) = self.uuid {
-            warn!("You're specifying a user description for a standard characteristic. This might be useless.");
+        if self.uuid.as_uuid16().is_some() {
+            warn!(target: GATTS, "You're specifying a user description for a standard characteristic. This might be useless.");
         }
 
         self
@@ -171,11 +394,16 @@ impl Characteristic {
 
     /// Sets the value of this [`Characteristic`].
     ///
-    /// Sends notifications and indications to all subscribed clients.
+    /// The value can be anything that implements [`ToGattValue`], such as a byte vector,
+    /// a string or a number, which is encoded in little-endian byte order.
     ///
-    /// # Panics
+    /// Sends notifications and indications to the clients selected by the
+    /// [`NotificationMode`] of this characteristic, which defaults to the subscribed clients only.
     ///
-    /// Panics if the value is too long and the characteristic is already registered.
+    /// A value the characteristic does not accept, such as a value longer than its maximum length,
+    /// is reported to the [error callback](crate::gatt_server::GattServer::on_error),
+    /// and the current value is kept. Use [`Characteristic::try_set_value`] to handle
+    /// the failure instead.
     ///
     /// # Notes
     ///
@@ -185,29 +413,63 @@ impl Characteristic {
     /// then you'll never need to use the [`Self.value_length`] method, because
     /// the maximum size will be automatically set to the length of the latest value
     /// set before starting the server.
-    pub fn set_value<T: Into<Vec<u8>>>(&mut self, value: T) -> &mut Self {
-        let value: Vec<u8> = value.into();
+    pub fn set_value<T: ToGattValue>(&mut self, value: T) -> &mut Self {
+        if let Err(error) = self.store_value(&value.to_gatt_value()) {
+            error.report();
+        }
 
-        #[allow(clippy::manual_assert)]
-        if let Some(max_value_length) = self.max_value_length {
-            if value.len() > max_value_length as usize {
-                panic!(
-                    "Value is too long for characteristic {self}. The explicitly set maximum length is {max_value_length} bytes."
-                );
-            }
-        } else if self.attribute_handle.is_some() && value.len() > self.internal_value.len() {
-            panic!(
-                "Value is too long for characteristic {}. The implicitly set maximum length is {} bytes.",
-                self,
-                self.internal_value.len()
-            );
+        self
+    }
+
+    /// Sets the value of this [`Characteristic`], like [`Characteristic::set_value`],
+    /// returning its failures instead of reporting them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::ValueTooLong`] if the value is too long, and the value is left
+    /// unchanged. Returns a [`GattServerError::Stack`] if the Bluetooth stack rejects the value:
+    /// the clients keep reading the previous one, while [`Characteristic::value`] returns the new one.
+    pub fn try_set_value<T: ToGattValue>(&mut self, value: T) -> Result<&mut Self, BluedroidError> {
+        self.store_value(&value.to_gatt_value())?;
+        Ok(self)
+    }
+
+    /// Stores a value, and hands it to the Bluetooth stack if the characteristic is registered.
+    fn store_value(&mut self, value: &[u8]) -> Result<(), GattServerError> {
+        // Once registered, the value cannot grow past the length it was registered with.
+        let capacity = match self.max_value_length {
+            Some(max_value_length) => Some(usize::from(max_value_length)),
+            None if self.attribute_handle.is_some() => Some(self.internal_value.len()),
+            None => None,
+        };
+
+        if let Some(capacity) = capacity.filter(|capacity| value.len() > *capacity) {
+            return Err(GattServerError::ValueTooLong {
+                attribute: self.to_string(),
+                length: value.len(),
+                capacity,
+            });
         }
 
+        let Some(value) = capacity::value(value) else {
+            return Err(GattServerError::ValueTooLong {
+                attribute: self.to_string(),
+                length: value.len(),
+                capacity: MAX_VALUE_LENGTH,
+            });
+        };
+
         self.internal_value = value;
-        self.control = AttributeControl::AutomaticResponse(self.internal_value.clone());
-        self.internal_control = self.control.clone().into();
+
+        // Once registered, the characteristic is answered the way it was registered with.
+        if self.attribute_handle.is_none() {
+            self.control =
+                AttributeControl::AutomaticResponse(self.internal_value.as_slice().to_vec());
+            self.internal_control = self.control.clone().into();
+        }
 
         debug!(
+            target: GATTS,
             "Trying to set value of {} to {:02X?}.",
             self, self.internal_value
         );
@@ -215,17 +477,300 @@ impl Characteristic {
         if let Some(handle) = self.attribute_handle {
             #[allow(clippy::cast_possible_truncation)]
             unsafe {
-                esp_nofail!(esp_ble_gatts_set_attr_value(
+                esp_check!(esp_ble_gatts_set_attr_value(
                     handle,
                     self.internal_value.len() as u16,
                     self.internal_value.as_slice().as_ptr()
-                ));
+                ))?;
             }
         }
 
+        Ok(())
+    }
+
+    /// Sets the initial value of this [`Characteristic`] from a constant declaration,
+    /// borrowing it until the value first changes.
+    pub(crate) fn set_static_value(&mut self, value: &'static [u8]) -> &mut Self {
+        self.internal_value = Value::Static(value);
         self
     }
 
+    /// Stores a value written by a client, in place of the Bluetooth stack.
+    ///
+    /// Like the values the stack stores, it does not notify the other clients.
+    pub(crate) fn store_written_value(&mut self, value: &[u8]) -> Result<(), GattServerError> {
+        self.store_value(value)?;
+
+        // The value change event must not trigger notifications.
+        if self.attribute_handle.is_some() {
+            self.deferred_notifications += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the current value of this [`Characteristic`], decoded as `T`.
+    ///
+    /// Returns `None` if the stored bytes cannot be decoded as `T`.
+    #[must_use]
+    pub fn value<T: FromGattValue>(&self) -> Option<T> {
+        T::from_gatt_value(&self.internal_value)
+    }
+
+    /// Encodes a value with the [`PayloadCodec`] `C`, and sets it as the value of this [`Characteristic`].
+    ///
+    /// If the value cannot be encoded, a warning is logged and the current value is kept.
+    /// An encoded value the characteristic does not accept is reported,
+    /// like with [`Characteristic::set_value`].
+    pub fn set_encoded_value<C: PayloadCodec<T>, T>(&mut self, value: &T) -> &mut Self {
+        if let Some(bytes) = C::encode(value) {
+            self.set_value(bytes)
+        } else {
+            warn!(target: GATTS, "Cannot encode the value of characteristic {}.", self);
+            self
+        }
+    }
+
+    /// Returns the current value of this [`Characteristic`], decoded with the [`PayloadCodec`] `C`.
+    ///
+    /// Returns `None` if the stored bytes cannot be decoded.
+    #[must_use]
+    pub fn decoded_value<C: PayloadCodec<T>, T>(&self) -> Option<T> {
+        C::decode(&self.internal_value)
+    }
+
+    /// Sets the write callback for this characteristic, decoding the written values
+    /// with the [`PayloadCodec`] `C`.
+    ///
+    /// Values that cannot be decoded are ignored, with a warning.
+    pub fn on_decoded_write<C: PayloadCodec<T>, T>(
+        &mut self,
+        callback: impl Fn(T, esp_ble_gatts_cb_param_t_gatts_write_evt_param) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let description = self.to_string();
+        self.on_write(move |value, param| match C::decode(&value) {
+            Some(value) => callback(value, param),
+            None => warn!(
+                target: GATTS,
+                "Cannot decode the value written to characteristic {}.",
+                description
+            ),
+        })
+    }
+
+    /// Sends an indication with the current value to the given [`Connection`],
+    /// and waits until the client confirms it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if the indication cannot be sent, if the client rejects it,
+    /// or if the client does not confirm it within the given timeout:
+    /// see [`GattServerError::NotConfirmed`].
+    ///
+    /// # Notes
+    ///
+    /// This function blocks the calling thread, so it must not be called from a callback:
+    /// the confirmation is delivered by the Bluetooth stack's context.
+    pub fn indicate_and_wait(
+        &self,
+        connection: Connection,
+        timeout: Duration,
+    ) -> Result<(), BluedroidError> {
+        let (token, receiver) = self.send_indication(connection)?;
+
+        Ok(Self::wait_for_confirmation(token, &receiver, timeout)?)
+    }
+
+    /// Sends an indication with the current value to all the clients that subscribed to indications,
+    /// and waits until they confirm it.
+    ///
+    /// Returns every subscribed [`Connection`], along with the outcome of its indication,
+    /// as [`Characteristic::indicate_and_wait`] returns it.
+    ///
+    /// The connections are a snapshot taken with [`GattServer::connections`] beforehand:
+    /// the GATT server must be locked before the characteristic, never while it is held,
+    /// because the Bluetooth stack's context locks them in that order.
+    ///
+    /// # Notes
+    ///
+    /// This function blocks the calling thread, so it must not be called from a callback:
+    /// the confirmations are delivered by the Bluetooth stack's context.
+    ///
+    /// [`GattServer::connections`]: crate::gatt_server::GattServer::connections
+    #[must_use]
+    pub fn indicate_all(
+        &self,
+        connections: &HashSet<Connection>,
+        timeout: Duration,
+    ) -> Vec<(Connection, Result<(), BluedroidError>)> {
+        let deadline = Instant::now() + timeout;
+
+        // Send all the indications first, so that the clients confirm them in parallel.
+        let pending: Vec<_> = connections
+            .iter()
+            .copied()
+            .filter(|connection| matches!(self.subscription_status(*connection), Some((_, true))))
+            .map(|connection| (connection, self.send_indication(connection)))
+            .collect();
+
+        pending
+            .into_iter()
+            .map(|(connection, pending)| {
+                let outcome = pending.and_then(|(token, receiver)| {
+                    Self::wait_for_confirmation(
+                        token,
+                        &receiver,
+                        deadline.saturating_duration_since(Instant::now()),
+                    )
+                });
+
+                (connection, outcome.map_err(BluedroidError::from))
+            })
+            .collect()
+    }
+
+    /// Notifies the given value to all the clients that subscribed to notifications,
+    /// without changing the current value of this [`Characteristic`].
+    ///
+    /// This suits telemetry that must not be readable afterwards. The value goes through
+    /// the delivery queue, like the value changes, and its outcome is reported to the
+    /// [`Self::on_delivery`] callback.
+    ///
+    /// The connections are a snapshot taken with [`GattServer::connections`] beforehand:
+    /// the GATT server must be locked before the characteristic, never while it is held,
+    /// because the Bluetooth stack's context locks them in that order.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if this [`Characteristic`] is not registered yet,
+    /// if it does not have the "notify" property, or if the value is longer than `mtu() - 3`
+    /// bytes on a connection: see [`Connection::mtu`].
+    /// The value is still sent to the other clients in the latter case.
+    ///
+    /// [`GattServer::connections`]: crate::gatt_server::GattServer::connections
+    pub fn notify<T: ToGattValue>(
+        &self,
+        connections: &HashSet<Connection>,
+        value: T,
+    ) -> Result<(), BluedroidError> {
+        Ok(self.send_value(connections, &value.to_gatt_value(), false)?)
+    }
+
+    /// Indicates the given value to all the clients that subscribed to indications,
+    /// without changing the current value of this [`Characteristic`].
+    ///
+    /// The value goes through the delivery queue, like the value changes, and its outcome
+    /// is reported to the [`Self::on_delivery`] callback once confirmed.
+    /// The connections are a snapshot taken with [`GattServer::connections`] beforehand,
+    /// as for [`Characteristic::notify`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BluedroidError`] if this [`Characteristic`] is not registered yet,
+    /// if it does not have the "indicate" property, or if the value is longer than `mtu() - 3`
+    /// bytes on a connection: see [`Connection::mtu`].
+    /// The value is still sent to the other clients in the latter case.
+    ///
+    /// [`GattServer::connections`]: crate::gatt_server::GattServer::connections
+    pub fn indicate<T: ToGattValue>(
+        &self,
+        connections: &HashSet<Connection>,
+        value: T,
+    ) -> Result<(), BluedroidError> {
+        Ok(self.send_value(connections, &value.to_gatt_value(), true)?)
+    }
+
+    /// Queues a value for the subscribed clients, as an indication or a notification,
+    /// without storing it.
+    fn send_value(
+        &self,
+        connections: &HashSet<Connection>,
+        value: &[u8],
+        indicate: bool,
+    ) -> Result<(), GattServerError> {
+        let (Some(interface), Some(handle)) = (self.interface, self.attribute_handle) else {
+            return Err(GattServerError::NotRegistered(self.to_string()));
+        };
+
+        let (property, supported) = if indicate {
+            ("indicate", self.properties.indicate)
+        } else {
+            ("notify", self.properties.notify)
+        };
+
+        if !supported {
+            return Err(GattServerError::MissingProperty {
+                attribute: self.to_string(),
+                property,
+            });
+        }
+
+        let mut result = Ok(());
+
+        for connection in connections.iter().copied() {
+            let subscribed = match self.subscription_status(connection) {
+                Some((_, indication)) if indicate => indication,
+                Some((notification, _)) => notification,
+                None => false,
+            };
+
+            if !subscribed {
+                continue;
+            }
+
+            // The ATT header of a notification or an indication takes 3 bytes.
+            let capacity = usize::from(connection.mtu().saturating_sub(3));
+            if value.len() > capacity {
+                warn!(
+                    target: NOTIFY,
+                    "Cannot send a value of {} to {}: {} bytes do not fit in its MTU.",
+                    self,
+                    connection,
+                    value.len()
+                );
+                result = result.and(Err(GattServerError::ValueTooLong {
+                    attribute: self.to_string(),
+                    length: value.len(),
+                    capacity,
+                }));
+                continue;
+            }
+
+            if indicate {
+                debug!(target: NOTIFY, "Indicating {} value to {}.", self, connection);
+            } else {
+                debug!(target: NOTIFY, "Notifying {} value to {}.", self, connection);
+            }
+
+            DELIVERY_QUEUE.push(
+                connection,
+                QueuedValue {
+                    interface,
+                    handle,
+                    value: value.to_vec(),
+                    indicate,
+                    settings: self.reliable_delivery.unwrap_or_default(),
+                    callback: self.delivery_callback.clone(),
+                    bounded: true,
+                },
+            );
+        }
+
+        result
+    }
+
+    /// Returns an iterator over the descriptors of the [`Characteristic`], in declaration order.
+    #[must_use]
+    pub fn descriptors(&self) -> impl ExactSizeIterator<Item = &LockedDescriptor> {
+        self.descriptors.iter()
+    }
+
+    /// Returns the attribute handle of the [`Characteristic`] value, once registered in the Bluetooth stack.
+    #[must_use]
+    pub const fn handle(&self) -> Option<u16> {
+        self.attribute_handle
+    }
+
     /// Returns a reference to the built [`Characteristic`] behind an `Arc` and an `RwLock`.
     ///
     /// The returned value can be passed to any function of this crate that expects a [`Characteristic`].
@@ -238,39 +783,97 @@ impl Characteristic {
     /// Registers the [`Characteristic`] at the given service handle.
     pub(crate) fn register_self(&mut self, service_handle: u16) {
         debug!(
+            target: GATTS,
             "Registering {} into service at handle 0x{:04x}.",
             self, service_handle
         );
         self.service_handle = Some(service_handle);
+        self.registration.requested();
+        self.prepare_registration();
 
+        // The Bluetooth stack copies the identifier and the value before the call returns.
+        let mut uuid = self.uuid.into();
+        #[allow(clippy::cast_possible_truncation)]
+        let mut value = esp_attr_value_t {
+            attr_max_len: self
+                .max_value_length
+                .unwrap_or(self.internal_value.len() as u16),
+            attr_len: self.internal_value.len() as u16,
+            attr_value: self.internal_value.as_ptr().cast_mut(),
+        };
+
+        unsafe {
+            esp_report!(esp_ble_gatts_add_char(
+                service_handle,
+                &mut uuid,
+                registered_permissions(self.permissions),
+                self.properties.into(),
+                &mut value,
+                &mut self.internal_control,
+            ));
+        }
+    }
+
+    /// Adds the declaration, the value and the descriptors of this [`Characteristic`]
+    /// to the attribute table of its service.
+    pub(crate) fn add_to_table(&mut self, table: &mut AttributeTable) {
+        debug!(target: GATTS, "Adding {} to an attribute table.", self);
+        self.registration.requested();
+        self.prepare_registration();
+
+        #[allow(clippy::cast_possible_truncation)]
+        let max_length = self
+            .max_value_length
+            .unwrap_or(self.internal_value.len() as u16);
+
+        table.characteristic(self.properties.into());
+        table.attribute(
+            self.uuid,
+            registered_permissions(self.permissions),
+            self.internal_control,
+            max_length,
+            &self.internal_value,
+        );
+
+        self.descriptors.iter().for_each(|descriptor| {
+            descriptor.read().add_to_table(table);
+        });
+    }
+
+    /// Checks the value and adds the configuration descriptors this [`Characteristic`] needs,
+    /// before registering it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the characteristic is answered automatically, but has no value.
+    fn prepare_registration(&mut self) {
         #[allow(clippy::manual_assert)]
         if let AttributeControl::AutomaticResponse(_) = self.control {
             if self.internal_value.is_empty() {
                 panic!("Automatic response requires a value to be set.");
             }
+
+            // The stack would answer without evaluating the access policy
+            // nor the required privilege level.
+            if self.access_policy.is_some() || self.required_privilege > PrivilegeLevel::Anonymous {
+                self.control = AttributeControl::ResponseByApp(Arc::new(read_stored_value));
+                self.internal_control = self.control.clone().into();
+                self.stores_writes = true;
+            }
         }
 
-        // Register a CCCD if needed.
-        if self.properties.notify || self.properties.indicate {
+        // Register a CCCD if needed, unless this is a registration retry.
+        if (self.properties.notify || self.properties.indicate)
+            && !self.has_descriptor(descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION)
+        {
             self.descriptor(&Descriptor::cccd().build());
         }
 
-        #[allow(clippy::cast_possible_truncation)]
-        unsafe {
-            esp_nofail!(esp_ble_gatts_add_char(
-                service_handle,
-                leaky_box_raw!(self.uuid.into()),
-                self.permissions.into(),
-                self.properties.into(),
-                leaky_box_raw!(esp_attr_value_t {
-                    attr_max_len: self
-                        .max_value_length
-                        .unwrap_or(self.internal_value.len() as u16),
-                    attr_len: self.internal_value.len() as u16,
-                    attr_value: self.internal_value.as_mut_slice().as_mut_ptr(),
-                }),
-                &mut self.internal_control,
-            ));
+        // Register a SCCD if needed, unless this is a registration retry.
+        if self.properties.broadcast
+            && !self.has_descriptor(descriptors::SERVER_CHARACTERISTIC_CONFIGURATION)
+        {
+            self.descriptor(&Descriptor::sccd().build());
         }
     }
 
@@ -280,21 +883,304 @@ impl Characteristic {
     ///
     /// # Panics
     ///
-    /// Panics if the service handle is not registered.
-    ///
     /// # Notes
     ///
     /// Bluedroid does not offer a way to register descriptors to a specific characteristic.
     /// This is simply done by registering the characteristic and then registering its descriptors.
     pub(crate) fn register_descriptors(&mut self) {
-        debug!("Registering {}'s descriptors.", &self);
+        debug!(target: GATTS, "Registering {}'s descriptors.", &self);
+
+        let Some(service_handle) = self.service_handle else {
+            GattServerError::NotRegistered(self.to_string()).report();
+            return;
+        };
+
         self.descriptors.iter_mut().for_each(|descriptor| {
-            descriptor.write().register_self(self.service_handle.expect(
-                "Cannot register a descriptor to a characteristic without a service handle.",
-            ));
+            descriptor.write().register_self(service_handle);
         });
     }
 
+    fn has_descriptor(&self, uuid: BleUuid) -> bool {
+        self.descriptors
+            .iter()
+            .any(|descriptor| descriptor.read().uuid == uuid)
+    }
+
+    /// Returns whether this [`Characteristic`] is declared readable, by its properties and permissions.
+    pub(crate) const fn is_readable(&self) -> bool {
+        self.properties.read && self.permissions.read_access
+    }
+
+    /// Returns whether this [`Characteristic`] is declared writable, by its properties and permissions.
+    pub(crate) const fn is_writable(&self) -> bool {
+        (self.properties.write || self.properties.write_without_response)
+            && self.permissions.write_access
+    }
+
+    /// Evaluates the required privilege level and the access policy of this [`Characteristic`]
+    /// for the given connection and operation.
+    pub(crate) fn is_allowed(&self, connection: Connection, operation: AttributeOperation) -> bool {
+        if connection.privilege() < self.required_privilege {
+            return false;
+        }
+
+        match &self.access_policy {
+            Some(policy) => policy(&connection, operation),
+            None => true,
+        }
+    }
+
+    /// Returns a copy of the value of this [`Characteristic`], as stored in the Bluetooth stack.
+    ///
+    /// The length returned by the stack is checked against the declared maximum length
+    /// before the value is read.
+    pub(crate) fn stack_value(&self) -> Option<Vec<u8>> {
+        let Some(handle) = self.attribute_handle else {
+            GattServerError::NotRegistered(self.to_string()).report();
+            return None;
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let max_length = self
+            .max_value_length
+            .unwrap_or(self.internal_value.len() as u16);
+
+        let mut length = 0;
+        let mut value: *const u8 = std::ptr::null();
+        unsafe {
+            if !esp_report!(esp_ble_gatts_get_attr_value(
+                handle,
+                &mut length,
+                &mut value
+            )) {
+                return None;
+            }
+        }
+
+        if length == 0 {
+            return Some(Vec::new());
+        }
+
+        if value.is_null() || length > max_length {
+            GattServerError::Stack {
+                operation: "esp_ble_gatts_get_attr_value",
+                code: ESP_ERR_INVALID_SIZE,
+            }
+            .report();
+            return None;
+        }
+
+        Some(unsafe { std::slice::from_raw_parts(value, length as usize) }.to_vec())
+    }
+
+    /// Returns the notification and indication subscription status of the given connection,
+    /// as stored in the CCCD of this [`Characteristic`].
+    pub(crate) fn subscription_status(&self, connection: Connection) -> Option<(bool, bool)> {
+        let cccd_handle = self
+            .descriptors
+            .iter()
+            .find(|desc| desc.read().uuid == descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION)
+            .and_then(|desc| desc.read().attribute_handle)?;
+
+        // Get the current status of the CCCD via a fake read operation.
+        let simulated_read_param = esp_ble_gatts_cb_param_t_gatts_read_evt_param {
+            bda: connection.remote_bda,
+            conn_id: connection.id,
+            handle: cccd_handle,
+            ..Default::default()
+        };
+
+        self.get_cccd_status(simulated_read_param)
+    }
+
+    /// Returns the given connections that receive the value changes of this [`Characteristic`],
+    /// according to its [`NotificationMode`], along with whether they are indicated or notified.
+    pub(crate) fn recipients(&self, connections: &HashSet<Connection>) -> Vec<(Connection, bool)> {
+        connections
+            .iter()
+            .filter_map(|connection| {
+                let (notification, indication) = match self.notification_mode {
+                    NotificationMode::Disabled => return None,
+                    NotificationMode::AllConnections => {
+                        (self.properties.notify, self.properties.indicate)
+                    }
+                    // Skip the connections whose subscription status is unknown.
+                    NotificationMode::Subscribers => self.subscription_status(*connection)?,
+                };
+
+                if self.properties.indicate && indication {
+                    Some((*connection, true))
+                } else if self.properties.notify && notification {
+                    Some((*connection, false))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Sends the current value to the given connections,
+    /// according to the [`NotificationMode`] of this [`Characteristic`].
+    pub(crate) fn send_notifications(&self, connections: &HashSet<Connection>) {
+        let (Some(interface), Some(handle)) = (self.interface, self.attribute_handle) else {
+            warn!(target: NOTIFY, "Cannot notify {}: it is not registered yet.", self);
+            return;
+        };
+
+        if self.notification_mode == NotificationMode::Disabled {
+            debug!(target: NOTIFY, "Notifications are disabled for characteristic {}.", self);
+            return;
+        }
+
+        for (connection, need_confirm) in self.recipients(connections) {
+            if need_confirm {
+                debug!(target: NOTIFY, "Indicating {} value change to {}.", self, connection);
+            } else {
+                debug!(target: NOTIFY, "Notifying {} value change to {}.", self, connection);
+            }
+
+            // Queued, so that the value changes wait for the congestion of the connection to clear.
+            DELIVERY_QUEUE.push(
+                connection,
+                QueuedValue {
+                    interface,
+                    handle,
+                    value: self.internal_value.as_slice().to_vec(),
+                    indicate: need_confirm,
+                    settings: self.reliable_delivery.unwrap_or_default(),
+                    callback: self.delivery_callback.clone(),
+                    bounded: true,
+                },
+            );
+        }
+    }
+
+    /// Queues the given value for delivery to the given connection, bypassing the current value.
+    ///
+    /// The value is indicated if the characteristic has the "indicate" property, and notified otherwise.
+    /// The outcome is reported to the given callback, or to the delivery callback of this characteristic.
+    /// Returns `false` if the value cannot be sent.
+    pub(crate) fn queue_value(
+        &self,
+        connection: Connection,
+        value: Vec<u8>,
+        callback: Option<Arc<DeliveryCallback>>,
+    ) -> bool {
+        self.queue_value_as(connection, value, self.properties.indicate, callback)
+    }
+
+    /// Queues the given value for delivery to the given connection, as an indication or a notification.
+    ///
+    /// Returns `false` if the value cannot be sent, for example if the characteristic
+    /// does not have the matching property.
+    pub(crate) fn queue_value_as(
+        &self,
+        connection: Connection,
+        value: Vec<u8>,
+        indicate: bool,
+        callback: Option<Arc<DeliveryCallback>>,
+    ) -> bool {
+        let (Some(interface), Some(handle)) = (self.interface, self.attribute_handle) else {
+            warn!(target: NOTIFY, "Cannot send a value on {}: it is not registered yet.", self);
+            return false;
+        };
+
+        if indicate && !self.properties.indicate {
+            warn!(
+                target: NOTIFY,
+                "Cannot indicate {}: it does not have the indicate property.",
+                self
+            );
+            return false;
+        }
+
+        if !indicate && !self.properties.notify {
+            warn!(
+                target: NOTIFY,
+                "Cannot notify {}: it does not have the notify property.",
+                self
+            );
+            return false;
+        }
+
+        DELIVERY_QUEUE.push(
+            connection,
+            QueuedValue {
+                interface,
+                handle,
+                value,
+                indicate,
+                settings: self.reliable_delivery.unwrap_or_default(),
+                callback: callback.or_else(|| self.delivery_callback.clone()),
+                bounded: false,
+            },
+        );
+
+        true
+    }
+
+    /// Sends an indication with the current value to the given connection.
+    ///
+    /// Returns the token and the receiver of the pending indication.
+    fn send_indication(
+        &self,
+        connection: Connection,
+    ) -> Result<(u32, Receiver<esp_gatt_status_t>), GattServerError> {
+        let (Some(interface), Some(handle)) = (self.interface, self.attribute_handle) else {
+            return Err(GattServerError::NotRegistered(self.to_string()));
+        };
+
+        if !self.properties.indicate {
+            return Err(GattServerError::MissingProperty {
+                attribute: self.to_string(),
+                property: "indicate",
+            });
+        }
+
+        let (token, receiver) = PENDING_INDICATIONS.register(connection.id, handle);
+        let mut internal_value = self.internal_value.to_vec();
+
+        debug!(target: NOTIFY, "Indicating {} value to {}.", self, connection);
+
+        #[allow(clippy::cast_possible_truncation)]
+        let result = unsafe {
+            esp_check!(esp_ble_gatts_send_indicate(
+                interface,
+                connection.id,
+                handle,
+                internal_value.len() as u16,
+                internal_value.as_mut_ptr(),
+                true
+            ))
+        };
+
+        if let Err(error) = result {
+            PENDING_INDICATIONS.cancel(token);
+            return Err(error);
+        }
+
+        Ok((token, receiver))
+    }
+
+    fn wait_for_confirmation(
+        token: u32,
+        receiver: &Receiver<esp_gatt_status_t>,
+        timeout: Duration,
+    ) -> Result<(), GattServerError> {
+        // A disconnection drops the sender, so an error is returned in that case too.
+        match receiver.recv_timeout(timeout) {
+            Ok(status) if status == esp_gatt_status_t_ESP_GATT_OK => Ok(()),
+            Ok(status) => Err(GattServerError::Event {
+                event: "ESP_GATTS_CONF_EVT",
+                status,
+            }),
+            Err(_) => {
+                PENDING_INDICATIONS.cancel(token);
+                Err(GattServerError::NotConfirmed)
+            }
+        }
+    }
+
     pub(crate) fn get_cccd_status(
         &self,
         param: esp_ble_gatts_cb_param_t_gatts_read_evt_param,
@@ -302,7 +1188,7 @@ impl Characteristic {
         if let Some(cccd) = self
             .descriptors
             .iter()
-            .find(|desc| desc.read().uuid == BleUuid::Uuid16(0x2902))
+            .find(|desc| desc.read().uuid == descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION)
         {
             if let AttributeControl::ResponseByApp(callback) = &cccd.read().control {
                 let value = callback(param);
@@ -323,9 +1209,7 @@ impl std::fmt::Display for Characteristic {
         write!(
             f,
             "{} ({})",
-            self.name
-                .clone()
-                .unwrap_or_else(|| "Unnamed characteristic".to_string()),
+            self.name.as_deref().unwrap_or("Unnamed characteristic"),
             self.uuid
         )
     }
@@ -338,15 +1222,49 @@ impl std::fmt::Debug for Characteristic {
             .field("name", &self.name)
             .field("uuid", &self.uuid)
             .field("write_callback", &self.write_callback.is_some())
+            .field("access_policy", &self.access_policy.is_some())
+            .field("required_privilege", &self.required_privilege)
             .field("descriptors", &self.descriptors)
             .field("attribute_handle", &self.attribute_handle)
             .field("service_handle", &self.service_handle)
+            .field("interface", &self.interface)
             .field("permissions", &self.permissions)
             .field("properties", &self.properties)
+            .field("notification_mode", &self.notification_mode)
+            .field("min_notify_interval", &self.min_notify_interval)
+            .field("last_notification", &self.last_notification)
+            .field("notification_pending", &self.notification_pending)
+            .field("deferred_notifications", &self.deferred_notifications)
+            .field("broadcast_enabled", &self.broadcast_enabled)
+            .field("reliable_delivery", &self.reliable_delivery)
+            .field("delivery_callback", &self.delivery_callback.is_some())
             .field("control", &self.control)
+            .field("stores_writes", &self.stores_writes)
+            .field("read_cache", &self.read_cache)
             .field("internal_value", &self.internal_value)
             .field("max_value_length", &self.max_value_length)
             .field("internal_control", &self.internal_control)
+            .field("registration", &self.registration)
+            .field("auto_notify", &self.auto_notify)
             .finish()
     }
 }
+
+/// Answers a read with the value stored by the Bluetooth stack, for the characteristics
+/// answered by the crate only for their access checks.
+fn read_stored_value(param: esp_ble_gatts_cb_param_t_gatts_read_evt_param) -> Vec<u8> {
+    let mut length = 0;
+    let mut value: *const u8 = std::ptr::null();
+    unsafe {
+        if !esp_report!(esp_ble_gatts_get_attr_value(
+            param.handle,
+            &mut length,
+            &mut value
+        )) || value.is_null()
+        {
+            return Vec::new();
+        }
+
+        std::slice::from_raw_parts(value, length as usize).to_vec()
+    }
+}
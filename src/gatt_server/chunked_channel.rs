@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use lazy_static::lazy_static;
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::NOTIFY;
+use crate::{
+    gatt_server::LockedCharacteristic,
+    utilities::{
+        chunked::{split_message, Reassembler},
+        Connection, PayloadCodec,
+    },
+};
+
+/// The default chunk size, fitting the default ATT MTU of 23 bytes.
+const DEFAULT_CHUNK_SIZE: usize = 20;
+
+/// The identifier of the next channel whose messages are received.
+static NEXT_CHANNEL: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The messages being received, by channel and connection identifier.
+    static ref REASSEMBLERS: Mutex<HashMap<(usize, u16), Reassembler>> = Mutex::new(HashMap::new());
+}
+
+/// Discards the messages being received from a connection, once it is closed.
+pub(crate) fn forget_reassemblers(conn_id: u16) {
+    REASSEMBLERS.lock().retain(|(_, id), _| *id != conn_id);
+}
+
+/// A message channel over a pair of characteristics, using the [`chunked`] framing.
+///
+/// Clients send messages by writing chunks to the RX characteristic, and receive messages
+/// as notifications or indications of the TX characteristic.
+///
+/// [`chunked`]: crate::utilities::chunked
+#[derive(Clone)]
+pub struct ChunkedChannel {
+    rx: LockedCharacteristic,
+    tx: LockedCharacteristic,
+    chunk_size: usize,
+    max_message_length: Option<usize>,
+}
+
+impl ChunkedChannel {
+    /// Creates a new [`ChunkedChannel`] over the given RX and TX characteristics.
+    #[must_use]
+    pub fn new(rx: &LockedCharacteristic, tx: &LockedCharacteristic) -> Self {
+        Self {
+            rx: rx.clone(),
+            tx: tx.clone(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_message_length: None,
+        }
+    }
+
+    /// Sets the size of the chunks sent on the TX characteristic. The default value is 20 bytes.
+    ///
+    /// The chunk size should not exceed the ATT MTU of the connections minus three bytes.
+    pub fn chunk_size(&mut self, chunk_size: usize) -> &mut Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets the maximum length of the messages received on the RX characteristic.
+    /// The default value is [`DEFAULT_MAX_LENGTH`] bytes.
+    ///
+    /// [`DEFAULT_MAX_LENGTH`]: crate::utilities::chunked::DEFAULT_MAX_LENGTH
+    pub fn max_message_length(&mut self, length: usize) -> &mut Self {
+        self.max_message_length = Some(length);
+        self
+    }
+
+    /// Sets the callback for the messages received on the RX characteristic.
+    ///
+    /// This replaces the write callback of the RX characteristic.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, so it must not block.
+    pub fn on_message(
+        &mut self,
+        callback: impl Fn(Connection, Vec<u8>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let channel = NEXT_CHANNEL.fetch_add(1, Ordering::Relaxed);
+        let max_message_length = self.max_message_length;
+
+        self.rx.write().on_write(move |value, param| {
+            let connection = Connection::from(param);
+
+            let result = REASSEMBLERS
+                .lock()
+                .entry((channel, connection.id))
+                .or_insert_with(|| {
+                    let reassembler = Reassembler::new();
+                    match max_message_length {
+                        Some(length) => reassembler.max_length(length),
+                        None => reassembler,
+                    }
+                })
+                .push(&value);
+
+            match result {
+                Ok(Some(message)) => callback(connection, message),
+                Ok(None) => {}
+                Err(error) => warn!(
+                    target: NOTIFY,
+                    "Discarding chunked message from {}: {}.",
+                    connection, error
+                ),
+            }
+        });
+
+        self
+    }
+
+    /// Sets the callback for the messages received on the RX characteristic,
+    /// decoded with the [`PayloadCodec`] `C`, for example [`Protobuf`].
+    ///
+    /// Messages that cannot be decoded are discarded, with a warning.
+    /// This replaces the write callback of the RX characteristic.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, so it must not block.
+    ///
+    /// [`Protobuf`]: crate::utilities::Protobuf
+    pub fn on_decoded_message<C: PayloadCodec<T>, T>(
+        &mut self,
+        callback: impl Fn(Connection, T) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_message(move |connection, message| match C::decode(&message) {
+            Some(message) => callback(connection, message),
+            None => warn!(target: NOTIFY, "Discarding undecodable message from {}.", connection),
+        })
+    }
+
+    /// Sends a message to the given connection, as a sequence of chunks on the TX characteristic.
+    ///
+    /// The chunks are sent in order through the reliable delivery queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk size is smaller than six bytes.
+    pub fn send(&self, connection: Connection, message: &[u8]) {
+        let tx = self.tx.read();
+
+        for chunk in split_message(message, self.chunk_size) {
+            if !tx.queue_value(connection, chunk, None) {
+                return;
+            }
+        }
+    }
+
+    /// Encodes a message with the [`PayloadCodec`] `C`, and sends it to the given connection
+    /// as a sequence of chunks on the TX characteristic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk size is smaller than six bytes.
+    pub fn send_encoded<C: PayloadCodec<T>, T>(&self, connection: Connection, message: &T) {
+        match C::encode(message) {
+            Some(message) => self.send(connection, &message),
+            None => warn!(target: NOTIFY, "Cannot encode message for {}.", connection),
+        }
+    }
+}
+
+impl std::fmt::Debug for ChunkedChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkedChannel")
+            .field("rx", &self.rx.read().to_string())
+            .field("tx", &self.tx.read().to_string())
+            .field("chunk_size", &self.chunk_size)
+            .field("max_message_length", &self.max_message_length)
+            .finish()
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use esp_idf_sys::{
+    esp_ble_gap_cb_param_t_ble_update_conn_params_evt_param, esp_bt_status_t_ESP_BT_STATUS_SUCCESS,
+};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use parking_lot::Mutex;
+
+use crate::gatt_server::{
+    event::{self, GattEvent},
+    GattServer,
+};
+use crate::utilities::log_targets::GAP;
+use crate::utilities::{Connection, ConnectionParameters};
+
+lazy_static! {
+    /// The live parameters of the connections, by remote address.
+    static ref PARAMETERS: Mutex<HashMap<[u8; 6], ConnectionParameters>> =
+        Mutex::new(HashMap::new());
+}
+
+impl Connection {
+    /// Returns the current parameters of this connection, as last set by the central device.
+    ///
+    /// Returns `None` once the client disconnected.
+    #[must_use]
+    pub fn parameters(&self) -> Option<ConnectionParameters> {
+        PARAMETERS.lock().get(&self.remote_bda()).copied()
+    }
+}
+
+impl GattServer {
+    /// Sets a callback receiving the new parameters of a connection, every time the central
+    /// device updates them.
+    ///
+    /// This is a shorthand for handling [`GattEvent::ConnectionParametersChanged`]
+    /// with [`GattServer::on_event`]. It is useful, for example, to adapt the notification rate
+    /// to the actual connection interval.
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the Bluetooth stack's context, so it must not block.
+    pub fn on_conn_params_changed(
+        &mut self,
+        callback: impl Fn(Connection, ConnectionParameters) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_event(move |event| {
+            if let GattEvent::ConnectionParametersChanged {
+                connection,
+                parameters,
+            } = event
+            {
+                callback(*connection, *parameters);
+            }
+        })
+    }
+
+    /// Records the parameters of a connection, and reports their update.
+    pub(crate) fn on_conn_params_update(
+        &self,
+        param: esp_ble_gap_cb_param_t_ble_update_conn_params_evt_param,
+    ) {
+        if param.status != esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+            warn!(
+                target: GAP,
+                "Connection parameters update of {:02X?} failed.",
+                param.bda
+            );
+            return;
+        }
+
+        let parameters = ConnectionParameters {
+            interval: param.conn_int,
+            latency: param.latency,
+            timeout: param.timeout,
+        };
+        info!(
+            target: GAP,
+            "Connection parameters of {:02X?} updated: {:?}.",
+            param.bda,
+            parameters
+        );
+        PARAMETERS.lock().insert(param.bda, parameters);
+
+        let connection = self
+            .active_connections
+            .iter()
+            .find(|connection| connection.remote_bda() == param.bda)
+            .copied();
+        if let Some(connection) = connection {
+            event::emit(&GattEvent::ConnectionParametersChanged {
+                connection,
+                parameters,
+            });
+        }
+    }
+}
+
+/// Records the initial parameters of a new connection.
+pub(crate) fn record_parameters(address: [u8; 6], parameters: ConnectionParameters) {
+    PARAMETERS.lock().insert(address, parameters);
+}
+
+/// Forgets the parameters of a closed connection.
+pub(crate) fn forget_parameters(address: [u8; 6]) {
+    PARAMETERS.lock().remove(&address);
+}
@@ -1,50 +1,316 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
+use crate::utilities::log_targets::NVS;
 use crate::{
-    gatt_server::Descriptor,
-    utilities::{AttributePermissions, BleUuid},
+    gatt_server::{
+        resolving_list::identity_address, Descriptor, DescriptorKey, DescriptorStore,
+        GattServerError, StoreError,
+    },
+    utilities::{sig::descriptors, AttributePermissions, BleUuid, PresentationFormat},
 };
 
 use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition};
-use log::debug;
+use esp_idf_sys::EspError;
+use lazy_static::lazy_static;
+use log::{debug, warn};
 use parking_lot::Mutex;
 
 pub struct SettableStorage {
     storage: Mutex<Option<Arc<Mutex<EspDefaultNvs>>>>,
+    /// The backend of the descriptor values: the one set by the user,
+    /// or the in-memory one used when the NVS is not available.
+    store: Mutex<Option<Arc<dyn DescriptorStore>>>,
+    /// The in-memory fallback of the other values, used when the NVS is not available.
+    /// Its values are lost on reboot.
+    fallback: Mutex<Option<HashMap<String, Vec<u8>>>>,
 }
 
 impl SettableStorage {
     pub fn set_storage_partition(&self, storage: EspDefaultNvsPartition) {
-        *self.storage.lock() = Some(Arc::new(Mutex::new(
-            EspDefaultNvs::new(storage, "ble", true)
-                .expect("Cannot create a new NVS storage. Did you declare an NVS partition?"),
-        )))
+        match EspDefaultNvs::new(storage, "ble", true) {
+            Ok(nvs) => *self.storage.lock() = Some(Arc::new(Mutex::new(nvs))),
+            Err(error) => self.use_fallback(error),
+        }
     }
     pub const fn new() -> Self {
         Self {
             storage: Mutex::new(None),
+            store: Mutex::new(None),
+            fallback: Mutex::new(None),
         }
     }
-    pub fn get(&self) -> Arc<Mutex<EspDefaultNvs>> {
-        if self.storage.lock().is_some() {
-            return self.storage.lock().clone().unwrap();
+
+    /// Persists the descriptor values in the given [`DescriptorStore`], instead of the NVS.
+    ///
+    /// This must be called before starting the server,
+    /// so that no value is read from the NVS meanwhile.
+    /// The other values, such as the connection counters of the bonds, stay in the NVS.
+    pub fn set_store(&self, store: impl DescriptorStore + 'static) {
+        *self.store.lock() = Some(Arc::new(store));
+    }
+    /// Returns the NVS storage, initialising the default NVS partition if needed,
+    /// or `None` if the in-memory fallback is used instead.
+    pub(crate) fn get(&self) -> Option<Arc<Mutex<EspDefaultNvs>>> {
+        if self.fallback.lock().is_some() {
+            return None;
+        }
+
+        if let Some(storage) = self.storage.lock().clone() {
+            return Some(storage);
         }
-        let nvs = EspDefaultNvs::new(
-            EspDefaultNvsPartition::take()
-                .expect("Cannot initialise the default NVS. Did you declare an NVS partition?"),
-            "ble",
-            true,
-        )
-        .expect("Cannot create a new NVS storage. Did you declare an NVS partition?");
-        let res = Arc::new(Mutex::new(nvs));
-        *self.storage.lock() = Some(res.clone());
-        res
+
+        match EspDefaultNvsPartition::take()
+            .and_then(|partition| EspDefaultNvs::new(partition, "ble", true))
+        {
+            Ok(nvs) => {
+                let storage = Arc::new(Mutex::new(nvs));
+                *self.storage.lock() = Some(storage.clone());
+                Some(storage)
+            }
+            Err(error) => {
+                self.use_fallback(error);
+                None
+            }
+        }
+    }
+
+    fn use_fallback(&self, error: EspError) {
+        warn!(
+            target: NVS,
+            "Cannot open the NVS storage ({}). Did you declare an NVS partition? CCCD values will not persist across reboots.",
+            error
+        );
+        *self.fallback.lock() = Some(HashMap::new());
+    }
+
+    /// Returns the backend the descriptor values are persisted in.
+    fn backend(&self) -> Arc<dyn DescriptorStore> {
+        if let Some(store) = self.store.lock().clone() {
+            return store;
+        }
+
+        match self.get() {
+            Some(storage) => storage,
+            None => self
+                .store
+                .lock()
+                .get_or_insert_with(|| {
+                    Arc::new(Mutex::new(HashMap::<DescriptorKey, Vec<u8>>::new()))
+                })
+                .clone(),
+        }
+    }
+
+    /// Reads a descriptor value from its backend.
+    pub(crate) fn load_descriptor(
+        &self,
+        key: &DescriptorKey,
+    ) -> Result<Option<Vec<u8>>, GattServerError> {
+        self.backend().get(key).map_err(|error| store_error(&error))
+    }
+
+    /// Writes a descriptor value to its backend.
+    pub(crate) fn store_descriptor(
+        &self,
+        key: &DescriptorKey,
+        value: &[u8],
+    ) -> Result<(), GattServerError> {
+        self.backend()
+            .set(key, value)
+            .map_err(|error| store_error(&error))
+    }
+
+    /// Removes a descriptor value from its backend.
+    pub(crate) fn remove_descriptor(&self, key: &DescriptorKey) -> Result<(), GattServerError> {
+        self.backend()
+            .remove(key)
+            .map_err(|error| store_error(&error))
+    }
+
+    /// Reads a value from the NVS, or from the in-memory fallback.
+    pub(crate) fn load(&self, key: &str) -> Result<Option<Vec<u8>>, EspError> {
+        let Some(storage) = self.get() else {
+            return Ok(self
+                .fallback
+                .lock()
+                .as_ref()
+                .and_then(|values| values.get(key).cloned()));
+        };
+
+        let mut buf: [u8; 4] = [0; 4];
+        let result = storage
+            .lock()
+            .get_raw(key, &mut buf)
+            .map(|value| value.map(<[u8]>::to_vec));
+        result
+    }
+
+    /// Writes a value to the NVS, or to the in-memory fallback.
+    pub(crate) fn store(&self, key: &str, value: &[u8]) -> Result<(), EspError> {
+        let Some(storage) = self.get() else {
+            if let Some(values) = self.fallback.lock().as_mut() {
+                values.insert(key.to_string(), value.to_vec());
+            }
+            return Ok(());
+        };
+
+        let result = storage.lock().set_raw(key, value).map(|_| ());
+        result
+    }
+
+    /// Removes a value from the NVS, or from the in-memory fallback.
+    pub(crate) fn remove(&self, key: &str) -> Result<(), EspError> {
+        let Some(storage) = self.get() else {
+            if let Some(values) = self.fallback.lock().as_mut() {
+                values.remove(key);
+            }
+            return Ok(());
+        };
+
+        let result = storage.lock().remove(key).map(|_| ());
+        result
+    }
+}
+
+/// Turns the error of a [`DescriptorStore`] into a [`GattServerError`],
+/// keeping the error code of the NVS.
+fn store_error(error: &StoreError) -> GattServerError {
+    match error.downcast_ref::<EspError>() {
+        Some(error) => GattServerError::Storage(error.code()),
+        None => GattServerError::DescriptorStore(error.to_string()),
     }
 }
 
 /// NVS Storage for our BLE CCCD's
 pub static STORAGE: SettableStorage = SettableStorage::new();
 
+/// The attribute a CCCD belongs to: its characteristic and the service of the characteristic.
+///
+/// The instances tell apart the services of a profile, and the characteristics of a service,
+/// that share a UUID: the first one is instance 0, the next one instance 1, and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CccdOwner {
+    pub(crate) service: BleUuid,
+    pub(crate) service_instance: u8,
+    pub(crate) characteristic: BleUuid,
+    pub(crate) characteristic_instance: u8,
+}
+
+/// Returns the instance of the attribute at `index` among the attributes sharing its UUID.
+pub(crate) fn instance_index(uuids: &[BleUuid], index: usize) -> u8 {
+    let instance = uuids[..index]
+        .iter()
+        .filter(|uuid| **uuid == uuids[index])
+        .count();
+
+    u8::try_from(instance).unwrap_or(u8::MAX)
+}
+
+lazy_static! {
+    /// The attribute every registered CCCD belongs to, by attribute handle.
+    static ref CCCD_OWNERS: Mutex<HashMap<u16, CccdOwner>> = Mutex::new(HashMap::new());
+}
+
+/// Records the attribute a CCCD belongs to, once registered.
+pub(crate) fn record_cccd(handle: u16, owner: CccdOwner) {
+    CCCD_OWNERS.lock().insert(handle, owner);
+}
+
+/// Returns the key of the CCCD value of a client, from its address and the CCCD handle,
+/// or `None` if the CCCD is not registered yet.
+///
+/// The value is keyed by the identity address of the client, so that a bonded client finds it
+/// from any of its private addresses, and by the UUIDs and instances of the service and the
+/// characteristic, so that it survives a firmware update that moves the handles.
+pub(crate) fn cccd_key(address: [u8; 6], handle: u16) -> Option<DescriptorKey> {
+    let owner = CCCD_OWNERS.lock().get(&handle).copied()?;
+
+    Some(DescriptorKey {
+        address: identity_address(address),
+        service: owner.service,
+        service_instance: owner.service_instance,
+        characteristic: owner.characteristic,
+        characteristic_instance: owner.characteristic_instance,
+        descriptor: descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION,
+    })
+}
+
+/// Returns the NVS key the CCCD values were stored with before [`cccd_key`]:
+/// the last four bytes of the client address and the CCCD handle.
+fn legacy_cccd_key(address: [u8; 6], handle: u16) -> String {
+    format!(
+        "{:02X}{:02X}{:02X}{:02X}-{:04X}",
+        address[2], address[3], address[4], address[5], handle
+    )
+}
+
+/// Reads the CCCD value of a client.
+///
+/// A value stored in the NVS by a previous firmware under its legacy key is moved
+/// to its current key, as long as the handle of the CCCD did not change meanwhile.
+fn load_cccd(address: [u8; 6], handle: u16) -> Result<Option<Vec<u8>>, GattServerError> {
+    let legacy_key = legacy_cccd_key(address, handle);
+    let Some(key) = cccd_key(address, handle) else {
+        // Not registered yet: fall back to the handle.
+        return STORAGE
+            .load(&legacy_key)
+            .map_err(|error| GattServerError::Storage(error.code()));
+    };
+
+    if let Some(value) = STORAGE.load_descriptor(&key)? {
+        return Ok(Some(value));
+    }
+
+    let Some(value) = STORAGE
+        .load(&legacy_key)
+        .map_err(|error| GattServerError::Storage(error.code()))?
+    else {
+        return Ok(None);
+    };
+
+    debug!(
+        target: NVS,
+        "Migrating CCCD value from key {} to {:?}.", legacy_key, key
+    );
+    STORAGE.store_descriptor(&key, &value)?;
+    STORAGE
+        .remove(&legacy_key)
+        .map_err(|error| GattServerError::Storage(error.code()))?;
+
+    Ok(Some(value))
+}
+
+/// Writes the CCCD value of a client.
+fn store_cccd(address: [u8; 6], handle: u16, value: &[u8]) -> Result<(), GattServerError> {
+    let Some(key) = cccd_key(address, handle) else {
+        // Not registered yet: fall back to the handle.
+        return STORAGE
+            .store(&legacy_cccd_key(address, handle), value)
+            .map_err(|error| GattServerError::Storage(error.code()));
+    };
+
+    debug!(target: NVS, "Write CCCD value: {:?} at key {:?}", value, key);
+
+    STORAGE.store_descriptor(&key, value)
+}
+
+/// Removes the CCCD values of a client, from all the registered CCCDs.
+pub(crate) fn forget_cccds(address: [u8; 6]) {
+    let handles: Vec<u16> = CCCD_OWNERS.lock().keys().copied().collect();
+
+    for handle in handles {
+        if let Some(key) = cccd_key(address, handle) {
+            if let Err(error) = STORAGE.remove_descriptor(&key) {
+                error.report();
+            }
+        }
+
+        if let Err(error) = STORAGE.remove(&legacy_cccd_key(address, handle)) {
+            GattServerError::Storage(error.code()).report();
+        }
+    }
+}
+
 impl Descriptor {
     /// Creates a new descriptor with the `0x2901` UUID, and the description string as its value.
     ///
@@ -54,77 +320,77 @@ impl Descriptor {
     /// [`Characteristic::show_name`]: crate::gatt_server::Characteristic::show_name
     /// [`Characteristic`]: crate::gatt_server::Characteristic
     pub fn user_description<S: AsRef<str>>(description: S) -> Self {
-        Self::new(BleUuid::from_uuid16(0x2901))
+        Self::new(descriptors::CHARACTERISTIC_USER_DESCRIPTION)
             .name("User Description")
             .permissions(AttributePermissions::new().read())
             .set_value(description.as_ref().as_bytes().to_vec())
             .clone()
     }
 
+    /// Creates a new descriptor with the `0x2904` UUID, telling clients how to display
+    /// the value of its characteristic.
+    #[must_use]
+    pub fn presentation_format(format: PresentationFormat) -> Self {
+        Self::new(descriptors::CHARACTERISTIC_PRESENTATION_FORMAT)
+            .name("Presentation Format")
+            .permissions(AttributePermissions::new().read())
+            .set_value(format.to_bytes().to_vec())
+            .clone()
+    }
+
     /// Creates a CCCD.
     ///
     /// The contents of the CCCD are stored in NVS and persisted across reboots.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the NVS is not configured.
+    /// Without an NVS partition, they are kept in memory instead.
     #[must_use]
     pub fn cccd() -> Self {
-        Self::new(BleUuid::from_uuid16(0x2902))
+        Self::new(descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION)
             .name("Client Characteristic Configuration")
             .permissions(AttributePermissions::new().read().write())
             .on_read(
                 |param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_read_evt_param| {
-                    let storage = STORAGE.get().clone();
-
-                    // Get the descriptor handle.
-
-                    // TODO: Find the characteristic that contains the handle.
-                    // WARNING: Using the handle is incredibly stupid as the NVS is not erased across flashes.
-
-                    // Create a key from the connection address.
-                    let key = format!(
-                        "{:02X}{:02X}{:02X}{:02X}-{:04X}",
-                        /* param.bda[1], */ param.bda[2],
-                        param.bda[3],
-                        param.bda[4],
-                        param.bda[5],
-                        param.handle
-                    );
-
-                    // Prepare buffer and read correct CCCD value from non-volatile storage.
-                    let mut buf: [u8; 2] = [0; 2];
-                    let val = storage.lock().get_raw(&key, &mut buf);
-                    if let Some(value) = val.unwrap() {
-                        debug!("Read CCCD value: {:?} for key {}.", value, key);
-                        value.to_vec()
-                    } else {
-                        debug!("No CCCD value found for key {}.", key);
-                        vec![0, 0]
+                    // Read correct CCCD value from non-volatile storage.
+                    match load_cccd(param.bda, param.handle) {
+                        Ok(Some(value)) => {
+                            debug!(
+                                target: NVS,
+                                "Read CCCD value: {:?} for handle 0x{:04x}.", value, param.handle
+                            );
+                            value
+                        }
+                        Ok(None) => {
+                            debug!(
+                                target: NVS,
+                                "No CCCD value found for handle 0x{:04x}.", param.handle
+                            );
+                            vec![0, 0]
+                        }
+                        Err(error) => {
+                            error.report();
+                            vec![0, 0]
+                        }
                     }
                 },
             )
             .on_write(|value, param| {
-                let storage = STORAGE.get();
-
-                // Create a key from the connection address.
-                let key = format!(
-                    "{:02X}{:02X}{:02X}{:02X}-{:04X}",
-                    /* param.bda[1], */ param.bda[2],
-                    param.bda[3],
-                    param.bda[4],
-                    param.bda[5],
-                    param.handle
-                );
-
-                debug!("Write CCCD value: {:?} at key {}", value, key);
-
                 // Write CCCD value to non-volatile storage.
-                storage
-                    .lock()
-                    .set_raw(&key, &value)
-                    .expect("Cannot put raw value to the NVS. Did you declare an NVS partition?");
+                if let Err(error) = store_cccd(param.bda, param.handle, &value) {
+                    error.report();
+                }
             })
             .clone()
     }
+
+    /// Creates a SCCD.
+    ///
+    /// Its "broadcast" bit enables the broadcast of the characteristic value
+    /// in the service data of the advertisement.
+    #[must_use]
+    pub fn sccd() -> Self {
+        Self::new(descriptors::SERVER_CHARACTERISTIC_CONFIGURATION)
+            .name("Server Characteristic Configuration")
+            .permissions(AttributePermissions::new().read().write())
+            .set_value(vec![0, 0])
+            .clone()
+    }
 }
@@ -0,0 +1,96 @@
+use esp_idf_sys::{
+    esp_ble_gap_set_security_param, esp_ble_key_t, esp_ble_sm_param_t,
+    esp_ble_sm_param_t_ESP_BLE_SM_AUTHEN_REQ_MODE, esp_ble_sm_param_t_ESP_BLE_SM_IOCAP_MODE,
+    esp_ble_sm_param_t_ESP_BLE_SM_SET_INIT_KEY, esp_ble_sm_param_t_ESP_BLE_SM_SET_RSP_KEY,
+    ESP_BLE_ENC_KEY_MASK, ESP_BLE_ID_KEY_MASK, ESP_IO_CAP_NONE, ESP_LE_AUTH_BOND, ESP_LE_KEY_LENC,
+    ESP_LE_KEY_PENC,
+};
+use log::warn;
+
+use crate::gatt_server::{error::esp_report, GattServer};
+use crate::utilities::log_targets::GAP;
+
+impl GattServer {
+    /// **Insecure**: makes the pairings of the server decryptable by protocol sniffers,
+    /// such as Wireshark with the nRF Sniffer, during development.
+    ///
+    /// Bluedroid does not expose the debug key pair of LE Secure Connections, so this mode:
+    ///
+    /// - restricts pairing to LE legacy pairing without MITM protection, whose keys
+    ///   the sniffers derive from a captured pairing;
+    /// - logs every long-term key distributed when bonding, so that the captures of
+    ///   connections encrypted with an existing bond can be decrypted too.
+    ///
+    /// This is only available with the `debug-keys` feature, which must never be enabled
+    /// in production firmware: any nearby sniffer can decrypt the traffic of the server.
+    pub fn debug_keys(&mut self) -> &mut Self {
+        self.debug_keys = true;
+
+        if self.started {
+            Self::configure_debug_keys();
+        }
+
+        self
+    }
+
+    /// Hands the insecure security parameters to the Bluetooth stack.
+    pub(crate) fn configure_debug_keys() {
+        warn!(
+            target: GAP,
+            "INSECURE: debug keys enabled, the pairings can be decrypted by any sniffer."
+        );
+
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_AUTHEN_REQ_MODE,
+            ESP_LE_AUTH_BOND,
+        );
+        set_security_param(esp_ble_sm_param_t_ESP_BLE_SM_IOCAP_MODE, ESP_IO_CAP_NONE);
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_SET_INIT_KEY,
+            ESP_BLE_ENC_KEY_MASK | ESP_BLE_ID_KEY_MASK,
+        );
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_SET_RSP_KEY,
+            ESP_BLE_ENC_KEY_MASK | ESP_BLE_ID_KEY_MASK,
+        );
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn set_security_param(param: esp_ble_sm_param_t, value: u32) {
+    let mut value = value as u8;
+    unsafe {
+        esp_report!(esp_ble_gap_set_security_param(
+            param,
+            std::ptr::addr_of_mut!(value).cast(),
+            1
+        ));
+    }
+}
+
+/// Logs the long-term keys distributed when bonding.
+pub(crate) fn on_key(key: esp_ble_key_t) {
+    match u32::from(key.key_type) {
+        ESP_LE_KEY_PENC => {
+            let value = unsafe { key.p_key_value.penc_key };
+            warn!(
+                target: GAP,
+                "INSECURE: LTK of {:02X?}: {:02X?}, EDIV 0x{:04X}, Rand {:02X?}.",
+                key.bd_addr,
+                value.ltk,
+                value.ediv,
+                value.rand
+            );
+        }
+        ESP_LE_KEY_LENC => {
+            let value = unsafe { key.p_key_value.lenc_key };
+            warn!(
+                target: GAP,
+                "INSECURE: local LTK for {:02X?}: {:02X?}.",
+                key.bd_addr,
+                value.ltk
+            );
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,232 @@
+use std::{borrow::Cow, sync::Arc};
+
+use parking_lot::RwLock;
+
+use crate::{
+    gatt_server::{
+        Characteristic, Descriptor, LockedCharacteristic, LockedDescriptor, LockedService, Service,
+    },
+    utilities::{AttributePermissions, BleUuid, CharacteristicProperties},
+};
+
+/// A constant declaration of a [`Service`], that can be stored in a `static` or a `const`.
+///
+/// The declarations hold the immutable parts of the GATT tree (identifiers, names,
+/// permissions, properties and initial values) and can live in flash. [`ServiceDefinition::build`]
+/// creates the runtime attributes from them, borrowing the names and the initial values
+/// instead of copying them.
+///
+/// # Examples
+///
+/// ```ignore
+/// static BATTERY_SERVICE: ServiceDefinition = ServiceDefinition::new(sig::services::BATTERY)
+///     .name("Battery")
+///     .primary()
+///     .characteristics(&[CharacteristicDefinition::new(sig::characteristics::BATTERY_LEVEL)
+///         .name("Battery Level")
+///         .permissions(AttributePermissions::new().read())
+///         .properties(CharacteristicProperties::new().read().notify())
+///         .value(&[100])]);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceDefinition {
+    uuid: BleUuid,
+    name: Option<&'static str>,
+    primary: bool,
+    characteristics: &'static [CharacteristicDefinition],
+}
+
+impl ServiceDefinition {
+    /// Creates a new [`ServiceDefinition`].
+    #[must_use]
+    pub const fn new(uuid: BleUuid) -> Self {
+        Self {
+            uuid,
+            name: None,
+            primary: false,
+            characteristics: &[],
+        }
+    }
+
+    /// Sets the name of the service.
+    #[must_use]
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the service as primary.
+    #[must_use]
+    pub const fn primary(mut self) -> Self {
+        self.primary = true;
+        self
+    }
+
+    /// Sets the characteristics of the service.
+    #[must_use]
+    pub const fn characteristics(
+        mut self,
+        characteristics: &'static [CharacteristicDefinition],
+    ) -> Self {
+        self.characteristics = characteristics;
+        self
+    }
+
+    /// Creates the runtime [`Service`] of this definition, with all its characteristics and descriptors.
+    ///
+    /// The returned service can be extended like any other, for example with read or write callbacks.
+    #[must_use]
+    pub fn build(&self) -> LockedService {
+        let mut service = Service::new(self.uuid);
+        service.name = self.name.map(Cow::Borrowed);
+        service.primary = self.primary;
+
+        for definition in self.characteristics {
+            service.characteristic(&definition.build());
+        }
+
+        Arc::new(RwLock::new(service))
+    }
+}
+
+/// A constant declaration of a [`Characteristic`], that can be stored in a `static` or a `const`.
+#[derive(Debug, Clone, Copy)]
+pub struct CharacteristicDefinition {
+    uuid: BleUuid,
+    name: Option<&'static str>,
+    permissions: AttributePermissions,
+    properties: CharacteristicProperties,
+    value: &'static [u8],
+    descriptors: &'static [DescriptorDefinition],
+}
+
+impl CharacteristicDefinition {
+    /// Creates a new [`CharacteristicDefinition`].
+    #[must_use]
+    pub const fn new(uuid: BleUuid) -> Self {
+        Self {
+            uuid,
+            name: None,
+            permissions: AttributePermissions::new(),
+            properties: CharacteristicProperties::new(),
+            value: &[],
+            descriptors: &[],
+        }
+    }
+
+    /// Sets the name of the characteristic.
+    #[must_use]
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the access permissions of the characteristic.
+    #[must_use]
+    pub const fn permissions(mut self, permissions: AttributePermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets the properties of the characteristic.
+    #[must_use]
+    pub const fn properties(mut self, properties: CharacteristicProperties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Sets the initial value of the characteristic.
+    ///
+    /// Without an initial value, the characteristic starts with the default one of [`Characteristic::new`].
+    #[must_use]
+    pub const fn value(mut self, value: &'static [u8]) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Sets the descriptors of the characteristic.
+    #[must_use]
+    pub const fn descriptors(mut self, descriptors: &'static [DescriptorDefinition]) -> Self {
+        self.descriptors = descriptors;
+        self
+    }
+
+    /// Creates the runtime [`Characteristic`] of this definition, with all its descriptors.
+    #[must_use]
+    pub fn build(&self) -> LockedCharacteristic {
+        let mut characteristic = Characteristic::new(self.uuid);
+        characteristic.name = self.name.map(Cow::Borrowed);
+        characteristic
+            .permissions(self.permissions)
+            .properties(self.properties);
+
+        if !self.value.is_empty() {
+            characteristic.set_static_value(self.value);
+        }
+
+        for definition in self.descriptors {
+            characteristic.descriptor(&definition.build());
+        }
+
+        Arc::new(RwLock::new(characteristic))
+    }
+}
+
+/// A constant declaration of a [`Descriptor`], that can be stored in a `static` or a `const`.
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptorDefinition {
+    uuid: BleUuid,
+    name: Option<&'static str>,
+    permissions: AttributePermissions,
+    value: &'static [u8],
+}
+
+impl DescriptorDefinition {
+    /// Creates a new [`DescriptorDefinition`].
+    #[must_use]
+    pub const fn new(uuid: BleUuid) -> Self {
+        Self {
+            uuid,
+            name: None,
+            permissions: AttributePermissions::new(),
+            value: &[],
+        }
+    }
+
+    /// Sets the name of the descriptor.
+    #[must_use]
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the access permissions of the descriptor.
+    #[must_use]
+    pub const fn permissions(mut self, permissions: AttributePermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets the initial value of the descriptor.
+    ///
+    /// Without an initial value, the descriptor starts with the default one of [`Descriptor::new`].
+    #[must_use]
+    pub const fn value(mut self, value: &'static [u8]) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Creates the runtime [`Descriptor`] of this definition.
+    #[must_use]
+    pub fn build(&self) -> LockedDescriptor {
+        let mut descriptor = Descriptor::new(self.uuid);
+        descriptor.name = self.name.map(Cow::Borrowed);
+        descriptor.permissions(self.permissions);
+
+        if !self.value.is_empty() {
+            descriptor.set_static_value(self.value);
+        }
+
+        Arc::new(RwLock::new(descriptor))
+    }
+}
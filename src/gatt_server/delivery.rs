@@ -0,0 +1,491 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp, esp_ble_gatts_send_indicate, esp_gatt_status_t, esp_gatt_status_t_ESP_GATT_CONGESTED,
+    esp_gatt_status_t_ESP_GATT_OK,
+};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::NOTIFY;
+use crate::{
+    gatt_server::{indication::PENDING_INDICATIONS, worker, GattServer},
+    utilities::{Connection, DeliveryOutcome},
+};
+
+lazy_static! {
+    /// The queue of the value changes waiting to be delivered reliably.
+    pub(crate) static ref DELIVERY_QUEUE: DeliveryQueue = DeliveryQueue::default();
+}
+
+/// The time to wait before retrying a failed delivery, unless the congestion clears earlier.
+///
+/// The delay doubles at every retry, up to `MAX_BACK_OFF_DOUBLINGS` times.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How many times the retry delay is doubled at most.
+const MAX_BACK_OFF_DOUBLINGS: u8 = 4;
+
+/// How long to wait for the stack to report the send status of a notification.
+const SEND_STATUS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The default maximum number of values waiting in the queue of a connection.
+const DEFAULT_CAPACITY: usize = 16;
+
+pub(crate) type DeliveryCallback = dyn Fn(Connection, DeliveryOutcome) + Send + Sync;
+
+/// The reliable delivery settings of a characteristic.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReliableDelivery {
+    /// How many times a failed delivery is retried.
+    pub(crate) retries: u8,
+    /// How long to wait for the confirmation of an indication.
+    pub(crate) confirmation_timeout: Duration,
+}
+
+impl Default for ReliableDelivery {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            confirmation_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A value change waiting to be delivered to a connection.
+pub(crate) struct QueuedValue {
+    pub(crate) interface: u8,
+    pub(crate) handle: u16,
+    pub(crate) value: Vec<u8>,
+    pub(crate) indicate: bool,
+    pub(crate) settings: ReliableDelivery,
+    pub(crate) callback: Option<Arc<DeliveryCallback>>,
+    /// Whether the value counts towards the capacity of the queue, and is dropped when it is full.
+    ///
+    /// The values that are part of a sequence, such as the chunks of a message, are never dropped:
+    /// their senders bound them.
+    pub(crate) bounded: bool,
+}
+
+impl QueuedValue {
+    fn report(&self, connection: Connection, outcome: DeliveryOutcome) {
+        debug!(
+            target: NOTIFY,
+            "Delivery of handle 0x{:04x} to {}: {:?}.",
+            self.handle, connection, outcome
+        );
+
+        if let Some(callback) = &self.callback {
+            callback(connection, outcome);
+        }
+    }
+}
+
+/// The value of a connection being delivered.
+struct InFlight {
+    value: QueuedValue,
+    /// The number of the current attempt, 0 for the first one.
+    attempt: u8,
+    stage: Stage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// The value is waiting to be sent, once the connection is not congested.
+    Ready,
+    /// The value was sent, and waits for the send status with the given token.
+    Sent(u32),
+    /// The value waits to be retried.
+    BackingOff,
+}
+
+#[derive(Default)]
+struct ConnectionQueue {
+    values: VecDeque<QueuedValue>,
+    in_flight: Option<InFlight>,
+    congested: bool,
+}
+
+/// Per-connection queues of value changes, delivered in order.
+///
+/// The queues do not block any thread: the values are sent by jobs of the worker thread,
+/// scheduled when a value is queued, when the stack reports the send status of a value,
+/// when the congestion of a connection clears, and when a retry or a timeout is due.
+pub(crate) struct DeliveryQueue {
+    queues: Mutex<HashMap<Connection, ConnectionQueue>>,
+    /// The maximum number of values waiting in the queue of a connection.
+    capacity: AtomicUsize,
+}
+
+impl Default for DeliveryQueue {
+    fn default() -> Self {
+        Self {
+            queues: Mutex::default(),
+            capacity: AtomicUsize::new(DEFAULT_CAPACITY),
+        }
+    }
+}
+
+impl DeliveryQueue {
+    /// Queues a value change for the given connection.
+    ///
+    /// If the queue of the connection is full of bounded values, its oldest bounded value is dropped.
+    pub(crate) fn push(&'static self, connection: Connection, value: QueuedValue) {
+        let dropped = {
+            let mut queues = self.queues.lock();
+            let queue = queues.entry(connection).or_default();
+
+            let bounded = queue.values.iter().filter(|queued| queued.bounded).count();
+            let dropped = if value.bounded && bounded >= self.capacity.load(Ordering::Relaxed) {
+                queue
+                    .values
+                    .iter()
+                    .position(|queued| queued.bounded)
+                    .and_then(|index| queue.values.remove(index))
+            } else {
+                None
+            };
+            queue.values.push_back(value);
+
+            dropped
+        };
+
+        self.schedule_send(connection);
+
+        if let Some(dropped) = dropped {
+            warn!(
+                target: NOTIFY,
+                "Delivery queue of {} is full, dropping the oldest value.",
+                connection
+            );
+            dropped.report(connection, DeliveryOutcome::Dropped);
+        }
+    }
+
+    /// Sets the maximum number of values waiting in the queue of a connection.
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity.max(1), Ordering::Relaxed);
+    }
+
+    /// Updates the congestion status of the given connection.
+    ///
+    /// Once the congestion clears, a value waiting to be retried is sent right away.
+    pub(crate) fn set_congested(&'static self, conn_id: u16, congested: bool) {
+        let connections: Vec<_> = {
+            let mut queues = self.queues.lock();
+
+            queues
+                .iter_mut()
+                .filter(|(connection, _)| connection.id == conn_id)
+                .map(|(connection, queue)| {
+                    queue.congested = congested;
+                    if let Some(in_flight) = queue.in_flight.as_mut() {
+                        if !congested && in_flight.stage == Stage::BackingOff {
+                            in_flight.stage = Stage::Ready;
+                        }
+                    }
+                    *connection
+                })
+                .collect()
+        };
+
+        if !congested {
+            for connection in connections {
+                self.schedule_send(connection);
+            }
+        }
+    }
+
+    /// Drops the queue of the given connection, reporting its values as undelivered.
+    pub(crate) fn abort_connection(&self, conn_id: u16) {
+        let aborted: Vec<_> = {
+            let mut queues = self.queues.lock();
+            let connections: Vec<_> = queues
+                .keys()
+                .filter(|connection| connection.id == conn_id)
+                .copied()
+                .collect();
+
+            connections
+                .into_iter()
+                .filter_map(|connection| {
+                    queues.remove(&connection).map(|queue| (connection, queue))
+                })
+                .collect()
+        };
+
+        for (connection, queue) in aborted {
+            let in_flight = queue.in_flight.map(|in_flight| in_flight.value);
+            for value in in_flight.into_iter().chain(queue.values) {
+                value.report(connection, DeliveryOutcome::Disconnected);
+            }
+        }
+    }
+
+    fn schedule_send(&'static self, connection: Connection) {
+        worker::schedule(Duration::ZERO, move || self.send_next(connection));
+    }
+
+    /// Sends the value in flight on the given connection, or the next queued one, unless
+    /// the connection is congested or the value in flight waits for its status or its retry.
+    fn send_next(&'static self, connection: Connection) {
+        let failed = {
+            let mut queues = self.queues.lock();
+            let Some(queue) = queues.get_mut(&connection) else {
+                return;
+            };
+
+            if queue.congested {
+                return;
+            }
+
+            if queue.in_flight.is_none() {
+                let Some(value) = queue.values.pop_front() else {
+                    return;
+                };
+                queue.in_flight = Some(InFlight {
+                    value,
+                    attempt: 0,
+                    stage: Stage::Ready,
+                });
+            }
+
+            let Some(in_flight) = queue.in_flight.as_mut() else {
+                return;
+            };
+            if in_flight.stage != Stage::Ready {
+                return;
+            }
+
+            let value = &in_flight.value;
+            if in_flight.attempt > 0 {
+                debug!(
+                    target: NOTIFY,
+                    "Retrying delivery of handle 0x{:04x} to {}, attempt {}.",
+                    value.handle, connection, in_flight.attempt
+                );
+            }
+
+            // The stack reports the outcome of notifications with a confirmation event too,
+            // once they are handed to the controller or dropped because of the congestion.
+            let token = PENDING_INDICATIONS.register_callback(
+                connection.id,
+                value.handle,
+                move |token, status| {
+                    worker::schedule(Duration::ZERO, move || {
+                        self.on_send_status(connection, token, status);
+                    });
+                },
+            );
+            let mut buffer = value.value.clone();
+
+            #[allow(clippy::cast_possible_truncation)]
+            let result = unsafe {
+                esp!(esp_ble_gatts_send_indicate(
+                    value.interface,
+                    connection.id,
+                    value.handle,
+                    buffer.len() as u16,
+                    buffer.as_mut_slice().as_mut_ptr(),
+                    value.indicate
+                ))
+            };
+
+            if let Err(error) = result {
+                warn!(
+                    target: NOTIFY,
+                    "Failed to send handle 0x{:04x} to {}: {}.",
+                    value.handle, connection, error
+                );
+                PENDING_INDICATIONS.cancel(token);
+                self.retry_later(connection, queue)
+            } else {
+                let timeout = if value.indicate {
+                    value.settings.confirmation_timeout
+                } else {
+                    SEND_STATUS_TIMEOUT
+                };
+                in_flight.stage = Stage::Sent(token);
+                worker::schedule(timeout, move || self.on_send_timeout(connection, token));
+
+                None
+            }
+        };
+
+        self.finish(
+            connection,
+            failed.map(|value| (value, DeliveryOutcome::Failed)),
+        );
+    }
+
+    /// Handles the send status reported by the stack for the value in flight.
+    fn on_send_status(
+        &'static self,
+        connection: Connection,
+        token: u32,
+        status: esp_gatt_status_t,
+    ) {
+        let outcome = {
+            let mut queues = self.queues.lock();
+            let Some(queue) = queues.get_mut(&connection) else {
+                return;
+            };
+            let Some(in_flight) = queue.in_flight.as_ref() else {
+                return;
+            };
+            if in_flight.stage != Stage::Sent(token) {
+                return;
+            }
+
+            let value = &in_flight.value;
+            if status == esp_gatt_status_t_ESP_GATT_OK {
+                let outcome = if value.indicate {
+                    DeliveryOutcome::Confirmed
+                } else {
+                    DeliveryOutcome::Sent
+                };
+                queue
+                    .in_flight
+                    .take()
+                    .map(|in_flight| (in_flight.value, outcome))
+            } else {
+                if status == esp_gatt_status_t_ESP_GATT_CONGESTED {
+                    debug!(
+                        target: NOTIFY,
+                        "Handle 0x{:04x} to {} was dropped, the connection is congested.",
+                        value.handle, connection
+                    );
+                } else {
+                    warn!(
+                        target: NOTIFY,
+                        "Sending handle 0x{:04x} to {} failed, error code: {:04x}.",
+                        value.handle, connection, status
+                    );
+                }
+
+                self.retry_later(connection, queue)
+                    .map(|value| (value, DeliveryOutcome::Failed))
+            }
+        };
+
+        self.finish(connection, outcome);
+    }
+
+    /// Handles the value in flight if the stack did not report its send status in time.
+    fn on_send_timeout(&'static self, connection: Connection, token: u32) {
+        let outcome = {
+            let mut queues = self.queues.lock();
+            let Some(queue) = queues.get_mut(&connection) else {
+                return;
+            };
+            let Some(in_flight) = queue.in_flight.as_ref() else {
+                return;
+            };
+            if in_flight.stage != Stage::Sent(token) {
+                return;
+            }
+
+            PENDING_INDICATIONS.cancel(token);
+
+            if in_flight.value.indicate {
+                warn!(
+                    target: NOTIFY,
+                    "Indication of handle 0x{:04x} to {} timed out.",
+                    in_flight.value.handle, connection
+                );
+                self.retry_later(connection, queue)
+                    .map(|value| (value, DeliveryOutcome::Failed))
+            } else {
+                // The call succeeded, but the stack did not report the send status.
+                queue
+                    .in_flight
+                    .take()
+                    .map(|in_flight| (in_flight.value, DeliveryOutcome::Unknown))
+            }
+        };
+
+        self.finish(connection, outcome);
+    }
+
+    /// Schedules a retry of the value in flight, doubling the delay at every attempt,
+    /// or takes it out of the queue once it ran out of retries.
+    fn retry_later(
+        &'static self,
+        connection: Connection,
+        queue: &mut ConnectionQueue,
+    ) -> Option<QueuedValue> {
+        let in_flight = queue.in_flight.as_mut()?;
+
+        if in_flight.attempt >= in_flight.value.settings.retries {
+            return queue.in_flight.take().map(|in_flight| in_flight.value);
+        }
+
+        let delay =
+            RETRY_DELAY * 2_u32.pow(u32::from(in_flight.attempt.min(MAX_BACK_OFF_DOUBLINGS)));
+        in_flight.attempt += 1;
+        in_flight.stage = Stage::BackingOff;
+
+        let attempt = in_flight.attempt;
+        worker::schedule(delay, move || self.retry(connection, attempt));
+
+        None
+    }
+
+    /// Retries the value in flight, unless it was retried early meanwhile.
+    fn retry(&'static self, connection: Connection, attempt: u8) {
+        {
+            let mut queues = self.queues.lock();
+            let Some(in_flight) = queues
+                .get_mut(&connection)
+                .and_then(|queue| queue.in_flight.as_mut())
+            else {
+                return;
+            };
+            if in_flight.attempt != attempt || in_flight.stage != Stage::BackingOff {
+                return;
+            }
+
+            in_flight.stage = Stage::Ready;
+        }
+
+        self.send_next(connection);
+    }
+
+    /// Reports the outcome of a finished delivery, if any, then sends the next value.
+    fn finish(
+        &'static self,
+        connection: Connection,
+        outcome: Option<(QueuedValue, DeliveryOutcome)>,
+    ) {
+        if let Some((value, outcome)) = outcome {
+            value.report(connection, outcome);
+        }
+
+        self.send_next(connection);
+    }
+}
+
+impl GattServer {
+    /// Sets the maximum number of value changes waiting for reliable delivery to each connection.
+    ///
+    /// When the queue of a connection is full, its oldest value is dropped and reported as
+    /// [`DeliveryOutcome::Dropped`]. A smaller queue bounds the memory used by slow or congested
+    /// connections, at the cost of losing intermediate values. By default, each queue holds up to
+    /// 16 values.
+    ///
+    /// Only the value changes of the characteristics count: the values sent by the streams,
+    /// the chunked channels, the histories and the control points are never dropped,
+    /// as they are bounded by their own settings.
+    pub fn delivery_queue_capacity(&mut self, capacity: usize) -> &mut Self {
+        DELIVERY_QUEUE.set_capacity(capacity);
+        self
+    }
+}
@@ -1,14 +1,23 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
+use crate::utilities::log_targets::GATTS;
 use crate::{
-    leaky_box_raw,
-    utilities::{AttributeControl, AttributePermissions, BleUuid},
+    gatt_server::{
+        attribute_table::AttributeTable,
+        capacity::{self, Value, MAX_VALUE_LENGTH},
+        encryption_policy::registered_permissions,
+        error::{esp_check, esp_report},
+        registration::RegistrationRetries,
+        GattServerError,
+    },
+    utilities::{AttributeControl, AttributePermissions, BleUuid, FromGattValue, ToGattValue},
+    BluedroidError,
 };
 
 use esp_idf_sys::{
     esp_attr_control_t, esp_attr_value_t, esp_ble_gatts_add_char_descr,
     esp_ble_gatts_cb_param_t_gatts_read_evt_param, esp_ble_gatts_cb_param_t_gatts_write_evt_param,
-    esp_ble_gatts_set_attr_value, esp_nofail,
+    esp_ble_gatts_set_attr_value,
 };
 use log::{debug, info, warn};
 use parking_lot::RwLock;
@@ -18,14 +27,15 @@ pub type LockedDescriptor = Arc<RwLock<Descriptor>>;
 /// Represents a GATT descriptor.
 #[derive(Debug, Clone)]
 pub struct Descriptor {
-    name: Option<String>,
+    pub(crate) name: Option<Cow<'static, str>>,
     pub(crate) uuid: BleUuid,
-    value: Vec<u8>,
+    value: Value,
     pub(crate) attribute_handle: Option<u16>,
-    permissions: AttributePermissions,
+    pub(crate) permissions: AttributePermissions,
     pub(crate) control: AttributeControl,
     internal_control: esp_attr_control_t,
     pub(crate) write_callback: Option<fn(Vec<u8>, esp_ble_gatts_cb_param_t_gatts_write_evt_param)>,
+    pub(crate) registration: RegistrationRetries,
 }
 
 impl Descriptor {
@@ -35,12 +45,13 @@ impl Descriptor {
         Self {
             name: None,
             uuid,
-            value: vec![0],
+            value: capacity::value(&[0]).unwrap_or_default(),
             attribute_handle: None,
             permissions: AttributePermissions::default(),
             control: AttributeControl::AutomaticResponse(vec![0]),
             internal_control: AttributeControl::AutomaticResponse(vec![0]).into(),
             write_callback: None,
+            registration: RegistrationRetries::new(),
         }
     }
 
@@ -48,7 +59,7 @@ impl Descriptor {
     ///
     /// This name is only used for debugging purposes.
     pub fn name(&mut self, name: &str) -> &mut Self {
-        self.name = Some(String::from(name));
+        self.name = Some(Cow::Owned(String::from(name)));
         self
     }
 
@@ -59,6 +70,9 @@ impl Descriptor {
     }
 
     /// Sets the read callback for the [`Descriptor`].
+    ///
+    /// As for characteristics, values longer than the MTU are read in several parts,
+    /// answered from the value returned for the first one.
     pub fn on_read<
         C: Fn(esp_ble_gatts_cb_param_t_gatts_read_evt_param) -> Vec<u8> + Send + Sync + 'static,
     >(
@@ -67,6 +81,7 @@ impl Descriptor {
     ) -> &mut Self {
         if !self.permissions.read_access {
             warn!(
+                target: GATTS,
                 "Descriptor {} does not have read permissions. Ignoring read callback.",
                 self
             );
@@ -87,6 +102,7 @@ impl Descriptor {
     ) -> &mut Self {
         if !self.permissions.write_access {
             warn!(
+                target: GATTS,
                 "Descriptor {} does not have write permissions. Ignoring write callback.",
                 self
             );
@@ -100,29 +116,89 @@ impl Descriptor {
     }
 
     /// Sets the value of the [`Descriptor`].
-    pub fn set_value<T: Into<Vec<u8>>>(&mut self, value: T) -> &mut Self {
-        self.value = value.into();
+    ///
+    /// The value can be anything that implements [`ToGattValue`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is longer than [`MAX_VALUE_LENGTH`], with the `heapless` feature.
+    pub fn set_value<T: ToGattValue>(&mut self, value: T) -> &mut Self {
+        match self.store_value(&value.to_gatt_value()) {
+            Ok(()) => {}
+            Err(error @ GattServerError::ValueTooLong { .. }) => panic!("{error}"),
+            Err(error) => error.report(),
+        }
+
+        self
+    }
+
+    /// Sets the value of the [`Descriptor`], like [`Descriptor::set_value`],
+    /// returning its failures instead of panicking or reporting them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::ValueTooLong`] if the value is too long, and the value is left
+    /// unchanged. Returns a [`GattServerError::Stack`] if the Bluetooth stack rejects the value.
+    pub fn try_set_value<T: ToGattValue>(&mut self, value: T) -> Result<&mut Self, BluedroidError> {
+        self.store_value(&value.to_gatt_value())?;
+        Ok(self)
+    }
+
+    /// Stores a value, and hands it to the Bluetooth stack if the descriptor is registered.
+    fn store_value(&mut self, value: &[u8]) -> Result<(), GattServerError> {
+        let Some(value) = capacity::value(value) else {
+            return Err(GattServerError::ValueTooLong {
+                attribute: self.to_string(),
+                length: value.len(),
+                capacity: MAX_VALUE_LENGTH,
+            });
+        };
+
+        self.value = value;
 
-        debug!("Trying to set value of {} to {:02X?}.", self, self.value);
+        debug!(target: GATTS, "Trying to set value of {} to {:02X?}.", self, self.value);
 
         if let Some(handle) = self.attribute_handle {
             #[allow(clippy::cast_possible_truncation)]
             unsafe {
-                esp_nofail!(esp_ble_gatts_set_attr_value(
+                esp_check!(esp_ble_gatts_set_attr_value(
                     handle,
                     self.value.len() as u16,
                     self.value.as_slice().as_ptr()
-                ));
+                ))?;
             }
         } else {
             info!(
+                target: GATTS,
                 "Descriptor {} not registered yet, value will be set on registration.",
                 self
             );
         }
+
+        Ok(())
+    }
+
+    /// Sets the initial value of the [`Descriptor`] from a constant declaration,
+    /// borrowing it until the value first changes.
+    pub(crate) fn set_static_value(&mut self, value: &'static [u8]) -> &mut Self {
+        self.value = Value::Static(value);
         self
     }
 
+    /// Returns the current value of the [`Descriptor`], decoded as `T`.
+    ///
+    /// Returns `None` if the stored bytes cannot be decoded as `T`.
+    #[must_use]
+    pub fn value<T: FromGattValue>(&self) -> Option<T> {
+        T::from_gatt_value(&self.value)
+    }
+
+    /// Returns the attribute handle of the [`Descriptor`], once registered in the Bluetooth stack.
+    #[must_use]
+    pub const fn handle(&self) -> Option<u16> {
+        self.attribute_handle
+    }
+
     /// Returns a reference to the built [`Descriptor`] behind an `Arc` and an `RwLock`.
     ///
     /// The returned value can be passed to any function of this crate that expects a [`Descriptor`].
@@ -133,25 +209,43 @@ impl Descriptor {
     }
     pub(crate) fn register_self(&mut self, service_handle: u16) {
         debug!(
+            target: GATTS,
             "Registering {} into service at handle 0x{:04x}.",
             self, service_handle
         );
+        self.registration.requested();
 
+        // The Bluetooth stack copies the identifier and the value before the call returns.
+        let mut uuid = self.uuid.into();
         #[allow(clippy::cast_possible_truncation)]
+        let mut value = esp_attr_value_t {
+            attr_max_len: self.value.len() as u16,
+            attr_len: self.value.len() as u16,
+            attr_value: self.value.as_ptr().cast_mut(),
+        };
+
         unsafe {
-            esp_nofail!(esp_ble_gatts_add_char_descr(
+            esp_report!(esp_ble_gatts_add_char_descr(
                 service_handle,
-                leaky_box_raw!(self.uuid.into()),
-                self.permissions.into(),
-                leaky_box_raw!(esp_attr_value_t {
-                    attr_max_len: self.value.len() as u16,
-                    attr_len: self.value.len() as u16,
-                    attr_value: self.value.as_mut_slice().as_mut_ptr(),
-                }),
+                &mut uuid,
+                registered_permissions(self.permissions),
+                &mut value,
                 &mut self.internal_control,
             ));
         }
     }
+
+    /// Adds this [`Descriptor`] to the attribute table of its service.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn add_to_table(&self, table: &mut AttributeTable) {
+        table.attribute(
+            self.uuid,
+            registered_permissions(self.permissions),
+            self.internal_control,
+            self.value.len() as u16,
+            &self.value,
+        );
+    }
 }
 
 impl std::fmt::Display for Descriptor {
@@ -159,9 +253,7 @@ impl std::fmt::Display for Descriptor {
         write!(
             f,
             "{} ({})",
-            self.name
-                .clone()
-                .unwrap_or_else(|| "Unnamed descriptor".to_string()),
+            self.name.as_deref().unwrap_or("Unnamed descriptor"),
             self.uuid
         )
     }
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, hash::BuildHasher};
+
+use esp_idf_svc::nvs::EspDefaultNvs;
+use parking_lot::Mutex;
+
+use crate::utilities::{sig::descriptors, BleUuid};
+
+/// The error returned by a [`DescriptorStore`].
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Identifies the value of a descriptor for a client.
+///
+/// The instances tell apart the services of a profile, and the characteristics of a service,
+/// that share a UUID: the first one is instance 0, the next one instance 1, and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DescriptorKey {
+    /// The identity address of the client.
+    pub address: [u8; 6],
+    /// The UUID of the service of the characteristic.
+    pub service: BleUuid,
+    /// The instance of the service among the services of its profile sharing its UUID.
+    pub service_instance: u8,
+    /// The UUID of the characteristic of the descriptor.
+    pub characteristic: BleUuid,
+    /// The instance of the characteristic among the characteristics of its service
+    /// sharing its UUID.
+    pub characteristic_instance: u8,
+    /// The UUID of the descriptor.
+    pub descriptor: BleUuid,
+}
+
+impl DescriptorKey {
+    /// The length of a serialised key.
+    pub(crate) const LENGTH: usize = 56;
+
+    /// Serialises the key: the address, then each UUID in little-endian order,
+    /// followed by its instance for the service and the characteristic.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::LENGTH);
+        bytes.extend_from_slice(&self.address);
+        bytes.extend_from_slice(&self.service.as_u128().to_le_bytes());
+        bytes.push(self.service_instance);
+        bytes.extend_from_slice(&self.characteristic.as_u128().to_le_bytes());
+        bytes.push(self.characteristic_instance);
+        bytes.extend_from_slice(&self.descriptor.as_u128().to_le_bytes());
+        bytes
+    }
+
+    /// Parses a key serialised with [`DescriptorKey::to_bytes`].
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::LENGTH {
+            return None;
+        }
+
+        let uuid = |offset: usize| {
+            let mut uuid = [0u8; 16];
+            uuid.copy_from_slice(&bytes[offset..offset + 16]);
+            BleUuid::from_uuid128(uuid)
+        };
+
+        let mut address = [0u8; 6];
+        address.copy_from_slice(&bytes[..6]);
+
+        Some(Self {
+            address,
+            service: uuid(6),
+            service_instance: bytes[22],
+            characteristic: uuid(23),
+            characteristic_instance: bytes[39],
+            descriptor: uuid(40),
+        })
+    }
+}
+
+/// A persistence backend for the descriptor values of the clients, such as their CCCDs.
+///
+/// By default, the values are stored in the `ble` namespace of the default NVS partition,
+/// through the implementation of this trait for `Mutex<EspDefaultNvs>`, or kept in memory
+/// if the NVS is not available.
+/// Call `set_store` on [`STORAGE`](crate::gatt_server::STORAGE) before starting the server
+/// to keep the values in your own settings system or in an external flash filesystem instead.
+pub trait DescriptorStore: Send + Sync {
+    /// Returns the value stored with the given key, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be read.
+    fn get(&self, key: &DescriptorKey) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Stores a value with the given key, replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be written.
+    fn set(&self, key: &DescriptorKey, value: &[u8]) -> Result<(), StoreError>;
+
+    /// Removes the value stored with the given key, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be written.
+    fn remove(&self, key: &DescriptorKey) -> Result<(), StoreError>;
+}
+
+impl DescriptorStore for Mutex<EspDefaultNvs> {
+    fn get(&self, key: &DescriptorKey) -> Result<Option<Vec<u8>>, StoreError> {
+        let mut buf: [u8; 4] = [0; 4];
+        let result = self
+            .lock()
+            .get_raw(&nvs_key(key), &mut buf)
+            .map(|value| value.map(<[u8]>::to_vec));
+        Ok(result?)
+    }
+
+    fn set(&self, key: &DescriptorKey, value: &[u8]) -> Result<(), StoreError> {
+        let result = self.lock().set_raw(&nvs_key(key), value);
+        result?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &DescriptorKey) -> Result<(), StoreError> {
+        let result = self.lock().remove(&nvs_key(key));
+        result?;
+        Ok(())
+    }
+}
+
+/// An in-memory store, whose values are lost on reboot.
+impl<S: BuildHasher + Send> DescriptorStore for Mutex<HashMap<DescriptorKey, Vec<u8>, S>> {
+    fn get(&self, key: &DescriptorKey) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.lock().get(key).cloned())
+    }
+
+    fn set(&self, key: &DescriptorKey, value: &[u8]) -> Result<(), StoreError> {
+        self.lock().insert(*key, value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &DescriptorKey) -> Result<(), StoreError> {
+        self.lock().remove(key);
+        Ok(())
+    }
+}
+
+/// Returns the NVS key of a descriptor value.
+///
+/// NVS keys are limited to 15 characters, so the key is a 60-bit hash of the identity address
+/// of the client and of the attributes. The instances are only hashed when not the first ones,
+/// and the descriptor UUID when not the CCCD one, so that the keys of the CCCD values stored
+/// before they were hashed do not change.
+fn nvs_key(key: &DescriptorKey) -> String {
+    let instances = if key.service_instance == 0 && key.characteristic_instance == 0 {
+        Vec::new()
+    } else {
+        vec![key.service_instance, key.characteristic_instance]
+    };
+    let descriptor = if key.descriptor == descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION {
+        Vec::new()
+    } else {
+        key.descriptor.as_u128().to_le_bytes().to_vec()
+    };
+
+    // 64-bit FNV-1a.
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in key
+        .address
+        .iter()
+        .chain(&key.service.as_u128().to_le_bytes())
+        .chain(&key.characteristic.as_u128().to_le_bytes())
+        .chain(&instances)
+        .chain(&descriptor)
+    {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+
+    format!("{:015X}", hash >> 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DescriptorKey;
+    use crate::utilities::{sig::descriptors, BleUuid};
+
+    #[test]
+    fn keys_survive_serialisation() {
+        let key = DescriptorKey {
+            address: [1, 2, 3, 4, 5, 6],
+            service: BleUuid::from_uuid16(0x180F),
+            service_instance: 1,
+            characteristic: BleUuid::from_uuid128_str("12345678-9abc-def0-1234-56789abcdef0"),
+            characteristic_instance: 2,
+            descriptor: descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION,
+        };
+
+        let bytes = key.to_bytes();
+
+        assert_eq!(bytes.len(), DescriptorKey::LENGTH);
+        assert_eq!(DescriptorKey::from_bytes(&bytes), Some(key));
+        assert_eq!(DescriptorKey::from_bytes(&bytes[1..]), None);
+    }
+}
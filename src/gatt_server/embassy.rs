@@ -0,0 +1,143 @@
+//! Async bridges for [embassy](https://embassy.dev) executors, with the `embassy` feature.
+//!
+//! The GATT server runs its callbacks in the Bluetooth stack's context. These bridges move
+//! its events and results into [`embassy_sync`] primitives, so that tasks running on an embassy
+//! executor can await them, in esp-idf + embassy hybrid firmware.
+//!
+//! The primitives of this module use the [`CriticalSectionRawMutex`], so the application must
+//! provide a `critical-section` implementation, for example the one of `esp-idf-hal`.
+
+use std::sync::Arc;
+
+use embassy_sync::{
+    blocking_mutex::raw::{CriticalSectionRawMutex, RawMutex},
+    channel::{Channel, TrySendError},
+    mutex::Mutex,
+    signal::Signal,
+};
+use esp_idf_sys::{
+    esp_ble_gap_cb_param_t_ble_read_rssi_cmpl_evt_param, esp_ble_gap_read_rssi,
+    esp_bt_status_t_ESP_BT_STATUS_SUCCESS,
+};
+use log::{debug, warn};
+
+use crate::utilities::log_targets::{GAP, NOTIFY};
+use crate::{
+    gatt_server::{error::esp_report, GattEvent, GattServer, LockedCharacteristic},
+    utilities::{Connection, DeliveryOutcome},
+};
+
+/// Serialises the RSSI requests: the Bluetooth stack reports a single result at a time.
+static RSSI_REQUEST: Mutex<CriticalSectionRawMutex, ()> = Mutex::new(());
+
+/// The result of the pending RSSI request.
+static RSSI: Signal<CriticalSectionRawMutex, Option<i8>> = Signal::new();
+
+impl GattServer {
+    /// Forwards the events of the GATT server to an embassy [`Channel`].
+    ///
+    /// The events are sent without waiting: when the channel is full, the event is dropped
+    /// and a warning is logged.
+    pub fn forward_events<M: RawMutex + Sync, const N: usize>(
+        &mut self,
+        channel: &'static Channel<M, GattEvent, N>,
+    ) -> &mut Self {
+        self.on_event(move |event| {
+            if let Err(TrySendError::Full(event)) = channel.try_send(event.clone()) {
+                warn!(target: NOTIFY, "GATT event channel full, dropping {:?}.", event);
+            }
+        })
+    }
+
+    /// Signals the events of the GATT server to an embassy [`Signal`].
+    ///
+    /// A signal only holds the latest event: use [`GattServer::forward_events`]
+    /// if every event matters.
+    pub fn signal_events<M: RawMutex + Sync>(
+        &mut self,
+        signal: &'static Signal<M, GattEvent>,
+    ) -> &mut Self {
+        self.on_event(move |event| signal.signal(event.clone()))
+    }
+}
+
+/// Notifies a value of a characteristic to a connection,
+/// resolving to the [`DeliveryOutcome`] once the Bluetooth stack accepted it.
+///
+/// The value goes through the reliable delivery queue, like the values of a [`NotifySink`],
+/// and does not change the current value of the characteristic.
+///
+/// [`NotifySink`]: crate::gatt_server::NotifySink
+pub async fn notify<T: Into<Vec<u8>>>(
+    characteristic: &LockedCharacteristic,
+    connection: Connection,
+    value: T,
+) -> DeliveryOutcome {
+    send(characteristic, connection, value.into(), false).await
+}
+
+/// Indicates a value of a characteristic to a connection,
+/// resolving to the [`DeliveryOutcome`] once the client confirmed it.
+///
+/// The value goes through the reliable delivery queue, like the values of a [`NotifySink`],
+/// and does not change the current value of the characteristic.
+///
+/// [`NotifySink`]: crate::gatt_server::NotifySink
+pub async fn indicate<T: Into<Vec<u8>>>(
+    characteristic: &LockedCharacteristic,
+    connection: Connection,
+    value: T,
+) -> DeliveryOutcome {
+    send(characteristic, connection, value.into(), true).await
+}
+
+async fn send(
+    characteristic: &LockedCharacteristic,
+    connection: Connection,
+    value: Vec<u8>,
+    indicate: bool,
+) -> DeliveryOutcome {
+    let outcome = Arc::new(Signal::<CriticalSectionRawMutex, DeliveryOutcome>::new());
+    let delivered = outcome.clone();
+
+    // The characteristic is released before waiting.
+    let queued = characteristic.read().queue_value_as(
+        connection,
+        value,
+        indicate,
+        Some(Arc::new(move |_, result| delivered.signal(result))),
+    );
+
+    if !queued {
+        return DeliveryOutcome::Failed;
+    }
+
+    outcome.wait().await
+}
+
+/// Reads the received signal strength of a connection, in dBm.
+///
+/// Returns `None` if the Bluetooth stack could not read it.
+/// Concurrent reads wait for each other.
+pub async fn read_rssi(connection: Connection) -> Option<i8> {
+    let _request = RSSI_REQUEST.lock().await;
+    RSSI.reset();
+
+    let mut address = connection.remote_bda();
+    if !unsafe { esp_report!(esp_ble_gap_read_rssi(address.as_mut_ptr())) } {
+        return None;
+    }
+
+    RSSI.wait().await
+}
+
+/// Completes the pending RSSI request.
+pub(crate) fn on_read_rssi(param: esp_ble_gap_cb_param_t_ble_read_rssi_cmpl_evt_param) {
+    if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+        debug!(target: GAP, "RSSI of {:02X?} is {} dBm.", param.remote_addr, param.rssi);
+        RSSI.signal(Some(param.rssi));
+    } else {
+        warn!(target: GAP, "Failed to read the RSSI of {:02X?}.", param.remote_addr);
+        RSSI.signal(None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp_ble_auth_cmpl_t, esp_ble_gap_disconnect, esp_ble_sec_act_t_ESP_BLE_SEC_ENCRYPT,
+    esp_ble_set_encryption, esp_gatt_perm_t,
+};
+use log::{debug, info, warn};
+
+use crate::gatt_server::{
+    bond_capacity::{evict_bond, record_connection},
+    error::esp_report,
+    resolving_list::{bonded_devices, identity_address},
+    worker, GattServer, GLOBAL_GATT_SERVER,
+};
+use crate::utilities::log_targets::GAP;
+use crate::utilities::{AttributePermissions, Connection};
+
+/// Whether the attributes are registered with encrypted permissions only.
+static ENCRYPTED_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// The token of the last encryption deadline, telling a deadline from the ones of the
+/// earlier connections that had the same connection ID.
+static DEADLINE: AtomicU32 = AtomicU32::new(0);
+
+impl GattServer {
+    /// Only serves clients over encrypted links.
+    ///
+    /// Every attribute is registered with encrypted permissions, whatever its own
+    /// [`AttributePermissions`], so the Bluetooth stack rejects any request received
+    /// before the link is encrypted. Upon connection, the server requests the encryption
+    /// of the link if the client is bonded, and otherwise waits for the client to pair.
+    /// Clients whose link is not encrypted within `timeout` are disconnected.
+    ///
+    /// # Notes
+    ///
+    /// The permissions are set when registering the attributes, so this must be called
+    /// before starting the server.
+    pub fn encrypted_connections_only(&mut self, timeout: Duration) -> &mut Self {
+        if self.started {
+            warn!(
+                target: GAP,
+                "Cannot require encryption once the GATT server is started: the attributes are already registered."
+            );
+            return self;
+        }
+
+        ENCRYPTED_ONLY.store(true, Ordering::Relaxed);
+        self.encryption_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Starts securing a new connection, and schedules its disconnection if it is still
+    /// not encrypted once the timeout elapsed.
+    pub(crate) fn require_encryption(&mut self, connection: Connection) {
+        let Some(timeout) = self.encryption_timeout else {
+            return;
+        };
+
+        let mut address = connection.remote_bda();
+        if bonded_devices()
+            .iter()
+            .any(|device| device.bd_addr == address)
+        {
+            debug!(
+                target: GAP,
+                "Requesting the encryption of the link with bonded peer {:02X?}.",
+                address
+            );
+            unsafe {
+                esp_report!(esp_ble_set_encryption(
+                    address.as_mut_ptr(),
+                    esp_ble_sec_act_t_ESP_BLE_SEC_ENCRYPT
+                ));
+            }
+        }
+
+        let token = DEADLINE.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        self.encryption_deadlines.insert(connection.id(), token);
+
+        worker::schedule(timeout, move || {
+            let mut server = GLOBAL_GATT_SERVER.lock();
+            if server.encryption_deadlines.get(&connection.id()) != Some(&token) {
+                return;
+            }
+            server.encryption_deadlines.remove(&connection.id());
+
+            warn!(
+                target: GAP,
+                "Link with {:02X?} not encrypted after {:?}, disconnecting.",
+                address,
+                timeout
+            );
+            unsafe {
+                esp_report!(esp_ble_gap_disconnect(address.as_mut_ptr()));
+            }
+        });
+    }
+
+    /// Cancels the encryption deadline of a connection, once it is encrypted or closed.
+    pub(crate) fn cancel_encryption_deadline(&mut self, conn_id: u16) {
+        self.encryption_deadlines.remove(&conn_id);
+    }
+
+    /// Records the links whose encryption succeeded.
+    pub(crate) fn on_authentication_complete(&mut self, param: esp_ble_auth_cmpl_t) {
+        if param.success {
+            info!(target: GAP, "Link with {:02X?} encrypted.", param.bd_addr);
+
+            // The event carries the address of the peer, which can be its identity address
+            // rather than the private address it connected with.
+            let identity = identity_address(param.bd_addr);
+            let encrypted: Vec<u16> = self
+                .active_connections
+                .iter()
+                .filter(|connection| {
+                    connection.remote_bda() == param.bd_addr
+                        || identity_address(connection.remote_bda()) == identity
+                })
+                .map(Connection::id)
+                .collect();
+            for conn_id in encrypted {
+                self.cancel_encryption_deadline(conn_id);
+            }
+
+            record_connection(param.bd_addr);
+            evict_bond(param.bd_addr, true);
+        } else {
+            warn!(
+                target: GAP,
+                "Authentication of {:02X?} failed with reason 0x{:02X}.",
+                param.bd_addr,
+                param.fail_reason
+            );
+            evict_bond(param.bd_addr, false);
+        }
+    }
+}
+
+/// Returns the permissions to register an attribute with, under the encryption policy.
+pub(crate) fn registered_permissions(permissions: AttributePermissions) -> esp_gatt_perm_t {
+    if ENCRYPTED_ONLY.load(Ordering::Relaxed) {
+        permissions.encrypted().into()
+    } else {
+        permissions.into()
+    }
+}
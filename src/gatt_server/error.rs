@@ -0,0 +1,248 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use esp_idf_sys::{esp_err_t, esp_gatt_status_t, ESP_OK};
+use log::warn;
+use parking_lot::RwLock;
+
+use crate::gatt_server::GattServer;
+use crate::utilities::log_targets::GATTS;
+
+type ErrorCallback = dyn Fn(&GattServerError) + Send + Sync;
+
+/// The function to be called when the GATT server encounters an error.
+static ERROR_CALLBACK: RwLock<Option<Arc<ErrorCallback>>> = RwLock::new(None);
+
+/// The number of Bluetooth stack function failures since the last call to [`take_stack_errors`].
+static STACK_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+/// Calls a Bluetooth stack function, reporting its failure as a [`GattServerError`] instead of panicking.
+///
+/// Evaluates to `true` if the function succeeded.
+macro_rules! esp_report {
+    ($function:ident($($argument:expr),* $(,)?)) => {
+        $crate::gatt_server::GattServerError::check(stringify!($function), $function($($argument),*))
+    };
+}
+
+/// Calls a Bluetooth stack function, turning its failure into a [`GattServerError`]
+/// returned to the caller instead of reported.
+///
+/// Evaluates to a `Result<(), GattServerError>`.
+macro_rules! esp_check {
+    ($function:ident($($argument:expr),* $(,)?)) => {
+        $crate::gatt_server::GattServerError::result(stringify!($function), $function($($argument),*))
+    };
+}
+
+pub(crate) use esp_check;
+pub(crate) use esp_report;
+
+/// An error encountered by the GATT server while handling the events of the Bluetooth stack.
+///
+/// These errors are logged and reported to the callback set with [`GattServer::on_error`],
+/// and the GATT server keeps running. The fallible operations return them to the caller
+/// wrapped in a [`BluedroidError`].
+///
+/// [`BluedroidError`]: crate::BluedroidError
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GattServerError {
+    /// A function of the Bluetooth stack failed.
+    Stack {
+        /// The name of the function.
+        operation: &'static str,
+        /// The returned error code.
+        code: esp_err_t,
+    },
+    /// The Bluetooth stack reported a failure in an event.
+    Event {
+        /// The name of the event.
+        event: &'static str,
+        /// The reported status code.
+        status: esp_gatt_status_t,
+    },
+    /// An event referred to a profile that is not part of the GATT server.
+    UnknownProfile(u16),
+    /// A profile was added with the identifier of another profile of the GATT server.
+    DuplicateProfile(u16),
+    /// An attribute was used before being registered in the Bluetooth stack.
+    NotRegistered(String),
+    /// An attribute could not be registered in the Bluetooth stack, even after retrying.
+    RegistrationFailed(String),
+    /// The non-volatile storage failed.
+    Storage(esp_err_t),
+    /// The descriptor store set with `set_store` on [`STORAGE`] failed, with the given message.
+    ///
+    /// [`STORAGE`]: crate::gatt_server::STORAGE
+    DescriptorStore(String),
+    /// The advertisement or scan response data does not fit in its packet.
+    AdvertisementTooLong {
+        /// The packet: "advertisement" or "scan response".
+        packet: &'static str,
+        /// The first field that does not fit.
+        field: &'static str,
+        /// The encoded length of the packet up to that field, included.
+        length: usize,
+    },
+    /// The extended advertisement or scan response data does not fit.
+    ExtendedAdvertisementTooLong {
+        /// The length of the data.
+        length: usize,
+        /// The longest data accepted by the advertisement.
+        capacity: usize,
+    },
+    /// A value is longer than its attribute accepts.
+    ValueTooLong {
+        /// The attribute.
+        attribute: String,
+        /// The length of the value.
+        length: usize,
+        /// The maximum length of the value of the attribute.
+        capacity: usize,
+    },
+    /// An operation needs a property the characteristic does not have.
+    MissingProperty {
+        /// The characteristic.
+        attribute: String,
+        /// The missing property, such as "indicate".
+        property: &'static str,
+    },
+    /// The client did not confirm an indication in time, or disconnected before confirming it.
+    NotConfirmed,
+    /// The GATT server singleton was already taken.
+    AlreadyTaken,
+    /// The GATT server was already started.
+    AlreadyStarted,
+    /// The device name was rejected, for the given reason.
+    InvalidDeviceName(&'static str),
+    /// A bond backup could not be exported, imported or parsed, for the given reason.
+    BondBackup(&'static str),
+    /// A thread of the crate could not be spawned, with the given message.
+    ThreadSpawn(String),
+    /// A collection of the GATT tree is full, with the `heapless` feature,
+    /// or a service has too many attributes to be registered as an attribute table.
+    CapacityExceeded {
+        /// The collection: "profiles", "services", "characteristics", "descriptors" or "attributes".
+        collection: &'static str,
+        /// The capacity of the collection.
+        capacity: usize,
+    },
+}
+
+impl GattServerError {
+    /// Logs this error and passes it to the error callback.
+    pub(crate) fn report(self) {
+        warn!(target: GATTS, "GATT server error: {}.", self);
+
+        // Do not hold the lock while running the callback.
+        let callback = ERROR_CALLBACK.read().clone();
+        if let Some(callback) = callback {
+            callback(&self);
+        }
+    }
+
+    /// Reports the error code returned by a Bluetooth stack function, if any.
+    ///
+    /// Returns `true` if the function succeeded.
+    pub(crate) fn check(operation: &'static str, code: esp_err_t) -> bool {
+        match Self::result(operation, code) {
+            Ok(()) => true,
+            Err(error) => {
+                error.report();
+                false
+            }
+        }
+    }
+
+    /// Turns the error code returned by a Bluetooth stack function into a result,
+    /// leaving the report of the failure to the caller.
+    pub(crate) fn result(operation: &'static str, code: esp_err_t) -> Result<(), Self> {
+        if code == ESP_OK {
+            return Ok(());
+        }
+
+        STACK_ERRORS.fetch_add(1, Ordering::Relaxed);
+        Err(Self::Stack { operation, code })
+    }
+}
+
+/// Returns the number of Bluetooth stack function failures since the last call, and resets it.
+pub(crate) fn take_stack_errors() -> u32 {
+    STACK_ERRORS.swap(0, Ordering::Relaxed)
+}
+
+impl std::fmt::Display for GattServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stack { operation, code } => {
+                write!(f, "{operation} failed with error code 0x{code:x}")
+            }
+            Self::Event { event, status } => {
+                write!(f, "{event} event failed with status 0x{status:x}")
+            }
+            Self::UnknownProfile(identifier) => {
+                write!(f, "unknown profile with identifier 0x{identifier:04x}")
+            }
+            Self::DuplicateProfile(identifier) => {
+                write!(f, "duplicate profile identifier 0x{identifier:04x}")
+            }
+            Self::NotRegistered(attribute) => write!(f, "{attribute} is not registered"),
+            Self::RegistrationFailed(attribute) => write!(f, "{attribute} registration failed"),
+            Self::Storage(code) => write!(f, "storage failed with error code 0x{code:x}"),
+            Self::DescriptorStore(message) => write!(f, "descriptor store failed: {message}"),
+            Self::AdvertisementTooLong {
+                packet,
+                field,
+                length,
+            } => write!(f, "{packet} {field} does not fit: {length} bytes out of 31"),
+            Self::ExtendedAdvertisementTooLong { length, capacity } => write!(
+                f,
+                "extended advertisement data does not fit: {length} bytes out of {capacity}"
+            ),
+            Self::ValueTooLong {
+                attribute,
+                length,
+                capacity,
+            } => write!(
+                f,
+                "value of {attribute} is too long: {length} bytes out of {capacity}"
+            ),
+            Self::MissingProperty {
+                attribute,
+                property,
+            } => write!(f, "{attribute} does not have the {property} property"),
+            Self::NotConfirmed => write!(f, "the indication was not confirmed in time"),
+            Self::AlreadyTaken => write!(f, "the GATT server is already taken"),
+            Self::AlreadyStarted => write!(f, "the GATT server is already started"),
+            Self::InvalidDeviceName(reason) => write!(f, "invalid device name: {reason}"),
+            Self::BondBackup(reason) => write!(f, "bond backup failed: {reason}"),
+            Self::ThreadSpawn(message) => write!(f, "cannot spawn a thread: {message}"),
+            Self::CapacityExceeded {
+                collection,
+                capacity,
+            } => write!(f, "too many {collection}: the capacity is {capacity}"),
+        }
+    }
+}
+
+impl std::error::Error for GattServerError {}
+
+impl GattServer {
+    /// Sets the callback for the errors encountered by the GATT server.
+    ///
+    /// The GATT server does not stop when it encounters an error: failures are logged,
+    /// reported to this callback, and the offending event is skipped.
+    ///
+    /// # Notes
+    ///
+    /// The callback might be called from the Bluetooth stack's context, so it must not block.
+    pub fn on_error(
+        &mut self,
+        callback: impl Fn(&GattServerError) + Send + Sync + 'static,
+    ) -> &mut Self {
+        *ERROR_CALLBACK.write() = Some(Arc::new(callback));
+        self
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+use crate::utilities::Phy;
+use crate::{
+    gatt_server::GattServer,
+    utilities::{BleUuid, Connection, ConnectionParameters},
+};
+
+type EventCallback = dyn Fn(&GattEvent) + Send + Sync;
+
+/// The functions to be called when the GATT server handles an event.
+static EVENT_CALLBACKS: RwLock<Vec<Arc<EventCallback>>> = RwLock::new(Vec::new());
+
+/// An event handled by the GATT server, passed to the callbacks set with [`GattServer::on_event`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum GattEvent {
+    /// A client connected.
+    Connected(Connection),
+    /// A client disconnected.
+    Disconnected(Connection),
+    /// A client wrote the value of a characteristic.
+    Write {
+        /// The connection of the client.
+        connection: Connection,
+        /// The identifier of the characteristic.
+        characteristic: BleUuid,
+        /// The attribute handle of the characteristic value.
+        handle: u16,
+        /// The written value.
+        value: Vec<u8>,
+    },
+    /// A client changed its subscription to the notifications or indications of a characteristic.
+    Subscription {
+        /// The connection of the client.
+        connection: Connection,
+        /// The identifier of the characteristic.
+        characteristic: BleUuid,
+        /// Whether the client is subscribed to notifications.
+        notify: bool,
+        /// Whether the client is subscribed to indications.
+        indicate: bool,
+    },
+    /// A client negotiated the MTU of its connection.
+    MtuChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The negotiated MTU, in bytes.
+        mtu: u16,
+    },
+    /// The central device updated the parameters of a connection.
+    ConnectionParametersChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The new parameters of the connection.
+        parameters: ConnectionParameters,
+    },
+    /// The physical layer of a connection changed.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    PhyChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The physical layer the server transmits on.
+        tx_phy: Phy,
+        /// The physical layer the server receives on.
+        rx_phy: Phy,
+    },
+}
+
+/// Passes an event to the callbacks set with [`GattServer::on_event`].
+pub(crate) fn emit(event: &GattEvent) {
+    // Do not hold the lock while running the callbacks.
+    let callbacks = EVENT_CALLBACKS.read().clone();
+    for callback in callbacks {
+        callback(event);
+    }
+}
+
+impl GattServer {
+    /// Adds a callback for the events handled by the GATT server.
+    ///
+    /// Every callback added receives every event, in the order they are added.
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the Bluetooth stack's context, so it must not block.
+    pub fn on_event(&mut self, callback: impl Fn(&GattEvent) + Send + Sync + 'static) -> &mut Self {
+        EVENT_CALLBACKS.write().push(Arc::new(callback));
+        self
+    }
+}
@@ -0,0 +1,213 @@
+use std::ffi::CStr;
+
+use esp_idf_svc::eventloop::{
+    EspEvent, EspEventDeserializer, EspEventLoop, EspEventLoopType, EspEventPostData,
+    EspEventSerializer, EspEventSource,
+};
+use log::warn;
+
+use crate::utilities::log_targets::GATTS;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+use crate::utilities::Phy;
+use crate::{
+    gatt_server::{GattEvent, GattServer, MAX_VALUE_LENGTH},
+    utilities::{BleUuid, Connection, ConnectionParameters},
+};
+
+/// The event base of the BLE events, as a nul-terminated string.
+///
+/// The event loop compares the bases by address, so the string must have a single location.
+static EVENT_BASE: [u8; 10] = *b"BLUEDROID\0";
+
+/// A BLE event posted to an ESP-IDF event loop, see [`GattServer::publish_events`].
+///
+/// The event can be received with the `subscribe` functions of an [`EspEventLoop`],
+/// next to the Wi-Fi and IP events.
+#[derive(Debug, Clone, Copy)]
+pub enum BleEvent {
+    /// A client connected.
+    Connected(Connection),
+    /// A client disconnected.
+    Disconnected(Connection),
+    /// A client wrote the value of a characteristic.
+    Write {
+        /// The connection of the client.
+        connection: Connection,
+        /// The identifier of the characteristic.
+        characteristic: BleUuid,
+        /// The attribute handle of the characteristic value.
+        handle: u16,
+        /// The written value, truncated to [`MAX_VALUE_LENGTH`] bytes.
+        value: BleEventValue,
+    },
+    /// A client changed its subscription to the notifications or indications of a characteristic.
+    Subscription {
+        /// The connection of the client.
+        connection: Connection,
+        /// The identifier of the characteristic.
+        characteristic: BleUuid,
+        /// Whether the client is subscribed to notifications.
+        notify: bool,
+        /// Whether the client is subscribed to indications.
+        indicate: bool,
+    },
+    /// A client negotiated the MTU of its connection.
+    MtuChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The negotiated MTU, in bytes.
+        mtu: u16,
+    },
+    /// The central device updated the parameters of a connection.
+    ConnectionParametersChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The new parameters of the connection.
+        parameters: ConnectionParameters,
+    },
+    /// The physical layer of a connection changed.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    PhyChanged {
+        /// The connection of the client.
+        connection: Connection,
+        /// The physical layer the server transmits on.
+        tx_phy: Phy,
+        /// The physical layer the server receives on.
+        rx_phy: Phy,
+    },
+}
+
+/// A value carried by a [`BleEvent`].
+///
+/// The events are copied by the event loop, so their values are stored inline.
+#[derive(Clone, Copy)]
+pub struct BleEventValue {
+    length: usize,
+    bytes: [u8; MAX_VALUE_LENGTH],
+}
+
+impl BleEventValue {
+    fn new(value: &[u8]) -> Self {
+        let length = value.len().min(MAX_VALUE_LENGTH);
+        let mut bytes = [0; MAX_VALUE_LENGTH];
+        bytes[..length].copy_from_slice(&value[..length]);
+
+        Self { length, bytes }
+    }
+
+    /// Returns the bytes of the value.
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+}
+
+impl std::fmt::Debug for BleEventValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02X?}", self.as_slice())
+    }
+}
+
+impl From<&GattEvent> for BleEvent {
+    fn from(event: &GattEvent) -> Self {
+        match event {
+            GattEvent::Connected(connection) => Self::Connected(*connection),
+            GattEvent::Disconnected(connection) => Self::Disconnected(*connection),
+            GattEvent::Write {
+                connection,
+                characteristic,
+                handle,
+                value,
+            } => Self::Write {
+                connection: *connection,
+                characteristic: *characteristic,
+                handle: *handle,
+                value: BleEventValue::new(value),
+            },
+            GattEvent::Subscription {
+                connection,
+                characteristic,
+                notify,
+                indicate,
+            } => Self::Subscription {
+                connection: *connection,
+                characteristic: *characteristic,
+                notify: *notify,
+                indicate: *indicate,
+            },
+            GattEvent::MtuChanged { connection, mtu } => Self::MtuChanged {
+                connection: *connection,
+                mtu: *mtu,
+            },
+            GattEvent::ConnectionParametersChanged {
+                connection,
+                parameters,
+            } => Self::ConnectionParametersChanged {
+                connection: *connection,
+                parameters: *parameters,
+            },
+            #[cfg(esp_idf_bt_ble_50_features_supported)]
+            GattEvent::PhyChanged {
+                connection,
+                tx_phy,
+                rx_phy,
+            } => Self::PhyChanged {
+                connection: *connection,
+                tx_phy: *tx_phy,
+                rx_phy: *rx_phy,
+            },
+        }
+    }
+}
+
+unsafe impl EspEventSource for BleEvent {
+    fn source() -> Option<&'static CStr> {
+        CStr::from_bytes_with_nul(&EVENT_BASE).ok()
+    }
+}
+
+impl EspEventSerializer for BleEvent {
+    type Data<'d> = Self;
+
+    fn serialize<F, R>(event: &Self::Data<'_>, f: F) -> R
+    where
+        F: FnOnce(&EspEventPostData) -> R,
+    {
+        let source = Self::source().expect("The BLE event base is nul-terminated");
+        f(&unsafe { EspEventPostData::new(source, Self::event_id(), event) })
+    }
+}
+
+impl EspEventDeserializer for BleEvent {
+    type Data<'d> = Self;
+
+    fn deserialize<'d>(data: &EspEvent<'d>) -> Self::Data<'d> {
+        *unsafe { data.as_payload::<Self>() }
+    }
+}
+
+impl GattServer {
+    /// Posts the events of the GATT server to an ESP-IDF event loop, as [`BleEvent`]s.
+    ///
+    /// Both the system event loop ([`EspSystemEventLoop`]) and custom event loops are supported,
+    /// so BLE events can be handled with the same code as the Wi-Fi and IP events.
+    ///
+    /// # Notes
+    ///
+    /// The events are posted without waiting: when the queue of the event loop is full,
+    /// the event is dropped and a warning is logged.
+    ///
+    /// [`EspSystemEventLoop`]: esp_idf_svc::eventloop::EspSystemEventLoop
+    pub fn publish_events<T>(&mut self, event_loop: EspEventLoop<T>) -> &mut Self
+    where
+        T: EspEventLoopType,
+        EspEventLoop<T>: Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            let event = BleEvent::from(event);
+            if !matches!(event_loop.post::<BleEvent>(&event, 0), Ok(true)) {
+                warn!(target: GATTS, "Cannot post {:?} to the event loop.", event);
+            }
+        })
+    }
+}
@@ -0,0 +1,423 @@
+use std::time::Duration;
+
+use esp_idf_sys::{
+    esp_ble_addr_type_t, esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+    esp_ble_adv_channel_t_ADV_CHNL_ALL, esp_ble_adv_filter_t, esp_ble_gap_cb_param_t,
+    esp_ble_gap_config_ext_adv_data_raw, esp_ble_gap_config_ext_scan_rsp_data_raw,
+    esp_ble_gap_ext_adv_params_t, esp_ble_gap_ext_adv_set_params,
+    esp_ble_gap_ext_adv_set_rand_addr, esp_ble_gap_ext_adv_start, esp_ble_gap_ext_adv_t,
+    esp_bt_status_t_ESP_BT_STATUS_SUCCESS, esp_gap_ble_cb_event_t,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_DATA_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_SET_PARAMS_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_SET_REMOVE_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_START_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_STOP_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_SCAN_RSP_DATA_SET_COMPLETE_EVT, ESP_BLE_GAP_PRI_PHY_1M,
+    ESP_BLE_GAP_PRI_PHY_CODED, ESP_BLE_GAP_SET_EXT_ADV_PROP_CONNECTABLE,
+    ESP_BLE_GAP_SET_EXT_ADV_PROP_SCANNABLE,
+};
+use log::{debug, info, warn};
+
+use crate::gatt_server::{
+    advertisement::{encode_fields, AD_TYPE_COMPLETE_NAME},
+    error::esp_report,
+    periodic_advertising, GattServer, GattServerError, PeriodicAdvertising,
+};
+use crate::utilities::{log_targets::GAP, Phy};
+
+/// The advertising set of the extended advertisement of the GATT server.
+pub(crate) const SERVER_INSTANCE: u8 = 0;
+
+/// The longest extended advertisement data, set by the Bluetooth specification.
+pub(crate) const MAX_DATA_LENGTH: usize = 1650;
+
+/// The longest data of a connectable extended advertisement: it cannot be chained over several
+/// packets, so it must fit in a single packet of 255 bytes, after its 10-byte extended header.
+const MAX_CONNECTABLE_DATA_LENGTH: usize = 245;
+
+/// The transmission power letting the controller choose, in the extended advertising parameters.
+const NO_TX_POWER_PREFERENCE: i8 = 127;
+
+/// The extended advertisement of the GATT server, the BLE 5.0 alternative to the legacy
+/// advertisement, selected with [`GattServer::extended_advertising`].
+///
+/// Extended advertisements carry up to 1650 bytes of data, can be sent on the 2M and Coded PHYs,
+/// and are only received by BLE 5.0 scanners.
+///
+/// A connectable extended advertisement cannot be scanned: its data must fit in 245 bytes,
+/// and the scan response is only sent when the advertisement is not connectable.
+/// An extended advertisement with a [`PeriodicAdvertising`] is neither connectable nor scannable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedAdvertising {
+    interval_min: Duration,
+    interval_max: Duration,
+    connectable: bool,
+    primary_phy: Phy,
+    secondary_phy: Phy,
+    tx_power: Option<i8>,
+    pub(crate) data: Option<Vec<u8>>,
+    scan_response: Option<Vec<u8>>,
+    pub(crate) periodic: Option<PeriodicAdvertising>,
+}
+
+impl ExtendedAdvertising {
+    /// Creates a new connectable [`ExtendedAdvertising`], sent every 20 to 40 ms on the 1M PHY,
+    /// with the data of the legacy advertisement and scan response of the GATT server.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            interval_min: Duration::from_millis(20),
+            interval_max: Duration::from_millis(40),
+            connectable: true,
+            primary_phy: Phy::Le1M,
+            secondary_phy: Phy::Le1M,
+            tx_power: None,
+            data: None,
+            scan_response: None,
+            periodic: None,
+        }
+    }
+
+    /// Sets the bounds of the advertising interval, clamped between 20 ms and 10.24 s.
+    #[must_use]
+    pub const fn interval(mut self, min: Duration, max: Duration) -> Self {
+        self.interval_min = min;
+        self.interval_max = max;
+        self
+    }
+
+    /// Sets whether clients can connect to the GATT server through the advertisement.
+    #[must_use]
+    pub const fn connectable(mut self, connectable: bool) -> Self {
+        self.connectable = connectable;
+        self
+    }
+
+    /// Sets the PHY of the advertisements on the primary advertising channels.
+    ///
+    /// The primary channels do not support the 2M PHY: [`Phy::Le2M`] is replaced by [`Phy::Le1M`].
+    #[must_use]
+    pub const fn primary_phy(mut self, phy: Phy) -> Self {
+        self.primary_phy = phy;
+        self
+    }
+
+    /// Sets the PHY of the advertisement data, sent on the secondary advertising channels.
+    #[must_use]
+    pub const fn secondary_phy(mut self, phy: Phy) -> Self {
+        self.secondary_phy = phy;
+        self
+    }
+
+    /// Sets the transmission power of the advertisements, in dBm,
+    /// instead of letting the controller choose it.
+    #[must_use]
+    pub const fn tx_power(mut self, dbm: i8) -> Self {
+        self.tx_power = Some(dbm);
+        self
+    }
+
+    /// Sets the raw advertisement data, as AD structures, instead of the one generated
+    /// from the configuration of the GATT server.
+    #[must_use]
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Sets the raw scan response data, as AD structures, sent when the advertisement
+    /// is not connectable.
+    #[must_use]
+    pub fn scan_response(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.scan_response = Some(data.into());
+        self
+    }
+
+    /// Adds a [`PeriodicAdvertising`] to the extended advertisement, making it neither
+    /// connectable nor scannable.
+    #[must_use]
+    pub fn periodic(mut self, periodic: PeriodicAdvertising) -> Self {
+        self.periodic = Some(periodic);
+        self
+    }
+
+    /// Returns whether clients can connect through the advertisement.
+    const fn is_connectable(&self) -> bool {
+        self.connectable && self.periodic.is_none()
+    }
+
+    /// Returns whether the advertisement sends a scan response.
+    const fn scannable(&self) -> bool {
+        !self.connectable && self.scan_response.is_some() && self.periodic.is_none()
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn parameters(
+        &self,
+        own_address_type: esp_ble_addr_type_t,
+        filter_policy: esp_ble_adv_filter_t,
+    ) -> esp_ble_gap_ext_adv_params_t {
+        // The interval is counted in units of 0.625 ms.
+        let units = |interval: Duration| (interval.as_micros() / 625).clamp(0x0020, 0x4000) as u32;
+
+        let mut properties = 0;
+        if self.is_connectable() {
+            properties |= ESP_BLE_GAP_SET_EXT_ADV_PROP_CONNECTABLE;
+        }
+        if self.scannable() {
+            properties |= ESP_BLE_GAP_SET_EXT_ADV_PROP_SCANNABLE;
+        }
+
+        let primary_phy = match self.primary_phy {
+            Phy::LeCoded => ESP_BLE_GAP_PRI_PHY_CODED,
+            Phy::Le1M | Phy::Le2M => ESP_BLE_GAP_PRI_PHY_1M,
+        };
+
+        esp_ble_gap_ext_adv_params_t {
+            type_: properties as _,
+            interval_min: units(self.interval_min),
+            interval_max: units(self.interval_max),
+            channel_map: esp_ble_adv_channel_t_ADV_CHNL_ALL,
+            own_addr_type: own_address_type,
+            peer_addr_type: esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+            filter_policy,
+            tx_power: self.tx_power.unwrap_or(NO_TX_POWER_PREFERENCE),
+            primary_phy: primary_phy as _,
+            secondary_phy: self.secondary_phy.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ExtendedAdvertising {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GattServer {
+    /// Advertises the GATT server with an [`ExtendedAdvertising`], instead of
+    /// the legacy advertisement.
+    ///
+    /// The advertising backend must be selected before starting the server.
+    /// The advertising rotations and the [`AdvertisingPolicy`](crate::gatt_server::AdvertisingPolicy)
+    /// only drive the legacy advertisement.
+    pub fn extended_advertising(&mut self, advertising: ExtendedAdvertising) -> &mut Self {
+        if self.started {
+            warn!(
+                target: GAP,
+                "Cannot select the extended advertisement after the server has started."
+            );
+            return self;
+        }
+
+        self.extended_advertising = Some(advertising);
+        self
+    }
+
+    /// Returns the configuration of an advertising set: the extended advertisement
+    /// of the server for [`SERVER_INSTANCE`], or one of the additional sets.
+    pub(crate) fn advertising_set_configuration(
+        &self,
+        instance: u8,
+    ) -> Option<&ExtendedAdvertising> {
+        if instance == SERVER_INSTANCE {
+            self.extended_advertising.as_ref()
+        } else {
+            self.advertising_sets.get(&instance)
+        }
+    }
+
+    /// Hands the parameters of an advertising set to the Bluetooth stack.
+    ///
+    /// The data, the scan response and the start follow, as the stack completes each step.
+    pub(crate) fn configure_extended_advertising(&mut self, instance: u8) {
+        let Some(advertising) = self.advertising_set_configuration(instance) else {
+            return;
+        };
+
+        let parameters = advertising.parameters(self.own_address_type(), self.advertising_filter());
+        debug!(
+            target: GAP,
+            "Setting the parameters of advertising set {}.", instance
+        );
+
+        unsafe {
+            esp_report!(esp_ble_gap_ext_adv_set_params(instance, &parameters));
+            if let Some(address) = self.random_address.as_mut() {
+                esp_report!(esp_ble_gap_ext_adv_set_rand_addr(
+                    instance,
+                    address.as_mut_ptr()
+                ));
+            }
+        }
+    }
+
+    /// Hands the data of an advertising set to the Bluetooth stack, unless it does not fit.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn configure_extended_data(&self, instance: u8) {
+        let Some(advertising) = self.advertising_set_configuration(instance) else {
+            return;
+        };
+
+        let data = advertising
+            .data
+            .clone()
+            .unwrap_or_else(|| self.extended_payload());
+        let capacity = if advertising.is_connectable() {
+            MAX_CONNECTABLE_DATA_LENGTH
+        } else {
+            MAX_DATA_LENGTH
+        };
+        if data.len() > capacity {
+            GattServerError::ExtendedAdvertisementTooLong {
+                length: data.len(),
+                capacity,
+            }
+            .report();
+            return;
+        }
+
+        unsafe {
+            esp_report!(esp_ble_gap_config_ext_adv_data_raw(
+                instance,
+                data.len() as u16,
+                data.as_ptr()
+            ));
+        }
+    }
+
+    /// Encodes the data of the legacy advertisement, with the complete device name,
+    /// followed by the fields of the scan response it does not already contain.
+    fn extended_payload(&self) -> Vec<u8> {
+        let name = self.device_name.trim_end_matches('\0');
+
+        let mut fields = self.packet_fields(&self.advertisement_data, AD_TYPE_COMPLETE_NAME, name);
+        for field in self.packet_fields(&self.scan_response_data, AD_TYPE_COMPLETE_NAME, name) {
+            if !fields.iter().any(|(ad_type, _)| *ad_type == field.0) {
+                fields.push(field);
+            }
+        }
+
+        encode_fields(&fields)
+    }
+
+    /// Starts sending an advertising set, unless advertising is paused.
+    pub(crate) fn start_extended_advertising(&self, instance: u8) {
+        if self.advertising_paused() {
+            return;
+        }
+
+        info!(target: GAP, "Starting BLE GAP advertising set {}.", instance);
+        let advertisement = esp_ble_gap_ext_adv_t {
+            instance,
+            duration: 0,
+            max_events: 0,
+        };
+
+        unsafe {
+            esp_report!(esp_ble_gap_ext_adv_start(1, &advertisement));
+        }
+    }
+
+    /// Handles the extended advertising events of the GAP,
+    /// and returns whether the event was one of them.
+    #[allow(non_upper_case_globals)]
+    pub(crate) fn extended_advertising_event_handler(
+        &mut self,
+        event: esp_gap_ble_cb_event_t,
+        param: *mut esp_ble_gap_cb_param_t,
+    ) -> bool {
+        let (status, instance) = match event {
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_SET_PARAMS_COMPLETE_EVT => {
+                let param = unsafe { (*param).ext_adv_set_params };
+                (param.status, Some(param.instance))
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_DATA_SET_COMPLETE_EVT => {
+                let param = unsafe { (*param).ext_adv_data_set };
+                (param.status, Some(param.instance))
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_SCAN_RSP_DATA_SET_COMPLETE_EVT => {
+                let param = unsafe { (*param).scan_rsp_set };
+                (param.status, Some(param.instance))
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_START_COMPLETE_EVT => {
+                let param = unsafe { (*param).ext_adv_start };
+                (param.status, None)
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_STOP_COMPLETE_EVT => {
+                let param = unsafe { (*param).ext_adv_stop };
+                (param.status, None)
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_SET_REMOVE_COMPLETE_EVT => {
+                let param = unsafe { (*param).ext_adv_remove };
+                (param.status, None)
+            }
+            _ => return false,
+        };
+
+        if status != esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+            warn!(
+                target: GAP,
+                "BLE GAP extended advertising event {} failed with status 0x{:x}.", event, status
+            );
+            return true;
+        }
+
+        let Some(instance) = instance else {
+            debug!(target: GAP, "BLE GAP extended advertising event {} complete.", event);
+            return true;
+        };
+
+        match event {
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_SET_PARAMS_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP advertising set {} parameters set.", instance);
+                self.configure_extended_data(instance);
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_EXT_ADV_DATA_SET_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP advertising set {} data set.", instance);
+                match self.advertising_set_configuration(instance) {
+                    Some(advertising) if advertising.scannable() => {
+                        let scan_response = advertising.scan_response.clone().unwrap_or_default();
+                        configure_scan_response(instance, &scan_response);
+                    }
+                    // The periodic advertisement starts first, then starts the extended one.
+                    Some(advertising)
+                        if advertising.periodic.is_some()
+                            && !periodic_advertising::is_running(instance) =>
+                    {
+                        self.configure_periodic_advertising(instance);
+                    }
+                    _ => self.start_extended_advertising(instance),
+                }
+            }
+            _ => {
+                debug!(target: GAP, "BLE GAP advertising set {} scan response set.", instance);
+                self.start_extended_advertising(instance);
+            }
+        }
+
+        true
+    }
+}
+
+/// Hands the scan response of the extended advertisement to the Bluetooth stack.
+#[allow(clippy::cast_possible_truncation)]
+fn configure_scan_response(instance: u8, scan_response: &[u8]) {
+    if scan_response.len() > MAX_DATA_LENGTH {
+        GattServerError::ExtendedAdvertisementTooLong {
+            length: scan_response.len(),
+            capacity: MAX_DATA_LENGTH,
+        }
+        .report();
+        return;
+    }
+
+    unsafe {
+        esp_report!(esp_ble_gap_config_ext_scan_rsp_data_raw(
+            instance,
+            scan_response.len() as u16,
+            scan_response.as_ptr()
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use esp_idf_sys::{
+    esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM, esp_ble_adv_type_t_ADV_TYPE_NONCONN_IND,
+    esp_ble_gap_stop_advertising,
+};
+use log::debug;
+
+use crate::gatt_server::{error::esp_report, worker, GattServer, GLOBAL_GATT_SERVER};
+use crate::utilities::log_targets::GAP;
+
+/// The Apple company identifier, in little-endian order.
+const APPLE_COMPANY_ID: [u8; 2] = [0x4C, 0x00];
+
+/// The type of offline finding advertisements.
+const OFFLINE_FINDING_TYPE: u8 = 0x12;
+
+/// The length of the offline finding payload following its length byte.
+const OFFLINE_FINDING_LENGTH: u8 = 0x19;
+
+/// The length of an advertised public key.
+pub const FIND_MY_KEY_LENGTH: usize = 28;
+
+/// An offline finding advertisement of the Find My network, for tracker-style accessories.
+///
+/// The advertisement is derived from a 28-byte public key: its first six bytes make the random
+/// static address of the device, and the remaining bytes are sent as manufacturer data.
+/// Accessories should rotate their keys regularly with [`GattServer::rotate_find_my_keys`],
+/// so they cannot be tracked by third parties.
+///
+/// # Notes
+///
+/// The manufacturer data fills the whole advertisement packet: advertising it removes the flags,
+/// the device name, the transmission power, the appearance, the service identifiers and the
+/// service data from the advertisement, which becomes non-connectable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FindMyAdvertisement {
+    key: [u8; FIND_MY_KEY_LENGTH],
+    status: u8,
+}
+
+impl FindMyAdvertisement {
+    /// Creates a new [`FindMyAdvertisement`] for the given public key.
+    #[must_use]
+    pub const fn new(key: [u8; FIND_MY_KEY_LENGTH]) -> Self {
+        Self { key, status: 0 }
+    }
+
+    /// Sets the status byte of the advertisement, whose upper two bits hold the battery level
+    /// (`0`: full, `1`: medium, `2`: low, `3`: critically low).
+    #[must_use]
+    pub const fn status(mut self, status: u8) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns the random static address derived from the public key.
+    #[must_use]
+    pub fn address(&self) -> [u8; 6] {
+        let mut address = [0u8; 6];
+        address.copy_from_slice(&self.key[..6]);
+        // Random static addresses have their two most significant bits set.
+        address[0] |= 0xC0;
+        address
+    }
+
+    /// Encodes the advertisement as manufacturer data, starting with the Apple company identifier.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = APPLE_COMPANY_ID.to_vec();
+        data.push(OFFLINE_FINDING_TYPE);
+        data.push(OFFLINE_FINDING_LENGTH);
+        data.push(self.status);
+        data.extend_from_slice(&self.key[6..]);
+        // The two bits of the key replaced in the address.
+        data.push(self.key[0] >> 6);
+        // The hint byte.
+        data.push(0x00);
+        data
+    }
+}
+
+impl GattServer {
+    /// Advertises a [`FindMyAdvertisement`], from its random static address.
+    ///
+    /// If the advertisement is already configured, advertising restarts with the new address.
+    pub fn advertise_find_my(&mut self, advertisement: &FindMyAdvertisement) -> &mut Self {
+        self.advertisement_parameters.adv_type = esp_ble_adv_type_t_ADV_TYPE_NONCONN_IND;
+        self.advertisement_parameters.own_addr_type = esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM;
+
+        self.advertisement_data.flag = 0;
+        self.advertisement_data.include_name = false;
+        self.advertisement_data.include_txpower = false;
+        self.advertisement_data.appearance = 0;
+        self.advertisement_data.service_uuid_len = 0;
+        self.advertisement_data.p_service_uuid = std::ptr::null_mut();
+        self.broadcast_data.clear();
+        self.advertisement_data.service_data_len = 0;
+        self.advertisement_data.p_service_data = std::ptr::null_mut();
+
+        self.store_manufacturer_data(advertisement.encode());
+        self.random_address = Some(advertisement.address());
+
+        // Before the registration, the address and the data are configured anyway.
+        if self.advertisement_configured {
+            debug!(target: GAP, "Rotating the Find My advertisement.");
+
+            // The address cannot change while advertising.
+            unsafe {
+                esp_report!(esp_ble_gap_stop_advertising());
+            }
+            self.configure_random_address();
+            // Advertising restarts once the data is set.
+            self.configure_advertisement_data();
+        }
+
+        self
+    }
+
+    /// Advertises the [`FindMyAdvertisement`] returned by `provider`, then a new one every `period`.
+    ///
+    /// The provider is called from the worker thread of the GATT server, so it must not block.
+    /// It usually derives the next public key of the accessory, as the Find My network expects
+    /// keys to rotate every 15 minutes.
+    pub fn rotate_find_my_keys(
+        &mut self,
+        period: Duration,
+        mut provider: impl FnMut() -> FindMyAdvertisement + Send + 'static,
+    ) -> &mut Self {
+        self.advertise_find_my(&provider());
+
+        worker::schedule_periodic(period, move || {
+            let advertisement = provider();
+            GLOBAL_GATT_SERVER.lock().advertise_find_my(&advertisement);
+            true
+        });
+
+        self
+    }
+}
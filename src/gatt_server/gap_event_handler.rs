@@ -1,67 +1,142 @@
 use esp_idf_sys::{
     esp_ble_gap_cb_param_t, esp_ble_gap_start_advertising, esp_bt_status_t_ESP_BT_STATUS_SUCCESS,
-    esp_gap_ble_cb_event_t, esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t, esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_RAW_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_SET_COMPLETE_EVT,
     esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_START_COMPLETE_EVT,
     esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_STOP_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_AUTH_CMPL_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_REMOVE_BOND_DEV_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RSP_DATA_RAW_SET_COMPLETE_EVT,
     esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RSP_DATA_SET_COMPLETE_EVT,
-    esp_gap_ble_cb_event_t_ESP_GAP_BLE_UPDATE_CONN_PARAMS_EVT, esp_nofail,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SEC_REQ_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_LOCAL_PRIVACY_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_UPDATE_CONN_PARAMS_EVT,
 };
 
+#[cfg(not(any(esp_idf_version_major = "4", esp_idf_version = "5.0")))]
+use esp_idf_sys::esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADD_DEV_TO_RESOLVING_LIST_COMPLETE_EVT;
+#[cfg(feature = "debug-keys")]
+use esp_idf_sys::esp_gap_ble_cb_event_t_ESP_GAP_BLE_KEY_EVT;
+#[cfg(feature = "embassy")]
+use esp_idf_sys::esp_gap_ble_cb_event_t_ESP_GAP_BLE_READ_RSSI_COMPLETE_EVT;
 use log::{debug, info, warn};
 
-use super::GattServer;
-use crate::leaky_box_raw;
+use super::{error::esp_report, GattServer};
+use crate::utilities::log_targets::GAP;
 
 impl GattServer {
+    #[allow(clippy::too_many_lines)]
     pub(crate) extern "C" fn gap_event_handler(
         &mut self,
         event: esp_gap_ble_cb_event_t,
         param: *mut esp_ble_gap_cb_param_t,
     ) {
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        if self.extended_advertising_event_handler(event, param)
+            || self.periodic_advertising_event_handler(event, param)
+            || self.phy_event_handler(event, param)
+        {
+            return;
+        }
+
+        #[cfg(feature = "security")]
+        if super::security::gap_event_handler(event, param) {
+            return;
+        }
+
         #[allow(non_upper_case_globals)]
         match event {
-            esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_SET_COMPLETE_EVT => {
-                debug!("BLE GAP advertisement data set complete.");
-                info!("Starting BLE GAP advertisement.");
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_SET_COMPLETE_EVT
+            | esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_RAW_SET_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP advertisement data set complete.");
+                if self.advertising_paused() {
+                    return;
+                }
+                info!(target: GAP, "Starting BLE GAP advertisement.");
 
                 unsafe {
-                    esp_nofail!(esp_ble_gap_start_advertising(leaky_box_raw!(
-                        self.advertisement_parameters
-                    )));
+                    esp_report!(esp_ble_gap_start_advertising(
+                        &mut self.advertisement_parameters
+                    ));
                 }
             }
-            esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RSP_DATA_SET_COMPLETE_EVT => {
-                debug!("BLE GAP scan response data set complete.");
-                info!("Starting BLE GAP response advertisement.");
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RSP_DATA_SET_COMPLETE_EVT
+            | esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RSP_DATA_RAW_SET_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP scan response data set complete.");
+                if self.advertising_paused() {
+                    return;
+                }
+                info!(target: GAP, "Starting BLE GAP response advertisement.");
 
                 unsafe {
-                    esp_nofail!(esp_ble_gap_start_advertising(leaky_box_raw!(
-                        self.advertisement_parameters
-                    )));
+                    esp_report!(esp_ble_gap_start_advertising(
+                        &mut self.advertisement_parameters
+                    ));
                 }
             }
             esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_START_COMPLETE_EVT => {
                 let param = unsafe { (*param).adv_data_cmpl };
                 if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
-                    debug!("BLE GAP advertisement started.");
+                    debug!(target: GAP, "BLE GAP advertisement started.");
                 } else {
-                    warn!("BLE GAP advertisement start failed.");
+                    warn!(target: GAP, "BLE GAP advertisement start failed.");
                 }
             }
             esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_STOP_COMPLETE_EVT => {
                 let param = unsafe { (*param).adv_data_cmpl };
                 if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
-                    debug!("BLE GAP advertisement stopped.");
+                    debug!(target: GAP, "BLE GAP advertisement stopped.");
                 } else {
-                    warn!("BLE GAP advertisement stop failed.");
+                    warn!(target: GAP, "BLE GAP advertisement stop failed.");
                 }
             }
             esp_gap_ble_cb_event_t_ESP_GAP_BLE_UPDATE_CONN_PARAMS_EVT => {
                 let param = unsafe { (*param).update_conn_params };
-                info!("Connection parameters updated: {:?}", param);
+                self.on_conn_params_update(param);
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_LOCAL_PRIVACY_COMPLETE_EVT => {
+                let param = unsafe { (*param).local_privacy_cmpl };
+                if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                    debug!(target: GAP, "BLE GAP local privacy configured.");
+                } else {
+                    warn!(target: GAP, "BLE GAP local privacy configuration failed.");
+                }
+            }
+            #[cfg(not(any(esp_idf_version_major = "4", esp_idf_version = "5.0")))]
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADD_DEV_TO_RESOLVING_LIST_COMPLETE_EVT => {
+                let param = unsafe { (*param).add_dev_to_resolving_list_cmpl };
+                if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                    debug!(target: GAP, "BLE GAP peer added to the resolving list.");
+                } else {
+                    warn!(target: GAP, "BLE GAP resolving list update failed.");
+                }
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_REMOVE_BOND_DEV_COMPLETE_EVT => {
+                let param = unsafe { (*param).remove_bond_dev_cmpl };
+                if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                    debug!(target: GAP, "BLE GAP bond with {:02X?} removed.", param.bd_addr);
+                } else {
+                    warn!(target: GAP, "BLE GAP bond removal failed.");
+                }
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_SEC_REQ_EVT => {
+                let param = unsafe { (*param).ble_security.ble_req };
+                self.on_security_request(param);
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_AUTH_CMPL_EVT => {
+                let param = unsafe { (*param).ble_security.auth_cmpl };
+                self.on_authentication_complete(param);
+            }
+            #[cfg(feature = "debug-keys")]
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_KEY_EVT => {
+                super::debug_keys::on_key(unsafe { (*param).ble_security.ble_key });
+            }
+            #[cfg(feature = "embassy")]
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_READ_RSSI_COMPLETE_EVT => {
+                super::embassy::on_read_rssi(unsafe { (*param).read_rssi_cmpl });
             }
             _ => {
-                warn!("Unhandled GAP event: {:?}", event);
+                warn!(target: GAP, "Unhandled GAP event: {:?}", event);
             }
         }
     }
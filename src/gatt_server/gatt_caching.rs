@@ -0,0 +1,36 @@
+use log::debug;
+
+use crate::gatt_server::GattServer;
+use crate::utilities::log_targets::GATTS;
+
+impl GattServer {
+    /// Returns whether the Bluetooth stack supports the GATT caching of Bluetooth 5.1.
+    ///
+    /// With `CONFIG_BT_GATTS_ROBUST_CACHING_ENABLED`, the Generic Attribute service of the stack
+    /// serves the Database Hash characteristic (0x2B2A), computed over the whole GATT table,
+    /// and the Client Supported Features characteristic (0x2B29). A bonded client that enables
+    /// robust caching keeps the handles it discovered as long as the hash does not change,
+    /// and the stack tracks whether each bonded client is aware of the latest changes.
+    ///
+    /// The attributes of this crate are registered one after another, in declaration order,
+    /// so a given GATT table gets the same handles, and the same hash, at every boot.
+    #[must_use]
+    pub const fn robust_caching() -> bool {
+        cfg!(esp_idf_bt_gatts_robust_caching_enabled)
+    }
+
+    /// Logs whether the bonded clients can cache the GATT table.
+    pub(crate) fn log_gatt_caching() {
+        if Self::robust_caching() {
+            debug!(
+                target: GATTS,
+                "Robust caching enabled: the stack serves the database hash."
+            );
+        } else {
+            debug!(
+                target: GATTS,
+                "Robust caching disabled: clients rely on service change indications."
+            );
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use crate::gatt_server::{GattServer, Profile};
+use crate::utilities::log_targets::GATTS;
 
 #[allow(clippy::wildcard_imports)]
 use esp_idf_sys::*;
@@ -8,14 +11,72 @@ mod profile;
 mod server;
 
 impl GattServer {
+    /// Sets a callback receiving every GATT server event, after the crate handled it,
+    /// with its raw parameters.
+    ///
+    /// Combined with [`GattServer::suppress_default_handling`], this lets users take over
+    /// the handling of specific events.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, while the GATT server
+    /// is locked: it must not block, nor lock the GATT server.
+    /// The parameters are only valid during the call.
+    pub fn on_raw_gatts_event(
+        &mut self,
+        callback: impl Fn(esp_gatts_cb_event_t, esp_gatt_if_t, *mut esp_ble_gatts_cb_param_t)
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.raw_gatts_event_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Skips the handling of the given GATT server event by the crate and its profiles.
+    ///
+    /// The event is still passed to the callback set with [`GattServer::on_raw_gatts_event`].
+    ///
+    /// # Notes
+    ///
+    /// The crate relies on its handling of the events to track its state: for example,
+    /// suppressing `ESP_GATTS_CONNECT_EVT` leaves the connection out of the notified clients.
+    pub fn suppress_default_handling(&mut self, event: esp_gatts_cb_event_t) -> &mut Self {
+        self.suppressed_events.insert(event);
+        self
+    }
+
     /// The main GATT server event loop.
     ///
-    /// Dispatches the received events across the appropriate profile-related handlers.
+    /// Passes the received events to the default handlers, unless suppressed,
+    /// then to the raw event callback.
     pub(crate) fn gatts_event_handler(
         &mut self,
         event: esp_gatts_cb_event_t,
         gatts_if: esp_gatt_if_t,
         param: *mut esp_ble_gatts_cb_param_t,
+    ) {
+        if self.suppressed_events.contains(&event) {
+            debug!(
+                target: GATTS,
+                "Default handling of GATT server event {} suppressed.",
+                event
+            );
+        } else {
+            self.handle_gatts_event(event, gatts_if, param);
+        }
+
+        if let Some(callback) = self.raw_gatts_event_callback.clone() {
+            callback(event, gatts_if, param);
+        }
+    }
+
+    /// Dispatches the received events across the appropriate profile-related handlers.
+    fn handle_gatts_event(
+        &mut self,
+        event: esp_gatts_cb_event_t,
+        gatts_if: esp_gatt_if_t,
+        param: *mut esp_ble_gatts_cb_param_t,
     ) {
         #[allow(non_upper_case_globals)]
         match event {
@@ -53,6 +114,27 @@ impl GattServer {
                 // Do not pass this event to the profile handlers.
                 return;
             }
+            esp_gatts_cb_event_t_ESP_GATTS_CONGEST_EVT => {
+                let param = unsafe { (*param).congest };
+                self.on_congest(param);
+
+                // Do not pass this event to the profile handlers.
+                return;
+            }
+            esp_gatts_cb_event_t_ESP_GATTS_EXEC_WRITE_EVT => {
+                let param = unsafe { (*param).exec_write };
+                self.on_exec_write(gatts_if, param);
+
+                // Do not pass this event to the profile handlers.
+                return;
+            }
+            esp_gatts_cb_event_t_ESP_GATTS_SEND_SERVICE_CHANGE_EVT => {
+                let param = unsafe { (*param).service_change };
+                self.on_service_change(param);
+
+                // Do not pass this event to the profile handlers.
+                return;
+            }
             esp_gatts_cb_event_t_ESP_GATTS_SET_ATTR_VAL_EVT => {
                 let param = unsafe { (*param).set_attr_val };
                 self.on_set_attr_val(gatts_if, param);
@@ -65,10 +147,21 @@ impl GattServer {
 
         self.profiles.iter().for_each(|profile| {
             if profile.read().interface == Some(gatts_if) {
-                debug!("Handling event {} on profile {}.", event, profile.read());
+                debug!(target: GATTS, "Handling event {} on profile {}.", event, profile.read());
                 profile.write().gatts_event_handler(event, gatts_if, param);
+
+                // Do not hold the profile lock while running the callback.
+                let callback = profile.read().raw_event_callback.clone();
+                if let Some(callback) = callback {
+                    callback(event, gatts_if, param);
+                }
             }
         });
+
+        // A write might have changed the broadcast configuration of a characteristic.
+        if event == esp_gatts_cb_event_t_ESP_GATTS_WRITE_EVT {
+            self.update_broadcast_data();
+        }
     }
 }
 
@@ -92,11 +185,26 @@ impl Profile {
 
                 self.on_create(param);
             }
+            esp_gatts_cb_event_t_ESP_GATTS_CREAT_ATTR_TAB_EVT => {
+                let param = unsafe { (*param).add_attr_tab };
+
+                self.on_create_attr_tab(param);
+            }
             esp_gatts_cb_event_t_ESP_GATTS_START_EVT => {
                 let param = unsafe { (*param).start };
 
                 self.on_start(param);
             }
+            esp_gatts_cb_event_t_ESP_GATTS_STOP_EVT => {
+                let param = unsafe { (*param).stop };
+
+                self.on_stop(param);
+            }
+            esp_gatts_cb_event_t_ESP_GATTS_ADD_INCL_SRVC_EVT => {
+                let param = unsafe { (*param).add_incl_srvc };
+
+                self.on_add_incl_srvc(param);
+            }
             esp_gatts_cb_event_t_ESP_GATTS_ADD_CHAR_EVT => {
                 let param = unsafe { (*param).add_char };
 
@@ -118,13 +226,19 @@ impl Profile {
                 self.on_read(gatts_if, param);
             }
             esp_gatts_cb_event_t_ESP_GATTS_CONF_EVT => {
-                let _param = unsafe { (*param).conf };
+                let param = unsafe { (*param).conf };
 
-                // TODO: on_conf.
-                debug!("Received confirmation event.");
+                self.on_conf(param);
+            }
+            _ if self.raw_event_callback.is_some() => {
+                debug!(
+                    target: GATTS,
+                    "Passing GATT server event {:?} to the raw event callback.",
+                    event
+                );
             }
             _ => {
-                warn!("Unhandled GATT server event: {:?}", event);
+                warn!(target: GATTS, "Unhandled GATT server event: {:?}", event);
             }
         }
     }
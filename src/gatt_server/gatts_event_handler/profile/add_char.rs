@@ -1,4 +1,5 @@
-use crate::gatt_server::Profile;
+use crate::gatt_server::{GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
 use crate::utilities::BleUuid;
 use esp_idf_sys::{
     esp_ble_gatts_cb_param_t_gatts_add_char_evt_param, esp_gatt_status_t_ESP_GATT_OK,
@@ -8,25 +9,56 @@ use log::{info, warn};
 impl Profile {
     pub(crate) fn on_char_add(&mut self, param: esp_ble_gatts_cb_param_t_gatts_add_char_evt_param) {
         let Some(service) = self.get_service(param.service_handle) else {
-            warn!("Cannot find service described by handle 0x{:04x} received in characteristic creation event.", param.service_handle);
+            warn!(target: GATTS, "Cannot find service described by handle 0x{:04x} received in characteristic creation event.", param.service_handle);
             return;
         };
 
+        if service.read().attribute_table {
+            // The attribute table event assigns the handles of the whole service.
+            return;
+        }
+
         let Some(characteristic) = service.read().get_characteristic_by_id(param.char_uuid) else {
-            warn!("Cannot find characteristic described by service handle 0x{:04x} and characteristic identifier {} received in characteristic creation event.", param.service_handle, BleUuid::from(param.char_uuid));
+            warn!(target: GATTS, "Cannot find characteristic described by service handle 0x{:04x} and characteristic identifier {} received in characteristic creation event.", param.service_handle, BleUuid::from(param.char_uuid));
             return;
         };
 
         if param.status == esp_gatt_status_t_ESP_GATT_OK {
             info!(
+                target: GATTS,
                 "GATT characteristic {} registered at attribute handle 0x{:04x}.",
                 characteristic.read(),
                 param.attr_handle
             );
             characteristic.write().attribute_handle = Some(param.attr_handle);
+            characteristic.write().interface = self.interface;
             characteristic.write().register_descriptors();
+            if let Some(auto_notify) = characteristic.write().auto_notify.as_mut() {
+                auto_notify.start(&characteristic);
+            }
+            service.read().register_next_characteristic();
         } else {
-            warn!("GATT characteristic registration failed.");
+            GattServerError::Event {
+                event: "Characteristic registration",
+                status: param.status,
+            }
+            .report();
+
+            // The next characteristic waits for this one, so the retry preserves the order.
+            let description = characteristic.read().to_string();
+            let retried_characteristic = characteristic.clone();
+            let service_handle = param.service_handle;
+            characteristic
+                .write()
+                .registration
+                .retry(description, move || {
+                    retried_characteristic.write().register_self(service_handle);
+                });
+
+            // Once given up, the characteristic no longer holds back the next one.
+            if characteristic.read().registration.failed() {
+                service.read().register_next_characteristic();
+            }
         }
     }
 }
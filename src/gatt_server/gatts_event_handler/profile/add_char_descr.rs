@@ -1,4 +1,5 @@
-use crate::gatt_server::Profile;
+use crate::gatt_server::{GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
 use crate::utilities::BleUuid;
 use esp_idf_sys::{
     esp_ble_gatts_cb_param_t_gatts_add_char_descr_evt_param, esp_gatt_status_t_ESP_GATT_OK,
@@ -13,27 +14,46 @@ impl Profile {
         // ATTENTION: Descriptors might have duplicate UUIDs!
         // We need to set them in order of creation.
 
-        let Some(service) = self.get_service(param.service_handle)  else {
-            warn!("Cannot find service described by handle 0x{:04x} received in descriptor creation event.", param.service_handle);
+        let Some(service) = self.get_service(param.service_handle) else {
+            warn!(target: GATTS, "Cannot find service described by handle 0x{:04x} received in descriptor creation event.", param.service_handle);
             return;
         };
 
+        if service.read().attribute_table {
+            // The attribute table event assigns the handles of the whole service.
+            return;
+        }
+
         let descriptors = service.read().get_descriptors_by_id(param.descr_uuid);
 
-        let Some(descriptor) = descriptors.iter().find(|d| d.read().attribute_handle.is_none()) else {
-            warn!("Cannot find service described by identifier {} received in descriptor creation event.", BleUuid::from(param.descr_uuid));
+        let Some(descriptor) = descriptors
+            .iter()
+            .find(|d| d.read().attribute_handle.is_none())
+        else {
+            warn!(target: GATTS, "Cannot find service described by identifier {} received in descriptor creation event.", BleUuid::from(param.descr_uuid));
             return;
         };
 
         if param.status == esp_gatt_status_t_ESP_GATT_OK {
             info!(
+                target: GATTS,
                 "GATT descriptor {:?} registered at attribute handle 0x{:04x}.",
                 descriptor.read(),
                 param.attr_handle
             );
             descriptor.write().attribute_handle = Some(param.attr_handle);
+            self.record_cccds();
         } else {
-            warn!("GATT descriptor registration failed.");
+            GattServerError::Event {
+                event: "Descriptor registration",
+                status: param.status,
+            }
+            .report();
+
+            // Descriptors are attached to the latest registered characteristic,
+            // so a late retry could end up in the wrong place.
+            let description = descriptor.read().to_string();
+            descriptor.write().registration.give_up(description);
         }
     }
 }
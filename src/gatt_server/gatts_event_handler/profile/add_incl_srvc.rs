@@ -0,0 +1,33 @@
+use crate::gatt_server::{GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_add_incl_srvc_evt_param, esp_gatt_status_t_ESP_GATT_OK,
+};
+use log::{info, warn};
+
+impl Profile {
+    pub(crate) fn on_add_incl_srvc(
+        &mut self,
+        param: esp_ble_gatts_cb_param_t_gatts_add_incl_srvc_evt_param,
+    ) {
+        let Some(service) = self.get_service(param.service_handle) else {
+            warn!(target: GATTS, "Cannot find service described by handle 0x{:04x} received in included service creation event.", param.service_handle);
+            return;
+        };
+
+        if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            info!(
+                target: GATTS,
+                "GATT include declaration of {} registered at attribute handle 0x{:04x}.",
+                service.read(),
+                param.attr_handle
+            );
+        } else {
+            GattServerError::Event {
+                event: "Included service registration",
+                status: param.status,
+            }
+            .report();
+        }
+    }
+}
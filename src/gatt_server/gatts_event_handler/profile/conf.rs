@@ -1 +1,34 @@
+use crate::gatt_server::{indication::PENDING_INDICATIONS, Profile};
+use crate::utilities::log_targets::NOTIFY;
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_conf_evt_param, esp_gatt_status_t_ESP_GATT_CONGESTED,
+    esp_gatt_status_t_ESP_GATT_OK,
+};
+use log::{debug, warn};
 
+impl Profile {
+    pub(crate) fn on_conf(&mut self, param: esp_ble_gatts_cb_param_t_gatts_conf_evt_param) {
+        if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            debug!(
+                target: NOTIFY,
+                "{} received confirmation for handle 0x{:04x} on connection {}.",
+                self, param.handle, param.conn_id
+            );
+        } else if param.status == esp_gatt_status_t_ESP_GATT_CONGESTED {
+            // The delivery queue retries the value once the congestion clears.
+            debug!(
+                target: NOTIFY,
+                "{} could not send handle 0x{:04x} on connection {}: the connection is congested.",
+                self, param.handle, param.conn_id
+            );
+        } else {
+            warn!(
+                target: NOTIFY,
+                "{} received failed confirmation for handle 0x{:04x} on connection {}, error code: {:04x}.",
+                self, param.handle, param.conn_id, param.status
+            );
+        }
+
+        PENDING_INDICATIONS.complete(param.conn_id, param.handle, param.status);
+    }
+}
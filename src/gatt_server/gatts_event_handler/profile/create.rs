@@ -1,4 +1,5 @@
-use crate::gatt_server::Profile;
+use crate::gatt_server::{error::esp_report, GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
 use crate::utilities::BleUuid;
 use esp_idf_sys::*;
 use log::{info, warn};
@@ -6,26 +7,52 @@ use log::{info, warn};
 impl Profile {
     pub(crate) fn on_create(&mut self, param: esp_ble_gatts_cb_param_t_gatts_create_evt_param) {
         let Some(service) = self.get_service_by_id(param.service_id.id) else {
-            warn!("Cannot find service with service identifier {} received in service creation event.", BleUuid::from(param.service_id.id));
+            warn!(target: GATTS, "Cannot find service with service identifier {} received in service creation event.", BleUuid::from(param.service_id.id));
             return;
         };
 
-        service.write().handle = Some(param.service_handle);
+        if service.read().attribute_table {
+            // The attribute table event reports the whole service, once registered.
+            if param.status == esp_gatt_status_t_ESP_GATT_OK {
+                service.write().handle = Some(param.service_handle);
+            }
+            return;
+        }
 
         if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            service.write().handle = Some(param.service_handle);
+
             info!(
+                target: GATTS,
                 "GATT service {} registered on handle 0x{:04x}.",
                 service.read(),
-                service.read().handle.unwrap()
+                param.service_handle
             );
 
-            unsafe {
-                esp_nofail!(esp_ble_gatts_start_service(service.read().handle.unwrap()));
+            if service.read().enabled {
+                unsafe {
+                    esp_report!(esp_ble_gatts_start_service(param.service_handle));
+                }
             }
 
-            service.write().register_characteristics();
+            service.write().register_contents();
+            self.register_including_services(&service);
         } else {
-            warn!("GATT service registration failed.");
+            GattServerError::Event {
+                event: "Service creation",
+                status: param.status,
+            }
+            .report();
+
+            let Some(interface) = self.interface else {
+                return;
+            };
+
+            let description = service.read().to_string();
+            let retried_service = service.clone();
+            service.write().registration.retry(description, move || {
+                retried_service.write().register_self(interface);
+            });
         }
     }
 }
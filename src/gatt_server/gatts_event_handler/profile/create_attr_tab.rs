@@ -0,0 +1,85 @@
+use crate::gatt_server::{error::esp_report, GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::BleUuid;
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_add_attr_tab_evt_param, esp_ble_gatts_start_service,
+    esp_gatt_id_t, esp_gatt_status_t_ESP_GATT_OK,
+};
+use log::{info, warn};
+
+impl Profile {
+    pub(crate) fn on_create_attr_tab(
+        &mut self,
+        param: esp_ble_gatts_cb_param_t_gatts_add_attr_tab_evt_param,
+    ) {
+        let id = esp_gatt_id_t {
+            uuid: param.svc_uuid,
+            inst_id: param.svc_inst_id,
+        };
+        let Some(service) = self.get_service_by_id(id) else {
+            warn!(target: GATTS, "Cannot find service with service identifier {} received in attribute table creation event.", BleUuid::from(param.svc_uuid));
+            return;
+        };
+
+        let Some(interface) = self.interface else {
+            return;
+        };
+
+        if param.status != esp_gatt_status_t_ESP_GATT_OK {
+            GattServerError::Event {
+                event: "Attribute table creation",
+                status: param.status,
+            }
+            .report();
+
+            let description = service.read().to_string();
+            let retried_service = service.clone();
+            service.write().registration.retry(description, move || {
+                retried_service.write().register_self(interface);
+            });
+            return;
+        }
+
+        let expected = service.read().table_length();
+        if usize::from(param.num_handle) != expected {
+            // The service exists in the Bluetooth stack, so it cannot be registered again.
+            warn!(
+                target: GATTS,
+                "Attribute table of {} registered with {} handles instead of {}.",
+                service.read(),
+                param.num_handle,
+                expected
+            );
+            let description = service.read().to_string();
+            service.write().registration.give_up(description);
+            return;
+        }
+
+        let handles =
+            unsafe { std::slice::from_raw_parts(param.handles, usize::from(param.num_handle)) };
+        service.write().assign_table_handles(interface, handles);
+        self.record_cccds();
+
+        info!(
+            target: GATTS,
+            "GATT service {} registered as an attribute table on handles 0x{:04x} to 0x{:04x}.",
+            service.read(),
+            handles[0],
+            handles[expected - 1]
+        );
+
+        for characteristic in service.read().characteristics() {
+            if let Some(auto_notify) = characteristic.write().auto_notify.as_mut() {
+                auto_notify.start(characteristic);
+            }
+        }
+
+        if service.read().enabled {
+            unsafe {
+                esp_report!(esp_ble_gatts_start_service(handles[0]));
+            }
+        }
+
+        self.register_including_services(&service);
+    }
+}
@@ -1,8 +1,11 @@
 mod add_char;
 mod add_char_descr;
+mod add_incl_srvc;
 mod conf;
 mod create;
+mod create_attr_tab;
 mod read;
 mod reg;
 mod start;
+mod stop;
 mod write;
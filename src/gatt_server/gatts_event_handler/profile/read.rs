@@ -1,9 +1,15 @@
-use crate::gatt_server::Profile;
-use crate::utilities::AttributeControl;
+use crate::gatt_server::{
+    long_read::read_part,
+    response::{send_response, send_response_at},
+    Profile,
+};
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::{AttributeControl, AttributeOperation, Connection};
 use esp_idf_sys::*;
-use log::debug;
+use log::{debug, trace, warn};
 
 impl Profile {
+    #[allow(clippy::too_many_lines)]
     pub(crate) fn on_read(
         &mut self,
         gatts_if: esp_gatt_if_t,
@@ -17,6 +23,7 @@ impl Profile {
                 .for_each(|characteristic| {
                     if characteristic.read().attribute_handle == Some(param.handle) {
                         debug!(
+                            target: GATTS,
                             "Received read event for characteristic {}.",
                             characteristic.read()
                         );
@@ -25,32 +32,53 @@ impl Profile {
                         if let AttributeControl::ResponseByApp(callback) =
                             &characteristic.read().control
                         {
-                            let value = callback(param);
+                            if !characteristic.read().is_readable() {
+                                warn!(
+                                    target: GATTS,
+                                    "Read of characteristic {} denied by its permissions.",
+                                    characteristic.read()
+                                );
 
-                            // Extend the response to the maximum length.
-                            let mut response = [0u8; 600];
-                            response[..value.len()].copy_from_slice(&value);
+                                send_response(
+                                    gatts_if,
+                                    param.conn_id,
+                                    param.trans_id,
+                                    param.handle,
+                                    esp_gatt_status_t_ESP_GATT_READ_NOT_PERMIT,
+                                    &[],
+                                );
 
-                            let mut esp_rsp = esp_gatt_rsp_t {
-                                attr_value: esp_gatt_value_t {
-                                    auth_req: 0,
-                                    handle: param.handle,
-                                    len: value.len() as u16,
-                                    offset: 0,
-                                    value: response,
-                                },
-                            };
+                                return;
+                            }
 
-                            unsafe {
-                                esp_nofail!(esp_ble_gatts_send_response(
+                            if !characteristic
+                                .read()
+                                .is_allowed(Connection::from(param), AttributeOperation::Read)
+                            {
+                                warn!(
+                                    target: GATTS,
+                                    "Read of characteristic {} denied by its access policy.",
+                                    characteristic.read()
+                                );
+
+                                send_response(
                                     gatts_if,
                                     param.conn_id,
                                     param.trans_id,
-                                    // TODO: Allow different statuses.
-                                    esp_gatt_status_t_ESP_GATT_OK,
-                                    &mut esp_rsp
-                                ));
+                                    param.handle,
+                                    esp_gatt_status_t_ESP_GATT_INSUF_AUTHORIZATION,
+                                    &[],
+                                );
+
+                                return;
                             }
+
+                            respond_with_part(gatts_if, param, || {
+                                match &characteristic.read().read_cache {
+                                    Some(cache) => cache.get_or_read(|| callback(param)),
+                                    None => callback(param),
+                                }
+                            });
                         }
                     } else {
                         characteristic
@@ -58,14 +86,16 @@ impl Profile {
                             .descriptors
                             .iter()
                             .for_each(|descriptor| {
-                                debug!(
-                                    "MCC: Checking descriptor {} ({:?}).",
+                                trace!(
+                                    target: GATTS,
+                                    "Checking descriptor {} ({:?}).",
                                     descriptor.read(),
                                     descriptor.read().attribute_handle
                                 );
 
                                 if descriptor.read().attribute_handle == Some(param.handle) {
                                     debug!(
+                                        target: GATTS,
                                         "Received read event for descriptor {}.",
                                         descriptor.read()
                                     );
@@ -73,31 +103,26 @@ impl Profile {
                                     if let AttributeControl::ResponseByApp(callback) =
                                         &descriptor.read().control
                                     {
-                                        let value = callback(param);
-
-                                        // Extend the response to the maximum length.
-                                        let mut response = [0u8; 600];
-                                        response[..value.len()].copy_from_slice(&value);
+                                        if !descriptor.read().permissions.read_access {
+                                            warn!(
+                                                target: GATTS,
+                                                "Read of descriptor {} denied by its permissions.",
+                                                descriptor.read()
+                                            );
 
-                                        let mut esp_rsp = esp_gatt_rsp_t {
-                                            attr_value: esp_gatt_value_t {
-                                                auth_req: 0,
-                                                handle: param.handle,
-                                                len: value.len() as u16,
-                                                offset: 0,
-                                                value: response,
-                                            },
-                                        };
-
-                                        unsafe {
-                                            esp_nofail!(esp_ble_gatts_send_response(
+                                            send_response(
                                                 gatts_if,
                                                 param.conn_id,
                                                 param.trans_id,
-                                                esp_gatt_status_t_ESP_GATT_OK,
-                                                &mut esp_rsp
-                                            ));
+                                                param.handle,
+                                                esp_gatt_status_t_ESP_GATT_READ_NOT_PERMIT,
+                                                &[],
+                                            );
+
+                                            return;
                                         }
+
+                                        respond_with_part(gatts_if, param, || callback(param));
                                     }
                                 }
                             });
@@ -106,3 +131,35 @@ impl Profile {
         }
     }
 }
+
+/// Answers a read with the part of the value starting at its offset,
+/// or with `ESP_GATT_INVALID_OFFSET` if the offset is past the end of the value.
+fn respond_with_part(
+    gatts_if: esp_gatt_if_t,
+    param: esp_ble_gatts_cb_param_t_gatts_read_evt_param,
+    read: impl FnOnce() -> Vec<u8>,
+) {
+    // TODO: Allow different statuses.
+    let (status, value) = match read_part(param, read) {
+        Ok(value) => (esp_gatt_status_t_ESP_GATT_OK, value),
+        Err(status) => {
+            warn!(
+                target: GATTS,
+                "Read of handle 0x{:04x} at offset {} is past the end of its value.",
+                param.handle,
+                param.offset
+            );
+            (status, Vec::new())
+        }
+    };
+
+    send_response_at(
+        gatts_if,
+        param.conn_id,
+        param.trans_id,
+        param.handle,
+        param.offset,
+        status,
+        &value,
+    );
+}
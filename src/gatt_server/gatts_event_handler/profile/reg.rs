@@ -1,21 +1,22 @@
-use crate::gatt_server::Profile;
+use crate::gatt_server::{GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
 use esp_idf_sys::{
     esp_ble_gatts_cb_param_t_gatts_reg_evt_param, esp_bt_status_t_ESP_BT_STATUS_SUCCESS,
 };
-use log::{info, warn};
+use log::info;
 
 impl Profile {
     pub(crate) fn on_reg(&mut self, param: esp_ble_gatts_cb_param_t_gatts_reg_evt_param) {
         // Check status
         if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
-            info!(
-                "{} registered on interface {}.",
-                &self,
-                self.interface.unwrap()
-            );
+            info!(target: GATTS, "{} registered on interface {:?}.", &self, self.interface);
             self.register_services();
         } else {
-            warn!("GATT profile registration failed.");
+            GattServerError::Event {
+                event: "Profile registration",
+                status: param.status,
+            }
+            .report();
         }
     }
 }
@@ -0,0 +1,23 @@
+use crate::gatt_server::{GattServerError, Profile};
+use crate::utilities::log_targets::GATTS;
+use esp_idf_sys::{esp_ble_gatts_cb_param_t_gatts_stop_evt_param, esp_gatt_status_t_ESP_GATT_OK};
+use log::{debug, warn};
+
+impl Profile {
+    pub(crate) fn on_stop(&mut self, param: esp_ble_gatts_cb_param_t_gatts_stop_evt_param) {
+        let Some(service) = self.get_service(param.service_handle) else {
+            warn!(target: GATTS, "Cannot find service described by service handle {} received in stop event.", param.service_handle);
+            return;
+        };
+
+        if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            debug!(target: GATTS, "GATT service {} stopped.", *service.read());
+        } else {
+            GattServerError::Event {
+                event: "Service stop",
+                status: param.status,
+            }
+            .report();
+        }
+    }
+}
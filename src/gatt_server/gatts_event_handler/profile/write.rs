@@ -1,15 +1,31 @@
-use crate::gatt_server::Profile;
-use crate::utilities::AttributeControl;
+use crate::gatt_server::{
+    event::{self, GattEvent},
+    prepared_write,
+    response::{send_response, send_response_at},
+    Profile,
+};
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::{sig::descriptors, AttributeControl, AttributeOperation, Connection};
 use esp_idf_sys::*;
-use log::debug;
+use log::{debug, warn};
 
 impl Profile {
+    /// Handles a write to an attribute of this profile.
+    ///
+    /// Returns the status the write was answered with, or would have been answered with
+    /// if it needed a response, so that the execution of prepared writes can report it.
     #[allow(clippy::too_many_lines)]
     pub(crate) fn on_write(
         &mut self,
         gatts_if: esp_gatt_if_t,
         param: esp_ble_gatts_cb_param_t_gatts_write_evt_param,
-    ) {
+    ) -> esp_gatt_status_t {
+        if param.is_prep {
+            self.on_prepare_write(gatts_if, param);
+            return esp_gatt_status_t_ESP_GATT_OK;
+        }
+
+        let mut status = esp_gatt_status_t_ESP_GATT_OK;
         for service in &self.services {
             service
                 .read()
@@ -18,10 +34,100 @@ impl Profile {
                 .for_each(|characteristic| {
                     if characteristic.read().attribute_handle == Some(param.handle) {
                         debug!(
+                            target: GATTS,
                             "Received write event for characteristic {}.",
                             characteristic.read()
                         );
 
+                        if !characteristic.read().is_writable() {
+                            warn!(
+                                target: GATTS,
+                                "Write to characteristic {} denied by its permissions.",
+                                characteristic.read()
+                            );
+                            status = esp_gatt_status_t_ESP_GATT_WRITE_NOT_PERMIT;
+
+                            // Reject the request, if the stack is not answering on its own.
+                            if param.need_rsp {
+                                if let AttributeControl::ResponseByApp(_) =
+                                    &characteristic.read().control
+                                {
+                                    send_response(
+                                        gatts_if,
+                                        param.conn_id,
+                                        param.trans_id,
+                                        param.handle,
+                                        status,
+                                        &[],
+                                    );
+                                }
+                            }
+
+                            return;
+                        }
+
+                        if !characteristic
+                            .read()
+                            .is_allowed(Connection::from(param), AttributeOperation::Write)
+                        {
+                            warn!(
+                                target: GATTS,
+                                "Write to characteristic {} denied by its access policy.",
+                                characteristic.read()
+                            );
+                            status = esp_gatt_status_t_ESP_GATT_INSUF_AUTHORIZATION;
+
+                            // Reject the request, if the stack is not answering on its own.
+                            if param.need_rsp {
+                                if let AttributeControl::ResponseByApp(_) =
+                                    &characteristic.read().control
+                                {
+                                    send_response(
+                                        gatts_if,
+                                        param.conn_id,
+                                        param.trans_id,
+                                        param.handle,
+                                        status,
+                                        &[],
+                                    );
+                                }
+                            }
+
+                            return;
+                        }
+
+                        let (uuid, value) = (characteristic.read().uuid, unsafe {
+                            std::slice::from_raw_parts(param.value, param.len as usize)
+                        });
+
+                        // Store the value, if the stack does not store it on its own.
+                        if characteristic.read().stores_writes {
+                            if let Err(error) = characteristic.write().store_written_value(value) {
+                                error.report();
+                                status = esp_gatt_status_t_ESP_GATT_INVALID_ATTR_LEN;
+
+                                if param.need_rsp {
+                                    send_response(
+                                        gatts_if,
+                                        param.conn_id,
+                                        param.trans_id,
+                                        param.handle,
+                                        status,
+                                        &[],
+                                    );
+                                }
+
+                                return;
+                            }
+                        }
+
+                        event::emit(&GattEvent::Write {
+                            connection: Connection::from(param),
+                            characteristic: uuid,
+                            handle: param.handle,
+                            value: value.to_vec(),
+                        });
+
                         // If the characteristic has a write handler, call it.
                         if let Some(write_callback) = &characteristic.read().write_callback {
                             let value = unsafe {
@@ -30,54 +136,62 @@ impl Profile {
                             .to_vec();
 
                             write_callback(value, param);
+                        }
 
-                            // Send response if needed.
-                            if param.need_rsp {
-                                if let AttributeControl::ResponseByApp(read_callback) =
-                                    &characteristic.read().control
-                                {
-                                    // Simulate a read operation.
-                                    let param_as_read_operation =
-                                        esp_ble_gatts_cb_param_t_gatts_read_evt_param {
-                                            bda: param.bda,
-                                            conn_id: param.conn_id,
-                                            handle: param.handle,
-                                            need_rsp: param.need_rsp,
-                                            offset: param.offset,
-                                            trans_id: param.trans_id,
-                                            ..Default::default()
-                                        };
-
-                                    // Get value.
-                                    let value = read_callback(param_as_read_operation);
-
-                                    // Extend the response to the maximum length.
-                                    let mut response = [0u8; 600];
-                                    response[..value.len()].copy_from_slice(&value);
-
-                                    let mut esp_rsp = esp_gatt_rsp_t {
-                                        attr_value: esp_gatt_value_t {
-                                            auth_req: 0,
-                                            handle: param.handle,
-                                            len: value.len() as u16,
-                                            offset: 0,
-                                            value: response,
-                                        },
+                        // Send response if needed.
+                        if param.need_rsp {
+                            if let AttributeControl::ResponseByApp(read_callback) =
+                                &characteristic.read().control
+                            {
+                                // Simulate a read operation.
+                                let param_as_read_operation =
+                                    esp_ble_gatts_cb_param_t_gatts_read_evt_param {
+                                        bda: param.bda,
+                                        conn_id: param.conn_id,
+                                        handle: param.handle,
+                                        need_rsp: param.need_rsp,
+                                        offset: param.offset,
+                                        trans_id: param.trans_id,
+                                        ..Default::default()
                                     };
 
-                                    unsafe {
-                                        esp_nofail!(esp_ble_gatts_send_response(
-                                            gatts_if,
-                                            param.conn_id,
-                                            param.trans_id,
-                                            esp_gatt_status_t_ESP_GATT_OK,
-                                            &mut esp_rsp
-                                        ));
-                                    }
-                                }
+                                // Get value.
+                                let value = read_callback(param_as_read_operation);
+
+                                send_response(
+                                    gatts_if,
+                                    param.conn_id,
+                                    param.trans_id,
+                                    param.handle,
+                                    esp_gatt_status_t_ESP_GATT_OK,
+                                    &value,
+                                );
                             }
                         }
                     } else {
+                        let is_sccd = characteristic.read().descriptors.iter().any(|descriptor| {
+                            descriptor.read().attribute_handle == Some(param.handle)
+                                && descriptor.read().uuid
+                                    == descriptors::SERVER_CHARACTERISTIC_CONFIGURATION
+                        });
+
+                        // The SCCD is a server-wide setting: keep track of it in the characteristic.
+                        if is_sccd {
+                            let value = unsafe {
+                                std::slice::from_raw_parts(param.value, param.len as usize)
+                            };
+
+                            characteristic.write().broadcast_enabled =
+                                matches!(value.first(), Some(bits) if bits & 0b0000_0001 != 0);
+                        }
+
+                        let is_subscription =
+                            characteristic.read().descriptors.iter().any(|descriptor| {
+                                descriptor.read().attribute_handle == Some(param.handle)
+                                    && descriptor.read().uuid
+                                        == descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION
+                            });
+
                         characteristic
                             .read()
                             .descriptors
@@ -85,10 +199,38 @@ impl Profile {
                             .for_each(|descriptor| {
                                 if descriptor.read().attribute_handle == Some(param.handle) {
                                     debug!(
+                                        target: GATTS,
                                         "Received write event for descriptor {}.",
                                         descriptor.read()
                                     );
 
+                                    if !descriptor.read().permissions.write_access {
+                                        warn!(
+                                            target: GATTS,
+                                            "Write to descriptor {} denied by its permissions.",
+                                            descriptor.read()
+                                        );
+                                        status = esp_gatt_status_t_ESP_GATT_WRITE_NOT_PERMIT;
+
+                                        // Reject the request, unless the stack answers on its own.
+                                        if param.need_rsp {
+                                            if let AttributeControl::ResponseByApp(_) =
+                                                &descriptor.read().control
+                                            {
+                                                send_response(
+                                                    gatts_if,
+                                                    param.conn_id,
+                                                    param.trans_id,
+                                                    param.handle,
+                                                    status,
+                                                    &[],
+                                                );
+                                            }
+                                        }
+
+                                        return;
+                                    }
+
                                     if let Some(write_callback) = descriptor.read().write_callback {
                                         let value = unsafe {
                                             std::slice::from_raw_parts(
@@ -120,36 +262,146 @@ impl Profile {
                                                 // Get value.
                                                 let value = read_callback(param_as_read_operation);
 
-                                                // Extend the response to the maximum length.
-                                                let mut response = [0u8; 600];
-                                                response[..value.len()].copy_from_slice(&value);
-
-                                                let mut esp_rsp = esp_gatt_rsp_t {
-                                                    attr_value: esp_gatt_value_t {
-                                                        auth_req: 0,
-                                                        handle: param.handle,
-                                                        len: value.len() as u16,
-                                                        offset: 0,
-                                                        value: response,
-                                                    },
-                                                };
-
-                                                unsafe {
-                                                    esp_nofail!(esp_ble_gatts_send_response(
-                                                        gatts_if,
-                                                        param.conn_id,
-                                                        param.trans_id,
-                                                        esp_gatt_status_t_ESP_GATT_OK,
-                                                        &mut esp_rsp
-                                                    ));
-                                                }
+                                                send_response(
+                                                    gatts_if,
+                                                    param.conn_id,
+                                                    param.trans_id,
+                                                    param.handle,
+                                                    esp_gatt_status_t_ESP_GATT_OK,
+                                                    &value,
+                                                );
                                             }
                                         }
                                     }
                                 }
                             });
+
+                        // Report the subscription once the CCCD is stored.
+                        if is_subscription {
+                            let value = unsafe {
+                                std::slice::from_raw_parts(param.value, param.len as usize)
+                            };
+                            let bits = value.first().copied().unwrap_or_default();
+
+                            event::emit(&GattEvent::Subscription {
+                                connection: Connection::from(param),
+                                characteristic: characteristic.read().uuid,
+                                notify: bits & 0b0000_0001 != 0,
+                                indicate: bits & 0b0000_0010 != 0,
+                            });
+                        }
                     }
                 });
         }
+
+        status
+    }
+
+    /// Queues a fragment of a long write, to be delivered to the write callback
+    /// once the client executes its prepared writes.
+    fn on_prepare_write(
+        &self,
+        gatts_if: esp_gatt_if_t,
+        param: esp_ble_gatts_cb_param_t_gatts_write_evt_param,
+    ) {
+        let Some((access, response_by_app)) =
+            self.write_access(param.handle, Connection::from(param))
+        else {
+            return;
+        };
+
+        let fragment = unsafe { std::slice::from_raw_parts(param.value, param.len as usize) };
+        let status = if access == esp_gatt_status_t_ESP_GATT_OK {
+            prepared_write::prepare(param.conn_id, param.handle, param.offset, fragment)
+                .map_or_else(|status| status, |()| esp_gatt_status_t_ESP_GATT_OK)
+        } else {
+            access
+        };
+
+        if status == esp_gatt_status_t_ESP_GATT_OK {
+            debug!(
+                target: GATTS,
+                "Prepared write of {} bytes at offset {} to handle 0x{:04x}.",
+                param.len,
+                param.offset,
+                param.handle
+            );
+        } else {
+            warn!(
+                target: GATTS,
+                "Prepared write to handle 0x{:04x} at offset {} rejected with status 0x{:x}.",
+                param.handle,
+                param.offset,
+                status
+            );
+        }
+
+        // Answer the request, unless the stack answers on its own.
+        // The response echoes the fragment, for the client to check it.
+        if param.need_rsp && response_by_app {
+            send_response_at(
+                gatts_if,
+                param.conn_id,
+                param.trans_id,
+                param.handle,
+                param.offset,
+                status,
+                if status == esp_gatt_status_t_ESP_GATT_OK {
+                    fragment
+                } else {
+                    &[]
+                },
+            );
+        }
+    }
+
+    /// Returns whether the attribute with the given handle can be written by a connection,
+    /// as a GATT status, and whether the application answers its requests,
+    /// or `None` if it is not part of this profile.
+    ///
+    /// Characteristics are checked against their permissions, their required privilege level
+    /// and their access policy, descriptors against their permissions.
+    fn write_access(
+        &self,
+        handle: u16,
+        connection: Connection,
+    ) -> Option<(esp_gatt_status_t, bool)> {
+        for service in &self.services {
+            for characteristic in &service.read().characteristics {
+                let characteristic = characteristic.read();
+                if characteristic.attribute_handle == Some(handle) {
+                    let access = if !characteristic.is_writable() {
+                        esp_gatt_status_t_ESP_GATT_WRITE_NOT_PERMIT
+                    } else if !characteristic.is_allowed(connection, AttributeOperation::Write) {
+                        esp_gatt_status_t_ESP_GATT_INSUF_AUTHORIZATION
+                    } else {
+                        esp_gatt_status_t_ESP_GATT_OK
+                    };
+
+                    return Some((
+                        access,
+                        matches!(characteristic.control, AttributeControl::ResponseByApp(_)),
+                    ));
+                }
+
+                for descriptor in &characteristic.descriptors {
+                    let descriptor = descriptor.read();
+                    if descriptor.attribute_handle == Some(handle) {
+                        let access = if descriptor.permissions.write_access {
+                            esp_gatt_status_t_ESP_GATT_OK
+                        } else {
+                            esp_gatt_status_t_ESP_GATT_WRITE_NOT_PERMIT
+                        };
+
+                        return Some((
+                            access,
+                            matches!(descriptor.control, AttributeControl::ResponseByApp(_)),
+                        ));
+                    }
+                }
+            }
+        }
+
+        None
     }
 }
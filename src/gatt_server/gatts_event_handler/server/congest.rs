@@ -0,0 +1,19 @@
+use crate::gatt_server::{delivery::DELIVERY_QUEUE, GattServer};
+use crate::utilities::log_targets::NOTIFY;
+use log::debug;
+
+impl GattServer {
+    #[allow(clippy::unused_self)]
+    pub(crate) fn on_congest(
+        &self,
+        param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_congest_evt_param,
+    ) {
+        debug!(
+            target: NOTIFY,
+            "Connection {} congestion status changed to {}.",
+            param.conn_id, param.congested
+        );
+
+        DELIVERY_QUEUE.set_congested(param.conn_id, param.congested);
+    }
+}
@@ -1,13 +1,22 @@
-use crate::gatt_server::GattServer;
+use crate::gatt_server::{
+    connection_parameters::record_parameters,
+    event::{self, GattEvent},
+    GattServer,
+};
+use crate::utilities::log_targets::GATTS;
 use crate::utilities::Connection;
 use log::info;
+use std::sync::Arc;
 
 impl GattServer {
     pub(crate) fn on_connect(
         &mut self,
         param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_connect_evt_param,
     ) {
-        info!("GATT client {} connected.", Connection::from(param));
-        self.active_connections.insert(param.into());
+        info!(target: GATTS, "GATT client {} connected.", Connection::from(param));
+        Arc::make_mut(&mut self.active_connections).insert(param.into());
+        record_parameters(param.remote_bda, param.conn_params.into());
+        self.require_encryption(param.into());
+        event::emit(&GattEvent::Connected(param.into()));
     }
 }
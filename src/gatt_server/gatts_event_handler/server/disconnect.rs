@@ -1,5 +1,20 @@
-use crate::gatt_server::GattServer;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+use crate::gatt_server::phy_update::forget_phy;
+use crate::gatt_server::{
+    chunked_channel::forget_reassemblers,
+    connection_parameters::forget_parameters,
+    delivery::DELIVERY_QUEUE,
+    event::{self, GattEvent},
+    indication::PENDING_INDICATIONS,
+    long_read::forget_long_reads,
+    prepared_write::forget_prepared_writes,
+    privilege::revoke_privilege,
+    secure_session::end_sessions,
+    GattServer,
+};
+use crate::utilities::{forget_mtu, log_targets::GATTS};
 use log::info;
+use std::sync::Arc;
 
 impl GattServer {
     pub(crate) fn on_disconnect(
@@ -7,12 +22,37 @@ impl GattServer {
         param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_disconnect_evt_param,
     ) {
         info!(
+            target: GATTS,
             "GATT client {:02X?} disconnected.",
             param.remote_bda.to_vec()
         );
 
-        self.active_connections.remove(&param.into());
+        Arc::make_mut(&mut self.active_connections).remove(&param.into());
+        self.cancel_encryption_deadline(param.conn_id);
+        PENDING_INDICATIONS.abort_connection(param.conn_id);
+        DELIVERY_QUEUE.abort_connection(param.conn_id);
+        end_sessions(param.conn_id);
+        revoke_privilege(param.conn_id);
+        forget_parameters(param.remote_bda);
+        forget_mtu(param.conn_id);
+        forget_long_reads(param.conn_id);
+        forget_prepared_writes(param.conn_id);
+        forget_reassemblers(param.conn_id);
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        forget_phy(param.remote_bda);
+        event::emit(&GattEvent::Disconnected(param.into()));
 
+        self.on_advertising_activity();
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        if self.extended_advertising.is_some() {
+            for instance in self.advertising_set_instances() {
+                self.start_extended_advertising(instance);
+            }
+            return;
+        }
+        if self.advertising_paused() {
+            return;
+        }
         unsafe {
             esp_idf_sys::esp_ble_gap_start_advertising(&mut self.advertisement_parameters);
         }
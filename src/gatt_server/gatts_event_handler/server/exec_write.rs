@@ -0,0 +1,92 @@
+use crate::gatt_server::{
+    prepared_write::execute_prepared_writes, response::send_response, GattServer,
+};
+use crate::utilities::log_targets::GATTS;
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_write_evt_param, esp_gatt_if_t, esp_gatt_status_t,
+    esp_gatt_status_t_ESP_GATT_OK, ESP_GATT_PREP_WRITE_CANCEL,
+};
+use log::debug;
+
+impl GattServer {
+    pub(crate) fn on_exec_write(
+        &mut self,
+        gatts_if: esp_gatt_if_t,
+        param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_exec_write_evt_param,
+    ) {
+        let cancel = u32::from(param.exec_write_flag) == ESP_GATT_PREP_WRITE_CANCEL;
+        // Cancelling discards the buffered fragments, which never reach the write callbacks.
+        let writes = execute_prepared_writes(param.conn_id, cancel);
+        let mut status = esp_gatt_status_t_ESP_GATT_OK;
+
+        if cancel {
+            debug!(
+                target: GATTS,
+                "GATT client {:02X?} cancelled its prepared writes.",
+                param.bda
+            );
+        } else {
+            debug!(
+                target: GATTS,
+                "GATT client {:02X?} executed its prepared writes.",
+                param.bda
+            );
+
+            status = self.deliver_prepared_writes(gatts_if, param, &writes);
+        }
+
+        // The client waits for the response in both cases.
+        send_response(gatts_if, param.conn_id, param.trans_id, 0, status, &[]);
+    }
+
+    /// Passes the reassembled values of the prepared writes to the profiles,
+    /// as single writes that the execute write response acknowledges.
+    ///
+    /// Returns the status of the first write that failed, or `ESP_GATT_OK` if they all succeeded,
+    /// for the execute write response to report it.
+    #[allow(clippy::cast_possible_truncation)]
+    fn deliver_prepared_writes(
+        &mut self,
+        gatts_if: esp_gatt_if_t,
+        param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_exec_write_evt_param,
+        writes: &[(u16, Vec<u8>)],
+    ) -> esp_gatt_status_t {
+        let mut status = esp_gatt_status_t_ESP_GATT_OK;
+        if writes.is_empty() {
+            return status;
+        }
+
+        for (handle, value) in writes {
+            debug!(
+                target: GATTS,
+                "Delivering the {} bytes prepared for handle 0x{:04x}.",
+                value.len(),
+                handle
+            );
+
+            let write = esp_ble_gatts_cb_param_t_gatts_write_evt_param {
+                conn_id: param.conn_id,
+                trans_id: param.trans_id,
+                bda: param.bda,
+                handle: *handle,
+                offset: 0,
+                need_rsp: false,
+                is_prep: false,
+                len: value.len() as u16,
+                value: value.as_ptr().cast_mut(),
+            };
+
+            self.profiles.iter().for_each(|profile| {
+                let written = profile.write().on_write(gatts_if, write);
+                if status == esp_gatt_status_t_ESP_GATT_OK {
+                    status = written;
+                }
+            });
+        }
+
+        // A long write might have changed the broadcast configuration of a characteristic.
+        self.update_broadcast_data();
+
+        status
+    }
+}
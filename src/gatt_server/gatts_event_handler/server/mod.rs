@@ -1,6 +1,9 @@
+mod congest;
 mod connect;
 mod disconnect;
+mod exec_write;
 mod mtu;
 mod reg;
 mod response;
+mod service_change;
 mod set_attr_val;
@@ -1,12 +1,47 @@
-use crate::gatt_server::GattServer;
-use log::debug;
+use crate::gatt_server::{
+    event::{self, GattEvent},
+    GattServer,
+};
+use crate::utilities::{log_targets::GATTS, record_mtu, Connection};
+use log::{debug, warn};
+use std::sync::Arc;
 
 impl GattServer {
-    #[allow(clippy::unused_self)]
     pub(crate) fn on_mtu_change(
-        &self,
+        &mut self,
         param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_mtu_evt_param,
     ) {
-        debug!("MTU changed to {}.", param.mtu);
+        let Some(connection) = self
+            .active_connections
+            .iter()
+            .find(|connection| connection.id() == param.conn_id)
+            .copied()
+        else {
+            warn!(
+                target: GATTS,
+                "Cannot find connection {} received in MTU event.",
+                param.conn_id
+            );
+            return;
+        };
+
+        debug!(
+            target: GATTS,
+            "MTU of GATT client {:02X?} changed to {}.",
+            connection.remote_bda(),
+            param.mtu
+        );
+
+        record_mtu(param.conn_id, param.mtu);
+        let connection = Connection {
+            mtu: param.mtu,
+            ..connection
+        };
+        Arc::make_mut(&mut self.active_connections).replace(connection);
+
+        event::emit(&GattEvent::MtuChanged {
+            connection,
+            mtu: param.mtu,
+        });
     }
 }
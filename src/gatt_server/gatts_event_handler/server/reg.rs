@@ -1,4 +1,5 @@
-use crate::gatt_server::GattServer;
+use crate::gatt_server::{error::esp_report, GattServer, GattServerError};
+use crate::utilities::log_targets::GATTS;
 #[allow(clippy::wildcard_imports)]
 use esp_idf_sys::*;
 use log::debug;
@@ -9,32 +10,59 @@ impl GattServer {
         gatts_if: esp_gatt_if_t,
         param: esp_ble_gatts_cb_param_t_gatts_reg_evt_param,
     ) {
-        if param.status == esp_gatt_status_t_ESP_GATT_OK {
-            debug!("New profile registered.");
+        let Some(profile) = self
+            .profiles
+            .iter()
+            .find(|profile| (*profile).read().identifier == param.app_id)
+            .cloned()
+        else {
+            GattServerError::UnknownProfile(param.app_id).report();
+            return;
+        };
 
-            let profile = self
-                .profiles
-                .iter()
-                .find(|profile| (*profile).read().identifier == param.app_id)
-                .expect("No profile found with received application identifier.");
+        if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            debug!(target: GATTS, "New profile registered.");
 
             profile.write().interface = Some(gatts_if);
 
             if !self.advertisement_configured {
                 unsafe {
-                    esp_nofail!(esp_ble_gap_set_device_name(
+                    esp_report!(esp_ble_gap_set_device_name(
                         self.device_name.as_ptr().cast::<i8>()
                     ));
+                }
 
-                    self.advertisement_configured = true;
-
-                    // Advertisement data.
-                    esp_nofail!(esp_ble_gap_config_adv_data(&mut self.advertisement_data));
+                self.advertisement_configured = true;
+                self.configure_random_address();
+                self.configure_appearance();
 
-                    // Scan response data.
-                    esp_nofail!(esp_ble_gap_config_adv_data(&mut self.scan_response_data));
+                #[cfg(esp_idf_bt_ble_50_features_supported)]
+                if self.extended_advertising.is_some() {
+                    for instance in self.advertising_set_instances() {
+                        self.configure_extended_advertising(instance);
+                    }
+                    return;
                 }
+
+                // Advertisement data.
+                self.configure_advertisement_data();
+
+                // Scan response data.
+                self.configure_scan_response_data();
             }
+        } else {
+            GattServerError::Event {
+                event: "Registration",
+                status: param.status,
+            }
+            .report();
+
+            let description = profile.read().to_string();
+            let retried_profile = profile.clone();
+            profile
+                .write()
+                .registration
+                .retry(description, move || retried_profile.write().register_self());
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::gatt_server::GattServer;
+use crate::utilities::log_targets::GATTS;
 use log::debug;
 
 impl GattServer {
@@ -7,6 +8,6 @@ impl GattServer {
         &self,
         param: esp_idf_sys::esp_ble_gatts_cb_param_t_gatts_rsp_evt_param,
     ) {
-        debug!("Responded to handle 0x{:04x}.", param.handle);
+        debug!(target: GATTS, "Responded to handle 0x{:04x}.", param.handle);
     }
 }
@@ -0,0 +1,24 @@
+use crate::gatt_server::{GattServer, GattServerError};
+use crate::utilities::log_targets::GATTS;
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_send_service_change_evt_param, esp_gatt_status_t_ESP_GATT_OK,
+};
+use log::debug;
+
+impl GattServer {
+    #[allow(clippy::unused_self)]
+    pub(crate) fn on_service_change(
+        &self,
+        param: esp_ble_gatts_cb_param_t_gatts_send_service_change_evt_param,
+    ) {
+        if param.status == esp_gatt_status_t_ESP_GATT_OK {
+            debug!(target: GATTS, "Service change indication sent.");
+        } else {
+            GattServerError::Event {
+                event: "Service change indication",
+                status: param.status,
+            }
+            .report();
+        }
+    }
+}
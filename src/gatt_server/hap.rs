@@ -0,0 +1,192 @@
+use esp_idf_sys::{mbedtls_md, mbedtls_md_info_from_type, mbedtls_md_type_t_MBEDTLS_MD_SHA512};
+use log::warn;
+
+use crate::gatt_server::GattServer;
+use crate::utilities::log_targets::GATTS;
+
+/// The Apple company identifier, in little-endian order.
+const APPLE_COMPANY_ID: [u8; 2] = [0x4C, 0x00];
+
+/// The type of HAP advertisements.
+const HAP_TYPE: u8 = 0x06;
+
+/// The subtype (upper three bits) and length (lower five bits) of the advertisement payload.
+const SUBTYPE_LENGTH: u8 = 0x31;
+
+/// The status flag of accessories that are not paired yet.
+const FLAG_NOT_PAIRED: u8 = 0x01;
+
+/// The compatible version of HAP over BLE.
+const COMPATIBLE_VERSION: u8 = 0x02;
+
+/// The accessory categories defined by the HomeKit Accessory Protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+#[allow(missing_docs)]
+pub enum HapCategory {
+    Other = 1,
+    Bridge = 2,
+    Fan = 3,
+    GarageDoorOpener = 4,
+    Lightbulb = 5,
+    DoorLock = 6,
+    Outlet = 7,
+    Switch = 8,
+    Thermostat = 9,
+    Sensor = 10,
+    SecuritySystem = 11,
+    Door = 12,
+    Window = 13,
+    WindowCovering = 14,
+    ProgrammableSwitch = 15,
+    RangeExtender = 16,
+    IpCamera = 17,
+    VideoDoorbell = 18,
+    AirPurifier = 19,
+    Heater = 20,
+    AirConditioner = 21,
+    Humidifier = 22,
+    Dehumidifier = 23,
+    Sprinkler = 28,
+    Faucet = 29,
+    ShowerSystem = 30,
+}
+
+/// A builder of the HomeKit Accessory Protocol BLE advertisement, sent as manufacturer data.
+///
+/// The advertisement carries the pairing status, the device identifier, the accessory category,
+/// the global state number, the configuration number and the setup hash of the accessory.
+///
+/// The global state number must be bumped with [`HapAdvertisement::bump_state_number`]
+/// when a characteristic changes while no controller is connected, and the configuration
+/// number with [`HapAdvertisement::bump_configuration_number`] when the attribute database
+/// changes. Advertise the result again with [`GattServer::advertise_hap`] after every change.
+///
+/// # Notes
+///
+/// The manufacturer data takes 23 bytes of the 31 bytes of the advertisement packet:
+/// the device name usually has to be moved to the scan response.
+#[derive(Debug, Clone)]
+pub struct HapAdvertisement {
+    device_id: [u8; 6],
+    category: HapCategory,
+    paired: bool,
+    global_state_number: u16,
+    configuration_number: u8,
+    setup_hash: [u8; 4],
+}
+
+impl HapAdvertisement {
+    /// Creates a new unpaired [`HapAdvertisement`], with the given device identifier and category.
+    #[must_use]
+    pub fn new(device_id: [u8; 6], category: HapCategory) -> Self {
+        Self {
+            device_id,
+            category,
+            paired: false,
+            global_state_number: 1,
+            configuration_number: 1,
+            setup_hash: [0; 4],
+        }
+    }
+
+    /// Sets whether the accessory is paired with a controller.
+    pub fn paired(&mut self, paired: bool) -> &mut Self {
+        self.paired = paired;
+        self
+    }
+
+    /// Sets the global state number, persisted by the accessory across reboots.
+    ///
+    /// Zero is not a valid state number, and is replaced by one.
+    pub fn global_state_number(&mut self, global_state_number: u16) -> &mut Self {
+        self.global_state_number = global_state_number.max(1);
+        self
+    }
+
+    /// Sets the configuration number, persisted by the accessory across reboots.
+    ///
+    /// Zero is not a valid configuration number, and is replaced by one.
+    pub fn configuration_number(&mut self, configuration_number: u8) -> &mut Self {
+        self.configuration_number = configuration_number.max(1);
+        self
+    }
+
+    /// Sets the setup hash from the four-character setup identifier of the accessory.
+    ///
+    /// The setup hash is the first four bytes of the SHA-512 digest of the setup identifier
+    /// followed by the device identifier, formatted as `XX:XX:XX:XX:XX:XX`.
+    pub fn setup_id(&mut self, setup_id: &str) -> &mut Self {
+        let message = format!("{}{}", setup_id, self.device_id_string());
+
+        let mut digest = [0u8; 64];
+        let result = unsafe {
+            mbedtls_md(
+                mbedtls_md_info_from_type(mbedtls_md_type_t_MBEDTLS_MD_SHA512),
+                message.as_ptr(),
+                message.len(),
+                digest.as_mut_ptr(),
+            )
+        };
+
+        if result == 0 {
+            self.setup_hash.copy_from_slice(&digest[..4]);
+        } else {
+            warn!(target: GATTS, "SHA-512 computation failed with error code {}.", result);
+        }
+
+        self
+    }
+
+    /// Increments the global state number, wrapping from 65535 to 1, and returns the new value.
+    pub fn bump_state_number(&mut self) -> u16 {
+        self.global_state_number = match self.global_state_number {
+            u16::MAX => 1,
+            number => number + 1,
+        };
+        self.global_state_number
+    }
+
+    /// Increments the configuration number, wrapping from 255 to 1, and returns the new value.
+    pub fn bump_configuration_number(&mut self) -> u8 {
+        self.configuration_number = match self.configuration_number {
+            u8::MAX => 1,
+            number => number + 1,
+        };
+        self.configuration_number
+    }
+
+    /// Encodes the advertisement as manufacturer data, starting with the Apple company identifier.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = APPLE_COMPANY_ID.to_vec();
+        data.push(HAP_TYPE);
+        data.push(SUBTYPE_LENGTH);
+        data.push(if self.paired { 0 } else { FLAG_NOT_PAIRED });
+        data.extend_from_slice(&self.device_id);
+        data.extend_from_slice(&(self.category as u16).to_le_bytes());
+        data.extend_from_slice(&self.global_state_number.to_le_bytes());
+        data.push(self.configuration_number);
+        data.push(COMPATIBLE_VERSION);
+        data.extend_from_slice(&self.setup_hash);
+        data
+    }
+
+    fn device_id_string(&self) -> String {
+        self.device_id
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+impl GattServer {
+    /// Advertises a [`HapAdvertisement`] in the manufacturer data of the advertisement.
+    ///
+    /// The advertisement is updated immediately if it is already configured.
+    pub fn advertise_hap(&mut self, advertisement: &HapAdvertisement) -> &mut Self {
+        self.set_manufacturer_data(advertisement.encode());
+        self
+    }
+}
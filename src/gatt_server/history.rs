@@ -0,0 +1,201 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{delivery::DeliveryCallback, LockedCharacteristic},
+    utilities::{Connection, DeliveryOutcome},
+};
+
+/// The control point command sending all the stored samples.
+const COMMAND_DRAIN: u8 = 0x01;
+
+/// The control point command discarding all the stored samples.
+const COMMAND_CLEAR: u8 = 0x02;
+
+/// The length of the control point commands.
+const COMMAND_LENGTH: u16 = 1;
+
+/// A ring buffer of timestamped samples, drained to clients on request.
+///
+/// Samples recorded while no client is connected are kept, up to the capacity of the history:
+/// when it is full, the oldest sample is discarded.
+///
+/// Clients drain the history by writing `0x01` to the control point characteristic.
+/// Every stored sample is then sent, oldest first, through the reliable delivery queue of
+/// the data characteristic, as a record made of:
+///
+/// - the sequence number of the record in this drain, as a little-endian `u16`;
+/// - the age of the sample in milliseconds, as a little-endian `u32`;
+/// - the sample itself.
+///
+/// The drain ends with a record made of the sequence number only.
+/// A sample is removed from the history once it is delivered. Writing `0x02` to the
+/// control point discards all the stored samples.
+///
+/// # Notes
+///
+/// The data characteristic must have the "notify" or "indicate" property,
+/// and the control point must be writable. The write callback and the maximum value length
+/// of the control point are replaced by the history, so it must be created before
+/// the server starts.
+#[derive(Clone)]
+pub struct HistoryCharacteristic {
+    samples: Arc<Mutex<History>>,
+}
+
+struct History {
+    records: VecDeque<Record>,
+    capacity: usize,
+    next_identifier: u32,
+}
+
+#[derive(Clone)]
+struct Record {
+    identifier: u32,
+    timestamp: Instant,
+    sample: Vec<u8>,
+}
+
+impl HistoryCharacteristic {
+    /// Creates a new [`HistoryCharacteristic`] storing up to `capacity` samples, sent on the `data`
+    /// characteristic when requested on the `control` characteristic.
+    #[must_use]
+    pub fn new(
+        data: &LockedCharacteristic,
+        control: &LockedCharacteristic,
+        capacity: usize,
+    ) -> Self {
+        let history = Self {
+            samples: Arc::new(Mutex::new(History {
+                records: VecDeque::with_capacity(capacity),
+                capacity,
+                next_identifier: 0,
+            })),
+        };
+
+        let control_history = history.clone();
+        let data = data.clone();
+        control
+            .write()
+            .max_value_length(COMMAND_LENGTH)
+            .on_write(move |value, param| {
+                let connection = Connection::from(param);
+
+                match value.first() {
+                    Some(&COMMAND_DRAIN) => control_history.drain(&data, connection),
+                    Some(&COMMAND_CLEAR) => control_history.clear(),
+                    _ => warn!(
+                        target: GATTS,
+                        "Unknown history command {:02X?} from {}.",
+                        value, connection
+                    ),
+                }
+            });
+
+        history
+    }
+
+    /// Records a new sample, discarding the oldest one if the history is full.
+    pub fn record(&self, sample: &[u8]) {
+        let mut history = self.samples.lock();
+
+        if history.capacity == 0 {
+            return;
+        }
+
+        if history.records.len() == history.capacity {
+            history.records.pop_front();
+        }
+
+        let identifier = history.next_identifier;
+        history.next_identifier = history.next_identifier.wrapping_add(1);
+        history.records.push_back(Record {
+            identifier,
+            timestamp: Instant::now(),
+            sample: sample.to_vec(),
+        });
+    }
+
+    /// Returns the number of stored samples.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.lock().records.len()
+    }
+
+    /// Returns `true` if no sample is stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.lock().records.is_empty()
+    }
+
+    /// Discards all the stored samples.
+    pub fn clear(&self) {
+        self.samples.lock().records.clear();
+    }
+
+    /// Sends the stored samples to the given connection.
+    fn drain(&self, data: &LockedCharacteristic, connection: Connection) {
+        let records: Vec<Record> = self.samples.lock().records.iter().cloned().collect();
+        debug!(
+            target: GATTS,
+            "Draining {} history records to {}.",
+            records.len(),
+            connection
+        );
+
+        let data = data.read();
+        let now = Instant::now();
+        let mut sequence: u16 = 0;
+
+        for record in records {
+            let age = now.saturating_duration_since(record.timestamp);
+
+            let mut value = sequence.to_le_bytes().to_vec();
+            value.extend_from_slice(&age_in_milliseconds(age).to_le_bytes());
+            value.extend_from_slice(&record.sample);
+
+            // Only forget the sample once it reached the client.
+            let samples = self.samples.clone();
+            let identifier = record.identifier;
+            let callback: Arc<DeliveryCallback> = Arc::new(move |_, outcome| {
+                if matches!(outcome, DeliveryOutcome::Sent | DeliveryOutcome::Confirmed) {
+                    samples
+                        .lock()
+                        .records
+                        .retain(|stored| stored.identifier != identifier);
+                }
+            });
+
+            if !data.queue_value(connection, value, Some(callback)) {
+                return;
+            }
+
+            sequence = sequence.wrapping_add(1);
+        }
+
+        data.queue_value(connection, sequence.to_le_bytes().to_vec(), None);
+    }
+}
+
+impl std::fmt::Debug for HistoryCharacteristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let history = self.samples.lock();
+
+        f.debug_struct("HistoryCharacteristic")
+            .field("len", &history.records.len())
+            .field("capacity", &history.capacity)
+            .finish()
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn age_in_milliseconds(age: Duration) -> u32 {
+    age.as_millis().min(u128::from(u32::MAX)) as u32
+}
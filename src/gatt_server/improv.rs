@@ -0,0 +1,409 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+use parking_lot::{Mutex, RwLock};
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{Characteristic, LockedCharacteristic, LockedService, Service, WifiCredentials},
+    utilities::{AttributePermissions, BleUuid, CharacteristicProperties, Connection},
+};
+
+/// The identifier of the Improv service.
+pub const IMPROV_SERVICE_UUID: BleUuid =
+    BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268000");
+
+/// The identifier of the current state characteristic.
+const STATE_UUID: BleUuid = BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268001");
+
+/// The identifier of the error state characteristic.
+const ERROR_UUID: BleUuid = BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268002");
+
+/// The identifier of the RPC command characteristic.
+const RPC_COMMAND_UUID: BleUuid = BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268003");
+
+/// The identifier of the RPC result characteristic.
+const RPC_RESULT_UUID: BleUuid = BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268004");
+
+/// The identifier of the capabilities characteristic.
+const CAPABILITIES_UUID: BleUuid =
+    BleUuid::from_uuid128_str("00467768-6228-2272-4663-277478268005");
+
+/// The RPC command sending the Wi-Fi settings.
+const COMMAND_WIFI_SETTINGS: u8 = 0x01;
+
+/// The RPC command asking the device to identify itself.
+const COMMAND_IDENTIFY: u8 = 0x02;
+
+/// The capability bit advertising support for the identify command.
+const CAPABILITY_IDENTIFY: u8 = 0x01;
+
+/// The maximum length of an RPC packet: the command, the data length, up to 255 bytes of data,
+/// and the checksum.
+const MAX_PACKET_LENGTH: u16 = 258;
+
+type CredentialsCallback = dyn Fn(Connection, WifiCredentials) + Send + Sync;
+type IdentifyCallback = dyn Fn() + Send + Sync;
+
+/// The states of an Improv device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ImprovState {
+    /// The device must be authorized, for example by pressing a button, before accepting credentials.
+    AuthorizationRequired = 0x01,
+    /// The device accepts credentials.
+    Authorized = 0x02,
+    /// The device is connecting to the network.
+    Provisioning = 0x03,
+    /// The device is connected to the network.
+    Provisioned = 0x04,
+}
+
+/// The errors reported by an Improv device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ImprovError {
+    /// No error occurred.
+    None = 0x00,
+    /// The RPC packet was malformed, or its checksum was wrong.
+    InvalidRpcPacket = 0x01,
+    /// The RPC command is not supported.
+    UnknownRpcCommand = 0x02,
+    /// The device could not connect to the network.
+    UnableToConnect = 0x03,
+    /// Credentials were sent before the device was authorized.
+    NotAuthorized = 0x04,
+    /// Any other error.
+    Unknown = 0xFF,
+}
+
+/// An implementation of the [Improv Wi-Fi](https://www.improv-wifi.com/ble/) BLE service,
+/// onboarding the device from any Improv-compatible tool.
+///
+/// The service exposes the current state, error state, RPC command, RPC result
+/// and capabilities characteristics of the specification. Credentials received with the
+/// "send Wi-Fi settings" command are handed to the [`ImprovService::on_credentials`] callback,
+/// and the application reports the outcome with [`ImprovService::provisioned`]
+/// or [`ImprovService::failed`].
+///
+/// # Notes
+///
+/// The Improv clients look for devices advertising the service:
+/// add [`IMPROV_SERVICE_UUID`] to the advertised services.
+#[derive(Clone)]
+pub struct ImprovService {
+    service: LockedService,
+    state: Arc<ImprovInner>,
+}
+
+struct ImprovInner {
+    current: Mutex<ImprovState>,
+    state: LockedCharacteristic,
+    error: LockedCharacteristic,
+    result: LockedCharacteristic,
+    capabilities: LockedCharacteristic,
+    /// The RPC packet being received, which may span several writes.
+    packet: Mutex<Vec<u8>>,
+    credentials_callback: RwLock<Option<Arc<CredentialsCallback>>>,
+    identify_callback: RwLock<Option<Arc<IdentifyCallback>>>,
+}
+
+impl ImprovService {
+    /// Creates a new [`ImprovService`], in the [`ImprovState::Authorized`] state.
+    #[must_use]
+    pub fn new() -> Self {
+        let state = Characteristic::new(STATE_UUID)
+            .name("Improv Current State")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .set_value(vec![ImprovState::Authorized as u8])
+            .build();
+
+        let error = Characteristic::new(ERROR_UUID)
+            .name("Improv Error State")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .set_value(vec![ImprovError::None as u8])
+            .build();
+
+        let result = Characteristic::new(RPC_RESULT_UUID)
+            .name("Improv RPC Result")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .max_value_length(MAX_PACKET_LENGTH)
+            // An empty result: no command, no data, and their checksum.
+            .set_value(vec![0u8, 0, 0])
+            .build();
+
+        let capabilities = Characteristic::new(CAPABILITIES_UUID)
+            .name("Improv Capabilities")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read())
+            .set_value(vec![0u8])
+            .build();
+
+        let inner = Arc::new(ImprovInner {
+            current: Mutex::new(ImprovState::Authorized),
+            state: state.clone(),
+            error: error.clone(),
+            result: result.clone(),
+            capabilities: capabilities.clone(),
+            packet: Mutex::new(Vec::new()),
+            credentials_callback: RwLock::new(None),
+            identify_callback: RwLock::new(None),
+        });
+
+        let command_inner = inner.clone();
+        let command = Characteristic::new(RPC_COMMAND_UUID)
+            .name("Improv RPC Command")
+            .permissions(AttributePermissions::new().write())
+            .properties(CharacteristicProperties::new().write())
+            .max_value_length(MAX_PACKET_LENGTH)
+            .on_write(move |value, param| command_inner.receive(Connection::from(param), &value))
+            .build();
+
+        Self {
+            service: Service::new(IMPROV_SERVICE_UUID)
+                .name("Improv")
+                .primary()
+                .characteristic(&state)
+                .characteristic(&error)
+                .characteristic(&command)
+                .characteristic(&result)
+                .characteristic(&capabilities)
+                .build(),
+            state: inner,
+        }
+    }
+
+    /// Sets the callback receiving the credentials sent by a client.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, so it must not block:
+    /// connect to the network from another task, and report the outcome with
+    /// [`Self::provisioned`] or [`Self::failed`].
+    pub fn on_credentials(
+        &mut self,
+        callback: impl Fn(Connection, WifiCredentials) + Send + Sync + 'static,
+    ) -> &mut Self {
+        *self.state.credentials_callback.write() = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets the callback called when a client asks the device to identify itself,
+    /// for example by blinking a light.
+    ///
+    /// Setting this callback advertises the identify capability.
+    pub fn on_identify(&mut self, callback: impl Fn() + Send + Sync + 'static) -> &mut Self {
+        *self.state.identify_callback.write() = Some(Arc::new(callback));
+        self.state
+            .capabilities
+            .write()
+            .set_value(vec![CAPABILITY_IDENTIFY]);
+        self
+    }
+
+    /// Requires the device to be authorized with [`Self::authorize`] before it accepts credentials.
+    pub fn require_authorization(&mut self) -> &mut Self {
+        self.state.set_state(ImprovState::AuthorizationRequired);
+        self
+    }
+
+    /// Authorizes the device to accept credentials, for example after a button press.
+    pub fn authorize(&self) {
+        if self.state() == ImprovState::AuthorizationRequired {
+            self.state.set_state(ImprovState::Authorized);
+        }
+    }
+
+    /// Reports that the device is connected to the network, optionally giving
+    /// the URLs the client should redirect the user to.
+    pub fn provisioned(&self, urls: &[&str]) {
+        info!(target: GATTS, "Improv provisioning complete.");
+        self.state
+            .send_result(COMMAND_WIFI_SETTINGS, urls.iter().map(|url| url.as_bytes()));
+        self.state.set_state(ImprovState::Provisioned);
+    }
+
+    /// Reports that the device could not connect to the network.
+    /// The device accepts new credentials.
+    pub fn failed(&self) {
+        warn!(target: GATTS, "Improv provisioning failed.");
+        self.state.set_error(ImprovError::UnableToConnect);
+        self.state.set_state(ImprovState::Authorized);
+    }
+
+    /// Returns the current state of the device.
+    #[must_use]
+    pub fn state(&self) -> ImprovState {
+        *self.state.current.lock()
+    }
+
+    /// Returns the service, to be added to a [`Profile`](crate::gatt_server::Profile).
+    #[must_use]
+    pub fn service(&self) -> LockedService {
+        self.service.clone()
+    }
+}
+
+impl Default for ImprovService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ImprovService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImprovService")
+            .field("service", &self.service.read().to_string())
+            .field("state", &*self.state.current.lock())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ImprovInner {
+    fn set_state(&self, state: ImprovState) {
+        *self.current.lock() = state;
+        self.state.write().set_value(vec![state as u8]);
+    }
+
+    fn set_error(&self, error: ImprovError) {
+        self.error.write().set_value(vec![error as u8]);
+    }
+
+    /// Accumulates a write to the RPC command characteristic, and runs the command once complete.
+    fn receive(&self, connection: Connection, value: &[u8]) {
+        let packet = {
+            let mut packet = self.packet.lock();
+            packet.extend_from_slice(value);
+
+            // The packet is made of the command, the data length, the data and the checksum.
+            let Some(&length) = packet.get(1) else {
+                return;
+            };
+            let total = usize::from(length) + 3;
+            if packet.len() < total {
+                return;
+            }
+            if packet.len() > total {
+                warn!(target: GATTS, "Improv RPC packet from {} is too long.", connection);
+                packet.clear();
+                drop(packet);
+                self.set_error(ImprovError::InvalidRpcPacket);
+                return;
+            }
+
+            std::mem::take(&mut *packet)
+        };
+
+        let (body, checksum) = packet.split_at(packet.len() - 1);
+        if checksum[0] != checksum_of(body) {
+            warn!(
+                target: GATTS,
+                "Improv RPC packet from {} has a wrong checksum.",
+                connection
+            );
+            self.set_error(ImprovError::InvalidRpcPacket);
+            return;
+        }
+
+        self.set_error(ImprovError::None);
+
+        let data = &body[2..];
+        match body[0] {
+            COMMAND_WIFI_SETTINGS => self.receive_wifi_settings(connection, data),
+            COMMAND_IDENTIFY => {
+                let callback = self.identify_callback.read().clone();
+                match callback {
+                    Some(callback) => callback(),
+                    None => self.set_error(ImprovError::UnknownRpcCommand),
+                }
+            }
+            command => {
+                warn!(target: GATTS, "Unknown Improv RPC command {:02X}.", command);
+                self.set_error(ImprovError::UnknownRpcCommand);
+            }
+        }
+    }
+
+    fn receive_wifi_settings(&self, connection: Connection, data: &[u8]) {
+        if *self.current.lock() != ImprovState::Authorized {
+            warn!(
+                target: GATTS,
+                "Improv credentials from {} while not authorized.",
+                connection
+            );
+            self.set_error(ImprovError::NotAuthorized);
+            return;
+        }
+
+        let mut fields = Fields(data);
+        let (Some(ssid), Some(passphrase)) = (fields.next(), fields.next()) else {
+            warn!(target: GATTS, "Malformed Improv Wi-Fi settings from {}.", connection);
+            self.set_error(ImprovError::InvalidRpcPacket);
+            return;
+        };
+
+        let (Ok(ssid), Ok(passphrase)) = (
+            String::from_utf8(ssid.to_vec()),
+            String::from_utf8(passphrase.to_vec()),
+        ) else {
+            warn!(target: GATTS, "Invalid Improv Wi-Fi settings from {}.", connection);
+            self.set_error(ImprovError::InvalidRpcPacket);
+            return;
+        };
+
+        info!(
+            target: GATTS,
+            "Received credentials for Wi-Fi network {} from {}.",
+            ssid, connection
+        );
+        self.set_state(ImprovState::Provisioning);
+
+        let callback = self.credentials_callback.read().clone();
+        if let Some(callback) = callback {
+            callback(connection, WifiCredentials { ssid, passphrase });
+        }
+    }
+
+    /// Sets the RPC result of a command, made of a list of strings.
+    fn send_result<'a>(&self, command: u8, strings: impl Iterator<Item = &'a [u8]>) {
+        let mut data = Vec::new();
+        for string in strings {
+            let length = u8::try_from(string.len()).unwrap_or(u8::MAX);
+            data.push(length);
+            data.extend_from_slice(&string[..usize::from(length)]);
+        }
+
+        let mut result = vec![command, u8::try_from(data.len()).unwrap_or(u8::MAX)];
+        result.extend_from_slice(&data);
+        result.push(checksum_of(&result));
+
+        self.result.write().set_value(result);
+    }
+}
+
+/// Iterates over the length-prefixed fields of an RPC command.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&length, rest) = self.0.split_first()?;
+        let length = usize::from(length);
+        if rest.len() < length {
+            return None;
+        }
+
+        let (field, rest) = rest.split_at(length);
+        self.0 = rest;
+        Some(field)
+    }
+}
+
+/// The checksum of an RPC packet: the sum of its bytes, modulo 256.
+fn checksum_of(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
@@ -0,0 +1,120 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    mpsc::{sync_channel, Receiver, SyncSender},
+};
+
+use esp_idf_sys::esp_gatt_status_t;
+use parking_lot::Mutex;
+
+/// The indications that are waiting for a confirmation from the client.
+pub(crate) static PENDING_INDICATIONS: PendingIndications = PendingIndications::new();
+
+/// The function called with the status of a pending indication and its token.
+type StatusCallback = Box<dyn FnOnce(u32, esp_gatt_status_t) + Send>;
+
+/// Where the status of a pending indication goes.
+enum Waiter {
+    Channel(SyncSender<esp_gatt_status_t>),
+    Callback(StatusCallback),
+}
+
+struct PendingIndication {
+    token: u32,
+    conn_id: u16,
+    handle: u16,
+    waiter: Waiter,
+}
+
+/// Keeps track of the indications sent to the clients, until their confirmation event arrives.
+///
+/// Notifications are tracked too: the stack reports their send status with the same event.
+pub(crate) struct PendingIndications {
+    next_token: AtomicU32,
+    pending: Mutex<Vec<PendingIndication>>,
+}
+
+impl PendingIndications {
+    const fn new() -> Self {
+        Self {
+            next_token: AtomicU32::new(0),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Registers an indication sent on the given connection and attribute handle.
+    ///
+    /// Returns a token that identifies the indication, and the receiver
+    /// that will get the status of the confirmation event.
+    pub(crate) fn register(&self, conn_id: u16, handle: u16) -> (u32, Receiver<esp_gatt_status_t>) {
+        let (sender, receiver) = sync_channel(1);
+        let token = self.push(conn_id, handle, Waiter::Channel(sender));
+
+        (token, receiver)
+    }
+
+    /// Registers an indication sent on the given connection and attribute handle,
+    /// whose status is passed to `callback` along with its token, instead of a receiver.
+    ///
+    /// The callback runs in the Bluetooth stack's context, so it must not block.
+    /// It is dropped without being called if the indication is cancelled or aborted.
+    pub(crate) fn register_callback(
+        &self,
+        conn_id: u16,
+        handle: u16,
+        callback: impl FnOnce(u32, esp_gatt_status_t) + Send + 'static,
+    ) -> u32 {
+        self.push(conn_id, handle, Waiter::Callback(Box::new(callback)))
+    }
+
+    fn push(&self, conn_id: u16, handle: u16, waiter: Waiter) -> u32 {
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+
+        self.pending.lock().push(PendingIndication {
+            token,
+            conn_id,
+            handle,
+            waiter,
+        });
+
+        token
+    }
+
+    /// Completes the oldest indication sent on the given connection and attribute handle.
+    pub(crate) fn complete(&self, conn_id: u16, handle: u16, status: esp_gatt_status_t) {
+        let completed = {
+            let mut pending = self.pending.lock();
+
+            pending
+                .iter()
+                .position(|indication| indication.conn_id == conn_id && indication.handle == handle)
+                .map(|index| pending.remove(index))
+        };
+
+        let Some(indication) = completed else {
+            return;
+        };
+
+        match indication.waiter {
+            // The receiver might be gone already, there's nothing to do in that case.
+            Waiter::Channel(sender) => {
+                let _ = sender.try_send(status);
+            }
+            Waiter::Callback(callback) => callback(indication.token, status),
+        }
+    }
+
+    /// Forgets an indication, for example after its confirmation timed out.
+    pub(crate) fn cancel(&self, token: u32) {
+        self.pending
+            .lock()
+            .retain(|indication| indication.token != token);
+    }
+
+    /// Fails all the indications sent on the given connection.
+    pub(crate) fn abort_connection(&self, conn_id: u16) {
+        // Dropping the senders wakes up the receivers with an error.
+        self.pending
+            .lock()
+            .retain(|indication| indication.conn_id != conn_id);
+    }
+}
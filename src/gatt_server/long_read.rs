@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use esp_idf_sys::{
+    esp_ble_gatts_cb_param_t_gatts_read_evt_param, esp_gatt_status_t,
+    esp_gatt_status_t_ESP_GATT_INVALID_OFFSET,
+};
+use lazy_static::lazy_static;
+use log::debug;
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::Connection;
+
+lazy_static! {
+    /// The values being read in several parts, by connection identifier and attribute handle.
+    ///
+    /// The value returned for the first part of a long read is served to the blob reads
+    /// that follow, so that the client reassembles a consistent value
+    /// even if the application updates it meanwhile.
+    static ref LONG_READS: Mutex<HashMap<(u16, u16), Vec<u8>>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the part of the value of an attribute requested by a read, starting at its offset.
+///
+/// The first part of a read calls `read`; the blob reads that follow are served from the value
+/// it returned, as long as it does not fit in a single response.
+/// Fails with `ESP_GATT_INVALID_OFFSET` if the offset is past the end of the value.
+pub(crate) fn read_part(
+    param: esp_ble_gatts_cb_param_t_gatts_read_evt_param,
+    read: impl FnOnce() -> Vec<u8>,
+) -> Result<Vec<u8>, esp_gatt_status_t> {
+    let key = (param.conn_id, param.handle);
+    let offset = usize::from(param.offset);
+    // A read response carries the value after its opcode.
+    let part_length = usize::from(Connection::from(param).mtu().saturating_sub(1));
+
+    let pending = if param.is_long {
+        LONG_READS.lock().get(&key).cloned()
+    } else {
+        None
+    };
+    let value = pending.unwrap_or_else(read);
+
+    let mut long_reads = LONG_READS.lock();
+    if offset > value.len() {
+        long_reads.remove(&key);
+        return Err(esp_gatt_status_t_ESP_GATT_INVALID_OFFSET);
+    }
+
+    if value.len() - offset > part_length {
+        if !param.is_long {
+            debug!(
+                target: GATTS,
+                "Value of handle 0x{:04x} is {} bytes long, expecting blob reads.",
+                param.handle,
+                value.len()
+            );
+        }
+
+        let part = value[offset..].to_vec();
+        long_reads.insert(key, value);
+        return Ok(part);
+    }
+
+    long_reads.remove(&key);
+    Ok(value[offset..].to_vec())
+}
+
+/// Discards the long reads of a connection, once it is closed.
+pub(crate) fn forget_long_reads(connection: u16) {
+    LONG_READS
+        .lock()
+        .retain(|(read_connection, _), _| *read_connection != connection);
+}
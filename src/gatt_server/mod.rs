@@ -2,44 +2,171 @@
 
 #![allow(clippy::cast_possible_truncation)]
 
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use esp_idf_sys::*;
 use lazy_static::lazy_static;
-use log::{info, warn};
+use log::warn;
 use parking_lot::Mutex;
 
 use crate::{
-    leaky_box_raw,
-    utilities::{Appearance, Connection},
+    ble_stack,
+    error::esp_result,
+    utilities::{log_targets::GATTS, AddressType, Appearance, BleUuid, Connection},
+    BluedroidError,
+};
+use adaptive_advertising::AdaptiveAdvertising;
+use bond_capacity::EvictionCallback;
+use capacity::Profiles;
+use pairing::PairingCallback;
+use profile::RawEventCallback;
+
+pub use adaptive_advertising::AdvertisingPolicy;
+pub use advertising_rotation::AdvertisingRotation;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+pub use advertising_sets::MAX_ADVERTISING_SETS;
+pub use ble_stream::BleStream;
+pub use bond_backup::BondBackup;
+#[cfg(feature = "standard-services")]
+pub use bthome::BtHome;
+pub use capacity::{
+    MAX_CHARACTERISTICS, MAX_DESCRIPTORS, MAX_PROFILES, MAX_SERVICES, MAX_VALUE_LENGTH,
 };
-
 pub use characteristic::Characteristic;
 pub use characteristic::LockedCharacteristic;
+pub use chunked_channel::ChunkedChannel;
 pub use custom_attributes::STORAGE;
+pub use definition::{CharacteristicDefinition, DescriptorDefinition, ServiceDefinition};
 pub use descriptor::Descriptor;
 pub use descriptor::LockedDescriptor;
+pub use descriptor_store::{DescriptorKey, DescriptorStore, StoreError};
+pub use error::GattServerError;
+pub use event::GattEvent;
+pub use event_loop::{BleEvent, BleEventValue};
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+pub use extended_advertising::ExtendedAdvertising;
+#[cfg(feature = "standard-services")]
+pub use find_my::{FindMyAdvertisement, FIND_MY_KEY_LENGTH};
+#[cfg(feature = "standard-services")]
+pub use hap::{HapAdvertisement, HapCategory};
+pub use history::HistoryCharacteristic;
+#[cfg(feature = "standard-services")]
+pub use improv::{ImprovError, ImprovService, ImprovState, IMPROV_SERVICE_UUID};
+pub use notify_sink::NotifySink;
+#[cfg(feature = "standard-services")]
+pub use ota::{OtaService, OTA_SERVICE_UUID};
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+pub use periodic_advertising::PeriodicAdvertising;
 pub use profile::LockedProfile;
 pub use profile::Profile;
+#[cfg(feature = "standard-services")]
+pub use provisioning::{
+    ProvisioningStatus, WifiCredentials, WifiProvisioning, PROVISIONING_SERVICE_UUID,
+};
+pub use registration::RegistrationState;
+pub use resolving_list::ResolvingListEntry;
+pub use scan_response::ScanResponse;
+pub use secure_session::SecureSession;
+#[cfg(feature = "security")]
+pub use security::{NumericComparison, Security};
 pub use service::LockedService;
 pub use service::Service;
+pub use snapshot::{
+    CharacteristicSnapshot, DescriptorSnapshot, GattTreeSnapshot, ProfileSnapshot, ServiceSnapshot,
+};
+pub use templates::{ControlPoint, ControlPointError};
+pub use transaction::Transaction;
 // Structs.
 mod characteristic;
 mod descriptor;
+mod descriptor_store;
 mod profile;
 mod service;
+mod transaction;
 
 // Custom stuff.
+mod adaptive_advertising;
+mod advertisement;
+mod advertising_rotation;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+mod advertising_sets;
+mod attribute_table;
+mod auto_notify;
+mod ble_stream;
+mod bond_backup;
+mod bond_capacity;
+mod broadcast;
+#[cfg(feature = "standard-services")]
+mod bthome;
+mod capacity;
+mod chunked_channel;
+mod connection_parameters;
 mod custom_attributes;
+#[cfg(feature = "debug-keys")]
+mod debug_keys;
+mod definition;
+mod delivery;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+mod encryption_policy;
+mod error;
+mod event;
+mod event_loop;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+mod extended_advertising;
+#[cfg(feature = "standard-services")]
+mod find_my;
+mod gatt_caching;
+#[cfg(feature = "standard-services")]
+mod hap;
+mod history;
+#[cfg(feature = "standard-services")]
+mod improv;
+mod indication;
+mod long_read;
+mod mtu;
+mod notify_sink;
+#[cfg(feature = "standard-services")]
+mod ota;
+mod pairing;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+mod periodic_advertising;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+mod phy_update;
+mod prepared_write;
+mod privilege;
+#[cfg(feature = "standard-services")]
+mod provisioning;
+mod read_cache;
+mod registration;
+mod resolving_list;
+mod response;
+mod scan_response;
+mod secure_session;
+#[cfg(feature = "security")]
+mod security;
+mod service_changed;
+mod snapshot;
+mod supervisor;
+mod templates;
+mod whitelist;
+mod worker;
 
 // Event handler.
 mod gap_event_handler;
 mod gatts_event_handler;
 
 lazy_static! {
-    /// The GATT server singleton.
-    pub static ref GLOBAL_GATT_SERVER: Mutex<GattServer> = Mutex::new(GattServer {
-        profiles: Vec::new(),
+    /// The GATT server singleton, handed to the application by [`GattServer::take`].
+    pub(crate) static ref GLOBAL_GATT_SERVER: Mutex<GattServer> = Mutex::new(GattServer {
+        profiles: Profiles::new(),
         started: false,
         advertisement_parameters: esp_ble_adv_params_t {
             adv_int_min: 0x20,
@@ -83,52 +210,179 @@ lazy_static! {
         },
         advertisement_configured: false,
         device_name: "ESP32".to_string(),
-        active_connections: HashSet::new(),
-        power_level: esp_power_level_t_ESP_PWR_LVL_P9
+        active_connections: Arc::new(HashSet::new()),
+        broadcast_data: Vec::new(),
+        bthome_data: Vec::new(),
+        manufacturer_data: Vec::new(),
+        random_address: None,
+        advertise_primary_services: false,
+        service_uuids: Vec::new(),
+        scan_response: ScanResponse::new(),
+        supervised: false,
+        raw_gatts_event_callback: None,
+        suppressed_events: HashSet::new(),
+        power_level: esp_power_level_t_ESP_PWR_LVL_P9,
+        resolving_list: Vec::new(),
+        address_resolution: false,
+        #[cfg(feature = "debug-keys")]
+        debug_keys: false,
+        encryption_timeout: None,
+        encryption_deadlines: HashMap::new(),
+        pairing_callback: None,
+        eviction_callback: None,
+        local_mtu: None,
+        rotated_advertisement_type: None,
+        adaptive_advertising: None,
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        extended_advertising: None,
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        advertising_sets: std::collections::BTreeMap::new(),
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        preferred_phy: None,
+        #[cfg(feature = "security")]
+        security: None,
+        whitelist: Vec::new(),
     });
 }
 
+/// The maximum length of the device name, in bytes, that the Bluetooth stack stores.
+///
+/// Set by the `CONFIG_BT_MAX_DEVICE_NAME_LEN` option of the ESP-IDF configuration;
+/// the stack truncates any longer name.
+pub const MAX_DEVICE_NAME_LENGTH: usize = CONFIG_BT_MAX_DEVICE_NAME_LEN as usize;
+
+/// Whether the GATT server singleton was taken with [`GattServer::take`].
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
 /// Represents a GATT server.
 ///
-/// This is a singleton, and can be accessed via the [`GLOBAL_GATT_SERVER`] static.
+/// This is a singleton, taken with [`GattServer::take`].
+#[allow(clippy::struct_excessive_bools)]
 pub struct GattServer {
-    profiles: Vec<LockedProfile>,
+    profiles: Profiles,
     started: bool,
     advertisement_parameters: esp_ble_adv_params_t,
     advertisement_data: esp_ble_adv_data_t,
     scan_response_data: esp_ble_adv_data_t,
     device_name: String,
     advertisement_configured: bool,
-    active_connections: HashSet<Connection>,
+    /// The connected clients, replaced on every change so that readers can keep a cheap snapshot.
+    active_connections: Arc<HashSet<Connection>>,
+    broadcast_data: Vec<u8>,
+    /// The service data of the BTHome advertisement, if any, advertised instead of the broadcasts.
+    bthome_data: Vec<u8>,
+    manufacturer_data: Vec<u8>,
+    random_address: Option<[u8; 6]>,
+    advertise_primary_services: bool,
+    service_uuids: Vec<u8>,
+    scan_response: ScanResponse,
+    supervised: bool,
+    raw_gatts_event_callback: Option<Arc<RawEventCallback>>,
+    suppressed_events: HashSet<esp_gatts_cb_event_t>,
     power_level: esp_power_level_t,
+    /// The peers to add to the resolving list once the Bluetooth stack is initialised.
+    resolving_list: Vec<ResolvingListEntry>,
+    address_resolution: bool,
+    #[cfg(feature = "debug-keys")]
+    debug_keys: bool,
+    /// The delay given to new connections to be encrypted, when encryption is required.
+    encryption_timeout: Option<Duration>,
+    /// The token of the encryption deadline of the connections waiting to be encrypted,
+    /// by connection ID.
+    encryption_deadlines: HashMap<u16, u32>,
+    pairing_callback: Option<Arc<PairingCallback>>,
+    eviction_callback: Option<Arc<EvictionCallback>>,
+    local_mtu: Option<u16>,
+    /// The type of the advertisement of the server, while an advertising rotation changes it.
+    rotated_advertisement_type: Option<esp_ble_adv_type_t>,
+    adaptive_advertising: Option<AdaptiveAdvertising>,
+    /// The extended advertisement replacing the legacy one, if selected.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    extended_advertising: Option<ExtendedAdvertising>,
+    /// The advertising sets sent next to the extended advertisement, by instance.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    advertising_sets: std::collections::BTreeMap<u8, ExtendedAdvertising>,
+    /// The physical layer preferred for the new connections.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    preferred_phy: Option<crate::utilities::Phy>,
+    #[cfg(feature = "security")]
+    security: Option<Security>,
+    /// The devices to add to the whitelist once the Bluetooth stack is initialised.
+    whitelist: Vec<([u8; 6], AddressType)>,
 }
 
 unsafe impl Send for GattServer {}
 
 impl GattServer {
+    /// Takes the GATT server singleton.
+    ///
+    /// The Bluetooth stack accepts a single GATTS callback, so a single component of the application
+    /// can own the GATT server. The first call returns the singleton, and the following calls fail,
+    /// instead of letting several components silently share and reconfigure it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::AlreadyTaken`] if the singleton was already taken.
+    pub fn take() -> Result<&'static Mutex<Self>, BluedroidError> {
+        if TAKEN.swap(true, Ordering::SeqCst) {
+            return Err(GattServerError::AlreadyTaken.into());
+        }
+
+        Ok(&GLOBAL_GATT_SERVER)
+    }
+
     /// Starts a [`GattServer`].
     ///
+    /// The registration of the profiles and the advertisement complete asynchronously:
+    /// their failures are reported to the callback set with [`GattServer::on_error`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::AlreadyStarted`] if the server is already started.
+    /// Returns a [`BluedroidError`] if the Bluetooth stack cannot be initialised,
+    /// or rejects the power level. The server is not started then, and can be started again.
+    ///
     /// # Panics
     ///
     /// Panics if a profile's lock is poisoned.
-    pub fn start(&mut self) {
+    pub fn start(&mut self) -> Result<(), BluedroidError> {
         if self.started {
-            warn!("GATT server already started.");
-            return;
+            return Err(GattServerError::AlreadyStarted.into());
         }
 
-        self.started = true;
-        Self::initialise_ble_stack();
+        // Allocate the response buffer now, rather than in the first Bluetooth callback.
+        lazy_static::initialize(&response::RESPONSE_BUFFER);
+        Self::initialise_ble_stack()?;
         unsafe {
-            esp_nofail!(esp_ble_tx_power_set(
+            esp_result!(esp_ble_tx_power_set(
                 esp_ble_power_type_t_ESP_BLE_PWR_TYPE_DEFAULT,
                 self.power_level
-            ));
+            ))?;
+        }
+
+        self.started = true;
+        self.configure_resolving_list();
+        self.configure_whitelist();
+        self.configure_local_mtu();
+        Self::log_gatt_caching();
+        #[cfg(esp_idf_bt_ble_50_features_supported)]
+        self.configure_preferred_phy();
+        #[cfg(feature = "security")]
+        self.configure_security();
+        #[cfg(feature = "debug-keys")]
+        if self.debug_keys {
+            Self::configure_debug_keys();
+        }
+        if self.advertise_primary_services {
+            self.place_primary_service_uuids();
         }
         // Registration of profiles, services, characteristics and descriptors.
         self.profiles.iter().for_each(|profile| {
             profile.write().register_self();
         });
+        Self::watch_registration();
+
+        Ok(())
     }
 
     /// Sets the default power level to be used for bluetooth
@@ -144,30 +398,72 @@ impl GattServer {
     /// Sets the name to be advertised in GAP packets.
     ///
     /// The name must be set before starting the GATT server.
+    /// An invalid name is reported as a [`GattServerError::InvalidDeviceName`], and ignored:
+    /// see [`GattServer::try_device_name`].
     pub fn device_name<S: Into<String>>(&mut self, name: S) -> &mut Self {
-        if self.advertisement_configured {
-            warn!(
-                "Device name already set. Please set the device name before starting the server."
-            );
-            return self;
+        // Only the server rejects a device name.
+        if let Err(BluedroidError::Server(error)) = self.try_device_name(name.into()) {
+            error.report();
         }
 
-        self.device_name = name.into();
-        self.device_name.push('\0');
-
         self
     }
 
-    /// Sets the device appearance value to be advertised in GAP packets.
-    pub fn appearance(&mut self, appearance: Appearance) -> &mut Self {
+    /// Sets the name to be advertised in GAP packets, and exposed by the Device Name
+    /// characteristic of the GAP service.
+    ///
+    /// When the complete name does not fit in the advertisement or the scan response
+    /// along with their other fields, the packet carries the shortened name instead,
+    /// cut to the space left on a character boundary.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GattServerError::InvalidDeviceName`] if the name is empty, is not valid UTF-8,
+    /// contains a nul character, or is longer than [`MAX_DEVICE_NAME_LENGTH`] bytes,
+    /// or if the server is already started.
+    pub fn try_device_name(&mut self, name: impl AsRef<[u8]>) -> Result<&mut Self, BluedroidError> {
         if self.advertisement_configured {
-            warn!("Appearance already set. Please set the appearance before starting the server.");
-            return self;
+            return Err(GattServerError::InvalidDeviceName(
+                "the device name must be set before starting the server",
+            )
+            .into());
+        }
+
+        let Ok(name) = std::str::from_utf8(name.as_ref()) else {
+            return Err(GattServerError::InvalidDeviceName("not valid UTF-8").into());
+        };
+
+        if name.is_empty() {
+            return Err(GattServerError::InvalidDeviceName("empty").into());
         }
 
+        if name.contains('\0') {
+            return Err(GattServerError::InvalidDeviceName("contains a nul character").into());
+        }
+
+        if name.len() > MAX_DEVICE_NAME_LENGTH {
+            return Err(GattServerError::InvalidDeviceName("too long").into());
+        }
+
+        self.device_name = format!("{name}\0");
+
+        Ok(self)
+    }
+
+    /// Sets the device appearance value to be advertised in GAP packets,
+    /// and exposed by the Appearance characteristic of the GAP service.
+    ///
+    /// If the advertisement is already configured, it is updated with the new value.
+    pub fn appearance(&mut self, appearance: Appearance) -> &mut Self {
         self.advertisement_data.appearance = appearance.into();
         self.scan_response_data.appearance = appearance.into();
 
+        if self.advertisement_configured {
+            self.configure_appearance();
+            self.configure_advertisement_data();
+            self.configure_scan_response_data();
+        }
+
         self
     }
 
@@ -190,195 +486,165 @@ impl GattServer {
     ///
     /// Panics if the service lock is poisoned.
     pub fn advertise_service(&mut self, service: &LockedService) -> &mut Self {
-        let uuid = service.read().uuid.as_uuid128_array();
-        self.scan_response_data.p_service_uuid = leaky_box_raw!(uuid).cast::<u8>();
-        self.scan_response_data.service_uuid_len = uuid.len() as u16;
+        // The identifier is kept by the server until the Bluetooth stack copies the scan response.
+        self.service_uuids = service.read().uuid.as_uuid128_array().to_vec();
+        self.scan_response_data.p_service_uuid = self.service_uuids.as_mut_ptr();
+        self.scan_response_data.service_uuid_len = self.service_uuids.len() as u16;
 
         self
     }
 
+    /// Advertises the identifiers of all the primary services, collected when the server starts.
+    ///
+    /// The identifiers are placed in the advertisement, or in the scan response if they do not fit.
+    /// They replace the service advertised with [`GattServer::advertise_service`].
+    pub fn advertise_primary_services(&mut self, enabled: bool) -> &mut Self {
+        self.advertise_primary_services = enabled;
+        self
+    }
+
     /// Add a [`Profile`] to the GATT server.
+    ///
+    /// A profile whose identifier is already used by another profile is rejected,
+    /// and a [`GattServerError::DuplicateProfile`] is reported.
     pub fn profile(&mut self, profile: LockedProfile) -> &mut Self {
         if self.started {
-            warn!("Cannot add profile after server has started.");
+            warn!(target: GATTS, "Cannot add profile after server has started.");
+            return self;
+        }
+
+        let new_profile = profile.read();
+        if self
+            .profiles
+            .iter()
+            .any(|existing| existing.read().identifier == new_profile.identifier)
+        {
+            GattServerError::DuplicateProfile(new_profile.identifier).report();
             return self;
         }
 
-        self.profiles.push(profile);
+        for service in &new_profile.services {
+            let uuid = service.read().uuid;
+            for existing in &self.profiles {
+                if existing
+                    .read()
+                    .services
+                    .iter()
+                    .any(|existing_service| existing_service.read().uuid == uuid)
+                {
+                    warn!(
+                        target: GATTS,
+                        "Service {} is declared in both {} and {}.",
+                        uuid,
+                        existing.read(),
+                        new_profile
+                    );
+                }
+            }
+        }
+        drop(new_profile);
+
+        capacity::push(&mut self.profiles, profile, "profiles");
         self
     }
 
-    pub(crate) fn get_profile(&self, interface: u8) -> Option<LockedProfile> {
+    /// Returns an iterator over the profiles of the GATT server, in declaration order.
+    #[must_use]
+    pub fn profiles(&self) -> impl ExactSizeIterator<Item = &LockedProfile> {
+        self.profiles.iter()
+    }
+
+    /// Returns a snapshot of the active connections of the GATT server.
+    ///
+    /// Take it before locking a characteristic, for example to pass it to
+    /// [`Characteristic::indicate_all`]: the GATT server is always locked first.
+    #[must_use]
+    pub fn connections(&self) -> Arc<HashSet<Connection>> {
+        self.active_connections.clone()
+    }
+
+    /// Returns an iterator over the services of all the profiles of the GATT server,
+    /// in declaration order.
+    ///
+    /// The services are collected when this function is called, so the profiles are not kept locked.
+    pub fn services(&self) -> impl Iterator<Item = LockedService> {
         self.profiles
             .iter()
-            .find(|profile| profile.write().interface == Some(interface))
+            .flat_map(|profile| profile.read().services.clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Returns the [`Profile`] with the given identifier, if it was added to the GATT server.
+    ///
+    /// Profiles are added with [`GattServer::profile`].
+    #[must_use]
+    pub fn find_profile(&self, identifier: u16) -> Option<LockedProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.read().identifier == identifier)
             .cloned()
     }
 
-    #[allow(clippy::too_many_lines)]
-    fn initialise_ble_stack() {
-        info!("Initialising BLE stack.");
+    /// Returns the [`Characteristic`] with the given identifier, in the service with the given identifier.
+    ///
+    /// If several characteristics match, the first one in declaration order is returned.
+    #[must_use]
+    pub fn find_characteristic(
+        &self,
+        service_uuid: BleUuid,
+        characteristic_uuid: BleUuid,
+    ) -> Option<LockedCharacteristic> {
+        self.profiles.iter().find_map(|profile| {
+            profile
+                .read()
+                .services
+                .iter()
+                .filter(|service| service.read().uuid == service_uuid)
+                .find_map(|service| {
+                    service
+                        .read()
+                        .characteristics
+                        .iter()
+                        .find(|characteristic| characteristic.read().uuid == characteristic_uuid)
+                        .cloned()
+                })
+        })
+    }
 
-        // NVS initialisation.
-        unsafe {
-            let result = nvs_flash_init();
-            if result == ESP_ERR_NVS_NO_FREE_PAGES || result == ESP_ERR_NVS_NEW_VERSION_FOUND {
-                warn!("NVS initialisation failed. Erasing NVS.");
-                esp_nofail!(nvs_flash_erase());
-                esp_nofail!(nvs_flash_init());
-            }
-        }
+    /// Returns the [`Descriptor`] with the given identifier, in the characteristic and the service
+    /// with the given identifiers.
+    ///
+    /// If several descriptors match, the first one in declaration order is returned.
+    #[must_use]
+    pub fn find_descriptor(
+        &self,
+        service_uuid: BleUuid,
+        characteristic_uuid: BleUuid,
+        descriptor_uuid: BleUuid,
+    ) -> Option<LockedDescriptor> {
+        self.find_characteristic(service_uuid, characteristic_uuid)?
+            .read()
+            .descriptors
+            .iter()
+            .find(|descriptor| descriptor.read().uuid == descriptor_uuid)
+            .cloned()
+    }
 
-        #[cfg(esp32)]
-        let default_controller_configuration = esp_bt_controller_config_t {
-            controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as _,
-            controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as _,
-            hci_uart_no: BT_HCI_UART_NO_DEFAULT as _,
-            hci_uart_baudrate: BT_HCI_UART_BAUDRATE_DEFAULT,
-            scan_duplicate_mode: SCAN_DUPLICATE_MODE as _,
-            scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as _,
-            normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as _,
-            mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as _,
-            send_adv_reserved_size: SCAN_SEND_ADV_RESERVED_SIZE as _,
-            controller_debug_flag: CONTROLLER_ADV_LOST_DEBUG_BIT,
-            mode: esp_bt_mode_t_ESP_BT_MODE_BLE as _,
-            ble_max_conn: CONFIG_BTDM_CTRL_BLE_MAX_CONN_EFF as _,
-            bt_max_acl_conn: CONFIG_BTDM_CTRL_BR_EDR_MAX_ACL_CONN_EFF as _,
-            bt_sco_datapath: CONFIG_BTDM_CTRL_BR_EDR_SCO_DATA_PATH_EFF as _,
-            auto_latency: BTDM_CTRL_AUTO_LATENCY_EFF != 0,
-            bt_legacy_auth_vs_evt: BTDM_CTRL_LEGACY_AUTH_VENDOR_EVT_EFF != 0,
-            bt_max_sync_conn: CONFIG_BTDM_CTRL_BR_EDR_MAX_SYNC_CONN_EFF as _,
-            ble_sca: CONFIG_BTDM_BLE_SLEEP_CLOCK_ACCURACY_INDEX_EFF as _,
-            pcm_role: CONFIG_BTDM_CTRL_PCM_ROLE_EFF as _,
-            pcm_polar: CONFIG_BTDM_CTRL_PCM_POLAR_EFF as _,
-            hli: BTDM_CTRL_HLI != 0,
-            magic: ESP_BT_CONTROLLER_CONFIG_MAGIC_VAL,
-            #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
-            dup_list_refresh_period: SCAN_DUPL_CACHE_REFRESH_PERIOD as u16,
-        };
+    pub(crate) fn get_profile(&self, interface: u8) -> Option<LockedProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.write().interface == Some(interface))
+            .cloned()
+    }
 
-        #[cfg(esp32c3)]
-        let default_controller_configuration = esp_bt_controller_config_t {
-            magic: ESP_BT_CTRL_CONFIG_MAGIC_VAL,
-            version: ESP_BT_CTRL_CONFIG_VERSION,
-            controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as u16,
-            controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as u8,
-            controller_task_run_cpu: CONFIG_BT_CTRL_PINNED_TO_CORE as u8,
-            bluetooth_mode: CONFIG_BT_CTRL_MODE_EFF as u8,
-            ble_max_act: CONFIG_BT_CTRL_BLE_MAX_ACT_EFF as u8,
-            sleep_mode: CONFIG_BT_CTRL_SLEEP_MODE_EFF as u8,
-            sleep_clock: CONFIG_BT_CTRL_SLEEP_CLOCK_EFF as u8,
-            ble_st_acl_tx_buf_nb: CONFIG_BT_CTRL_BLE_STATIC_ACL_TX_BUF_NB as u8,
-            ble_hw_cca_check: CONFIG_BT_CTRL_HW_CCA_EFF as u8,
-            ble_adv_dup_filt_max: CONFIG_BT_CTRL_ADV_DUP_FILT_MAX as u16,
-            coex_param_en: false,
-            ce_len_type: CONFIG_BT_CTRL_CE_LENGTH_TYPE_EFF as u8,
-            coex_use_hooks: false,
-            hci_tl_type: CONFIG_BT_CTRL_HCI_TL_EFF as u8,
-            hci_tl_funcs: std::ptr::null_mut(),
-            txant_dft: CONFIG_BT_CTRL_TX_ANTENNA_INDEX_EFF as u8,
-            rxant_dft: CONFIG_BT_CTRL_RX_ANTENNA_INDEX_EFF as u8,
-            txpwr_dft: CONFIG_BT_CTRL_DFT_TX_POWER_LEVEL_EFF as u8,
-            #[cfg(any(esp_idf_version = "5.1"))]
-            cfg_mask: CFG_MASK,
-            #[cfg(any(
-                esp_idf_version_full = "4.4.3",
-                esp_idf_version_full = "4.4.4",
-                esp_idf_version = "5.0"
-            ))]
-            cfg_mask: CFG_NASK,
-            scan_duplicate_mode: SCAN_DUPLICATE_MODE as u8,
-            scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as u8,
-            normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as u16,
-            mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as u16,
-            coex_phy_coded_tx_rx_time_limit: CONFIG_BT_CTRL_COEX_PHY_CODED_TX_RX_TLIM_EFF as u8,
-            #[cfg(any(
-                esp_idf_version_full = "4.4.3",
-                esp_idf_version_full = "4.4.4",
-                esp_idf_version = "5.0"
-            ))]
-            hw_target_code: BLE_HW_TARGET_CODE_ESP32C3_CHIP_ECO0,
-            #[cfg(any(esp_idf_version = "5.1"))]
-            hw_target_code: BLE_HW_TARGET_CODE_CHIP_ECO0,
-            slave_ce_len_min: SLAVE_CE_LEN_MIN_DEFAULT as u8,
-            hw_recorrect_en: AGC_RECORRECT_EN as u8,
-            cca_thresh: CONFIG_BT_CTRL_HW_CCA_VAL as u8,
-            #[cfg(any(
-                esp_idf_version_full = "4.4.4",
-                esp_idf_version = "5.0",
-                esp_idf_version = "5.1"
-            ))]
-            scan_backoff_upperlimitmax: BT_CTRL_SCAN_BACKOFF_UPPERLIMITMAX as u16,
-            #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
-            dup_list_refresh_period: DUPL_SCAN_CACHE_REFRESH_PERIOD as u16,
-            #[cfg(any(esp_idf_version = "5.1"))]
-            ble_50_feat_supp: BT_CTRL_50_FEATURE_SUPPORT != 0,
-        };
+    fn initialise_ble_stack() -> Result<(), BluedroidError> {
+        ble_stack::initialise()?;
 
-        #[cfg(esp32s3)]
-        let default_controller_configuration = esp_bt_controller_config_t {
-            magic: ESP_BT_CTRL_CONFIG_MAGIC_VAL,
-            version: ESP_BT_CTRL_CONFIG_VERSION,
-            controller_task_stack_size: ESP_TASK_BT_CONTROLLER_STACK as u16,
-            controller_task_prio: ESP_TASK_BT_CONTROLLER_PRIO as u8,
-            controller_task_run_cpu: CONFIG_BT_CTRL_PINNED_TO_CORE as u8,
-            bluetooth_mode: CONFIG_BT_CTRL_MODE_EFF as u8,
-            ble_max_act: CONFIG_BT_CTRL_BLE_MAX_ACT_EFF as u8,
-            sleep_mode: CONFIG_BT_CTRL_SLEEP_MODE_EFF as u8,
-            sleep_clock: CONFIG_BT_CTRL_SLEEP_CLOCK_EFF as u8,
-            ble_st_acl_tx_buf_nb: CONFIG_BT_CTRL_BLE_STATIC_ACL_TX_BUF_NB as u8,
-            ble_hw_cca_check: CONFIG_BT_CTRL_HW_CCA_EFF as u8,
-            ble_adv_dup_filt_max: CONFIG_BT_CTRL_ADV_DUP_FILT_MAX as u16,
-            coex_param_en: false,
-            ce_len_type: CONFIG_BT_CTRL_CE_LENGTH_TYPE_EFF as u8,
-            coex_use_hooks: false,
-            hci_tl_type: CONFIG_BT_CTRL_HCI_TL_EFF as u8,
-            hci_tl_funcs: std::ptr::null_mut(),
-            txant_dft: CONFIG_BT_CTRL_TX_ANTENNA_INDEX_EFF as u8,
-            rxant_dft: CONFIG_BT_CTRL_RX_ANTENNA_INDEX_EFF as u8,
-            txpwr_dft: CONFIG_BT_CTRL_DFT_TX_POWER_LEVEL_EFF as u8,
-            cfg_mask: CFG_MASK,
-            scan_duplicate_mode: SCAN_DUPLICATE_MODE as u8,
-            scan_duplicate_type: SCAN_DUPLICATE_TYPE_VALUE as u8,
-            normal_adv_size: NORMAL_SCAN_DUPLICATE_CACHE_SIZE as u16,
-            mesh_adv_size: MESH_DUPLICATE_SCAN_CACHE_SIZE as u16,
-            coex_phy_coded_tx_rx_time_limit: CONFIG_BT_CTRL_COEX_PHY_CODED_TX_RX_TLIM_EFF as u8,
-
-            #[cfg(any(esp_idf_version = "4.4"))]
-            hw_target_code: BLE_HW_TARGET_CODE_ESP32S3_CHIP_ECO0,
-            #[cfg(esp_idf_version = "5.0")]
-            hw_target_code: BLE_HW_TARGET_CODE_CHIP_ECO0,
-            slave_ce_len_min: SLAVE_CE_LEN_MIN_DEFAULT as u8,
-            hw_recorrect_en: AGC_RECORRECT_EN as u8,
-            cca_thresh: CONFIG_BT_CTRL_HW_CCA_VAL as u8,
-            #[cfg(any(
-                esp_idf_version_full = "4.4.4",
-                esp_idf_version = "5.0",
-                esp_idf_version = "5.1"
-            ))]
-            scan_backoff_upperlimitmax: BT_CTRL_SCAN_BACKOFF_UPPERLIMITMAX as u16,
-            #[cfg(any(esp_idf_version = "5.0", esp_idf_version = "5.1"))]
-            dup_list_refresh_period: DUPL_SCAN_CACHE_REFRESH_PERIOD as u16,
-            #[cfg(any(esp_idf_version = "5.0"))]
-            ble_50_feat_supp: EXT_CSD_SEC_FEATURE_SUPPORT != 0,
-        };
-        // BLE controller initialisation.
         unsafe {
-            esp_nofail!(esp_bt_controller_mem_release(
-                esp_bt_mode_t_ESP_BT_MODE_CLASSIC_BT
-            ));
-            esp_nofail!(esp_bt_controller_init(leaky_box_raw!(
-                default_controller_configuration
-            )));
-            esp_nofail!(esp_bt_controller_enable(esp_bt_mode_t_ESP_BT_MODE_BLE));
-            esp_nofail!(esp_bluedroid_init());
-            esp_nofail!(esp_bluedroid_enable());
-            esp_nofail!(esp_ble_gatts_register_callback(Some(
+            esp_result!(esp_ble_gatts_register_callback(Some(
                 Self::default_gatts_callback
-            )));
-            esp_nofail!(esp_ble_gap_register_callback(Some(
-                Self::default_gap_callback
-            )));
+            )))
         }
     }
 
@@ -394,14 +660,4 @@ impl GattServer {
             .lock()
             .gatts_event_handler(event, gatts_if, param);
     }
-
-    /// Calls the global server's GAP event callback.
-    ///
-    /// This is a bad workaround, and only works because we have a singleton server.
-    extern "C" fn default_gap_callback(
-        event: esp_gap_ble_cb_event_t,
-        param: *mut esp_ble_gap_cb_param_t,
-    ) {
-        GLOBAL_GATT_SERVER.lock().gap_event_handler(event, param);
-    }
 }
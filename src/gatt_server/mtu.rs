@@ -0,0 +1,63 @@
+use esp_idf_sys::{esp_ble_gatt_set_local_mtu, ESP_GATT_DEF_BLE_MTU_SIZE, ESP_GATT_MAX_MTU_SIZE};
+use log::{debug, warn};
+
+use crate::gatt_server::{error::esp_report, GattEvent, GattServer};
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::Connection;
+
+impl GattServer {
+    /// Sets the MTU the server proposes when a client negotiates it.
+    ///
+    /// The MTU must be between 23 and 517 bytes, the bounds of the Bluetooth specification.
+    /// Before the server is started, the MTU is set once the Bluetooth stack is initialised.
+    pub fn local_mtu(&mut self, mtu: u16) -> &mut Self {
+        if !(ESP_GATT_DEF_BLE_MTU_SIZE..=ESP_GATT_MAX_MTU_SIZE).contains(&u32::from(mtu)) {
+            warn!(
+                target: GATTS,
+                "Ignoring local MTU {}: it must be between {} and {} bytes.",
+                mtu,
+                ESP_GATT_DEF_BLE_MTU_SIZE,
+                ESP_GATT_MAX_MTU_SIZE
+            );
+            return self;
+        }
+
+        self.local_mtu = Some(mtu);
+
+        if self.started {
+            self.configure_local_mtu();
+        }
+
+        self
+    }
+
+    /// Sets a callback receiving the MTU negotiated with each client.
+    ///
+    /// This is a shorthand for handling [`GattEvent::MtuChanged`] with [`GattServer::on_event`].
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the Bluetooth stack's context, so it must not block.
+    pub fn on_mtu_changed(
+        &mut self,
+        callback: impl Fn(Connection, u16) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_event(move |event| {
+            if let GattEvent::MtuChanged { connection, mtu } = event {
+                callback(*connection, *mtu);
+            }
+        })
+    }
+
+    /// Hands the local MTU to the Bluetooth stack, if set.
+    pub(crate) fn configure_local_mtu(&self) {
+        let Some(mtu) = self.local_mtu else {
+            return;
+        };
+
+        debug!(target: GATTS, "Setting the local MTU to {}.", mtu);
+        unsafe {
+            esp_report!(esp_ble_gatt_set_local_mtu(mtu));
+        }
+    }
+}
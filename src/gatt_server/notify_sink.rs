@@ -0,0 +1,175 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    gatt_server::LockedCharacteristic,
+    utilities::{Connection, DeliveryOutcome},
+};
+
+/// The default number of values that can be waiting for delivery before sends wait.
+const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+#[derive(Default)]
+struct SinkState {
+    in_flight: usize,
+    waiters: Vec<Waker>,
+}
+
+#[derive(Default)]
+struct DeliveryState {
+    outcome: Option<DeliveryOutcome>,
+    waker: Option<Waker>,
+}
+
+/// An asynchronous writer of notifications or indications of a [`Characteristic`] to a [`Connection`].
+///
+/// Every value is sent through the reliable delivery queue, which respects the congestion
+/// of the connection, and [`NotifySink::send`] only resolves once the value has been
+/// handed to the Bluetooth stack (or, for indications, confirmed by the client).
+/// At most a fixed number of values can be in flight at the same time,
+/// so that streaming producers are slowed down to the pace of the connection.
+///
+/// The futures do not depend on any specific executor.
+///
+/// [`Characteristic`]: crate::gatt_server::Characteristic
+#[derive(Clone)]
+pub struct NotifySink {
+    characteristic: LockedCharacteristic,
+    connection: Connection,
+    max_in_flight: usize,
+    state: Arc<Mutex<SinkState>>,
+}
+
+impl NotifySink {
+    /// Creates a new [`NotifySink`] for the given characteristic and connection.
+    #[must_use]
+    pub fn new(characteristic: &LockedCharacteristic, connection: Connection) -> Self {
+        Self {
+            characteristic: characteristic.clone(),
+            connection,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            state: Arc::new(Mutex::new(SinkState::default())),
+        }
+    }
+
+    /// Sets how many values can be waiting for delivery before sends wait. The default value is 4.
+    pub fn max_in_flight(&mut self, max_in_flight: usize) -> &mut Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Returns the [`Connection`] this sink sends values to.
+    #[must_use]
+    pub const fn connection(&self) -> Connection {
+        self.connection
+    }
+
+    /// Sends a value, resolving to the [`DeliveryOutcome`] once it has been delivered or given up on.
+    pub fn send<T: Into<Vec<u8>>>(
+        &self,
+        value: T,
+    ) -> impl Future<Output = DeliveryOutcome> + Send + '_ {
+        SendFuture {
+            sink: self,
+            value: Some(value.into()),
+            delivery: None,
+        }
+    }
+
+    fn complete(
+        state: &Mutex<SinkState>,
+        delivery: &Mutex<DeliveryState>,
+        outcome: DeliveryOutcome,
+    ) {
+        let waiters = {
+            let mut state = state.lock();
+            state.in_flight -= 1;
+            std::mem::take(&mut state.waiters)
+        };
+
+        let waker = {
+            let mut delivery = delivery.lock();
+            delivery.outcome = Some(outcome);
+            delivery.waker.take()
+        };
+
+        waiters.into_iter().chain(waker).for_each(Waker::wake);
+    }
+}
+
+impl std::fmt::Debug for NotifySink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotifySink")
+            .field("characteristic", &self.characteristic.read().to_string())
+            .field("connection", &self.connection)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("in_flight", &self.state.lock().in_flight)
+            .finish()
+    }
+}
+
+struct SendFuture<'a> {
+    sink: &'a NotifySink,
+    value: Option<Vec<u8>>,
+    delivery: Option<Arc<Mutex<DeliveryState>>>,
+}
+
+impl Future for SendFuture<'_> {
+    type Output = DeliveryOutcome;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(value) = this.value.take() {
+            {
+                let mut state = this.sink.state.lock();
+
+                // Wait for a previous value to be delivered.
+                if state.in_flight >= this.sink.max_in_flight {
+                    state.waiters.push(cx.waker().clone());
+                    this.value = Some(value);
+                    return Poll::Pending;
+                }
+
+                state.in_flight += 1;
+            }
+
+            let delivery = Arc::new(Mutex::new(DeliveryState::default()));
+            let sink_state = this.sink.state.clone();
+            let delivery_state = delivery.clone();
+
+            let queued = this.sink.characteristic.read().queue_value(
+                this.sink.connection,
+                value,
+                Some(Arc::new(move |_, outcome| {
+                    NotifySink::complete(&sink_state, &delivery_state, outcome);
+                })),
+            );
+
+            if !queued {
+                this.sink.state.lock().in_flight -= 1;
+                return Poll::Ready(DeliveryOutcome::Failed);
+            }
+
+            this.delivery = Some(delivery);
+        }
+
+        let Some(delivery) = &this.delivery else {
+            return Poll::Ready(DeliveryOutcome::Failed);
+        };
+
+        let mut delivery = delivery.lock();
+        if let Some(outcome) = delivery.outcome {
+            Poll::Ready(outcome)
+        } else {
+            delivery.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
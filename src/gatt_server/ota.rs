@@ -0,0 +1,378 @@
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc,
+};
+
+use esp_idf_sys::{
+    esp_err_t, esp_ota_abort, esp_ota_begin, esp_ota_end, esp_ota_get_next_update_partition,
+    esp_ota_get_running_partition, esp_ota_get_state_partition, esp_ota_handle_t,
+    esp_ota_img_states_t, esp_ota_img_states_t_ESP_OTA_IMG_PENDING_VERIFY,
+    esp_ota_mark_app_valid_cancel_rollback, esp_ota_set_boot_partition, esp_ota_write,
+    esp_partition_t, ESP_ERR_INVALID_STATE, ESP_ERR_NOT_FOUND, ESP_OK, OTA_SIZE_UNKNOWN,
+};
+use log::{info, warn};
+use parking_lot::{Mutex, RwLock};
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{
+        worker, Characteristic, GattServerError, LockedCharacteristic, LockedService, Service,
+    },
+    utilities::{AttributePermissions, BleUuid, CharacteristicProperties},
+};
+
+/// The identifier of the OTA service.
+pub const OTA_SERVICE_UUID: BleUuid =
+    BleUuid::from_uuid128_str("c2a10000-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the control point characteristic.
+const CONTROL_UUID: BleUuid = BleUuid::from_uuid128_str("c2a10001-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the firmware data characteristic.
+const DATA_UUID: BleUuid = BleUuid::from_uuid128_str("c2a10002-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the status characteristic.
+const STATUS_UUID: BleUuid = BleUuid::from_uuid128_str("c2a10003-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The control point command starting an update, optionally followed by the image size.
+const COMMAND_BEGIN: u8 = 0x01;
+
+/// The control point command finishing an update and selecting the new firmware for the next boot.
+const COMMAND_END: u8 = 0x02;
+
+/// The control point command cancelling an update.
+const COMMAND_ABORT: u8 = 0x03;
+
+/// The maximum length of the written values: the longest attribute value the specification allows.
+const MAX_WRITE_LENGTH: u16 = 512;
+
+type CompletionCallback = dyn Fn() + Send + Sync;
+
+/// A firmware update service, writing the received image to the next OTA partition with `esp_ota_ops`.
+///
+/// The service exposes three characteristics:
+///
+/// - the control point, accepting the `0x01` (begin, optionally followed by the image size
+///   as a little-endian `u32`), `0x02` (end) and `0x03` (abort) commands;
+/// - the data characteristic, receiving the firmware image as a sequence of writes;
+/// - the status characteristic, readable and notified on every change, made of the state
+///   (`0`: idle, `1`: receiving, `2`: complete, `3`: failed), the number of bytes written as a
+///   little-endian `u32`, and the last error code as a little-endian `i32`.
+///
+/// The control point and the data characteristic can only be written over encrypted links,
+/// so clients must pair before updating the firmware.
+///
+/// The flash operations run in a dedicated thread, so they do not block the Bluetooth stack.
+/// The thread runs with the priority and on the core of the other threads of the crate,
+/// set with [`GattServer::worker_priority`] and [`GattServer::worker_core`].
+/// When the update is complete, the new firmware is selected for the next boot,
+/// and the [`OtaService::on_complete`] callback is called: it usually restarts the device.
+///
+/// # Notes
+///
+/// With rollback enabled in the bootloader, the new firmware must call
+/// [`OtaService::mark_running_firmware_valid`] once it is working, or it is rolled back on the next reboot.
+///
+/// [`GattServer::worker_priority`]: crate::gatt_server::GattServer::worker_priority
+/// [`GattServer::worker_core`]: crate::gatt_server::GattServer::worker_core
+#[derive(Clone)]
+pub struct OtaService {
+    service: LockedService,
+    completion_callback: Arc<RwLock<Option<Arc<CompletionCallback>>>>,
+}
+
+enum OtaCommand {
+    Begin(Option<u32>),
+    Data(Vec<u8>),
+    End,
+    Abort,
+}
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum OtaState {
+    Idle = 0,
+    Receiving = 1,
+    Complete = 2,
+    Failed = 3,
+}
+
+/// An update in progress.
+struct Update {
+    handle: esp_ota_handle_t,
+    partition: *const esp_partition_t,
+    written: u32,
+}
+
+impl OtaService {
+    /// Creates a new [`OtaService`].
+    #[must_use]
+    pub fn new() -> Self {
+        let status = Characteristic::new(STATUS_UUID)
+            .name("OTA Status")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .set_value(encode_status(OtaState::Idle, 0, ESP_OK))
+            .build();
+
+        let (sender, receiver) = channel();
+        let sender = Arc::new(Mutex::new(sender));
+
+        let control_sender = sender.clone();
+        let control = Characteristic::new(CONTROL_UUID)
+            .name("OTA Control Point")
+            .permissions(AttributePermissions::new().write_encrypted())
+            .properties(CharacteristicProperties::new().write())
+            .max_value_length(MAX_WRITE_LENGTH)
+            .on_write(move |value, _| {
+                let command = match value.first() {
+                    Some(&COMMAND_BEGIN) => OtaCommand::Begin(
+                        value
+                            .get(1..5)
+                            .and_then(|size| size.try_into().ok())
+                            .map(u32::from_le_bytes),
+                    ),
+                    Some(&COMMAND_END) => OtaCommand::End,
+                    Some(&COMMAND_ABORT) => OtaCommand::Abort,
+                    _ => {
+                        warn!(target: GATTS, "Unknown OTA command {:02X?}.", value);
+                        return;
+                    }
+                };
+
+                send_command(&control_sender, command);
+            })
+            .build();
+
+        let data = Characteristic::new(DATA_UUID)
+            .name("OTA Data")
+            .permissions(AttributePermissions::new().write_encrypted())
+            .properties(
+                CharacteristicProperties::new()
+                    .write()
+                    .write_without_response(),
+            )
+            .max_value_length(MAX_WRITE_LENGTH)
+            .on_write(move |value, _| send_command(&sender, OtaCommand::Data(value)))
+            .build();
+
+        let completion_callback: Arc<RwLock<Option<Arc<CompletionCallback>>>> =
+            Arc::new(RwLock::new(None));
+
+        let worker_status = status.clone();
+        let worker_callback = completion_callback.clone();
+        // Without the thread, the commands are dropped and reported by `send_command`.
+        if let Err(error) = worker::spawn(None, move || {
+            run_updates(&receiver, &worker_status, &worker_callback);
+        }) {
+            warn!(target: GATTS, "Cannot spawn the OTA thread: {}.", error);
+        }
+
+        Self {
+            service: Service::new(OTA_SERVICE_UUID)
+                .name("OTA")
+                .primary()
+                .characteristic(&control)
+                .characteristic(&data)
+                .characteristic(&status)
+                .build(),
+            completion_callback,
+        }
+    }
+
+    /// Sets the callback called once the new firmware is written and selected for the next boot.
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the OTA thread.
+    pub fn on_complete(&mut self, callback: impl Fn() + Send + Sync + 'static) -> &mut Self {
+        *self.completion_callback.write() = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns the service, to be added to a [`Profile`](crate::gatt_server::Profile).
+    #[must_use]
+    pub fn service(&self) -> LockedService {
+        self.service.clone()
+    }
+
+    /// Marks the running firmware as valid, cancelling the rollback to the previous firmware.
+    ///
+    /// Does nothing if the running firmware is not waiting for verification.
+    /// Returns `false` if the firmware could not be marked.
+    #[must_use]
+    pub fn mark_running_firmware_valid() -> bool {
+        let mut state: esp_ota_img_states_t = 0;
+        let code =
+            unsafe { esp_ota_get_state_partition(esp_ota_get_running_partition(), &mut state) };
+
+        if code == ESP_ERR_NOT_FOUND {
+            // The running partition is not an OTA partition: there is nothing to roll back.
+            return true;
+        }
+        if !GattServerError::check("esp_ota_get_state_partition", code) {
+            return false;
+        }
+        if state != esp_ota_img_states_t_ESP_OTA_IMG_PENDING_VERIFY {
+            return true;
+        }
+
+        info!(target: GATTS, "Marking the running firmware as valid.");
+        GattServerError::check("esp_ota_mark_app_valid_cancel_rollback", unsafe {
+            esp_ota_mark_app_valid_cancel_rollback()
+        })
+    }
+}
+
+impl Default for OtaService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for OtaService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OtaService")
+            .field("service", &self.service.read().to_string())
+            .field("on_complete", &self.completion_callback.read().is_some())
+            .finish()
+    }
+}
+
+fn send_command(sender: &Mutex<Sender<OtaCommand>>, command: OtaCommand) {
+    if sender.lock().send(command).is_err() {
+        warn!(target: GATTS, "The OTA thread is not running.");
+    }
+}
+
+/// Runs the updates requested on the control point, until the service is dropped.
+fn run_updates(
+    receiver: &Receiver<OtaCommand>,
+    status: &LockedCharacteristic,
+    completion_callback: &RwLock<Option<Arc<CompletionCallback>>>,
+) {
+    let mut update: Option<Update> = None;
+
+    while let Ok(command) = receiver.recv() {
+        let result = match command {
+            OtaCommand::Begin(size) => {
+                if let Some(previous) = update.take() {
+                    warn!(target: GATTS, "Restarting an OTA update in progress.");
+                    unsafe { esp_ota_abort(previous.handle) };
+                }
+
+                begin(size).map(|started| {
+                    update = Some(started);
+                    (OtaState::Receiving, 0)
+                })
+            }
+            OtaCommand::Data(chunk) => match update.as_mut() {
+                Some(current) => {
+                    write(current, &chunk).map(|()| (OtaState::Receiving, current.written))
+                }
+                None => Err(ESP_ERR_INVALID_STATE),
+            },
+            OtaCommand::End => match update.take() {
+                Some(finished) => end(&finished).map(|()| (OtaState::Complete, finished.written)),
+                None => Err(ESP_ERR_INVALID_STATE),
+            },
+            OtaCommand::Abort => {
+                if let Some(aborted) = update.take() {
+                    info!(target: GATTS, "OTA update aborted.");
+                    unsafe { esp_ota_abort(aborted.handle) };
+                }
+                Ok((OtaState::Idle, 0))
+            }
+        };
+
+        match result {
+            Ok((state, written)) => {
+                status
+                    .write()
+                    .set_value(encode_status(state, written, ESP_OK));
+
+                if let OtaState::Complete = state {
+                    let callback = completion_callback.read().clone();
+                    if let Some(callback) = callback {
+                        callback();
+                    }
+                }
+            }
+            Err(code) => {
+                let written = update.as_ref().map_or(0, |current| current.written);
+                if let Some(failed) = update.take() {
+                    unsafe { esp_ota_abort(failed.handle) };
+                }
+
+                status
+                    .write()
+                    .set_value(encode_status(OtaState::Failed, written, code));
+            }
+        }
+    }
+}
+
+fn begin(size: Option<u32>) -> Result<Update, esp_err_t> {
+    let partition = unsafe { esp_ota_get_next_update_partition(std::ptr::null()) };
+    if partition.is_null() {
+        GattServerError::Stack {
+            operation: "esp_ota_get_next_update_partition",
+            code: ESP_ERR_NOT_FOUND,
+        }
+        .report();
+        return Err(ESP_ERR_NOT_FOUND);
+    }
+
+    let mut handle: esp_ota_handle_t = 0;
+    let code = unsafe {
+        esp_ota_begin(
+            partition,
+            size.unwrap_or(OTA_SIZE_UNKNOWN) as usize,
+            &mut handle,
+        )
+    };
+    if !GattServerError::check("esp_ota_begin", code) {
+        return Err(code);
+    }
+
+    info!(target: GATTS, "OTA update started.");
+    Ok(Update {
+        handle,
+        partition,
+        written: 0,
+    })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write(update: &mut Update, chunk: &[u8]) -> Result<(), esp_err_t> {
+    let code = unsafe { esp_ota_write(update.handle, chunk.as_ptr().cast(), chunk.len()) };
+    if !GattServerError::check("esp_ota_write", code) {
+        return Err(code);
+    }
+
+    update.written = update.written.saturating_add(chunk.len() as u32);
+    Ok(())
+}
+
+fn end(update: &Update) -> Result<(), esp_err_t> {
+    // The handle is freed by `esp_ota_end`, even if it fails.
+    let code = unsafe { esp_ota_end(update.handle) };
+    if !GattServerError::check("esp_ota_end", code) {
+        return Err(code);
+    }
+
+    let code = unsafe { esp_ota_set_boot_partition(update.partition) };
+    if !GattServerError::check("esp_ota_set_boot_partition", code) {
+        return Err(code);
+    }
+
+    info!(target: GATTS, "OTA update complete: {} bytes written.", update.written);
+    Ok(())
+}
+
+fn encode_status(state: OtaState, written: u32, code: esp_err_t) -> Vec<u8> {
+    let mut status = vec![state as u8];
+    status.extend_from_slice(&written.to_le_bytes());
+    status.extend_from_slice(&code.to_le_bytes());
+    status
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use esp_idf_sys::{esp_ble_gap_security_rsp, esp_ble_sec_req_t};
+use log::info;
+
+use crate::gatt_server::{error::esp_report, GattServer};
+use crate::utilities::log_targets::GAP;
+
+pub(crate) type PairingCallback = dyn Fn([u8; 6]) -> bool + Send + Sync;
+
+impl GattServer {
+    /// Sets a callback deciding whether to accept the pairing requests of the peers.
+    ///
+    /// The callback receives the address of the peer and returns `true` to accept the pairing,
+    /// for example only while a "pairing mode" button was recently pressed.
+    /// Without a callback, every pairing request is accepted.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, while the GATT server
+    /// is locked: it must not block, nor lock the GATT server.
+    pub fn on_pairing_request(
+        &mut self,
+        callback: impl Fn([u8; 6]) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.pairing_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Answers the pairing request of a peer.
+    pub(crate) fn on_security_request(&self, param: esp_ble_sec_req_t) {
+        let mut address = param.bd_addr;
+        let accept = match &self.pairing_callback {
+            Some(callback) => callback(address),
+            None => true,
+        } && self.make_room_for_bond(address);
+
+        info!(
+            target: GAP,
+            "{} pairing request of {:02X?}.",
+            if accept { "Accepting" } else { "Rejecting" },
+            address
+        );
+
+        unsafe {
+            esp_report!(esp_ble_gap_security_rsp(address.as_mut_ptr(), accept));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    sync::atomic::{AtomicU16, Ordering},
+    time::Duration,
+};
+
+use esp_idf_sys::{
+    esp_ble_gap_cb_param_t, esp_ble_gap_config_periodic_adv_data_raw,
+    esp_ble_gap_periodic_adv_params_t, esp_ble_gap_periodic_adv_set_params,
+    esp_ble_gap_periodic_adv_start, esp_bt_status_t_ESP_BT_STATUS_SUCCESS, esp_gap_ble_cb_event_t,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_DATA_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_SET_PARAMS_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_START_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_STOP_COMPLETE_EVT,
+};
+use log::{debug, info, warn};
+
+use crate::gatt_server::{
+    error::esp_report,
+    extended_advertising::{MAX_DATA_LENGTH, SERVER_INSTANCE},
+    GattServer, GattServerError,
+};
+use crate::utilities::log_targets::GAP;
+
+/// The advertising sets whose periodic advertisement is running, as a bit mask of their instances.
+static RUNNING: AtomicU16 = AtomicU16::new(0);
+
+/// A periodic advertisement, added to the extended advertisement of the GATT server
+/// or to an advertising set with [`ExtendedAdvertising::periodic`].
+///
+/// A periodic advertisement sends its data at a fixed interval, to the scanners synchronised
+/// with it through the extended advertisement: sensors can stream their readings without
+/// any connection. The data is updated with [`GattServer::periodic_advertising_data`]
+/// and [`GattServer::advertising_set_periodic_data`].
+///
+/// [`ExtendedAdvertising`]: crate::gatt_server::ExtendedAdvertising
+/// [`ExtendedAdvertising::periodic`]: crate::gatt_server::ExtendedAdvertising::periodic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodicAdvertising {
+    interval_min: Duration,
+    interval_max: Duration,
+    pub(crate) data: Vec<u8>,
+}
+
+impl PeriodicAdvertising {
+    /// Creates a new [`PeriodicAdvertising`], without data.
+    ///
+    /// The bounds of the interval are clamped between 7.5 ms and 81.91 s.
+    #[must_use]
+    pub const fn new(interval_min: Duration, interval_max: Duration) -> Self {
+        Self {
+            interval_min,
+            interval_max,
+            data: Vec::new(),
+        }
+    }
+
+    /// Sets the raw data of the periodic advertisement, as AD structures.
+    #[must_use]
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn parameters(&self) -> esp_ble_gap_periodic_adv_params_t {
+        // The interval is counted in units of 1.25 ms.
+        let units = |interval: Duration| (interval.as_micros() / 1250).clamp(0x0006, 0xFFFF) as u16;
+
+        esp_ble_gap_periodic_adv_params_t {
+            interval_min: units(self.interval_min),
+            interval_max: units(self.interval_max),
+            properties: 0,
+        }
+    }
+}
+
+/// Returns whether the periodic advertisement of an advertising set is running.
+pub(crate) fn is_running(instance: u8) -> bool {
+    RUNNING.load(Ordering::Acquire) & (1 << instance) != 0
+}
+
+impl GattServer {
+    /// Replaces the data of the periodic advertisement, updating it if it is running.
+    ///
+    /// Does nothing unless a [`PeriodicAdvertising`] was added to the extended advertisement.
+    /// See [`GattServer::advertising_set_periodic_data`] for the other advertising sets.
+    pub fn periodic_advertising_data(&mut self, data: impl Into<Vec<u8>>) -> &mut Self {
+        self.advertising_set_periodic_data(SERVER_INSTANCE, data)
+    }
+
+    /// Hands the parameters of the periodic advertisement of an advertising set
+    /// to the Bluetooth stack.
+    ///
+    /// The data, the start of the periodic advertisement and the start of the extended one
+    /// follow, as the stack completes each step.
+    pub(crate) fn configure_periodic_advertising(&self, instance: u8) {
+        let Some(periodic) = self.periodic_advertising(instance) else {
+            return;
+        };
+
+        debug!(target: GAP, "Setting the periodic advertising parameters.");
+        let parameters = periodic.parameters();
+        unsafe {
+            esp_report!(esp_ble_gap_periodic_adv_set_params(instance, &parameters));
+        }
+    }
+
+    /// Hands the data of the periodic advertisement to the Bluetooth stack, unless it does not fit.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn configure_periodic_data(&self, instance: u8) {
+        let Some(periodic) = self.periodic_advertising(instance) else {
+            return;
+        };
+
+        if periodic.data.len() > MAX_DATA_LENGTH {
+            GattServerError::ExtendedAdvertisementTooLong {
+                length: periodic.data.len(),
+                capacity: MAX_DATA_LENGTH,
+            }
+            .report();
+            return;
+        }
+
+        unsafe {
+            esp_report!(esp_ble_gap_config_periodic_adv_data_raw(
+                instance,
+                periodic.data.len() as u16,
+                periodic.data.as_ptr()
+            ));
+        }
+    }
+
+    fn periodic_advertising(&self, instance: u8) -> Option<&PeriodicAdvertising> {
+        self.advertising_set_configuration(instance)?
+            .periodic
+            .as_ref()
+    }
+
+    /// Handles the periodic advertising events of the GAP,
+    /// and returns whether the event was one of them.
+    #[allow(non_upper_case_globals)]
+    pub(crate) fn periodic_advertising_event_handler(
+        &mut self,
+        event: esp_gap_ble_cb_event_t,
+        param: *mut esp_ble_gap_cb_param_t,
+    ) -> bool {
+        let (status, instance) = match event {
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_SET_PARAMS_COMPLETE_EVT => {
+                let param = unsafe { (*param).peroid_adv_set_params };
+                (param.status, param.instance)
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_DATA_SET_COMPLETE_EVT => {
+                let param = unsafe { (*param).period_adv_data_set };
+                (param.status, param.instance)
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_START_COMPLETE_EVT => {
+                let param = unsafe { (*param).period_adv_start };
+                (param.status, param.instance)
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_STOP_COMPLETE_EVT => {
+                let param = unsafe { (*param).period_adv_stop };
+                (param.status, param.instance)
+            }
+            _ => return false,
+        };
+
+        if status != esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+            warn!(
+                target: GAP,
+                "BLE GAP periodic advertising event {} failed with status 0x{:x}.", event, status
+            );
+            return true;
+        }
+
+        match event {
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_SET_PARAMS_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP periodic advertising parameters set.");
+                self.configure_periodic_data(instance);
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_DATA_SET_COMPLETE_EVT => {
+                debug!(target: GAP, "BLE GAP periodic advertisement data set.");
+                if !is_running(instance) {
+                    unsafe {
+                        esp_report!(esp_ble_gap_periodic_adv_start(instance));
+                    }
+                }
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PERIODIC_ADV_START_COMPLETE_EVT => {
+                info!(
+                    target: GAP,
+                    "BLE GAP periodic advertisement of set {} started.", instance
+                );
+                RUNNING.fetch_or(1 << instance, Ordering::AcqRel);
+                self.start_extended_advertising(instance);
+            }
+            _ => {
+                debug!(
+                    target: GAP,
+                    "BLE GAP periodic advertisement of set {} stopped.", instance
+                );
+                RUNNING.fetch_and(!(1 << instance), Ordering::AcqRel);
+            }
+        }
+
+        true
+    }
+}
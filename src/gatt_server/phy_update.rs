@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use esp_idf_sys::{
+    esp_ble_gap_cb_param_t, esp_ble_gap_set_preferred_default_phy, esp_ble_gap_set_preferred_phy,
+    esp_bt_status_t_ESP_BT_STATUS_SUCCESS, esp_gap_ble_cb_event_t,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PHY_UPDATE_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_PREFERRED_DEFAULT_PHY_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_PREFERRED_PHY_COMPLETE_EVT,
+    ESP_BLE_GAP_PHY_OPTIONS_NO_PREF,
+};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+
+use crate::gatt_server::{
+    error::esp_report,
+    event::{self, GattEvent},
+    GattServer,
+};
+use crate::utilities::log_targets::GAP;
+use crate::utilities::{Connection, Phy};
+
+lazy_static! {
+    /// The transmitter and receiver physical layers of the connections, by remote address,
+    /// once updated.
+    static ref PHYS: Mutex<HashMap<[u8; 6], (Phy, Phy)>> = Mutex::new(HashMap::new());
+}
+
+impl Connection {
+    /// Requests the controller to switch this connection to a physical layer,
+    /// in both directions.
+    ///
+    /// The central device may refuse the change, or the controller may not support
+    /// the physical layer: the actual one is reported by [`GattEvent::PhyChanged`].
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn set_preferred_phy(&self, phy: Phy) {
+        debug!(
+            target: GAP,
+            "Requesting the {:?} PHY for {:02X?}.",
+            phy,
+            self.remote_bda()
+        );
+
+        let mut address = self.remote_bda();
+        unsafe {
+            esp_report!(esp_ble_gap_set_preferred_phy(
+                address.as_mut_ptr(),
+                0,
+                phy.mask(),
+                phy.mask(),
+                ESP_BLE_GAP_PHY_OPTIONS_NO_PREF as u16
+            ));
+        }
+    }
+
+    /// Returns the transmitter and receiver physical layers of this connection.
+    ///
+    /// A connection uses [`Phy::Le1M`] until its physical layer is updated.
+    #[must_use]
+    pub fn phy(&self) -> (Phy, Phy) {
+        PHYS.lock()
+            .get(&self.remote_bda())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl GattServer {
+    /// Sets the physical layer preferred for the new connections, in both directions.
+    ///
+    /// Use [`Phy::Le2M`] for a higher throughput, or [`Phy::LeCoded`] for a longer range.
+    /// Before the server is started, the preference is set once the Bluetooth stack
+    /// is initialised. See [`Connection::set_preferred_phy`] for a single connection.
+    pub fn preferred_phy(&mut self, phy: Phy) -> &mut Self {
+        self.preferred_phy = Some(phy);
+
+        if self.started {
+            self.configure_preferred_phy();
+        }
+
+        self
+    }
+
+    /// Sets a callback receiving the transmitter and receiver physical layers of a connection,
+    /// every time they change.
+    ///
+    /// This is a shorthand for handling [`GattEvent::PhyChanged`] with [`GattServer::on_event`].
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the Bluetooth stack's context, so it must not block.
+    pub fn on_phy_changed(
+        &mut self,
+        callback: impl Fn(Connection, Phy, Phy) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_event(move |event| {
+            if let GattEvent::PhyChanged {
+                connection,
+                tx_phy,
+                rx_phy,
+            } = event
+            {
+                callback(*connection, *tx_phy, *rx_phy);
+            }
+        })
+    }
+
+    /// Hands the preferred physical layer to the Bluetooth stack, if set.
+    pub(crate) fn configure_preferred_phy(&self) {
+        let Some(phy) = self.preferred_phy else {
+            return;
+        };
+
+        debug!(target: GAP, "Setting the preferred PHY to {:?}.", phy);
+        unsafe {
+            esp_report!(esp_ble_gap_set_preferred_default_phy(
+                phy.mask(),
+                phy.mask()
+            ));
+        }
+    }
+
+    /// Handles the PHY events of the GAP, and returns whether the event was one of them.
+    #[allow(non_upper_case_globals)]
+    pub(crate) fn phy_event_handler(
+        &self,
+        event: esp_gap_ble_cb_event_t,
+        param: *mut esp_ble_gap_cb_param_t,
+    ) -> bool {
+        match event {
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_PREFERRED_DEFAULT_PHY_COMPLETE_EVT
+            | esp_gap_ble_cb_event_t_ESP_GAP_BLE_SET_PREFERRED_PHY_COMPLETE_EVT => {
+                let param = unsafe { (*param).set_perf_phy };
+                if param.status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                    debug!(target: GAP, "BLE GAP preferred PHY set.");
+                } else {
+                    warn!(
+                        target: GAP,
+                        "BLE GAP preferred PHY failed with status 0x{:x}.", param.status
+                    );
+                }
+            }
+            esp_gap_ble_cb_event_t_ESP_GAP_BLE_PHY_UPDATE_COMPLETE_EVT => {
+                let param = unsafe { (*param).phy_update };
+                if param.status != esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                    warn!(
+                        target: GAP,
+                        "PHY update of {:02X?} failed with status 0x{:x}.", param.bda, param.status
+                    );
+                    return true;
+                }
+
+                let (Some(tx_phy), Some(rx_phy)) =
+                    (Phy::from_raw(param.tx_phy), Phy::from_raw(param.rx_phy))
+                else {
+                    warn!(target: GAP, "Unknown PHY reported for {:02X?}.", param.bda);
+                    return true;
+                };
+
+                info!(
+                    target: GAP,
+                    "PHY of {:02X?} updated: {:?} transmitter, {:?} receiver.",
+                    param.bda,
+                    tx_phy,
+                    rx_phy
+                );
+                PHYS.lock().insert(param.bda, (tx_phy, rx_phy));
+
+                let connection = self
+                    .active_connections
+                    .iter()
+                    .find(|connection| connection.remote_bda() == param.bda)
+                    .copied();
+                if let Some(connection) = connection {
+                    event::emit(&GattEvent::PhyChanged {
+                        connection,
+                        tx_phy,
+                        rx_phy,
+                    });
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// Forgets the physical layers of a closed connection.
+pub(crate) fn forget_phy(address: [u8; 6]) {
+    PHYS.lock().remove(&address);
+}
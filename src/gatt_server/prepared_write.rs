@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use esp_idf_sys::{
+    esp_gatt_status_t, esp_gatt_status_t_ESP_GATT_INVALID_ATTR_LEN,
+    esp_gatt_status_t_ESP_GATT_INVALID_OFFSET,
+};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::gatt_server::capacity::RESPONSE_LENGTH;
+
+/// The values assembled from the prepared writes of a connection, by attribute handle.
+pub(crate) type PreparedValues = Vec<(u16, Vec<u8>)>;
+
+lazy_static! {
+    /// The prepared writes of the connections.
+    static ref PREPARED_WRITES: Mutex<PreparedWrites> =
+        Mutex::new(PreparedWrites::new(RESPONSE_LENGTH));
+}
+
+/// The reason a fragment of a long write is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PrepareError {
+    /// The fragment does not start where the value assembled so far ends.
+    InvalidOffset,
+    /// The value grows longer than an attribute can be.
+    TooLong,
+}
+
+impl PrepareError {
+    /// Returns the status the request is answered with.
+    pub(crate) const fn status(self) -> esp_gatt_status_t {
+        match self {
+            Self::InvalidOffset => esp_gatt_status_t_ESP_GATT_INVALID_OFFSET,
+            Self::TooLong => esp_gatt_status_t_ESP_GATT_INVALID_ATTR_LEN,
+        }
+    }
+}
+
+/// The buffer of the prepared writes of the connections, by connection identifier:
+/// the value assembled so far for each attribute handle, in the order they were prepared.
+///
+/// It does not call into the Bluetooth stack, the event handlers do.
+#[derive(Debug)]
+pub(crate) struct PreparedWrites {
+    connections: HashMap<u16, PreparedValues>,
+    max_length: usize,
+}
+
+impl PreparedWrites {
+    /// Creates an empty buffer, for values of at most `max_length` bytes.
+    pub(crate) fn new(max_length: usize) -> Self {
+        Self {
+            connections: HashMap::new(),
+            max_length,
+        }
+    }
+
+    /// Queues a fragment of a long write, until the client executes its prepared writes.
+    ///
+    /// The fragments of an attribute must follow each other.
+    pub(crate) fn prepare(
+        &mut self,
+        connection: u16,
+        handle: u16,
+        offset: u16,
+        fragment: &[u8],
+    ) -> Result<(), PrepareError> {
+        let writes = self.connections.entry(connection).or_default();
+
+        let index = if let Some(index) = writes.iter().position(|(queued, _)| *queued == handle) {
+            index
+        } else {
+            writes.push((handle, Vec::new()));
+            writes.len() - 1
+        };
+        let value = &mut writes[index].1;
+
+        if usize::from(offset) != value.len() {
+            return Err(PrepareError::InvalidOffset);
+        }
+
+        if value.len() + fragment.len() > self.max_length {
+            return Err(PrepareError::TooLong);
+        }
+
+        value.extend_from_slice(fragment);
+        Ok(())
+    }
+
+    /// Ends the prepared writes of a connection, removing them from the buffer.
+    ///
+    /// Returns the values to deliver to the write callbacks: none if the client cancelled.
+    pub(crate) fn execute(&mut self, connection: u16, cancel: bool) -> PreparedValues {
+        let writes = self.connections.remove(&connection).unwrap_or_default();
+
+        if cancel {
+            Vec::new()
+        } else {
+            writes
+        }
+    }
+
+    /// Discards the prepared writes of a connection.
+    pub(crate) fn forget(&mut self, connection: u16) {
+        self.connections.remove(&connection);
+    }
+}
+
+/// Queues a fragment of a long write, until the client executes its prepared writes.
+///
+/// Fails with `ESP_GATT_INVALID_OFFSET` if the fragment does not start where the value
+/// assembled so far ends, and with `ESP_GATT_INVALID_ATTR_LEN` if the value grows longer
+/// than an attribute can be.
+pub(crate) fn prepare(
+    connection: u16,
+    handle: u16,
+    offset: u16,
+    fragment: &[u8],
+) -> Result<(), esp_gatt_status_t> {
+    PREPARED_WRITES
+        .lock()
+        .prepare(connection, handle, offset, fragment)
+        .map_err(PrepareError::status)
+}
+
+/// Ends the prepared writes of a connection, returning the values to deliver.
+pub(crate) fn execute_prepared_writes(connection: u16, cancel: bool) -> PreparedValues {
+    PREPARED_WRITES.lock().execute(connection, cancel)
+}
+
+/// Discards the prepared writes of a connection, once it is closed.
+pub(crate) fn forget_prepared_writes(connection: u16) {
+    PREPARED_WRITES.lock().forget(connection);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PrepareError, PreparedWrites};
+
+    const CONNECTION: u16 = 1;
+    const HANDLE: u16 = 0x2A;
+
+    fn buffer_with_fragments() -> PreparedWrites {
+        let mut writes = PreparedWrites::new(512);
+        writes.prepare(CONNECTION, HANDLE, 0, b"hello ").unwrap();
+        writes.prepare(CONNECTION, HANDLE, 6, b"world").unwrap();
+        writes
+    }
+
+    #[test]
+    fn execute_delivers_the_reassembled_value() {
+        let mut writes = buffer_with_fragments();
+
+        assert_eq!(
+            writes.execute(CONNECTION, false),
+            vec![(HANDLE, b"hello world".to_vec())]
+        );
+    }
+
+    #[test]
+    fn cancel_discards_the_fragments() {
+        let mut writes = buffer_with_fragments();
+
+        assert!(writes.execute(CONNECTION, true).is_empty());
+        assert!(writes.execute(CONNECTION, false).is_empty());
+
+        // A new long write starts from scratch.
+        writes.prepare(CONNECTION, HANDLE, 0, b"again").unwrap();
+        assert_eq!(
+            writes.execute(CONNECTION, false),
+            vec![(HANDLE, b"again".to_vec())]
+        );
+    }
+
+    #[test]
+    fn cancel_keeps_the_other_connections() {
+        let mut writes = buffer_with_fragments();
+        writes.prepare(CONNECTION + 1, HANDLE, 0, b"other").unwrap();
+        writes.execute(CONNECTION, true);
+
+        assert_eq!(
+            writes.execute(CONNECTION + 1, false),
+            vec![(HANDLE, b"other".to_vec())]
+        );
+    }
+
+    #[test]
+    fn prepare_rejects_gaps_and_overflows() {
+        let mut writes = PreparedWrites::new(8);
+
+        assert_eq!(
+            writes.prepare(CONNECTION, HANDLE, 2, b"ab"),
+            Err(PrepareError::InvalidOffset)
+        );
+        assert_eq!(
+            writes.prepare(CONNECTION, HANDLE, 0, b"too long!"),
+            Err(PrepareError::TooLong)
+        );
+    }
+}
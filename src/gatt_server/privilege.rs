@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use log::info;
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::{Connection, PrivilegeLevel};
+
+lazy_static! {
+    /// The privilege levels granted to the connections, by connection identifier.
+    static ref PRIVILEGES: Mutex<HashMap<u16, PrivilegeLevel>> = Mutex::new(HashMap::new());
+}
+
+impl Connection {
+    /// Grants a privilege level to this connection, usually once the client authenticated.
+    ///
+    /// The level is checked against the level required by each characteristic,
+    /// set with [`Characteristic::required_privilege`](crate::gatt_server::Characteristic::required_privilege).
+    /// It is kept until the client disconnects.
+    ///
+    /// This does not lock the GATT server, so it can be called from the read and write callbacks.
+    pub fn grant_privilege(&self, level: PrivilegeLevel) {
+        info!(
+            target: GATTS,
+            "Granting the {:?} privilege level to connection {}.",
+            level,
+            self.id()
+        );
+        PRIVILEGES.lock().insert(self.id(), level);
+    }
+
+    /// Returns the privilege level granted to this connection.
+    #[must_use]
+    pub fn privilege(&self) -> PrivilegeLevel {
+        PRIVILEGES
+            .lock()
+            .get(&self.id())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Forgets the privilege level of a closed connection.
+pub(crate) fn revoke_privilege(conn_id: u16) {
+    PRIVILEGES.lock().remove(&conn_id);
+}
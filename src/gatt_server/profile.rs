@@ -1,4 +1,11 @@
-use super::LockedService;
+use super::{
+    capacity::{self, Services},
+    custom_attributes::instance_index,
+    error::esp_report,
+    registration::RegistrationRetries,
+    GattServerError, LockedService,
+};
+use crate::utilities::{log_targets::GATTS, BleUuid};
 use esp_idf_sys::*;
 use log::debug;
 use parking_lot::RwLock;
@@ -6,6 +13,10 @@ use std::sync::Arc;
 
 /// Shorthand for our locked profiles that are returned everywhere
 pub type LockedProfile = Arc<RwLock<Profile>>;
+
+pub(crate) type RawEventCallback =
+    dyn Fn(esp_gatts_cb_event_t, esp_gatt_if_t, *mut esp_ble_gatts_cb_param_t) + Send + Sync;
+
 /// Represents a GATT profile.
 ///
 /// # Notes
@@ -14,12 +25,14 @@ pub type LockedProfile = Arc<RwLock<Profile>>;
 /// In this context, a profile is also called "application" in the ESP-IDF documentation.
 ///
 /// Internally, grouping services into different profiles only defines different event handlers.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Profile {
-    name: Option<String>,
-    pub(crate) services: Vec<LockedService>,
+    pub(crate) name: Option<String>,
+    pub(crate) services: Services,
     pub(crate) identifier: u16,
     pub(crate) interface: Option<u8>,
+    pub(crate) registration: RegistrationRetries,
+    pub(crate) raw_event_callback: Option<Arc<RawEventCallback>>,
 }
 
 impl Profile {
@@ -28,9 +41,11 @@ impl Profile {
     pub const fn new(identifier: u16) -> Self {
         Self {
             name: None,
-            services: Vec::new(),
+            services: Services::new(),
             identifier,
             interface: None,
+            registration: RegistrationRetries::new(),
+            raw_event_callback: None,
         }
     }
 
@@ -45,7 +60,40 @@ impl Profile {
     /// Adds a [`Service`] to the [`Profile`].
     #[must_use]
     pub fn service(&mut self, service: &LockedService) -> &mut Self {
-        self.services.push(service.clone());
+        capacity::push(&mut self.services, service.clone(), "services");
+        self
+    }
+
+    /// Returns the application identifier of the [`Profile`].
+    #[must_use]
+    pub const fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    /// Returns the GATT interface assigned to the [`Profile`], once registered in the Bluetooth stack.
+    #[must_use]
+    pub const fn interface(&self) -> Option<u8> {
+        self.interface
+    }
+
+    /// Sets a callback receiving every GATT server event of the [`Profile`],
+    /// after the crate handled it, with its raw parameters.
+    ///
+    /// This lets users implement the events this crate does not handle yet.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, while the GATT server
+    /// is locked: it must not block, nor lock the GATT server.
+    /// The parameters are only valid during the call.
+    pub fn on_raw_event(
+        &mut self,
+        callback: impl Fn(esp_gatts_cb_event_t, esp_gatt_if_t, *mut esp_ble_gatts_cb_param_t)
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.raw_event_callback = Some(Arc::new(callback));
         self
     }
 
@@ -70,7 +118,7 @@ impl Profile {
 
     pub(crate) fn get_service_by_id(&self, id: esp_gatt_id_t) -> Option<LockedService> {
         for service in &self.services {
-            if service.read().uuid == id.into() {
+            if service.read().uuid == id.uuid {
                 return Some(service.clone());
             }
         }
@@ -78,19 +126,72 @@ impl Profile {
         None
     }
 
-    pub(crate) fn register_self(&self) {
-        debug!("Registering {}.", self);
-        unsafe { esp_nofail!(esp_ble_gatts_app_register(self.identifier)) };
+    /// Records the characteristics the registered CCCDs of the services belong to,
+    /// for the storage of their values.
+    pub(crate) fn record_cccds(&self) {
+        let uuids: Vec<BleUuid> = self
+            .services
+            .iter()
+            .map(|service| service.read().uuid)
+            .collect();
+
+        for (index, service) in self.services.iter().enumerate() {
+            service.read().record_cccds(instance_index(&uuids, index));
+        }
+    }
+
+    /// Registers the contents of the services that were waiting for `included` to be registered.
+    pub(crate) fn register_including_services(&self, included: &LockedService) {
+        for service in &self.services {
+            let waiting = {
+                let service = service.read();
+                service.handle.is_some()
+                    && !service.includes_added
+                    && service
+                        .included_services
+                        .iter()
+                        .any(|candidate| Arc::ptr_eq(candidate, included))
+            };
+
+            if waiting {
+                service.write().register_contents();
+            }
+        }
+    }
+
+    pub(crate) fn register_self(&mut self) {
+        debug!(target: GATTS, "Registering {}.", self);
+        self.registration.requested();
+        unsafe { esp_report!(esp_ble_gatts_app_register(self.identifier)) };
     }
 
     pub(crate) fn register_services(&mut self) {
-        debug!("Registering {}'s services.", &self);
+        debug!(target: GATTS, "Registering {}'s services.", &self);
+
+        let Some(interface) = self.interface else {
+            GattServerError::NotRegistered(self.to_string()).report();
+            return;
+        };
+
         self.services.iter_mut().for_each(|service| {
-            service.write().register_self(self.interface.unwrap());
+            service.write().register_self(interface);
         });
     }
 }
 
+impl std::fmt::Debug for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profile")
+            .field("name", &self.name)
+            .field("services", &self.services)
+            .field("identifier", &self.identifier)
+            .field("interface", &self.interface)
+            .field("registration", &self.registration)
+            .field("raw_event_callback", &self.raw_event_callback.is_some())
+            .finish()
+    }
+}
+
 impl std::fmt::Display for Profile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
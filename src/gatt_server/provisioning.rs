@@ -0,0 +1,422 @@
+use std::{collections::HashMap, sync::Arc};
+
+use esp_idf_sys::{
+    mbedtls_ccm_auth_decrypt, mbedtls_ccm_context, mbedtls_ccm_free, mbedtls_ccm_init,
+    mbedtls_ccm_setkey, mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+};
+use log::{info, warn};
+use parking_lot::{Mutex, RwLock};
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{
+        secure_session::hmac_sha256, Characteristic, LockedCharacteristic, LockedService,
+        SecureSession, Service,
+    },
+    utilities::{AttributePermissions, BleUuid, CharacteristicProperties, Connection},
+};
+
+/// The identifier of the Wi-Fi provisioning service.
+pub const PROVISIONING_SERVICE_UUID: BleUuid =
+    BleUuid::from_uuid128_str("c2a20000-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the SSID characteristic.
+const SSID_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20001-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the passphrase characteristic.
+const PASSPHRASE_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20002-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the control point characteristic.
+const CONTROL_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20003-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the status characteristic.
+const STATUS_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20004-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the proof-of-possession challenge characteristic.
+const CHALLENGE_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20005-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The identifier of the proof-of-possession response characteristic.
+const RESPONSE_UUID: BleUuid = BleUuid::from_uuid128_str("c2a20006-7f1e-4a8b-9d1e-3f5b2c9e0a01");
+
+/// The control point command handing the received credentials to the application.
+const COMMAND_APPLY: u8 = 0x01;
+
+/// The control point command discarding the received credentials.
+const COMMAND_RESET: u8 = 0x02;
+
+/// The label of the encryption key, derived from the verified session.
+const KEY_LABEL: u8 = 0x00;
+
+/// The labels of the encrypted values, authenticated along with them.
+const SSID_LABEL: u8 = 0x01;
+const PASSPHRASE_LABEL: u8 = 0x02;
+
+/// The maximum length of an SSID.
+const SSID_LENGTH: u16 = 32;
+
+/// The maximum length of a WPA passphrase.
+const PASSPHRASE_LENGTH: u16 = 64;
+
+const NONCE_LENGTH: usize = 13;
+const TAG_LENGTH: usize = 16;
+
+type CredentialsCallback = dyn Fn(Connection, WifiCredentials) + Send + Sync;
+
+/// Wi-Fi credentials received over BLE.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WifiCredentials {
+    /// The SSID of the network.
+    pub ssid: String,
+    /// The passphrase of the network, empty for open networks.
+    pub passphrase: String,
+}
+
+impl std::fmt::Debug for WifiCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Do not leak the passphrase in the logs.
+        f.debug_struct("WifiCredentials")
+            .field("ssid", &self.ssid)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The provisioning status reported to the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProvisioningStatus {
+    /// No credentials were received yet.
+    Idle = 0,
+    /// The credentials were handed to the application.
+    Received = 1,
+    /// The device is connecting to the network.
+    Connecting = 2,
+    /// The device is connected to the network.
+    Connected = 3,
+    /// The device could not connect to the network.
+    Failed = 4,
+}
+
+/// A Wi-Fi provisioning service, receiving network credentials from a client.
+///
+/// The client writes the SSID and the passphrase to their characteristics, then writes
+/// `0x01` to the control point to hand them to the [`WifiProvisioning::on_credentials`] callback,
+/// or `0x02` to discard them. The application reports the outcome with
+/// [`WifiProvisioning::report_status`], which is notified on the status characteristic.
+///
+/// With a proof-of-possession key, the client must first verify a [`SecureSession`] over the
+/// challenge and response characteristics of the service. The SSID and the passphrase
+/// are then encrypted and authenticated with AES-128-CCM, under the first 16 bytes of
+/// `HMAC-SHA256(key, challenge || 0x00)`, where `challenge` is the verified challenge.
+/// Each written value is a random 13-byte nonce, followed by the ciphertext and a 16-byte tag,
+/// with the label `0x01` for the SSID or `0x02` for the passphrase as associated data.
+#[derive(Clone)]
+pub struct WifiProvisioning {
+    service: LockedService,
+    status: LockedCharacteristic,
+    state: Arc<ProvisioningState>,
+}
+
+struct ProvisioningState {
+    pending: Mutex<HashMap<Connection, PendingCredentials>>,
+    callback: RwLock<Option<Arc<CredentialsCallback>>>,
+    session: Option<SecureSession>,
+}
+
+#[derive(Default)]
+struct PendingCredentials {
+    ssid: Option<Vec<u8>>,
+    passphrase: Vec<u8>,
+}
+
+impl WifiProvisioning {
+    /// Creates a new [`WifiProvisioning`] service, accepting plain text credentials.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::build(None)
+    }
+
+    /// Creates a new [`WifiProvisioning`] service, accepting encrypted credentials
+    /// from the clients that prove they know the given key.
+    #[must_use]
+    pub fn with_proof_of_possession(key: &[u8]) -> Self {
+        Self::build(Some(key))
+    }
+
+    fn build(proof_of_possession: Option<&[u8]>) -> Self {
+        let mut service = Service::new(PROVISIONING_SERVICE_UUID);
+        service.name("Wi-Fi Provisioning").primary();
+
+        let session = proof_of_possession.map(|key| {
+            let challenge = Characteristic::new(CHALLENGE_UUID)
+                .name("Provisioning Challenge")
+                .permissions(AttributePermissions::new().read())
+                .properties(CharacteristicProperties::new().read())
+                .build();
+            let response = Characteristic::new(RESPONSE_UUID)
+                .name("Provisioning Response")
+                .permissions(AttributePermissions::new().write())
+                .properties(CharacteristicProperties::new().write())
+                .build();
+
+            service.characteristic(&challenge).characteristic(&response);
+            SecureSession::new(key, &challenge, &response)
+        });
+
+        let state = Arc::new(ProvisioningState {
+            pending: Mutex::new(HashMap::new()),
+            callback: RwLock::new(None),
+            session,
+        });
+
+        let ssid_state = state.clone();
+        let ssid = state
+            .writable(SSID_UUID, "Wi-Fi SSID")
+            .max_value_length(state.written_length(SSID_LENGTH))
+            .on_write(move |value, param| {
+                let connection = Connection::from(param);
+                if let Some(value) = ssid_state.decrypt(connection, SSID_LABEL, value) {
+                    ssid_state
+                        .pending
+                        .lock()
+                        .entry(connection)
+                        .or_default()
+                        .ssid = Some(value);
+                }
+            })
+            .build();
+
+        let passphrase_state = state.clone();
+        let passphrase = state
+            .writable(PASSPHRASE_UUID, "Wi-Fi Passphrase")
+            .max_value_length(state.written_length(PASSPHRASE_LENGTH))
+            .on_write(move |value, param| {
+                let connection = Connection::from(param);
+                if let Some(value) = passphrase_state.decrypt(connection, PASSPHRASE_LABEL, value) {
+                    passphrase_state
+                        .pending
+                        .lock()
+                        .entry(connection)
+                        .or_default()
+                        .passphrase = value;
+                }
+            })
+            .build();
+
+        let status = Characteristic::new(STATUS_UUID)
+            .name("Provisioning Status")
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .set_value(vec![ProvisioningStatus::Idle as u8])
+            .build();
+
+        let control_state = state.clone();
+        let control_status = status.clone();
+        let control = state
+            .writable(CONTROL_UUID, "Provisioning Control Point")
+            .on_write(move |value, param| {
+                let connection = Connection::from(param);
+
+                match value.first() {
+                    Some(&COMMAND_APPLY) => {
+                        if control_state.apply(connection) {
+                            control_status
+                                .write()
+                                .set_value(vec![ProvisioningStatus::Received as u8]);
+                        }
+                    }
+                    Some(&COMMAND_RESET) => {
+                        control_state.pending.lock().remove(&connection);
+                        control_status
+                            .write()
+                            .set_value(vec![ProvisioningStatus::Idle as u8]);
+                    }
+                    _ => warn!(target: GATTS, "Unknown provisioning command {:02X?}.", value),
+                }
+            })
+            .build();
+
+        service
+            .characteristic(&ssid)
+            .characteristic(&passphrase)
+            .characteristic(&control)
+            .characteristic(&status);
+
+        Self {
+            service: service.build(),
+            status,
+            state,
+        }
+    }
+
+    /// Sets the callback receiving the credentials applied by a client.
+    ///
+    /// # Notes
+    ///
+    /// The callback will be called from the Bluetooth stack's context, so it must not block:
+    /// connect to the network from another task, and report the outcome with [`Self::report_status`].
+    pub fn on_credentials(
+        &mut self,
+        callback: impl Fn(Connection, WifiCredentials) + Send + Sync + 'static,
+    ) -> &mut Self {
+        *self.state.callback.write() = Some(Arc::new(callback));
+        self
+    }
+
+    /// Reports the provisioning status to the clients.
+    pub fn report_status(&self, status: ProvisioningStatus) {
+        self.status.write().set_value(vec![status as u8]);
+    }
+
+    /// Returns the service, to be added to a [`Profile`](crate::gatt_server::Profile).
+    #[must_use]
+    pub fn service(&self) -> LockedService {
+        self.service.clone()
+    }
+}
+
+impl Default for WifiProvisioning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for WifiProvisioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WifiProvisioning")
+            .field("service", &self.service.read().to_string())
+            .field("proof_of_possession", &self.state.session.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProvisioningState {
+    /// Returns a writable characteristic, restricted to verified sessions with a proof of possession.
+    fn writable(&self, uuid: BleUuid, name: &str) -> Characteristic {
+        let mut characteristic = Characteristic::new(uuid);
+        characteristic
+            .name(name)
+            .permissions(AttributePermissions::new().write())
+            .properties(CharacteristicProperties::new().write());
+
+        if let Some(session) = &self.session {
+            characteristic.access_policy(session.policy());
+        }
+
+        characteristic
+    }
+
+    /// Returns the maximum length of the written values holding up to `length` bytes,
+    /// once encrypted if a proof of possession is required.
+    #[allow(clippy::cast_possible_truncation)]
+    fn written_length(&self, length: u16) -> u16 {
+        match self.session {
+            Some(_) => length + (NONCE_LENGTH + TAG_LENGTH) as u16,
+            None => length,
+        }
+    }
+
+    /// Decrypts a value written by the given connection, if a proof of possession is required.
+    fn decrypt(&self, connection: Connection, label: u8, value: Vec<u8>) -> Option<Vec<u8>> {
+        let Some(session) = &self.session else {
+            return Some(value);
+        };
+
+        let Some(challenge) = session.nonce(connection) else {
+            warn!(
+                target: GATTS,
+                "Provisioning value from {} without a verified session.",
+                connection
+            );
+            return None;
+        };
+
+        if value.len() < NONCE_LENGTH + TAG_LENGTH {
+            warn!(target: GATTS, "Truncated provisioning value from {}.", connection);
+            return None;
+        }
+
+        let mut message = challenge.to_vec();
+        message.push(KEY_LABEL);
+        let key = hmac_sha256(session.key(), &message)?;
+
+        let (nonce, body) = value.split_at(NONCE_LENGTH);
+        let (ciphertext, tag) = body.split_at(body.len() - TAG_LENGTH);
+        let mut plaintext = vec![0u8; ciphertext.len()];
+        let mut context = mbedtls_ccm_context::default();
+
+        let result = unsafe {
+            mbedtls_ccm_init(&mut context);
+
+            let mut result = mbedtls_ccm_setkey(
+                &mut context,
+                mbedtls_cipher_id_t_MBEDTLS_CIPHER_ID_AES,
+                key.as_ptr(),
+                128,
+            );
+            if result == 0 {
+                result = mbedtls_ccm_auth_decrypt(
+                    &mut context,
+                    ciphertext.len(),
+                    nonce.as_ptr(),
+                    NONCE_LENGTH,
+                    &label,
+                    1,
+                    ciphertext.as_ptr(),
+                    plaintext.as_mut_ptr(),
+                    tag.as_ptr(),
+                    TAG_LENGTH,
+                );
+            }
+
+            mbedtls_ccm_free(&mut context);
+            result
+        };
+
+        if result != 0 {
+            warn!(
+                target: GATTS,
+                "Provisioning value from {} cannot be decrypted.",
+                connection
+            );
+            return None;
+        }
+
+        Some(plaintext)
+    }
+
+    /// Hands the credentials received from the given connection to the callback.
+    fn apply(&self, connection: Connection) -> bool {
+        let Some(pending) = self.pending.lock().remove(&connection) else {
+            warn!(target: GATTS, "No provisioning credentials received from {}.", connection);
+            return false;
+        };
+
+        let (Some(ssid), passphrase) = (pending.ssid, pending.passphrase) else {
+            warn!(target: GATTS, "No SSID received from {}.", connection);
+            return false;
+        };
+
+        let (Ok(ssid), Ok(passphrase)) = (String::from_utf8(ssid), String::from_utf8(passphrase))
+        else {
+            warn!(
+                target: GATTS,
+                "Invalid provisioning credentials received from {}.",
+                connection
+            );
+            return false;
+        };
+
+        info!(
+            target: GATTS,
+            "Received credentials for Wi-Fi network {} from {}.",
+            ssid, connection
+        );
+
+        let callback = self.callback.read().clone();
+        if let Some(callback) = callback {
+            callback(connection, WifiCredentials { ssid, passphrase });
+        }
+
+        true
+    }
+}
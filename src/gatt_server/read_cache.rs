@@ -0,0 +1,51 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// A value returned by a read callback, along with the time it was read.
+type Entry = Option<(Instant, Vec<u8>)>;
+
+/// The latest value returned by the read callback of a characteristic,
+/// served again to the reads arriving before it expires.
+#[derive(Debug, Clone)]
+pub(crate) struct ReadCache {
+    ttl: Duration,
+    /// The cached value, along with the time it was read. Shared by the clones of the characteristic.
+    entry: Arc<Mutex<Entry>>,
+}
+
+impl ReadCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Arc::default(),
+        }
+    }
+
+    /// Returns the cached value if it is still fresh, or calls `read` and caches its result.
+    ///
+    /// The cache stays locked while `read` runs, so concurrent reads wait for the same value
+    /// instead of calling `read` again.
+    pub(crate) fn get_or_read(&self, read: impl FnOnce() -> Vec<u8>) -> Vec<u8> {
+        let mut entry = self.entry.lock();
+
+        if let Some((timestamp, value)) = entry.as_ref() {
+            if timestamp.elapsed() < self.ttl {
+                return value.clone();
+            }
+        }
+
+        let value = read();
+        *entry = Some((Instant::now(), value.clone()));
+
+        value
+    }
+
+    /// Discards the cached value, so that the next read calls the read callback.
+    pub(crate) fn invalidate(&self) {
+        *self.entry.lock() = None;
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::gatt_server::{worker, GattServer, GattServerError, GLOBAL_GATT_SERVER};
+use crate::utilities::log_targets::GATTS;
+
+/// How many times a failed registration is retried.
+const MAX_RETRIES: u8 = 3;
+
+/// The delay before the first retry, doubled at every following retry.
+const BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a registration step may wait for its event before the attribute is considered stalled.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the registration watchdog looks for stalled attributes.
+const WATCHDOG_PERIOD: Duration = Duration::from_millis(100);
+
+/// The registration state of the attributes of a [`GattServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationState {
+    /// The GATT server has not been started yet.
+    NotStarted,
+    /// The attributes are being registered in the Bluetooth stack.
+    InProgress,
+    /// All the attributes are registered.
+    Complete,
+    /// The registration of an attribute failed, even after retrying.
+    ///
+    /// Contains the description of the attribute.
+    Failed(String),
+}
+
+/// Keeps track of the failed registration attempts of an attribute.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RegistrationRetries {
+    failures: u8,
+    requested: Option<Instant>,
+}
+
+impl RegistrationRetries {
+    pub(crate) const fn new() -> Self {
+        Self {
+            failures: 0,
+            requested: None,
+        }
+    }
+
+    /// Whether the registration was requested to the Bluetooth stack.
+    pub(crate) const fn is_requested(self) -> bool {
+        self.requested.is_some()
+    }
+
+    /// Records that the registration was just requested to the Bluetooth stack.
+    pub(crate) fn requested(&mut self) {
+        self.requested = Some(Instant::now());
+    }
+
+    /// Whether the registration was requested, but the Bluetooth stack did not answer in time.
+    ///
+    /// Only meaningful while the attribute is not registered.
+    pub(crate) fn stalled(self) -> bool {
+        !self.failed()
+            && self
+                .requested
+                .is_some_and(|requested| requested.elapsed() > STEP_TIMEOUT)
+    }
+
+    /// Records a registration whose event never arrived.
+    pub(crate) fn time_out(&mut self, attribute: String) {
+        warn!(
+            target: GATTS,
+            "Registration of {} stalled: no event received within {:?}.",
+            attribute, STEP_TIMEOUT
+        );
+        self.give_up(attribute);
+    }
+
+    /// Whether the registration failed for good.
+    pub(crate) const fn failed(self) -> bool {
+        self.failures > MAX_RETRIES
+    }
+
+    /// Records a failed registration, and retries it after a backoff delay.
+    ///
+    /// When the retries are exhausted, a [`GattServerError::RegistrationFailed`] is reported instead.
+    pub(crate) fn retry<F: FnOnce() + Send + 'static>(&mut self, attribute: String, register: F) {
+        self.failures = self.failures.saturating_add(1);
+
+        if self.failed() {
+            GattServerError::RegistrationFailed(attribute).report();
+            return;
+        }
+
+        let delay = BASE_DELAY * 2u32.pow(u32::from(self.failures - 1));
+        warn!(
+            target: GATTS,
+            "Registration of {} failed, retrying in {:?}.",
+            attribute, delay
+        );
+
+        worker::schedule(delay, register);
+    }
+
+    /// Records a failed registration that cannot be retried.
+    pub(crate) fn give_up(&mut self, attribute: String) {
+        self.failures = MAX_RETRIES + 1;
+        GattServerError::RegistrationFailed(attribute).report();
+    }
+}
+
+impl GattServer {
+    /// Starts the registration watchdog, which gives up on the attributes
+    /// whose registration event never arrives, until the registration is over.
+    pub(crate) fn watch_registration() {
+        worker::schedule_periodic(WATCHDOG_PERIOD, || {
+            let server = GLOBAL_GATT_SERVER.lock();
+            server.give_up_stalled_registrations();
+            server.registration_state() == RegistrationState::InProgress
+        });
+    }
+
+    fn give_up_stalled_registrations(&self) {
+        for profile in &self.profiles {
+            let mut profile = profile.write();
+            if profile.interface.is_none() && profile.registration.stalled() {
+                let attribute = profile.to_string();
+                profile.registration.time_out(attribute);
+            }
+
+            for service in &profile.services {
+                let mut service = service.write();
+                if service.handle.is_none() && service.registration.stalled() {
+                    let attribute = service.to_string();
+                    service.registration.time_out(attribute);
+                }
+
+                let mut characteristic_stalled = false;
+                for characteristic in &service.characteristics {
+                    let mut characteristic = characteristic.write();
+                    if characteristic.attribute_handle.is_none()
+                        && characteristic.registration.stalled()
+                    {
+                        let attribute = characteristic.to_string();
+                        characteristic.registration.time_out(attribute);
+                        characteristic_stalled = true;
+                    }
+
+                    for descriptor in &characteristic.descriptors {
+                        let mut descriptor = descriptor.write();
+                        if descriptor.attribute_handle.is_none()
+                            && descriptor.registration.stalled()
+                        {
+                            let attribute = descriptor.to_string();
+                            descriptor.registration.time_out(attribute);
+                        }
+                    }
+                }
+
+                // The next characteristic was waiting for the event of the stalled one.
+                if characteristic_stalled && !service.attribute_table {
+                    service.register_next_characteristic();
+                }
+            }
+        }
+    }
+
+    /// Returns the registration state of the attributes of this [`GattServer`].
+    #[must_use]
+    pub fn registration_state(&self) -> RegistrationState {
+        if !self.started {
+            return RegistrationState::NotStarted;
+        }
+
+        let mut complete = true;
+
+        for profile in &self.profiles {
+            let profile = profile.read();
+            if profile.registration.failed() {
+                return RegistrationState::Failed(profile.to_string());
+            }
+            complete &= profile.interface.is_some();
+
+            for service in &profile.services {
+                let service = service.read();
+                if service.registration.failed() {
+                    return RegistrationState::Failed(service.to_string());
+                }
+                complete &= service.handle.is_some();
+
+                for characteristic in &service.characteristics {
+                    let characteristic = characteristic.read();
+                    if characteristic.registration.failed() {
+                        return RegistrationState::Failed(characteristic.to_string());
+                    }
+                    complete &= characteristic.attribute_handle.is_some();
+
+                    for descriptor in &characteristic.descriptors {
+                        let descriptor = descriptor.read();
+                        if descriptor.registration.failed() {
+                            return RegistrationState::Failed(descriptor.to_string());
+                        }
+                        complete &= descriptor.attribute_handle.is_some();
+                    }
+                }
+            }
+        }
+
+        if complete {
+            RegistrationState::Complete
+        } else {
+            RegistrationState::InProgress
+        }
+    }
+}
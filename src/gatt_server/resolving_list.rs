@@ -0,0 +1,285 @@
+#[cfg(not(any(esp_idf_version_major = "4", esp_idf_version = "5.0")))]
+use esp_idf_sys::esp_ble_gap_add_device_to_resolving_list;
+use esp_idf_sys::{
+    esp_ble_addr_type_t, esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+    esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM, esp_ble_addr_type_t_BLE_ADDR_TYPE_RPA_PUBLIC,
+    esp_ble_addr_type_t_BLE_ADDR_TYPE_RPA_RANDOM, esp_ble_bond_dev_t,
+    esp_ble_gap_config_local_privacy, esp_ble_get_bond_device_list, esp_ble_get_bond_device_num,
+    esp_ble_remove_bond_device, mbedtls_aes_context, mbedtls_aes_crypt_ecb, mbedtls_aes_free,
+    mbedtls_aes_init, mbedtls_aes_setkey_enc, ESP_LE_KEY_PID, MBEDTLS_AES_ENCRYPT,
+};
+#[cfg(not(any(esp_idf_version_major = "4", esp_idf_version = "5.0")))]
+use log::debug;
+#[cfg(any(esp_idf_version_major = "4", esp_idf_version = "5.0"))]
+use log::warn;
+
+use crate::gatt_server::{error::esp_report, GattServer};
+use crate::utilities::{log_targets::GAP, AddressType};
+
+/// A peer of the resolving list of the controller.
+///
+/// The controller resolves the resolvable private addresses of the peers of its list
+/// to their identity addresses, so that directed advertising and the whitelist keep working
+/// with privacy-enabled devices, such as phones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvingListEntry {
+    /// The identity address of the peer.
+    pub address: [u8; 6],
+    /// The type of the identity address of the peer.
+    pub address_type: AddressType,
+    /// The identity resolving key distributed by the peer when bonding.
+    pub irk: [u8; 16],
+}
+
+impl GattServer {
+    /// Adds a peer to the resolving list of the controller.
+    ///
+    /// Before the server is started, the peer is added once the Bluetooth stack is initialised.
+    ///
+    /// # Notes
+    ///
+    /// Bluedroid only exposes the resolving list from ESP-IDF 5.1. With older versions,
+    /// the peer is ignored: the stack still loads the bonded peers in the list by itself
+    /// when [address resolution](GattServer::address_resolution) is enabled.
+    pub fn add_to_resolving_list(&mut self, entry: ResolvingListEntry) -> &mut Self {
+        if self.started {
+            add_entry(entry);
+        } else {
+            self.resolving_list.push(entry);
+        }
+
+        self
+    }
+
+    /// Adds every bonded peer that distributed an identity resolving key to the resolving list.
+    ///
+    /// The bonds are loaded with the Bluetooth stack, so this must be called after
+    /// starting the server.
+    pub fn resolve_bonded_peers(&mut self) -> &mut Self {
+        for entry in bonded_peers() {
+            self.add_to_resolving_list(entry);
+        }
+
+        self
+    }
+
+    /// Removes the bond of a peer, which takes it out of the resolving list of the controller.
+    ///
+    /// The peer loses its keys and has to pair again. Before the server is started,
+    /// the peer is only dropped from the entries waiting to be added to the resolving list.
+    ///
+    /// # Notes
+    ///
+    /// Bluedroid does not expose the removal of a single peer from the resolving list:
+    /// it only removes a peer from the list along with its bond.
+    pub fn remove_bond(&mut self, address: [u8; 6]) -> &mut Self {
+        self.resolving_list.retain(|entry| entry.address != address);
+
+        if self.started {
+            let mut address = address;
+            unsafe {
+                esp_report!(esp_ble_remove_bond_device(address.as_mut_ptr()));
+            }
+        }
+
+        self
+    }
+
+    /// Enables or disables LE privacy, so that the device cannot be tracked by its address.
+    ///
+    /// While privacy is enabled, the server advertises from a resolvable private address,
+    /// which the controller regenerates periodically, rather than from its public address.
+    /// The bonded peers, which received the identity resolving key of the server when
+    /// pairing, still recognise it. The controller also resolves the private addresses
+    /// of the peers of the resolving list, where Bluedroid loads the bonded peers.
+    ///
+    /// Privacy is disabled by default. Once the server is started, the new address type
+    /// is used the next time the legacy advertisement starts, and the extended advertising
+    /// sets keep the address type they were configured with.
+    pub fn privacy(&mut self, enabled: bool) -> &mut Self {
+        self.address_resolution = enabled;
+        self.advertisement_parameters.own_addr_type = self.own_address_type();
+
+        if self.started {
+            self.configure_address_resolution();
+        }
+
+        self
+    }
+
+    /// Enables or disables the resolution of private addresses by the controller.
+    ///
+    /// Address resolution is disabled by default.
+    ///
+    /// # Notes
+    ///
+    /// Bluedroid enables address resolution along with local privacy, so this is the same
+    /// as [`GattServer::privacy`]: the server also advertises from a resolvable private
+    /// address while it is enabled.
+    pub fn address_resolution(&mut self, enabled: bool) -> &mut Self {
+        self.privacy(enabled)
+    }
+
+    /// Returns the type of the address the server advertises from.
+    pub(crate) const fn own_address_type(&self) -> esp_ble_addr_type_t {
+        match (self.address_resolution, self.random_address.is_some()) {
+            (false, false) => esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+            (false, true) => esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM,
+            (true, false) => esp_ble_addr_type_t_BLE_ADDR_TYPE_RPA_PUBLIC,
+            (true, true) => esp_ble_addr_type_t_BLE_ADDR_TYPE_RPA_RANDOM,
+        }
+    }
+
+    /// Hands the address resolution setting and the pending peers to the Bluetooth stack.
+    pub(crate) fn configure_resolving_list(&mut self) {
+        if self.address_resolution {
+            self.configure_address_resolution();
+        }
+
+        for entry in std::mem::take(&mut self.resolving_list) {
+            add_entry(entry);
+        }
+    }
+
+    fn configure_address_resolution(&self) {
+        unsafe {
+            esp_report!(esp_ble_gap_config_local_privacy(self.address_resolution));
+        }
+    }
+}
+
+#[cfg(not(any(esp_idf_version_major = "4", esp_idf_version = "5.0")))]
+fn add_entry(mut entry: ResolvingListEntry) {
+    debug!(
+        target: GAP,
+        "Adding {:02X?} to the resolving list.",
+        entry.address
+    );
+
+    #[allow(clippy::cast_possible_truncation)]
+    let address_type = esp_ble_addr_type_t::from(entry.address_type) as u8;
+    unsafe {
+        esp_report!(esp_ble_gap_add_device_to_resolving_list(
+            entry.address.as_mut_ptr(),
+            address_type,
+            entry.irk.as_mut_ptr()
+        ));
+    }
+}
+
+#[cfg(any(esp_idf_version_major = "4", esp_idf_version = "5.0"))]
+fn add_entry(entry: ResolvingListEntry) {
+    warn!(
+        target: GAP,
+        "Cannot add {:02X?} to the resolving list: not supported before ESP-IDF 5.1.",
+        entry.address
+    );
+}
+
+/// Returns the bonded peers that distributed an identity resolving key.
+fn bonded_peers() -> Vec<ResolvingListEntry> {
+    bonded_devices()
+        .iter()
+        .filter(|device| u32::from(device.bond_key.key_mask) & ESP_LE_KEY_PID != 0)
+        .map(|device| ResolvingListEntry {
+            address: device.bond_key.pid_key.static_addr,
+            address_type: AddressType::from(device.bond_key.pid_key.addr_type),
+            irk: device.bond_key.pid_key.irk,
+        })
+        .collect()
+}
+
+/// Returns the identity address of a peer, so that its data outlives its private addresses.
+///
+/// That is the identity address of the bond of the peer, found by its address, or by resolving
+/// its resolvable private address with the identity resolving keys of the bonds.
+/// The address of a peer that belongs to no bond is returned as is.
+pub(crate) fn identity_address(address: [u8; 6]) -> [u8; 6] {
+    let devices = bonded_devices();
+    let identity = |device: &esp_ble_bond_dev_t| {
+        if u32::from(device.bond_key.key_mask) & ESP_LE_KEY_PID == 0 {
+            device.bd_addr
+        } else {
+            device.bond_key.pid_key.static_addr
+        }
+    };
+
+    if let Some(device) = devices
+        .iter()
+        .find(|device| device.bd_addr == address || identity(device) == address)
+    {
+        return identity(device);
+    }
+
+    if !is_resolvable(address) {
+        return address;
+    }
+
+    bonded_peers()
+        .iter()
+        .find(|peer| resolves(address, &peer.irk))
+        .map_or(address, |peer| peer.address)
+}
+
+/// Whether an address is a resolvable private address: its two most significant bits are `01`.
+const fn is_resolvable(address: [u8; 6]) -> bool {
+    address[0] & 0xC0 == 0x40
+}
+
+/// Whether a resolvable private address was generated from an identity resolving key,
+/// with the `ah` function of the Bluetooth Core specification (Vol 3, Part H, 2.2.2).
+fn resolves(address: [u8; 6], irk: &[u8; 16]) -> bool {
+    // The stack stores its keys least significant byte first, AES expects the opposite.
+    let mut key = *irk;
+    key.reverse();
+
+    // The random part of the address, padded with zeros.
+    let mut plaintext = [0u8; 16];
+    plaintext[13..].copy_from_slice(&address[..3]);
+    let mut ciphertext = [0u8; 16];
+    let mut context = mbedtls_aes_context::default();
+
+    let result = unsafe {
+        mbedtls_aes_init(&mut context);
+
+        let mut result = mbedtls_aes_setkey_enc(&mut context, key.as_ptr(), 128);
+        if result == 0 {
+            #[allow(clippy::cast_possible_wrap)]
+            let mode = MBEDTLS_AES_ENCRYPT as i32;
+            result = mbedtls_aes_crypt_ecb(
+                &mut context,
+                mode,
+                plaintext.as_ptr(),
+                ciphertext.as_mut_ptr(),
+            );
+        }
+
+        mbedtls_aes_free(&mut context);
+        result
+    };
+
+    // The hash part of the address.
+    result == 0 && ciphertext[13..] == address[3..]
+}
+
+/// Returns the bonded devices known to the Bluetooth stack, with their keys.
+pub(crate) fn bonded_devices() -> Vec<esp_ble_bond_dev_t> {
+    let mut count = unsafe { esp_ble_get_bond_device_num() };
+    let Ok(capacity) = usize::try_from(count) else {
+        return Vec::new();
+    };
+
+    let mut devices = vec![esp_ble_bond_dev_t::default(); capacity];
+    let listed = unsafe {
+        esp_report!(esp_ble_get_bond_device_list(
+            &mut count,
+            devices.as_mut_ptr()
+        ))
+    };
+    if !listed {
+        return Vec::new();
+    }
+    devices.truncate(usize::try_from(count).unwrap_or_default());
+
+    devices
+}
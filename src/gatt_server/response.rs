@@ -0,0 +1,74 @@
+use esp_idf_sys::{esp_ble_gatts_send_response, esp_gatt_if_t, esp_gatt_rsp_t, esp_gatt_status_t};
+use lazy_static::lazy_static;
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::gatt_server::{capacity::RESPONSE_LENGTH, error::esp_report};
+use crate::utilities::log_targets::GATTS;
+
+lazy_static! {
+    /// The buffer in which the responses of the application are assembled.
+    ///
+    /// A response takes more than 600 bytes: keeping it off the stack of the Bluetooth task
+    /// leaves the headroom of its callbacks to the application.
+    pub(crate) static ref RESPONSE_BUFFER: Mutex<Box<esp_gatt_rsp_t>> =
+        Mutex::new(Box::new(unsafe { std::mem::zeroed() }));
+}
+
+/// Sends the response to a read or write request of a client.
+///
+/// Values longer than the response buffer of the Bluetooth stack are truncated.
+/// Returns `true` if the response was sent.
+pub(crate) fn send_response(
+    gatts_if: esp_gatt_if_t,
+    conn_id: u16,
+    trans_id: u32,
+    handle: u16,
+    status: esp_gatt_status_t,
+    value: &[u8],
+) -> bool {
+    send_response_at(gatts_if, conn_id, trans_id, handle, 0, status, value)
+}
+
+/// Sends the response to a blob read, carrying the part of the value starting at `offset`.
+pub(crate) fn send_response_at(
+    gatts_if: esp_gatt_if_t,
+    conn_id: u16,
+    trans_id: u32,
+    handle: u16,
+    offset: u16,
+    status: esp_gatt_status_t,
+    value: &[u8],
+) -> bool {
+    if value.len() > RESPONSE_LENGTH {
+        warn!(
+            target: GATTS,
+            "Response to handle 0x{:04x} is {} bytes long, truncating it to {} bytes.",
+            handle,
+            value.len(),
+            RESPONSE_LENGTH
+        );
+    }
+
+    let length = value.len().min(RESPONSE_LENGTH);
+    let mut response = RESPONSE_BUFFER.lock();
+
+    // The union only holds plain data, so writing through its value is sound.
+    #[allow(clippy::cast_possible_truncation)]
+    unsafe {
+        let attribute = &mut response.attr_value;
+        attribute.value[..length].copy_from_slice(&value[..length]);
+        attribute.handle = handle;
+        attribute.offset = offset;
+        attribute.len = length as u16;
+        attribute.auth_req = 0;
+
+        esp_report!(esp_ble_gatts_send_response(
+            gatts_if,
+            conn_id,
+            trans_id,
+            status,
+            &mut **response
+        ))
+    }
+}
@@ -0,0 +1,127 @@
+use log::debug;
+
+use crate::utilities::log_targets::GAP;
+use crate::{
+    gatt_server::{advertisement::check_packet, GattServer},
+    utilities::{Appearance, BleUuid},
+};
+
+/// The content of the scan response, configured independently of the advertisement.
+///
+/// The scan response is sent to the scanners requesting it, so it usually carries
+/// the data that does not fit in the advertisement, like the full device name
+/// or additional manufacturer data.
+#[derive(Debug, Clone, Default)]
+pub struct ScanResponse {
+    include_name: bool,
+    include_tx_power: bool,
+    appearance: Option<Appearance>,
+    manufacturer_data: Vec<u8>,
+    service_data: Vec<u8>,
+    service_uuids: Vec<u8>,
+}
+
+impl ScanResponse {
+    /// Creates a new, empty [`ScanResponse`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Includes the device name in the [`ScanResponse`].
+    #[must_use]
+    pub fn include_name(mut self) -> Self {
+        self.include_name = true;
+        self
+    }
+
+    /// Includes the transmission power level in the [`ScanResponse`].
+    #[must_use]
+    pub fn include_tx_power(mut self) -> Self {
+        self.include_tx_power = true;
+        self
+    }
+
+    /// Includes the given appearance in the [`ScanResponse`].
+    #[must_use]
+    pub fn appearance(mut self, appearance: Appearance) -> Self {
+        self.appearance = Some(appearance);
+        self
+    }
+
+    /// Sets the manufacturer data of the [`ScanResponse`].
+    ///
+    /// The manufacturer data must start with the company identifier, in little-endian order.
+    #[must_use]
+    pub fn manufacturer_data(mut self, data: &[u8]) -> Self {
+        self.manufacturer_data = data.to_vec();
+        self
+    }
+
+    /// Sets the service data of the [`ScanResponse`].
+    ///
+    /// The service data must start with the 16-bit service identifier, in little-endian order.
+    #[must_use]
+    pub fn service_data(mut self, data: &[u8]) -> Self {
+        self.service_data = data.to_vec();
+        self
+    }
+
+    /// Adds a service identifier to the [`ScanResponse`].
+    #[must_use]
+    pub fn service_uuid(mut self, uuid: BleUuid) -> Self {
+        self.service_uuids
+            .extend_from_slice(&uuid.as_uuid128_array());
+        self
+    }
+}
+
+impl GattServer {
+    /// Sets the content of the scan response, replacing the previous one.
+    ///
+    /// The scan response is rejected, and a [`GattServerError::AdvertisementTooLong`] is reported,
+    /// if it does not fit in its packet. If the scan response is already configured,
+    /// it is updated with the new content.
+    ///
+    /// [`GattServerError::AdvertisementTooLong`]: crate::gatt_server::GattServerError::AdvertisementTooLong
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn scan_response(&mut self, mut scan_response: ScanResponse) -> &mut Self {
+        let mut data = self.scan_response_data;
+
+        data.include_name = scan_response.include_name;
+        data.include_txpower = scan_response.include_tx_power;
+        data.appearance = scan_response.appearance.map_or(0, Into::into);
+
+        data.manufacturer_len = scan_response.manufacturer_data.len() as u16;
+        data.p_manufacturer_data = pointer_to(&mut scan_response.manufacturer_data);
+        data.service_data_len = scan_response.service_data.len() as u16;
+        data.p_service_data = pointer_to(&mut scan_response.service_data);
+        data.service_uuid_len = scan_response.service_uuids.len() as u16;
+        data.p_service_uuid = pointer_to(&mut scan_response.service_uuids);
+
+        if let Err(error) = check_packet(&data, &self.device_name) {
+            error.report();
+            return self;
+        }
+
+        debug!(target: GAP, "Setting the scan response to {:?}.", scan_response);
+
+        // The buffers are moved along with the scan response, so the pointers stay valid.
+        self.scan_response_data = data;
+        self.scan_response = scan_response;
+
+        if self.advertisement_configured {
+            self.configure_scan_response_data();
+        }
+
+        self
+    }
+}
+
+fn pointer_to(buffer: &mut Vec<u8>) -> *mut u8 {
+    if buffer.is_empty() {
+        std::ptr::null_mut()
+    } else {
+        buffer.as_mut_ptr()
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use esp_idf_sys::{
+    esp_fill_random, mbedtls_md_hmac, mbedtls_md_info_from_type,
+    mbedtls_md_type_t_MBEDTLS_MD_SHA256,
+};
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::LockedCharacteristic,
+    utilities::{AttributeOperation, Connection},
+};
+
+/// The length of the challenge nonces.
+pub(crate) const NONCE_LENGTH: usize = 16;
+
+/// The length of the expected responses: an HMAC-SHA256 digest.
+const RESPONSE_LENGTH: usize = 32;
+
+/// The default lifetime of a verified session.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(300);
+
+/// The sessions to end when their connection is closed.
+static SESSIONS: Mutex<Vec<Weak<SessionState>>> = Mutex::new(Vec::new());
+
+/// A challenge–response session, verifying that a client knows a shared key,
+/// independently of BLE pairing.
+///
+/// Clients read a random nonce from the challenge characteristic, and write
+/// `HMAC-SHA256(key, nonce)` to the response characteristic. A correct response
+/// verifies the session of the connection until it expires or the client disconnects.
+/// Every nonce can only be answered once.
+///
+/// Commands on other characteristics can be gated on a verified session with [`SecureSession::policy`].
+///
+/// # Notes
+///
+/// The challenge characteristic must be readable, and the response characteristic writable.
+/// Their read and write callbacks are replaced by the session.
+#[derive(Clone)]
+pub struct SecureSession {
+    state: Arc<SessionState>,
+}
+
+struct SessionState {
+    key: Vec<u8>,
+    lifetime: Mutex<Duration>,
+    sessions: Mutex<HashMap<Connection, Session>>,
+}
+
+#[derive(Clone, Copy)]
+enum Session {
+    /// A nonce was issued and waits for its response.
+    Challenged([u8; NONCE_LENGTH]),
+    /// The client answered the challenge of the given nonce at the given instant.
+    Verified(Instant, [u8; NONCE_LENGTH]),
+}
+
+impl SecureSession {
+    /// Creates a new [`SecureSession`] with the given shared key, over the given characteristics.
+    ///
+    /// The maximum value length of the response characteristic is set to the length of the responses,
+    /// so the session must be created before the server is started.
+    #[must_use]
+    pub fn new(
+        key: &[u8],
+        challenge: &LockedCharacteristic,
+        response: &LockedCharacteristic,
+    ) -> Self {
+        let state = Arc::new(SessionState {
+            key: key.to_vec(),
+            lifetime: Mutex::new(DEFAULT_LIFETIME),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let challenge_state = state.clone();
+        challenge.write().on_read(move |param| {
+            let mut nonce = [0u8; NONCE_LENGTH];
+            unsafe { esp_fill_random(nonce.as_mut_ptr().cast(), NONCE_LENGTH) };
+
+            challenge_state
+                .sessions
+                .lock()
+                .insert(Connection::from(param), Session::Challenged(nonce));
+
+            nonce.to_vec()
+        });
+
+        #[allow(clippy::cast_possible_truncation)]
+        response.write().max_value_length(RESPONSE_LENGTH as u16);
+
+        let response_state = state.clone();
+        response.write().on_write(move |value, param| {
+            let connection = Connection::from(param);
+            let mut sessions = response_state.sessions.lock();
+
+            // The nonce is consumed by the first answer, right or wrong.
+            let Some(Session::Challenged(nonce)) = sessions.remove(&connection) else {
+                warn!(
+                    target: GATTS,
+                    "Secure session response from {} without a challenge.",
+                    connection
+                );
+                return;
+            };
+
+            match hmac_sha256(&response_state.key, &nonce) {
+                Some(expected) if constant_time_eq(&expected, &value) => {
+                    debug!(target: GATTS, "Secure session verified for {}.", connection);
+                    sessions.insert(connection, Session::Verified(Instant::now(), nonce));
+                }
+                _ => warn!(target: GATTS, "Secure session verification failed for {}.", connection),
+            }
+        });
+
+        SESSIONS.lock().push(Arc::downgrade(&state));
+
+        Self { state }
+    }
+
+    /// Sets how long a verified session lasts. The default value is five minutes.
+    pub fn lifetime(&mut self, lifetime: Duration) -> &mut Self {
+        *self.state.lifetime.lock() = lifetime;
+        self
+    }
+
+    /// Returns `true` if the given connection has a verified, unexpired session.
+    #[must_use]
+    pub fn is_verified(&self, connection: Connection) -> bool {
+        self.state.is_verified(connection)
+    }
+
+    /// Returns the shared key of this [`SecureSession`].
+    #[cfg(feature = "standard-services")]
+    pub(crate) fn key(&self) -> &[u8] {
+        &self.state.key
+    }
+
+    /// Returns the nonce of the verified session of the given connection.
+    #[cfg(feature = "standard-services")]
+    pub(crate) fn nonce(&self, connection: Connection) -> Option<[u8; NONCE_LENGTH]> {
+        if !self.state.is_verified(connection) {
+            return None;
+        }
+
+        match self.state.sessions.lock().get(&connection) {
+            Some(Session::Verified(_, nonce)) => Some(*nonce),
+            _ => None,
+        }
+    }
+
+    /// Ends the session of the given connection.
+    pub fn end(&self, connection: Connection) {
+        self.state.sessions.lock().remove(&connection);
+    }
+
+    /// Returns an access policy allowing only the connections with a verified session.
+    ///
+    /// The returned value can be passed to [`Characteristic::access_policy`].
+    ///
+    /// [`Characteristic::access_policy`]: crate::gatt_server::Characteristic::access_policy
+    pub fn policy(
+        &self,
+    ) -> impl Fn(&Connection, AttributeOperation) -> bool + Send + Sync + 'static {
+        let state = self.state.clone();
+        move |connection, _| state.is_verified(*connection)
+    }
+}
+
+impl SessionState {
+    fn is_verified(&self, connection: Connection) -> bool {
+        let lifetime = *self.lifetime.lock();
+        let mut sessions = self.sessions.lock();
+
+        match sessions.get(&connection) {
+            Some(Session::Verified(since, _)) if since.elapsed() < lifetime => true,
+            Some(Session::Verified(..)) => {
+                debug!(target: GATTS, "Secure session of {} expired.", connection);
+                sessions.remove(&connection);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for SecureSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecureSession")
+            .field("lifetime", &*self.state.lifetime.lock())
+            .field("sessions", &self.state.sessions.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Ends all the sessions of a closed connection.
+pub(crate) fn end_sessions(conn_id: u16) {
+    SESSIONS.lock().retain(|state| {
+        let Some(state) = state.upgrade() else {
+            return false;
+        };
+
+        state
+            .sessions
+            .lock()
+            .retain(|connection, _| connection.id() != conn_id);
+        true
+    });
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Option<[u8; RESPONSE_LENGTH]> {
+    let mut digest = [0u8; RESPONSE_LENGTH];
+
+    let result = unsafe {
+        mbedtls_md_hmac(
+            mbedtls_md_info_from_type(mbedtls_md_type_t_MBEDTLS_MD_SHA256),
+            key.as_ptr(),
+            key.len(),
+            message.as_ptr(),
+            message.len(),
+            digest.as_mut_ptr(),
+        )
+    };
+
+    if result != 0 {
+        warn!(target: GATTS, "HMAC computation failed with error code {}.", result);
+        return None;
+    }
+
+    Some(digest)
+}
+
+/// Compares two byte strings in a time independent of their contents.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
@@ -0,0 +1,368 @@
+use std::sync::Arc;
+
+use esp_idf_sys::{
+    esp_ble_confirm_reply, esp_ble_gap_cb_param_t, esp_ble_gap_set_security_param,
+    esp_ble_io_cap_t, esp_ble_passkey_reply, esp_ble_sm_param_t,
+    esp_ble_sm_param_t_ESP_BLE_SM_AUTHEN_REQ_MODE, esp_ble_sm_param_t_ESP_BLE_SM_IOCAP_MODE,
+    esp_ble_sm_param_t_ESP_BLE_SM_MAX_KEY_SIZE, esp_ble_sm_param_t_ESP_BLE_SM_SET_INIT_KEY,
+    esp_ble_sm_param_t_ESP_BLE_SM_SET_RSP_KEY, esp_ble_sm_param_t_ESP_BLE_SM_SET_STATIC_PASSKEY,
+    esp_gap_ble_cb_event_t, esp_gap_ble_cb_event_t_ESP_GAP_BLE_NC_REQ_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PASSKEY_NOTIF_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_PASSKEY_REQ_EVT, ESP_BLE_CSR_KEY_MASK, ESP_BLE_ENC_KEY_MASK,
+    ESP_BLE_ID_KEY_MASK, ESP_LE_AUTH_BOND, ESP_LE_AUTH_REQ_MITM, ESP_LE_AUTH_REQ_SC_ONLY,
+};
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+
+use crate::gatt_server::{error::esp_report, GattServer};
+use crate::utilities::{log_targets::GAP, IoCapability};
+
+/// The largest static passkey, as passkeys have six decimal digits.
+const MAX_PASSKEY: u32 = 999_999;
+
+type NumericComparisonCallback = dyn Fn(NumericComparison) + Send + Sync;
+
+/// The callback receiving the numeric comparison requests.
+static NUMERIC_COMPARISON_CALLBACK: RwLock<Option<Arc<NumericComparisonCallback>>> =
+    RwLock::new(None);
+
+/// The configuration of the security manager, which pairs and bonds the server with its peers.
+///
+/// The IO capabilities of the device select the pairing method: with
+/// [`IoCapability::DisplayOnly`] and a [`Security::static_passkey`], the peer pairs by entering
+/// the passkey printed on the device; with [`IoCapability::NoInputNoOutput`], it pairs
+/// without confirmation.
+///
+/// ```ignore
+/// server.security(
+///     Security::new()
+///         .io_capability(IoCapability::DisplayOnly)
+///         .mitm(true)
+///         .static_passkey(123_456),
+/// );
+/// ```
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Security {
+    io_capability: IoCapability,
+    bonding: bool,
+    mitm: bool,
+    secure_connections: bool,
+    encryption_key: bool,
+    identity_key: bool,
+    signing_key: bool,
+    max_key_size: u8,
+    static_passkey: Option<u32>,
+}
+
+impl Security {
+    /// Creates a new [`Security`] configuration: bonding with LE Secure Connections,
+    /// without input nor output, distributing the encryption and identity keys.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            io_capability: IoCapability::NoInputNoOutput,
+            bonding: true,
+            mitm: false,
+            secure_connections: true,
+            encryption_key: true,
+            identity_key: true,
+            signing_key: false,
+            max_key_size: 16,
+            static_passkey: None,
+        }
+    }
+
+    /// Sets the input and output capabilities of the device.
+    #[must_use]
+    pub const fn io_capability(mut self, io_capability: IoCapability) -> Self {
+        self.io_capability = io_capability;
+        self
+    }
+
+    /// Sets whether the keys of the pairing are stored, so that the peer reconnects
+    /// without pairing again.
+    #[must_use]
+    pub const fn bonding(mut self, bonding: bool) -> Self {
+        self.bonding = bonding;
+        self
+    }
+
+    /// Sets whether the pairing must be protected against man-in-the-middle attacks,
+    /// which requires IO capabilities on both devices.
+    #[must_use]
+    pub const fn mitm(mut self, mitm: bool) -> Self {
+        self.mitm = mitm;
+        self
+    }
+
+    /// Sets whether the pairing uses LE Secure Connections rather than LE legacy pairing.
+    #[must_use]
+    pub const fn secure_connections(mut self, secure_connections: bool) -> Self {
+        self.secure_connections = secure_connections;
+        self
+    }
+
+    /// Sets whether the devices exchange their long-term keys, to encrypt the links
+    /// of the next connections.
+    #[must_use]
+    pub const fn encryption_key(mut self, distribute: bool) -> Self {
+        self.encryption_key = distribute;
+        self
+    }
+
+    /// Sets whether the devices exchange their identity resolving keys, to recognise
+    /// each other behind resolvable private addresses.
+    #[must_use]
+    pub const fn identity_key(mut self, distribute: bool) -> Self {
+        self.identity_key = distribute;
+        self
+    }
+
+    /// Sets whether the devices exchange their signature keys, to sign their writes
+    /// over unencrypted links.
+    #[must_use]
+    pub const fn signing_key(mut self, distribute: bool) -> Self {
+        self.signing_key = distribute;
+        self
+    }
+
+    /// Sets the largest encryption key size accepted, between 7 and 16 bytes.
+    #[must_use]
+    pub const fn max_key_size(mut self, size: u8) -> Self {
+        self.max_key_size = if size < 7 {
+            7
+        } else if size > 16 {
+            16
+        } else {
+            size
+        };
+        self
+    }
+
+    /// Sets the passkey displayed by the device, rather than a random one for every pairing.
+    ///
+    /// The passkey has six decimal digits: larger values are ignored.
+    #[must_use]
+    pub const fn static_passkey(mut self, passkey: u32) -> Self {
+        if passkey <= MAX_PASSKEY {
+            self.static_passkey = Some(passkey);
+        }
+        self
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    const fn authentication_requirements(&self) -> u8 {
+        let mut requirements = 0;
+        if self.bonding {
+            requirements |= ESP_LE_AUTH_BOND;
+        }
+        if self.mitm {
+            requirements |= ESP_LE_AUTH_REQ_MITM;
+        }
+        if self.secure_connections {
+            requirements |= ESP_LE_AUTH_REQ_SC_ONLY;
+        }
+        requirements as u8
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    const fn key_distribution(&self) -> u8 {
+        let mut keys = 0;
+        if self.encryption_key {
+            keys |= ESP_BLE_ENC_KEY_MASK;
+        }
+        if self.identity_key {
+            keys |= ESP_BLE_ID_KEY_MASK;
+        }
+        if self.signing_key {
+            keys |= ESP_BLE_CSR_KEY_MASK;
+        }
+        keys as u8
+    }
+}
+
+impl Default for Security {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pending numeric comparison, the pairing method of LE Secure Connections
+/// between two devices able to display a number and confirm it.
+///
+/// Both devices display the same six-digit number: the user checks that they match,
+/// then the pairing is confirmed or rejected with [`NumericComparison::confirm`].
+/// The request can be moved to another task, for example to wait for a button press,
+/// but the pairing fails if it is not answered within the 30 seconds of the protocol.
+#[derive(Debug)]
+#[must_use = "the pairing waits until the numeric comparison is confirmed or rejected"]
+pub struct NumericComparison {
+    address: [u8; 6],
+    number: u32,
+}
+
+impl NumericComparison {
+    /// Returns the address of the peer.
+    #[must_use]
+    pub const fn address(&self) -> [u8; 6] {
+        self.address
+    }
+
+    /// Returns the number to compare with the one displayed by the peer.
+    #[must_use]
+    pub const fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Confirms the pairing if `matches` is `true`, and rejects it otherwise.
+    pub fn confirm(mut self, matches: bool) {
+        info!(
+            target: GAP,
+            "{} the numeric comparison with {:02X?}.",
+            if matches { "Confirming" } else { "Rejecting" },
+            self.address
+        );
+        unsafe {
+            esp_report!(esp_ble_confirm_reply(self.address.as_mut_ptr(), matches));
+        }
+    }
+}
+
+impl GattServer {
+    /// Configures the security manager, which pairs and bonds the server with its peers.
+    ///
+    /// Before the server is started, the configuration is set once the Bluetooth stack
+    /// is initialised.
+    pub fn security(&mut self, security: Security) -> &mut Self {
+        self.security = Some(security);
+
+        if self.started {
+            self.configure_security();
+        }
+
+        self
+    }
+
+    /// Sets a callback receiving the numeric comparison requests of the pairings.
+    ///
+    /// The numeric comparison requires [`IoCapability::DisplayYesNo`] or
+    /// [`IoCapability::KeyboardDisplay`], and LE Secure Connections on both devices.
+    /// Without a callback, the requests are rejected.
+    ///
+    /// ```ignore
+    /// server.on_numeric_comparison(|request| {
+    ///     info!("Does the peer display {:06}?", request.number());
+    ///     request.confirm(button.wait_for_press(Duration::from_secs(20)));
+    /// });
+    /// ```
+    ///
+    /// # Notes
+    ///
+    /// The callback is called from the Bluetooth stack's context, so it must not block:
+    /// move the request to another task to wait for the user.
+    pub fn on_numeric_comparison(
+        &mut self,
+        callback: impl Fn(NumericComparison) + Send + Sync + 'static,
+    ) -> &mut Self {
+        *NUMERIC_COMPARISON_CALLBACK.write() = Some(Arc::new(callback));
+        self
+    }
+
+    /// Hands the security configuration to the Bluetooth stack, if set.
+    pub(crate) fn configure_security(&self) {
+        let Some(security) = self.security else {
+            return;
+        };
+
+        debug!(target: GAP, "Configuring the security manager: {:?}.", security);
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_AUTHEN_REQ_MODE,
+            security.authentication_requirements(),
+        );
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_IOCAP_MODE,
+            esp_ble_io_cap_t::from(security.io_capability),
+        );
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_SET_INIT_KEY,
+            security.key_distribution(),
+        );
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_SET_RSP_KEY,
+            security.key_distribution(),
+        );
+        set_security_param(
+            esp_ble_sm_param_t_ESP_BLE_SM_MAX_KEY_SIZE,
+            security.max_key_size,
+        );
+        if let Some(passkey) = security.static_passkey {
+            set_security_param(esp_ble_sm_param_t_ESP_BLE_SM_SET_STATIC_PASSKEY, passkey);
+        }
+    }
+}
+
+/// Handles the passkey events of the GAP, and returns whether the event was one of them.
+#[allow(non_upper_case_globals)]
+pub(crate) fn gap_event_handler(
+    event: esp_gap_ble_cb_event_t,
+    param: *mut esp_ble_gap_cb_param_t,
+) -> bool {
+    match event {
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_PASSKEY_NOTIF_EVT => {
+            let param = unsafe { (*param).ble_security.key_notif };
+            info!(
+                target: GAP,
+                "Passkey for the pairing with {:02X?}: {:06}.", param.bd_addr, param.passkey
+            );
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_PASSKEY_REQ_EVT => {
+            let mut address = unsafe { (*param).ble_security.ble_req.bd_addr };
+            warn!(
+                target: GAP,
+                "Rejecting the pairing with {:02X?}: no passkey to enter.", address
+            );
+            unsafe {
+                esp_report!(esp_ble_passkey_reply(address.as_mut_ptr(), false, 0));
+            }
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_NC_REQ_EVT => {
+            let param = unsafe { (*param).ble_security.key_notif };
+            let request = NumericComparison {
+                address: param.bd_addr,
+                number: param.passkey,
+            };
+
+            let callback = NUMERIC_COMPARISON_CALLBACK.read().clone();
+            if let Some(callback) = callback {
+                debug!(
+                    target: GAP,
+                    "Numeric comparison with {:02X?}: {:06}.", request.address, request.number
+                );
+                callback(request);
+            } else {
+                warn!(
+                    target: GAP,
+                    "Rejecting the pairing with {:02X?}: no numeric comparison callback.",
+                    request.address
+                );
+                request.confirm(false);
+            }
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn set_security_param<T>(param: esp_ble_sm_param_t, mut value: T) {
+    #[allow(clippy::cast_possible_truncation)]
+    let length = std::mem::size_of::<T>() as u8;
+    unsafe {
+        esp_report!(esp_ble_gap_set_security_param(
+            param,
+            std::ptr::addr_of_mut!(value).cast(),
+            length
+        ));
+    }
+}
@@ -1,8 +1,19 @@
-use crate::{leaky_box_raw, utilities::BleUuid};
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{
+        attribute_table::{AttributeTable, TableBuffers},
+        capacity::{self, Characteristics},
+        custom_attributes::{instance_index, record_cccd, CccdOwner},
+        error::esp_report,
+        registration::RegistrationRetries,
+        GattServerError,
+    },
+    utilities::{sig::descriptors, BleUuid},
+};
 use esp_idf_sys::*;
-use log::debug;
+use log::{debug, warn};
 use parking_lot::RwLock;
-use std::{fmt::Formatter, sync::Arc};
+use std::{borrow::Cow, fmt::Formatter, sync::Arc};
 
 use super::{LockedCharacteristic, LockedDescriptor};
 
@@ -11,12 +22,24 @@ pub type LockedService = Arc<RwLock<Service>>;
 
 /// Represents a GATT service.
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Service {
-    name: Option<String>,
+    pub(crate) name: Option<Cow<'static, str>>,
     pub(crate) uuid: BleUuid,
-    pub(crate) characteristics: Vec<LockedCharacteristic>,
-    primary: bool,
+    pub(crate) characteristics: Characteristics,
+    pub(crate) primary: bool,
     pub(crate) handle: Option<u16>,
+    /// Whether the service should be exposed to the clients.
+    pub(crate) enabled: bool,
+    pub(crate) registration: RegistrationRetries,
+    /// Whether the service is registered as a single attribute table.
+    pub(crate) attribute_table: bool,
+    /// The buffers of the attribute table being registered.
+    pub(crate) table_buffers: TableBuffers,
+    /// The services included in this service.
+    pub(crate) included_services: Vec<LockedService>,
+    /// Whether the include declarations were added, once the included services were registered.
+    pub(crate) includes_added: bool,
 }
 
 impl Service {
@@ -26,9 +49,15 @@ impl Service {
         Self {
             name: None,
             uuid,
-            characteristics: Vec::new(),
+            characteristics: Characteristics::new(),
             primary: false,
             handle: None,
+            enabled: true,
+            registration: RegistrationRetries::new(),
+            attribute_table: false,
+            table_buffers: Vec::new(),
+            included_services: Vec::new(),
+            includes_added: false,
         }
     }
 
@@ -36,7 +65,7 @@ impl Service {
     ///
     /// This name is only used for debugging purposes.
     pub fn name<S: Into<String>>(&mut self, name: S) -> &mut Self {
-        self.name = Some(name.into());
+        self.name = Some(Cow::Owned(name.into()));
         self
     }
 
@@ -48,12 +77,109 @@ impl Service {
         self
     }
 
+    /// Registers the [`Service`] as a single attribute table.
+    ///
+    /// By default, the characteristics and descriptors are registered one after another,
+    /// each waiting for the event of the previous one. With an attribute table,
+    /// the whole service is registered by a single `esp_ble_gatts_create_attr_tab` call,
+    /// which is faster, but limited to 255 attributes.
+    pub fn attribute_table(&mut self) -> &mut Self {
+        self.attribute_table = true;
+        self
+    }
+
+    /// Includes another [`Service`] in the [`Service`].
+    ///
+    /// The included service is usually a secondary service, one that is not set as primary,
+    /// which is only meaningful as a part of the services including it, as in a HID device.
+    /// It must be added to the same profile: its include declaration is added once both
+    /// services are registered, before the characteristics of this service.
+    ///
+    /// Services registered as an attribute table do not support included services.
+    pub fn include_service(&mut self, service: &LockedService) -> &mut Self {
+        self.included_services.push(service.clone());
+        self
+    }
+
     /// Adds a [`Characteristic`] to the [`Service`].
+    ///
+    /// A characteristic added to a service that is already registered is registered right away,
+    /// after the ones being registered, so the GATT table can grow without restarting the server:
+    /// call [`GattServer::indicate_service_changed`](crate::gatt_server::GattServer::indicate_service_changed)
+    /// once it is registered,
+    /// so that the connected clients discover it.
+    /// A service registered as an attribute table cannot grow:
+    /// the registration of the characteristic fails instead.
     pub fn characteristic(&mut self, characteristic: &LockedCharacteristic) -> &mut Self {
-        self.characteristics.push(characteristic.clone());
+        capacity::push(
+            &mut self.characteristics,
+            characteristic.clone(),
+            "characteristics",
+        );
+
+        if self.attribute_table && self.handle.is_some() {
+            let description = characteristic.read().to_string();
+            characteristic.write().registration.give_up(description);
+        } else if self.includes_added {
+            // The characteristics of the service are being registered, or already are.
+            debug!(target: GATTS, "Adding {} to registered {}.", characteristic.read(), &self);
+            self.register_next_characteristic();
+        }
+
+        self
+    }
+
+    /// Returns an iterator over the characteristics of the [`Service`], in declaration order.
+    #[must_use]
+    pub fn characteristics(&self) -> impl ExactSizeIterator<Item = &LockedCharacteristic> {
+        self.characteristics.iter()
+    }
+
+    /// Returns the attribute handle of the [`Service`], once registered in the Bluetooth stack.
+    #[must_use]
+    pub const fn handle(&self) -> Option<u16> {
+        self.handle
+    }
+
+    /// Starts the [`Service`], exposing it to the clients again after [`Service::stop`].
+    ///
+    /// Services are started when registered, so this is only needed after stopping them.
+    pub fn start(&mut self) -> &mut Self {
+        self.enabled = true;
+
+        if let Some(handle) = self.handle {
+            debug!(target: GATTS, "Starting {}.", &self);
+            unsafe {
+                esp_report!(esp_ble_gatts_start_service(handle));
+            }
+        }
+
+        self
+    }
+
+    /// Stops the [`Service`], hiding it from the clients until [`Service::start`] is called,
+    /// without tearing down the GATT server.
+    ///
+    /// A service stopped before the server starts is registered without being started.
+    pub fn stop(&mut self) -> &mut Self {
+        self.enabled = false;
+
+        if let Some(handle) = self.handle {
+            debug!(target: GATTS, "Stopping {}.", &self);
+            unsafe {
+                esp_report!(esp_ble_gatts_stop_service(handle));
+            }
+        }
+
         self
     }
 
+    /// Returns whether the [`Service`] is exposed to the clients, unless stopped.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     /// Returns a reference to the built [`Service`] behind an `Arc` and an `RwLock`.
     ///
     /// The returned value can be passed to any function of this crate that expects a [`Service`].
@@ -70,13 +196,23 @@ impl Service {
             .cloned()
     }
 
+    /// Returns the characteristic being registered with the given identifier.
+    ///
+    /// A service might contain several characteristics with the same identifier.
+    /// Since they are registered one after another, the registration event refers to
+    /// the first one that is not registered yet.
     pub(crate) fn get_characteristic_by_id(
         &self,
         id: esp_bt_uuid_t,
     ) -> Option<LockedCharacteristic> {
         self.characteristics
             .iter()
-            .find(|characteristic| characteristic.read().uuid == id.into())
+            .find(|characteristic| {
+                let characteristic = characteristic.read();
+                characteristic.uuid == id
+                    && characteristic.attribute_handle.is_none()
+                    && !characteristic.registration.failed()
+            })
             .cloned()
     }
 
@@ -86,53 +222,208 @@ impl Service {
             .filter_map(|characteristic| {
                 characteristic
                     .read()
-                    .clone()
                     .descriptors
-                    .into_iter()
-                    .find(|descriptor| descriptor.read().uuid == id.into())
+                    .iter()
+                    .find(|descriptor| descriptor.read().uuid == id)
+                    .cloned()
             })
             .collect()
     }
 
     pub(crate) fn register_self(&mut self, interface: u8) {
-        debug!("Registering {} on interface {}.", &self, interface);
+        debug!(target: GATTS, "Registering {} on interface {}.", &self, interface);
+        self.registration.requested();
+
+        if self.attribute_table {
+            self.register_attribute_table(interface);
+            return;
+        }
 
-        let id: esp_gatt_srvc_id_t = esp_gatt_srvc_id_t {
+        // The Bluetooth stack copies the identifier before the call returns.
+        let mut id: esp_gatt_srvc_id_t = esp_gatt_srvc_id_t {
             id: self.uuid.into(),
             is_primary: self.primary,
         };
 
         unsafe {
-            esp_nofail!(esp_ble_gatts_create_service(
-                interface,
-                leaky_box_raw!(id),
+            esp_report!(esp_ble_gatts_create_service(
+                interface, &mut id,
                 256, // TODO: count the number of characteristics and descriptors.
             ));
         }
     }
 
-    pub(crate) fn register_characteristics(&mut self) {
-        debug!("Registering {}'s characteristics.", &self);
+    fn register_attribute_table(&mut self, interface: u8) {
+        if !self.included_services.is_empty() {
+            warn!(
+                target: GATTS,
+                "{} is registered as an attribute table, without its included services.",
+                &self
+            );
+        }
+
+        let mut table = AttributeTable::new(self.uuid, self.primary);
+        self.characteristics.iter().for_each(|characteristic| {
+            characteristic.write().add_to_table(&mut table);
+        });
 
-        // Attention: The characteristics should be registered one after another.
-        // We need to wait for the previous characteristic to be registered before we can register the next one.
+        debug!(
+            target: GATTS,
+            "Registering {} as a table of {} attributes.",
+            &self,
+            table.len()
+        );
 
-        if self.characteristics.is_empty() {
-            return;
+        match table.create(interface) {
+            Ok(buffers) => self.table_buffers = buffers,
+            Err(error) => {
+                error.report();
+                let description = self.to_string();
+                self.registration.give_up(description);
+            }
         }
+    }
+
+    /// Records the characteristics the registered CCCDs of the [`Service`] belong to,
+    /// for the storage of their values.
+    ///
+    /// `instance` tells the [`Service`] apart from the services of its profile sharing its UUID.
+    pub(crate) fn record_cccds(&self, instance: u8) {
+        let uuids: Vec<BleUuid> = self
+            .characteristics
+            .iter()
+            .map(|characteristic| characteristic.read().uuid)
+            .collect();
+
+        for (index, characteristic) in self.characteristics.iter().enumerate() {
+            let characteristic = characteristic.read();
+            let owner = CccdOwner {
+                service: self.uuid,
+                service_instance: instance,
+                characteristic: characteristic.uuid,
+                characteristic_instance: instance_index(&uuids, index),
+            };
 
-        // Loghi docet.
+            for descriptor in &characteristic.descriptors {
+                let descriptor = descriptor.read();
+                if descriptor.uuid != descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION {
+                    continue;
+                }
 
-        let service_handle = self.handle.unwrap();
-        let characteristics = self.characteristics.clone();
-        std::thread::spawn(move || {
-            for c in characteristics {
-                c.write().register_self(service_handle);
-                while c.read().attribute_handle.is_none() {
-                    std::thread::yield_now();
+                if let Some(handle) = descriptor.attribute_handle {
+                    record_cccd(handle, owner);
                 }
             }
-        });
+        }
+    }
+
+    /// Returns the number of attributes of the [`Service`], once registered as an attribute table.
+    pub(crate) fn table_length(&self) -> usize {
+        1 + self
+            .characteristics
+            .iter()
+            .map(|characteristic| 2 + characteristic.read().descriptors.len())
+            .sum::<usize>()
+    }
+
+    /// Assigns the handles of the registered attribute table to the characteristics
+    /// and descriptors of the [`Service`], in the order they were added to the table.
+    pub(crate) fn assign_table_handles(&mut self, interface: u8, handles: &[u16]) {
+        self.table_buffers = Vec::new();
+
+        let mut handles = handles.iter().copied();
+        self.handle = handles.next();
+
+        for characteristic in &self.characteristics {
+            // Skip the characteristic declaration.
+            handles.next();
+
+            let mut characteristic = characteristic.write();
+            characteristic.attribute_handle = handles.next();
+            characteristic.service_handle = self.handle;
+            characteristic.interface = Some(interface);
+
+            for descriptor in &characteristic.descriptors {
+                descriptor.write().attribute_handle = handles.next();
+            }
+        }
+    }
+
+    /// Adds the include declarations of the [`Service`], then registers its characteristics,
+    /// once the service and the services it includes are registered.
+    ///
+    /// Does nothing while an included service is not registered: its registration
+    /// calls this function again.
+    pub(crate) fn register_contents(&mut self) {
+        let Some(service_handle) = self.handle else {
+            GattServerError::NotRegistered(self.to_string()).report();
+            return;
+        };
+
+        if self.includes_added {
+            return;
+        }
+
+        let mut included_handles = Vec::with_capacity(self.included_services.len());
+        for included in &self.included_services {
+            let Some(handle) = included.read().handle else {
+                debug!(
+                    target: GATTS,
+                    "{} waits for {} to be registered.",
+                    &self,
+                    included.read()
+                );
+                return;
+            };
+            included_handles.push(handle);
+        }
+
+        self.includes_added = true;
+        for handle in included_handles {
+            debug!(
+                target: GATTS,
+                "Including service at handle 0x{:04x} in {}.", handle, &self
+            );
+            unsafe {
+                esp_report!(esp_ble_gatts_add_included_service(service_handle, handle));
+            }
+        }
+
+        self.register_characteristics();
+    }
+
+    /// Starts registering the characteristics of the [`Service`], once the service is registered.
+    fn register_characteristics(&self) {
+        debug!(target: GATTS, "Registering {}'s characteristics.", &self);
+        self.register_next_characteristic();
+    }
+
+    /// Registers the first characteristic of the [`Service`] that is not registered yet.
+    ///
+    /// Bluedroid attaches characteristics and descriptors to the latest registered attribute,
+    /// so the characteristics are registered one after another: the registration event
+    /// of a characteristic, or its final failure, registers the next one.
+    /// Nothing is registered while a characteristic is waiting for its event or for a retry.
+    pub(crate) fn register_next_characteristic(&self) {
+        let Some(service_handle) = self.handle else {
+            GattServerError::NotRegistered(self.to_string()).report();
+            return;
+        };
+
+        for characteristic in &self.characteristics {
+            let mut characteristic = characteristic.write();
+            if characteristic.attribute_handle.is_some() || characteristic.registration.failed() {
+                continue;
+            }
+
+            if !characteristic.registration.is_requested() {
+                characteristic.register_self(service_handle);
+            }
+
+            return;
+        }
+
+        debug!(target: GATTS, "{}'s characteristics are registered.", &self);
     }
 }
 
@@ -141,9 +432,7 @@ impl std::fmt::Display for Service {
         write!(
             f,
             "{} ({})",
-            self.name
-                .clone()
-                .unwrap_or_else(|| "Unnamed service".to_string()),
+            self.name.as_deref().unwrap_or("Unnamed service"),
             self.uuid,
         )
     }
@@ -0,0 +1,63 @@
+use esp_idf_sys::esp_ble_gatts_send_service_change_indication;
+use log::info;
+
+use crate::gatt_server::{error::esp_check, GattServer, GattServerError};
+use crate::utilities::log_targets::GATTS;
+use crate::utilities::Connection;
+use crate::BluedroidError;
+
+impl GattServer {
+    /// Indicates the Service Changed characteristic (0x2A05) of the Generic Attribute
+    /// service (0x1801), so that the clients discover the GATT table again.
+    ///
+    /// The indication is sent to the given connection, or to every connected client.
+    /// Call it after modifying the GATT table of a started server, for example after adding
+    /// a characteristic with [`Service::characteristic`](crate::gatt_server::Service::characteristic),
+    /// or the clients keep using the handles they cached.
+    ///
+    /// # Notes
+    ///
+    /// The Generic Attribute service and its Service Changed characteristic are part of
+    /// the Bluetooth stack, which registers them before any profile.
+    /// Depending on `CONFIG_BT_GATTS_SEND_SERVICE_CHANGE_MODE`, the stack also sends
+    /// this indication by itself when services are added or removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no profile is registered yet, or if the Bluetooth stack
+    /// fails to send the indication.
+    pub fn indicate_service_changed(
+        &self,
+        connection: Option<Connection>,
+    ) -> Result<(), BluedroidError> {
+        let Some(interface) = self
+            .profiles
+            .iter()
+            .find_map(|profile| profile.read().interface)
+        else {
+            return Err(GattServerError::NotRegistered(String::from("GATT server")).into());
+        };
+
+        let mut remote_bda = connection.map(|connection| connection.remote_bda());
+        let remote_bda = remote_bda
+            .as_mut()
+            .map_or(std::ptr::null_mut(), |bda| bda.as_mut_ptr());
+
+        info!(
+            target: GATTS,
+            "Indicating a service change to {}.",
+            connection.map_or_else(
+                || String::from("every client"),
+                |connection| format!("{:02X?}", connection.remote_bda())
+            )
+        );
+
+        unsafe {
+            esp_check!(esp_ble_gatts_send_service_change_indication(
+                interface, remote_bda
+            ))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+
+use esp_idf_sys::{esp_gatt_char_prop_t, esp_gatt_perm_t};
+
+use crate::{
+    gatt_server::{
+        GattServer, LockedCharacteristic, LockedDescriptor, LockedProfile, LockedService,
+    },
+    utilities::BleUuid,
+};
+
+/// A plain-data copy of the GATT tree of a [`GattServer`].
+///
+/// The snapshot holds no lock nor reference to the GATT server, so it can be kept
+/// or handed to another task, for example to display the attribute database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GattTreeSnapshot {
+    /// The profiles of the GATT server, in declaration order.
+    pub profiles: Vec<ProfileSnapshot>,
+}
+
+/// A plain-data copy of a [`Profile`](crate::gatt_server::Profile).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileSnapshot {
+    /// The application identifier of the profile.
+    pub identifier: u16,
+    /// The name of the profile.
+    pub name: Option<String>,
+    /// The GATT interface assigned to the profile, once registered.
+    pub interface: Option<u8>,
+    /// The services of the profile, in declaration order.
+    pub services: Vec<ServiceSnapshot>,
+}
+
+/// A plain-data copy of a [`Service`](crate::gatt_server::Service).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceSnapshot {
+    /// The identifier of the service.
+    pub uuid: BleUuid,
+    /// The name of the service.
+    pub name: Option<String>,
+    /// Whether the service is primary.
+    pub primary: bool,
+    /// The attribute handle of the service, once registered.
+    pub handle: Option<u16>,
+    /// The characteristics of the service, in declaration order.
+    pub characteristics: Vec<CharacteristicSnapshot>,
+}
+
+/// A plain-data copy of a [`Characteristic`](crate::gatt_server::Characteristic).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacteristicSnapshot {
+    /// The identifier of the characteristic.
+    pub uuid: BleUuid,
+    /// The name of the characteristic.
+    pub name: Option<String>,
+    /// The attribute handle of the characteristic value, once registered.
+    pub handle: Option<u16>,
+    /// The properties of the characteristic, as an `ESP_GATT_CHAR_PROP_BIT_*` bit mask.
+    pub properties: esp_gatt_char_prop_t,
+    /// The permissions of the characteristic, as an `ESP_GATT_PERM_*` bit mask.
+    pub permissions: esp_gatt_perm_t,
+    /// The descriptors of the characteristic, in declaration order.
+    pub descriptors: Vec<DescriptorSnapshot>,
+}
+
+/// A plain-data copy of a [`Descriptor`](crate::gatt_server::Descriptor).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorSnapshot {
+    /// The identifier of the descriptor.
+    pub uuid: BleUuid,
+    /// The name of the descriptor.
+    pub name: Option<String>,
+    /// The attribute handle of the descriptor, once registered.
+    pub handle: Option<u16>,
+    /// The permissions of the descriptor, as an `ESP_GATT_PERM_*` bit mask.
+    pub permissions: esp_gatt_perm_t,
+}
+
+impl GattServer {
+    /// Returns a plain-data copy of the GATT tree of the server.
+    ///
+    /// Every attribute is locked in turn while it is copied, and released before the next one.
+    #[must_use]
+    pub fn snapshot(&self) -> GattTreeSnapshot {
+        GattTreeSnapshot {
+            profiles: self.profiles.iter().map(ProfileSnapshot::from).collect(),
+        }
+    }
+}
+
+impl From<&LockedProfile> for ProfileSnapshot {
+    fn from(profile: &LockedProfile) -> Self {
+        let services = profile.read().services.clone();
+        let profile = profile.read();
+
+        Self {
+            identifier: profile.identifier,
+            name: profile.name.clone(),
+            interface: profile.interface,
+            services: services.iter().map(ServiceSnapshot::from).collect(),
+        }
+    }
+}
+
+impl From<&LockedService> for ServiceSnapshot {
+    fn from(service: &LockedService) -> Self {
+        let service = service.read().clone();
+
+        Self {
+            uuid: service.uuid,
+            name: service.name.map(Cow::into_owned),
+            primary: service.primary,
+            handle: service.handle,
+            characteristics: service
+                .characteristics
+                .iter()
+                .map(CharacteristicSnapshot::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&LockedCharacteristic> for CharacteristicSnapshot {
+    fn from(characteristic: &LockedCharacteristic) -> Self {
+        let (uuid, name, handle, properties, permissions, descriptors) = {
+            let characteristic = characteristic.read();
+            (
+                characteristic.uuid,
+                characteristic.name.as_deref().map(String::from),
+                characteristic.attribute_handle,
+                characteristic.properties.into(),
+                characteristic.permissions.into(),
+                characteristic.descriptors.clone(),
+            )
+        };
+
+        Self {
+            uuid,
+            name,
+            handle,
+            properties,
+            permissions,
+            descriptors: descriptors.iter().map(DescriptorSnapshot::from).collect(),
+        }
+    }
+}
+
+impl From<&LockedDescriptor> for DescriptorSnapshot {
+    fn from(descriptor: &LockedDescriptor) -> Self {
+        let descriptor = descriptor.read();
+
+        Self {
+            uuid: descriptor.uuid,
+            name: descriptor.name.as_deref().map(String::from),
+            handle: descriptor.attribute_handle,
+            permissions: descriptor.permissions.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for GattTreeSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for profile in &self.profiles {
+            writeln!(
+                f,
+                "{} (0x{:04x})",
+                profile.name.as_deref().unwrap_or("Unnamed profile"),
+                profile.identifier
+            )?;
+
+            for service in &profile.services {
+                writeln!(
+                    f,
+                    "  {} ({}){} at {}",
+                    Name(service.name.as_deref(), service.uuid, "service"),
+                    service.uuid,
+                    if service.primary { ", primary" } else { "" },
+                    Handle(service.handle)
+                )?;
+
+                for characteristic in &service.characteristics {
+                    writeln!(
+                        f,
+                        "    {} ({}) at {}, properties 0x{:02x}, permissions 0x{:04x}",
+                        Name(
+                            characteristic.name.as_deref(),
+                            characteristic.uuid,
+                            "characteristic"
+                        ),
+                        characteristic.uuid,
+                        Handle(characteristic.handle),
+                        characteristic.properties,
+                        characteristic.permissions
+                    )?;
+
+                    for descriptor in &characteristic.descriptors {
+                        writeln!(
+                            f,
+                            "      {} ({}) at {}, permissions 0x{:04x}",
+                            Name(descriptor.name.as_deref(), descriptor.uuid, "descriptor"),
+                            descriptor.uuid,
+                            Handle(descriptor.handle),
+                            descriptor.permissions
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays an attribute handle, or its absence before registration.
+struct Handle(Option<u16>);
+
+impl std::fmt::Display for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(handle) => write!(f, "0x{handle:04x}"),
+            None => write!(f, "no handle"),
+        }
+    }
+}
+
+/// Displays the name of an attribute, its assigned name if it has none, or its absence.
+struct Name<'a>(Option<&'a str>, BleUuid, &'static str);
+
+impl std::fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.0, self.1.name()) {
+            (Some(name), _) | (None, Some(name)) => write!(f, "{name}"),
+            (None, None) => write!(f, "Unnamed {}", self.2),
+        }
+    }
+}
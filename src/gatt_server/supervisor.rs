@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+#[allow(clippy::wildcard_imports)]
+use esp_idf_sys::*;
+use log::{info, warn};
+
+use crate::gatt_server::{
+    delivery::DELIVERY_QUEUE,
+    error::{esp_report, take_stack_errors},
+    indication::PENDING_INDICATIONS,
+    registration::RegistrationRetries,
+    worker, GattServer, GLOBAL_GATT_SERVER,
+};
+use crate::utilities::log_targets::GATTS;
+
+impl GattServer {
+    /// Supervises the Bluetooth stack, re-creating the GATT server when the stack fails.
+    ///
+    /// Every `interval`, the supervisor checks that the Bluetooth controller and the Bluedroid host
+    /// are still enabled, and that no more than `max_stack_errors` Bluetooth stack functions failed
+    /// since the previous check. Otherwise, the Bluetooth stack is torn down and initialised again,
+    /// the profiles, services, characteristics and descriptors of this server are registered again,
+    /// and advertising restarts, without rebooting the device.
+    ///
+    /// Active connections are dropped, and pending deliveries are reported as
+    /// [`DeliveryOutcome::Disconnected`](crate::utilities::DeliveryOutcome::Disconnected).
+    pub fn supervise(&mut self, interval: Duration, max_stack_errors: u32) -> &mut Self {
+        if self.supervised {
+            warn!(target: GATTS, "GATT server already supervised.");
+            return self;
+        }
+
+        self.supervised = true;
+        worker::schedule_periodic(interval, move || {
+            let stack_errors = take_stack_errors();
+            if !GLOBAL_GATT_SERVER.lock().started {
+                return true;
+            }
+
+            let healthy = Self::is_stack_enabled();
+            if healthy && stack_errors <= max_stack_errors {
+                return true;
+            }
+
+            warn!(
+                target: GATTS,
+                "Bluetooth stack failure detected (stack enabled: {}, {} stack errors). Restarting the GATT server.",
+                healthy, stack_errors
+            );
+            Self::recover();
+            true
+        });
+
+        self
+    }
+
+    fn is_stack_enabled() -> bool {
+        unsafe {
+            esp_bt_controller_get_status()
+                == esp_bt_controller_status_t_ESP_BT_CONTROLLER_STATUS_ENABLED
+                && esp_bluedroid_get_status() == esp_bluedroid_status_t_ESP_BLUEDROID_STATUS_ENABLED
+        }
+    }
+
+    fn recover() {
+        // Do not hold the server lock during the teardown:
+        // the Bluetooth tasks might still need it to handle their last events.
+        unsafe {
+            if esp_bluedroid_get_status() == esp_bluedroid_status_t_ESP_BLUEDROID_STATUS_ENABLED {
+                esp_report!(esp_bluedroid_disable());
+            }
+            if esp_bluedroid_get_status()
+                != esp_bluedroid_status_t_ESP_BLUEDROID_STATUS_UNINITIALIZED
+            {
+                esp_report!(esp_bluedroid_deinit());
+            }
+            if esp_bt_controller_get_status()
+                == esp_bt_controller_status_t_ESP_BT_CONTROLLER_STATUS_ENABLED
+            {
+                esp_report!(esp_bt_controller_disable());
+            }
+            if esp_bt_controller_get_status()
+                != esp_bt_controller_status_t_ESP_BT_CONTROLLER_STATUS_IDLE
+            {
+                esp_report!(esp_bt_controller_deinit());
+            }
+        }
+
+        // The teardown failures must not trigger another recovery.
+        take_stack_errors();
+
+        let mut server = GLOBAL_GATT_SERVER.lock();
+        server.reset_registration();
+        if let Err(error) = server.start() {
+            warn!(target: GATTS, "Restarting the GATT server failed: {}.", error);
+            return;
+        }
+        info!(target: GATTS, "GATT server restarted.");
+    }
+
+    /// Forgets everything the previous Bluetooth stack instance assigned to this server.
+    fn reset_registration(&mut self) {
+        for connection in std::mem::take(&mut self.active_connections).iter() {
+            PENDING_INDICATIONS.abort_connection(connection.id());
+            DELIVERY_QUEUE.abort_connection(connection.id());
+        }
+
+        self.started = false;
+        self.advertisement_configured = false;
+
+        for profile in &self.profiles {
+            let mut profile = profile.write();
+            profile.interface = None;
+            profile.registration = RegistrationRetries::new();
+
+            for service in &profile.services {
+                let mut service = service.write();
+                service.handle = None;
+                service.registration = RegistrationRetries::new();
+                service.includes_added = false;
+
+                for characteristic in &service.characteristics {
+                    let mut characteristic = characteristic.write();
+                    characteristic.attribute_handle = None;
+                    characteristic.interface = None;
+                    characteristic.broadcast_enabled = false;
+                    characteristic.registration = RegistrationRetries::new();
+
+                    for descriptor in &characteristic.descriptors {
+                        let mut descriptor = descriptor.write();
+                        descriptor.attribute_handle = None;
+                        descriptor.registration = RegistrationRetries::new();
+                    }
+                }
+            }
+        }
+    }
+}
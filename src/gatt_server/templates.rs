@@ -0,0 +1,205 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::{debug, warn};
+use parking_lot::RwLock;
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{Characteristic, Descriptor, LockedCharacteristic},
+    utilities::{
+        AttributePermissions, BleUuid, CharacteristicProperties, Connection, PresentationFormat,
+    },
+};
+
+/// The opcode of the responses sent on the status characteristic of a [`ControlPoint`].
+const RESPONSE_OPCODE: u8 = 0x80;
+
+/// The result code of a successful control point request.
+const RESULT_SUCCESS: u8 = 0x01;
+
+type OpcodeHandler = dyn Fn(Connection, &[u8]) -> Result<Vec<u8>, ControlPointError> + Send + Sync;
+
+/// The errors a [`ControlPoint`] request can fail with, sent back to the client
+/// as the result code of the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ControlPointError {
+    /// The opcode is not supported. Sent for the opcodes without a handler.
+    OpcodeNotSupported = 0x02,
+    /// The parameters of the request are invalid.
+    InvalidParameter = 0x03,
+    /// The request was valid, but could not be carried out.
+    OperationFailed = 0x04,
+}
+
+impl Characteristic {
+    /// Creates a readable, notifying [`Characteristic`] along with its CCCD,
+    /// a [presentation format](Descriptor::presentation_format) and a
+    /// [user description](Descriptor::user_description).
+    ///
+    /// This is the usual shape of a measurement exposed to generic clients,
+    /// which can then display the value without knowing the characteristic.
+    ///
+    /// # Notes
+    ///
+    /// Setting other properties afterwards replaces the "read" and "notify" ones.
+    #[must_use]
+    pub fn formatted_value<S: AsRef<str>>(
+        uuid: BleUuid,
+        description: S,
+        format: PresentationFormat,
+    ) -> Self {
+        Self::new(uuid)
+            .name(description.as_ref())
+            .permissions(AttributePermissions::new().read())
+            .properties(CharacteristicProperties::new().read().notify())
+            .descriptor(&Descriptor::cccd().build())
+            .descriptor(&Descriptor::presentation_format(format).build())
+            .descriptor(&Descriptor::user_description(description).build())
+            .clone()
+    }
+}
+
+/// A control point characteristic paired with a status characteristic.
+///
+/// Clients write requests to the control point: the first byte is the opcode,
+/// and the remaining bytes are the parameters. Each request is handed to the handler of its
+/// opcode, registered with [`ControlPoint::on_opcode`], and the outcome is indicated
+/// to the requesting client on the status characteristic, as a response made of:
+///
+/// - the response opcode, `0x80`;
+/// - the opcode of the request;
+/// - the result code: `0x01` on success, or a [`ControlPointError`];
+/// - on success, the bytes returned by the handler.
+///
+/// # Notes
+///
+/// Clients must enable indications on the status characteristic before writing requests.
+/// Both characteristics must be added to the same [`Service`](crate::gatt_server::Service).
+#[derive(Clone)]
+pub struct ControlPoint {
+    control: LockedCharacteristic,
+    status: LockedCharacteristic,
+    handlers: Arc<RwLock<HashMap<u8, Arc<OpcodeHandler>>>>,
+}
+
+impl ControlPoint {
+    /// Creates a new [`ControlPoint`], with the given control point and status characteristic UUIDs.
+    ///
+    /// `max_request_length` is the length of the longest request the control point accepts,
+    /// opcode included. The Bluetooth stack rejects longer writes.
+    #[must_use]
+    pub fn new(control_uuid: BleUuid, status_uuid: BleUuid, max_request_length: u16) -> Self {
+        let status = Characteristic::new(status_uuid)
+            .name("Control Point Status")
+            .permissions(AttributePermissions::new())
+            .properties(CharacteristicProperties::new().indicate())
+            .build();
+
+        let handlers: Arc<RwLock<HashMap<u8, Arc<OpcodeHandler>>>> = Arc::default();
+
+        let control_handlers = handlers.clone();
+        let control_status = status.clone();
+        let control = Characteristic::new(control_uuid)
+            .name("Control Point")
+            .permissions(AttributePermissions::new().write())
+            .properties(CharacteristicProperties::new().write())
+            .max_value_length(max_request_length)
+            .on_write(move |value, param| {
+                dispatch(
+                    &control_handlers,
+                    &control_status,
+                    Connection::from(param),
+                    &value,
+                );
+            })
+            .build();
+
+        Self {
+            control,
+            status,
+            handlers,
+        }
+    }
+
+    /// Sets the handler of the requests with the given opcode, replacing the previous one.
+    ///
+    /// The handler receives the parameters of the request, and returns the parameters
+    /// of the response or the error to report.
+    ///
+    /// # Notes
+    ///
+    /// The handler will be called from the Bluetooth stack's context, so it must not block.
+    pub fn on_opcode(
+        &mut self,
+        opcode: u8,
+        handler: impl Fn(Connection, &[u8]) -> Result<Vec<u8>, ControlPointError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.handlers.write().insert(opcode, Arc::new(handler));
+        self
+    }
+
+    /// Returns the control point characteristic, to be added to a [`Service`](crate::gatt_server::Service).
+    #[must_use]
+    pub fn control(&self) -> LockedCharacteristic {
+        self.control.clone()
+    }
+
+    /// Returns the status characteristic, to be added to a [`Service`](crate::gatt_server::Service).
+    #[must_use]
+    pub fn status(&self) -> LockedCharacteristic {
+        self.status.clone()
+    }
+}
+
+impl std::fmt::Debug for ControlPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut opcodes: Vec<u8> = self.handlers.read().keys().copied().collect();
+        opcodes.sort_unstable();
+
+        f.debug_struct("ControlPoint")
+            .field("control", &self.control.read().to_string())
+            .field("status", &self.status.read().to_string())
+            .field("opcodes", &opcodes)
+            .finish()
+    }
+}
+
+/// Hands a request to the handler of its opcode, and indicates the response to the client.
+fn dispatch(
+    handlers: &RwLock<HashMap<u8, Arc<OpcodeHandler>>>,
+    status: &LockedCharacteristic,
+    connection: Connection,
+    request: &[u8],
+) {
+    let Some((&opcode, parameters)) = request.split_first() else {
+        warn!(target: GATTS, "Ignoring an empty control point request from {}.", connection);
+        return;
+    };
+
+    debug!(
+        target: GATTS,
+        "Control point request {:02X} from {}.",
+        opcode, connection
+    );
+
+    let handler = handlers.read().get(&opcode).cloned();
+    let outcome = match handler {
+        Some(handler) => handler(connection, parameters),
+        None => Err(ControlPointError::OpcodeNotSupported),
+    };
+
+    let response = match outcome {
+        Ok(mut parameters) => {
+            let mut response = vec![RESPONSE_OPCODE, opcode, RESULT_SUCCESS];
+            response.append(&mut parameters);
+            response
+        }
+        Err(error) => vec![RESPONSE_OPCODE, opcode, error as u8],
+    };
+
+    status.read().queue_value(connection, response, None);
+}
@@ -0,0 +1,102 @@
+use std::{sync::Arc, time::Instant};
+
+use log::{debug, warn};
+
+use crate::utilities::log_targets::GATTS;
+use crate::{
+    gatt_server::{GattServer, LockedCharacteristic},
+    utilities::ToGattValue,
+};
+
+/// A set of characteristic value changes that are applied and notified together.
+///
+/// See [`GattServer::transaction`].
+#[derive(Default)]
+pub struct Transaction {
+    values: Vec<(LockedCharacteristic, Vec<u8>)>,
+}
+
+impl Transaction {
+    /// Sets the value of a [`Characteristic`] as part of this transaction.
+    ///
+    /// The value is staged, and applied when the transaction is committed.
+    /// Setting the same characteristic twice keeps the latest value.
+    ///
+    /// [`Characteristic`]: crate::gatt_server::Characteristic
+    pub fn set<T: ToGattValue>(
+        &mut self,
+        characteristic: &LockedCharacteristic,
+        value: T,
+    ) -> &mut Self {
+        let value = value.to_gatt_value();
+
+        if let Some((_, staged)) = self
+            .values
+            .iter_mut()
+            .find(|(other, _)| Arc::ptr_eq(other, characteristic))
+        {
+            *staged = value;
+        } else {
+            self.values.push((characteristic.clone(), value));
+        }
+
+        self
+    }
+}
+
+impl GattServer {
+    /// Updates the values of several characteristics together.
+    ///
+    /// The values are staged while `f` runs, then handed to the Bluetooth stack one after
+    /// the other, and the notifications and indications are sent once all of them are set:
+    /// subscribed clients never receive an intermediate state across related characteristics.
+    /// A client reading the characteristics while the transaction is committed can still observe
+    /// some of the new values only, because the stack applies them one by one.
+    ///
+    /// A value that cannot be set, for example because it is too long, is logged and skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// server.lock().transaction(|tx| {
+    ///     tx.set(&latitude, 45.07f32);
+    ///     tx.set(&longitude, 7.68f32);
+    /// });
+    /// ```
+    pub fn transaction<F: FnOnce(&mut Transaction)>(&self, f: F) {
+        let mut transaction = Transaction::default();
+        f(&mut transaction);
+
+        debug!(
+            target: GATTS,
+            "Committing transaction on {} characteristics.",
+            transaction.values.len()
+        );
+
+        let mut changed = Vec::with_capacity(transaction.values.len());
+
+        for (characteristic, value) in transaction.values {
+            let mut guard = characteristic.write();
+
+            if let Err(error) = guard.try_set_value(value) {
+                warn!(target: GATTS, "Cannot set the value of {} in a transaction: {}.", guard, error);
+                continue;
+            }
+
+            // The value change event of a registered characteristic must not trigger notifications.
+            // It cannot arrive before the guard is released.
+            if guard.attribute_handle.is_some() {
+                guard.deferred_notifications += 1;
+            }
+
+            drop(guard);
+            changed.push(characteristic);
+        }
+
+        for characteristic in changed {
+            let mut characteristic = characteristic.write();
+            characteristic.last_notification = Some(Instant::now());
+            characteristic.send_notifications(&self.active_connections);
+        }
+    }
+}
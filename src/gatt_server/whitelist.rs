@@ -0,0 +1,113 @@
+use esp_idf_sys::{
+    esp_ble_adv_filter_t, esp_ble_adv_filter_t_ADV_FILTER_ALLOW_SCAN_ANY_CON_WLST,
+    esp_ble_adv_filter_t_ADV_FILTER_ALLOW_SCAN_WLST_CON_ANY,
+};
+use log::warn;
+
+use crate::ble_stack;
+use crate::gatt_server::GattServer;
+use crate::utilities::{log_targets::GAP, AddressType};
+
+impl GattServer {
+    /// Adds a device to the whitelist of the controller.
+    ///
+    /// The whitelist restricts the centrals that can connect or send scan requests,
+    /// see [`GattServer::connections_from_whitelist_only`]. It is shared with the scanner.
+    /// Before the server is started, the device is added once the Bluetooth stack
+    /// is initialised.
+    pub fn add_to_whitelist(&mut self, address: [u8; 6], address_type: AddressType) -> &mut Self {
+        if self.started {
+            update_whitelist(true, address, address_type);
+        } else {
+            self.whitelist.push((address, address_type));
+        }
+
+        self
+    }
+
+    /// Removes a device from the whitelist of the controller.
+    pub fn remove_from_whitelist(
+        &mut self,
+        address: [u8; 6],
+        address_type: AddressType,
+    ) -> &mut Self {
+        self.whitelist
+            .retain(|entry| *entry != (address, address_type));
+
+        if self.started {
+            update_whitelist(false, address, address_type);
+        }
+
+        self
+    }
+
+    /// Removes every device from the whitelist of the controller.
+    pub fn clear_whitelist(&mut self) -> &mut Self {
+        self.whitelist.clear();
+
+        if self.started {
+            if let Err(error) = ble_stack::clear_whitelist() {
+                warn!(target: GAP, "Clearing the whitelist failed: {}.", error);
+            }
+        }
+
+        self
+    }
+
+    /// Sets whether only the centrals of the whitelist can connect to the server.
+    ///
+    /// Once the server is started, the filter is used the next time the legacy advertisement
+    /// starts, and the extended advertising sets keep the filter they were configured with.
+    pub fn connections_from_whitelist_only(&mut self, enabled: bool) -> &mut Self {
+        self.set_advertising_filter(
+            esp_ble_adv_filter_t_ADV_FILTER_ALLOW_SCAN_ANY_CON_WLST,
+            enabled,
+        );
+        self
+    }
+
+    /// Sets whether only the scanners of the whitelist receive the scan response.
+    ///
+    /// Once the server is started, the filter is used the next time the legacy advertisement
+    /// starts, and the extended advertising sets keep the filter they were configured with.
+    pub fn scan_requests_from_whitelist_only(&mut self, enabled: bool) -> &mut Self {
+        self.set_advertising_filter(
+            esp_ble_adv_filter_t_ADV_FILTER_ALLOW_SCAN_WLST_CON_ANY,
+            enabled,
+        );
+        self
+    }
+
+    /// Returns the filter policy of the advertisements.
+    #[cfg(esp_idf_bt_ble_50_features_supported)]
+    pub(crate) const fn advertising_filter(&self) -> esp_ble_adv_filter_t {
+        self.advertisement_parameters.adv_filter_policy
+    }
+
+    /// Hands the pending whitelist to the Bluetooth stack.
+    pub(crate) fn configure_whitelist(&mut self) {
+        for (address, address_type) in std::mem::take(&mut self.whitelist) {
+            update_whitelist(true, address, address_type);
+        }
+    }
+
+    /// Sets or clears a filter of the advertisements: the connection and the scan request
+    /// filters are the two bits of the filter policy.
+    fn set_advertising_filter(&mut self, filter: esp_ble_adv_filter_t, enabled: bool) {
+        let policy = &mut self.advertisement_parameters.adv_filter_policy;
+        if enabled {
+            *policy |= filter;
+        } else {
+            *policy &= !filter;
+        }
+    }
+}
+
+fn update_whitelist(add: bool, address: [u8; 6], address_type: AddressType) {
+    if let Err(error) = ble_stack::update_whitelist(add, address, address_type) {
+        warn!(
+            target: GAP,
+            "Updating the whitelist with {:02X?} failed: {}.", address, error
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(not(esp_idf_freertos_unicore))]
+use esp_idf_sys::SOC_CPU_CORES_NUM;
+use esp_idf_sys::{
+    esp_pthread_cfg_t, esp_pthread_get_cfg, esp_pthread_get_default_config, esp_pthread_set_cfg,
+    tskNO_AFFINITY, ESP_OK,
+};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use parking_lot::{Condvar, Mutex};
+
+use crate::gatt_server::{error::esp_report, GattServer, GattServerError};
+use crate::utilities::log_targets::GATTS;
+use crate::BluedroidError;
+
+/// The number of cores the threads can be pinned to.
+#[cfg(not(esp_idf_freertos_unicore))]
+const CORES: u32 = SOC_CPU_CORES_NUM;
+#[cfg(esp_idf_freertos_unicore)]
+const CORES: u32 = 1;
+
+/// The default stack size of the worker thread, in bytes.
+const DEFAULT_STACK_SIZE: usize = 6 * 1024;
+
+/// The stack size of the worker thread, in bytes.
+static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
+
+/// The priority of the threads of the crate, or 0 for the default priority.
+static PRIORITY: AtomicUsize = AtomicUsize::new(0);
+
+/// The core the threads of the crate are pinned to, or `tskNO_AFFINITY`.
+static CORE: AtomicU32 = AtomicU32::new(tskNO_AFFINITY);
+
+/// Whether the worker thread is spawned, or being spawned.
+static SPAWNED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The jobs waiting for their deadline.
+    static ref JOBS: Mutex<Vec<(Instant, Job)>> = Mutex::new(Vec::new());
+    /// Wakes the worker up when a job is scheduled.
+    static ref CONDVAR: Condvar = Condvar::new();
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs `job` on the worker thread, once `delay` has elapsed.
+///
+/// The background work of the GATT server (registration retries and watchdog, coalesced
+/// notifications, delivery queues, supervision, advertisement rotation) shares this single
+/// thread instead of spawning one thread each: every thread costs its own stack,
+/// `CONFIG_PTHREAD_TASK_STACK_SIZE_DEFAULT` bytes (3 KB by default), and its task control block.
+/// Only the OTA service keeps a thread of its own, as it blocks on the flash writes.
+/// Jobs run one after another, so they must not block.
+pub(crate) fn schedule(delay: Duration, job: impl FnOnce() + Send + 'static) {
+    if SPAWNED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        let stack_size = STACK_SIZE.load(Ordering::Relaxed);
+        debug!(
+            target: GATTS,
+            "Spawning the GATT server worker, with a {} bytes stack.",
+            stack_size
+        );
+
+        // The jobs stay queued, and the next one scheduled tries again.
+        if let Err(error) = spawn(Some(stack_size), run) {
+            warn!(target: GATTS, "Cannot spawn the GATT server worker: {}.", error);
+            SPAWNED.store(false, Ordering::Release);
+        }
+    }
+
+    JOBS.lock().push((Instant::now() + delay, Box::new(job)));
+    CONDVAR.notify_one();
+}
+
+/// Runs `job` on the worker thread every `period`, until it returns `false`.
+pub(crate) fn schedule_periodic(period: Duration, mut job: impl FnMut() -> bool + Send + 'static) {
+    schedule(period, move || {
+        if job() {
+            schedule_periodic(period, job);
+        }
+    });
+}
+
+/// Spawns a thread of the crate, with the priority and the core affinity set with
+/// [`GattServer::worker_priority`] and [`GattServer::worker_core`].
+///
+/// # Errors
+///
+/// Returns a [`GattServerError::ThreadSpawn`] if the thread cannot be created,
+/// usually for lack of memory for its stack.
+#[allow(clippy::cast_possible_wrap)]
+pub(crate) fn spawn<T: Send + 'static>(
+    stack_size: Option<usize>,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<JoinHandle<T>, BluedroidError> {
+    let mut builder = std::thread::Builder::new().name("bluedroid".to_string());
+    if let Some(stack_size) = stack_size {
+        builder = builder.stack_size(stack_size);
+    }
+
+    let priority = PRIORITY.load(Ordering::Relaxed);
+    let core = CORE.load(Ordering::Relaxed);
+    if priority == 0 && core == tskNO_AFFINITY {
+        return builder
+            .spawn(f)
+            .map_err(|error| GattServerError::ThreadSpawn(error.to_string()).into());
+    }
+
+    // The pthread configuration applies to the threads spawned by the current thread,
+    // so it is restored once the thread is spawned.
+    unsafe {
+        let mut previous = esp_pthread_get_default_config();
+        let configured = esp_pthread_get_cfg(&mut previous) == ESP_OK;
+
+        let mut config: esp_pthread_cfg_t = esp_pthread_get_default_config();
+        if priority != 0 {
+            config.prio = priority as _;
+        }
+        config.pin_to_core = core as _;
+        esp_report!(esp_pthread_set_cfg(&config));
+
+        let handle = builder.spawn(f);
+
+        if configured {
+            esp_report!(esp_pthread_set_cfg(&previous));
+        } else {
+            esp_report!(esp_pthread_set_cfg(&esp_pthread_get_default_config()));
+        }
+
+        handle.map_err(|error| GattServerError::ThreadSpawn(error.to_string()).into())
+    }
+}
+
+fn run() {
+    loop {
+        let job = {
+            let mut jobs = JOBS.lock();
+
+            loop {
+                let next = jobs
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (deadline, _))| *deadline)
+                    .map(|(index, (deadline, _))| (index, *deadline));
+
+                match next {
+                    Some((index, deadline)) if deadline <= Instant::now() => {
+                        break jobs.swap_remove(index).1;
+                    }
+                    Some((_, deadline)) => {
+                        CONDVAR.wait_until(&mut jobs, deadline);
+                    }
+                    None => CONDVAR.wait(&mut jobs),
+                }
+            }
+        };
+
+        job();
+    }
+}
+
+impl GattServer {
+    /// Sets the stack size of the thread running the background work of the GATT server, in bytes.
+    ///
+    /// The registration retries and watchdog, the coalesced notifications, the delivery queues
+    /// of every connection, the supervision and the advertisement rotations all run on this
+    /// single thread. The default stack size is 6 KB.
+    ///
+    /// With the default ESP-IDF configuration, each of these used a thread of its own, with
+    /// a 3 KB stack: a server with supervision and two connected clients ran at least four
+    /// such threads, about 12 KB of stacks, where the worker takes 6 KB.
+    ///
+    /// # Notes
+    ///
+    /// The thread is spawned when the first job is scheduled, usually when the server starts:
+    /// the stack size must be set before.
+    pub fn worker_stack_size(&mut self, stack_size: usize) -> &mut Self {
+        if SPAWNED.load(Ordering::Acquire) {
+            warn!(
+                target: GATTS,
+                "The GATT server worker is already running, ignoring its new stack size."
+            );
+        } else {
+            STACK_SIZE.store(stack_size, Ordering::Relaxed);
+        }
+
+        self
+    }
+
+    /// Sets the task priority of the threads running the background work of the GATT server.
+    ///
+    /// This covers the worker thread, which runs the delivery queues and the registration
+    /// retries among the other background work, and the thread of the OTA service.
+    /// The default priority is the one of the ESP-IDF pthread configuration.
+    ///
+    /// # Notes
+    ///
+    /// The priority applies to the threads spawned afterwards: set it before starting the server.
+    pub fn worker_priority(&mut self, priority: u8) -> &mut Self {
+        PRIORITY.store(usize::from(priority), Ordering::Relaxed);
+        self
+    }
+
+    /// Pins the threads running the background work of the GATT server to the given core,
+    /// so that BLE processing stays away from a time-critical application core.
+    ///
+    /// This covers the worker thread, which runs the delivery queues and the registration
+    /// retries among the other background work, and the thread of the OTA service.
+    /// By default, the threads can run on any core.
+    ///
+    /// # Notes
+    ///
+    /// The core applies to the threads spawned afterwards: set it before starting the server.
+    /// A core the chip does not have is ignored.
+    pub fn worker_core(&mut self, core: u8) -> &mut Self {
+        if u32::from(core) >= CORES {
+            warn!(
+                target: GATTS,
+                "Cannot pin the GATT server threads to core {}: the chip has {} core(s).",
+                core,
+                CORES
+            );
+            return self;
+        }
+
+        CORE.store(u32::from(core), Ordering::Relaxed);
+        self
+    }
+}
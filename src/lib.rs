@@ -15,7 +15,30 @@
 // In ESP32-S2, the Bluetooth controller is not present.
 // Completely disable this crate.
 
-#[cfg(not(esp32s2))]
+#[cfg(all(
+    not(esp32s2),
+    any(feature = "server", feature = "client", feature = "scanner")
+))]
+mod ble_stack;
+
+#[cfg(all(
+    not(esp32s2),
+    any(feature = "server", feature = "client", feature = "scanner")
+))]
+mod error;
+#[cfg(all(
+    not(esp32s2),
+    any(feature = "server", feature = "client", feature = "scanner")
+))]
+pub use error::BluedroidError;
+
+#[cfg(all(not(esp32s2), feature = "scanner"))]
+pub mod gap;
+
+#[cfg(all(not(esp32s2), feature = "client"))]
+pub mod gatt_client;
+
+#[cfg(all(not(esp32s2), feature = "server"))]
 pub mod gatt_server;
 
 #[cfg(not(esp32s2))]
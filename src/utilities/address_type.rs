@@ -0,0 +1,33 @@
+use esp_idf_sys::{
+    esp_ble_addr_type_t, esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+    esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM,
+};
+
+/// The type of the identity address of a Bluetooth device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AddressType {
+    /// A public device address, assigned by the IEEE.
+    #[default]
+    Public,
+    /// A random static device address.
+    Random,
+}
+
+impl From<AddressType> for esp_ble_addr_type_t {
+    fn from(address_type: AddressType) -> Self {
+        match address_type {
+            AddressType::Public => esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+            AddressType::Random => esp_ble_addr_type_t_BLE_ADDR_TYPE_RANDOM,
+        }
+    }
+}
+
+impl From<esp_ble_addr_type_t> for AddressType {
+    #[allow(non_upper_case_globals)]
+    fn from(address_type: esp_ble_addr_type_t) -> Self {
+        match address_type {
+            esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC => Self::Public,
+            _ => Self::Random,
+        }
+    }
+}
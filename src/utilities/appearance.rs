@@ -3,7 +3,10 @@
 /// A list of standard appearance values.
 ///
 /// This list was copied from the Bluetooth SIG website.
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// Every value is made of a category, in its upper ten bits, and a subcategory,
+/// in its lower six bits. Generic appearances have a zero subcategory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
 pub enum Appearance {
     GenericUnknown = 0x0000,
     GenericPhone = 0x0040,
@@ -306,6 +309,33 @@ pub enum Appearance {
     LocationAndNavigationPod = 0x1444,
 }
 
+impl Appearance {
+    /// Returns the category of this appearance.
+    #[must_use]
+    pub const fn category(self) -> u16 {
+        self as u16 >> 6
+    }
+
+    /// Returns the subcategory of this appearance, zero for generic appearances.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn subcategory(self) -> u8 {
+        (self as u16 & 0x3F) as u8
+    }
+
+    /// Returns `true` if this appearance is the generic appearance of its category.
+    #[must_use]
+    pub const fn is_generic(self) -> bool {
+        self.subcategory() == 0
+    }
+}
+
+impl From<Appearance> for u16 {
+    fn from(appearance: Appearance) -> Self {
+        appearance as Self
+    }
+}
+
 impl From<Appearance> for i32 {
     fn from(appearance: Appearance) -> Self {
         appearance as Self
@@ -0,0 +1,12 @@
+/// An operation requested by a client on an attribute.
+///
+/// This is passed to access policies, such as the one set with [`Characteristic::access_policy`].
+///
+/// [`Characteristic::access_policy`]: crate::gatt_server::Characteristic::access_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperation {
+    /// The client is reading the attribute value.
+    Read,
+    /// The client is writing the attribute value.
+    Write,
+}
@@ -1,21 +1,60 @@
 use esp_idf_sys::*;
 
+/// The security a link needs to access an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum LinkSecurity {
+    #[default]
+    Open,
+    Encrypted,
+    /// Encrypted with keys exchanged by a pairing protected against man-in-the-middle attacks.
+    Authenticated,
+}
+
+impl LinkSecurity {
+    /// Returns the stricter of the two levels.
+    const fn at_least(self, level: Self) -> Self {
+        match (self, level) {
+            (Self::Authenticated, _) | (_, Self::Authenticated) => Self::Authenticated,
+            (Self::Encrypted, _) | (_, Self::Encrypted) => Self::Encrypted,
+            _ => Self::Open,
+        }
+    }
+}
+
 /// Represents an attribute's access permissions.
 ///
 /// This struct is used to set the permissions of a [`Characteristic`] or a [`Descriptor`].
-/// It can represent read and write permissions, and encryption requirements.
+/// It can represent read and write permissions, and the security required from the link
+/// for each of them: the Bluetooth stack rejects the requests of the clients whose link
+/// is not secure enough, which then pair with the server.
+///
+/// ```ignore
+/// // Anyone can read, only authenticated clients can write.
+/// AttributePermissions::new().read().write_authenticated()
+/// ```
+///
+/// [`Characteristic`]: crate::gatt_server::Characteristic
+/// [`Descriptor`]: crate::gatt_server::Descriptor
 #[derive(Debug, Clone, Copy, Default)]
 pub struct AttributePermissions {
     pub(crate) read_access: bool,
     pub(crate) write_access: bool,
-    pub(crate) encryption_required: bool,
+    read_security: LinkSecurity,
+    write_security: LinkSecurity,
+    signed_writes: bool,
 }
 
 impl AttributePermissions {
     /// Creates a new [`AttributePermissions`].
     #[must_use]
-    pub fn new() -> Self {
-        Self::default()
+    pub const fn new() -> Self {
+        Self {
+            read_access: false,
+            write_access: false,
+            read_security: LinkSecurity::Open,
+            write_security: LinkSecurity::Open,
+            signed_writes: false,
+        }
     }
 
     /// Sets the read access of the [`AttributePermissions`].
@@ -32,10 +71,69 @@ impl AttributePermissions {
         self
     }
 
-    /// Sets the encryption requirement of the [`AttributePermissions`].
+    /// Sets the encryption requirement of the [`AttributePermissions`],
+    /// for both reads and writes.
     #[must_use]
     pub const fn encrypted(mut self) -> Self {
-        self.encryption_required = true;
+        self.read_security = self.read_security.at_least(LinkSecurity::Encrypted);
+        self.write_security = self.write_security.at_least(LinkSecurity::Encrypted);
+        self
+    }
+
+    /// Requires an authenticated link for both reads and writes: a link encrypted with keys
+    /// exchanged by a pairing protected against man-in-the-middle attacks.
+    #[must_use]
+    pub const fn authenticated(mut self) -> Self {
+        self.read_security = LinkSecurity::Authenticated;
+        self.write_security = LinkSecurity::Authenticated;
+        self
+    }
+
+    /// Sets the read access, over encrypted links only.
+    #[must_use]
+    pub const fn read_encrypted(mut self) -> Self {
+        self.read_access = true;
+        self.read_security = self.read_security.at_least(LinkSecurity::Encrypted);
+        self
+    }
+
+    /// Sets the read access, over authenticated links only.
+    #[must_use]
+    pub const fn read_authenticated(mut self) -> Self {
+        self.read_access = true;
+        self.read_security = LinkSecurity::Authenticated;
+        self
+    }
+
+    /// Sets the write access, over encrypted links only.
+    #[must_use]
+    pub const fn write_encrypted(mut self) -> Self {
+        self.write_access = true;
+        self.write_security = self.write_security.at_least(LinkSecurity::Encrypted);
+        self
+    }
+
+    /// Sets the write access, over authenticated links only.
+    #[must_use]
+    pub const fn write_authenticated(mut self) -> Self {
+        self.write_access = true;
+        self.write_security = LinkSecurity::Authenticated;
+        self
+    }
+
+    /// Accepts the writes signed by a bonded client over an unencrypted link, with the
+    /// signature key distributed when pairing.
+    ///
+    /// Combine it with [`AttributePermissions::write_encrypted`], so that the other writes
+    /// require an encrypted link, or with [`AttributePermissions::write_authenticated`],
+    /// so that the signature key must also come from an authenticated pairing.
+    /// The characteristic should have the
+    /// [`authenticated_signed_writes`](crate::utilities::CharacteristicProperties::authenticated_signed_writes)
+    /// property.
+    #[must_use]
+    pub const fn write_signed(mut self) -> Self {
+        self.write_access = true;
+        self.signed_writes = true;
         self
     }
 }
@@ -43,20 +141,31 @@ impl AttributePermissions {
 impl From<AttributePermissions> for esp_gatt_perm_t {
     #[allow(clippy::cast_possible_truncation)]
     fn from(permissions: AttributePermissions) -> Self {
-        let result = match (
-            permissions.read_access,
-            permissions.write_access,
-            permissions.encryption_required,
-        ) {
-            // TODO: Implement all the supported modes.
-            (false, false, _) => 0,
-            (true, false, false) => ESP_GATT_PERM_READ,
-            (false, true, false) => ESP_GATT_PERM_WRITE,
-            (true, true, false) => ESP_GATT_PERM_READ | ESP_GATT_PERM_WRITE,
-            (true, false, true) => ESP_GATT_PERM_READ_ENCRYPTED,
-            (false, true, true) => ESP_GATT_PERM_WRITE_ENCRYPTED,
-            (true, true, true) => ESP_GATT_PERM_READ_ENCRYPTED | ESP_GATT_PERM_WRITE_ENCRYPTED,
-        };
+        let mut result = 0;
+
+        if permissions.read_access {
+            result |= match permissions.read_security {
+                LinkSecurity::Open => ESP_GATT_PERM_READ,
+                LinkSecurity::Encrypted => ESP_GATT_PERM_READ_ENCRYPTED,
+                LinkSecurity::Authenticated => ESP_GATT_PERM_READ_ENC_MITM,
+            };
+        }
+
+        if permissions.write_access {
+            result |= match permissions.write_security {
+                LinkSecurity::Open => ESP_GATT_PERM_WRITE,
+                LinkSecurity::Encrypted => ESP_GATT_PERM_WRITE_ENCRYPTED,
+                LinkSecurity::Authenticated => ESP_GATT_PERM_WRITE_ENC_MITM,
+            };
+
+            if permissions.signed_writes {
+                result |= if permissions.write_security == LinkSecurity::Authenticated {
+                    ESP_GATT_PERM_WRITE_SIGNED_MITM
+                } else {
+                    ESP_GATT_PERM_WRITE_SIGNED
+                };
+            }
+        }
 
         result as Self
     }
@@ -2,15 +2,26 @@ use esp_idf_sys::{
     esp_bt_uuid_t, esp_gatt_id_t, ESP_UUID_LEN_128, ESP_UUID_LEN_16, ESP_UUID_LEN_32,
 };
 
+/// The Bluetooth base UUID, `00000000-0000-1000-8000-00805F9B34FB`.
+const BASE_UUID: u128 = 0x0000_0000_0000_1000_8000_0080_5F9B_34FB;
+
 /// A Bluetooth UUID.
+///
+/// The UUID is stored as its canonical 128-bit value, along with the length it is declared
+/// and sent with. UUIDs are compared, hashed and ordered by their canonical value, so a 16-bit
+/// UUID equals its 128-bit expansion over the Bluetooth base UUID.
 #[derive(Copy, Clone)]
-pub enum BleUuid {
-    /// A 16-bit UUID.
-    Uuid16(u16),
-    /// A 32-bit UUID.
-    Uuid32(u32),
-    /// A 128-bit UUID.
-    Uuid128([u8; 16]),
+pub struct BleUuid {
+    uuid: u128,
+    kind: UuidKind,
+}
+
+/// The length of a [`BleUuid`].
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum UuidKind {
+    Uuid16,
+    Uuid32,
+    Uuid128,
 }
 
 /// Creates a contant [`BleUuid`] from a const string.
@@ -27,19 +38,28 @@ impl BleUuid {
     /// Creates a new [`BleUuid`] from a 16-bit integer.
     #[must_use]
     pub const fn from_uuid16(uuid: u16) -> Self {
-        Self::Uuid16(uuid)
+        Self {
+            uuid: BASE_UUID | (uuid as u128) << 96,
+            kind: UuidKind::Uuid16,
+        }
     }
 
     /// Creates a new [`BleUuid`] from a 32-bit integer.
     #[must_use]
     pub const fn from_uuid32(uuid: u32) -> Self {
-        Self::Uuid32(uuid)
+        Self {
+            uuid: BASE_UUID | (uuid as u128) << 96,
+            kind: UuidKind::Uuid32,
+        }
     }
 
-    /// Creates a new [`BleUuid`] from a 16 byte array.
+    /// Creates a new [`BleUuid`] from a 16 byte array, in little-endian order.
     #[must_use]
     pub const fn from_uuid128(uuid: [u8; 16]) -> Self {
-        Self::Uuid128(uuid)
+        Self {
+            uuid: u128::from_le_bytes(uuid),
+            kind: UuidKind::Uuid128,
+        }
     }
 
     /// Creates a new [`BleUuid`] from a const string.
@@ -98,7 +118,7 @@ impl BleUuid {
             panic!("Too short UUID string");
         }
 
-        Self::Uuid128(uuid)
+        Self::from_uuid128(uuid)
     }
 
     /// Creates a new [`BleUuid`] from a formatted string.
@@ -110,40 +130,91 @@ impl BleUuid {
         Self::from_uuid128_str(uuid.as_ref())
     }
 
+    /// Returns the 16-bit value of the [`BleUuid`], if it was declared as a 16-bit UUID.
     #[must_use]
-    pub(crate) fn as_uuid128_array(&self) -> [u8; 16] {
-        let base_ble_uuid = [
-            0xfb, 0x34, 0x9b, 0x5f, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00,
-        ];
+    pub const fn as_uuid16(&self) -> Option<u16> {
+        match self.kind {
+            #[allow(clippy::cast_possible_truncation)]
+            UuidKind::Uuid16 => Some((self.uuid >> 96) as u16),
+            _ => None,
+        }
+    }
 
-        match self {
-            Self::Uuid16(uuid) => {
-                let mut uuid128 = base_ble_uuid;
+    /// Returns the 32-bit value of the [`BleUuid`], if it was declared as a 32-bit UUID.
+    #[must_use]
+    pub const fn as_uuid32(&self) -> Option<u32> {
+        match self.kind {
+            #[allow(clippy::cast_possible_truncation)]
+            UuidKind::Uuid32 => Some((self.uuid >> 96) as u32),
+            _ => None,
+        }
+    }
 
-                let mut uuid_as_bytes: [u8; 2] = uuid.to_be_bytes();
-                uuid_as_bytes.reverse();
+    /// Returns the canonical 128-bit value of the [`BleUuid`].
+    #[must_use]
+    pub const fn as_u128(&self) -> u128 {
+        self.uuid
+    }
 
-                uuid128[12..=13].copy_from_slice(&uuid_as_bytes[..]);
-                uuid128
-            }
-            Self::Uuid32(uuid) => {
-                let mut uuid128 = base_ble_uuid;
+    /// Returns the canonical 128-bit value of the [`BleUuid`], in little-endian order.
+    #[must_use]
+    pub(crate) const fn as_uuid128_array(&self) -> [u8; 16] {
+        self.uuid.to_le_bytes()
+    }
 
-                let mut uuid_as_bytes: [u8; 4] = uuid.to_be_bytes();
-                uuid_as_bytes.reverse();
+    /// Returns the [`BleUuid`] in its declared length, in little-endian order.
+    #[cfg(feature = "server")]
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn as_bytes(&self) -> Vec<u8> {
+        match self.kind {
+            UuidKind::Uuid16 => ((self.uuid >> 96) as u16).to_le_bytes().to_vec(),
+            UuidKind::Uuid32 => ((self.uuid >> 96) as u32).to_le_bytes().to_vec(),
+            UuidKind::Uuid128 => self.as_uuid128_array().to_vec(),
+        }
+    }
 
-                uuid128[12..=15].copy_from_slice(&uuid_as_bytes[..]);
-                uuid128
+    /// Returns the canonical 128-bit value of a UUID of the Bluetooth stack.
+    fn canonical(uuid: &esp_bt_uuid_t) -> Option<u128> {
+        unsafe {
+            match u32::from(uuid.len) {
+                ESP_UUID_LEN_16 => Some(BASE_UUID | u128::from(uuid.uuid.uuid16) << 96),
+                ESP_UUID_LEN_32 => Some(BASE_UUID | u128::from(uuid.uuid.uuid32) << 96),
+                ESP_UUID_LEN_128 => Some(u128::from_le_bytes(uuid.uuid.uuid128)),
+                _ => None,
             }
-            Self::Uuid128(uuid) => *uuid,
         }
     }
 }
 
 impl PartialEq for BleUuid {
     fn eq(&self, other: &Self) -> bool {
-        self.as_uuid128_array() == other.as_uuid128_array()
+        self.uuid == other.uuid
+    }
+}
+
+impl Eq for BleUuid {}
+
+impl PartialEq<esp_bt_uuid_t> for BleUuid {
+    fn eq(&self, other: &esp_bt_uuid_t) -> bool {
+        Self::canonical(other) == Some(self.uuid)
+    }
+}
+
+impl std::hash::Hash for BleUuid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.uuid.hash(state);
+    }
+}
+
+impl PartialOrd for BleUuid {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BleUuid {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.uuid.cmp(&other.uuid)
     }
 }
 
@@ -161,18 +232,18 @@ impl From<BleUuid> for esp_bt_uuid_t {
     fn from(val: BleUuid) -> Self {
         let mut result: Self = Self::default();
 
-        match val {
-            BleUuid::Uuid16(uuid) => {
+        match val.kind {
+            UuidKind::Uuid16 => {
                 result.len = ESP_UUID_LEN_16 as u16;
-                result.uuid.uuid16 = uuid;
+                result.uuid.uuid16 = (val.uuid >> 96) as u16;
             }
-            BleUuid::Uuid32(uuid) => {
+            UuidKind::Uuid32 => {
                 result.len = ESP_UUID_LEN_32 as u16;
-                result.uuid.uuid32 = uuid;
+                result.uuid.uuid32 = (val.uuid >> 96) as u32;
             }
-            BleUuid::Uuid128(uuid) => {
+            UuidKind::Uuid128 => {
                 result.len = ESP_UUID_LEN_128 as u16;
-                result.uuid.uuid128 = uuid;
+                result.uuid.uuid128 = val.as_uuid128_array();
             }
         }
 
@@ -183,10 +254,10 @@ impl From<BleUuid> for esp_bt_uuid_t {
 impl From<esp_bt_uuid_t> for BleUuid {
     fn from(uuid: esp_bt_uuid_t) -> Self {
         unsafe {
-            match uuid.len {
-                2 => Self::Uuid16(uuid.uuid.uuid16),
-                4 => Self::Uuid32(uuid.uuid.uuid32),
-                16 => Self::Uuid128(uuid.uuid.uuid128),
+            match u32::from(uuid.len) {
+                ESP_UUID_LEN_16 => Self::from_uuid16(uuid.uuid.uuid16),
+                ESP_UUID_LEN_32 => Self::from_uuid32(uuid.uuid.uuid32),
+                ESP_UUID_LEN_128 => Self::from_uuid128(uuid.uuid.uuid128),
                 // Never happens
                 _ => unreachable!("Invalid UUID length"),
             }
@@ -202,12 +273,11 @@ impl From<esp_gatt_id_t> for BleUuid {
 
 impl std::fmt::Display for BleUuid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Uuid16(uuid) => write!(f, "0x{uuid:04x}"),
-            Self::Uuid32(uuid) => write!(f, "0x{uuid:08x}"),
-            Self::Uuid128(uuid) => {
-                let mut uuid = *uuid;
-                uuid.reverse();
+        match self.kind {
+            UuidKind::Uuid16 => write!(f, "0x{:04x}", self.uuid >> 96),
+            UuidKind::Uuid32 => write!(f, "0x{:08x}", self.uuid >> 96),
+            UuidKind::Uuid128 => {
+                let uuid = self.uuid.to_be_bytes();
 
                 let mut uuid_str = String::new();
 
@@ -1,5 +1,4 @@
 use esp_idf_sys::*;
-use log::warn;
 
 /// Represents the properties of a [`Characteristic`].
 ///
@@ -16,7 +15,7 @@ use log::warn;
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Clone, Copy, Debug, Default)]
 pub struct CharacteristicProperties {
-    broadcast: bool,
+    pub(crate) broadcast: bool,
     pub(crate) read: bool,
     pub(crate) write_without_response: bool,
     pub(crate) write: bool,
@@ -24,13 +23,25 @@ pub struct CharacteristicProperties {
     pub(crate) indicate: bool,
     authenticated_signed_writes: bool,
     extended_properties: bool,
+    /// The property ignored because notify and indicate were both set.
+    pub(crate) ignored: Option<&'static str>,
 }
 
 impl CharacteristicProperties {
     /// Creates a new [`CharacteristicProperties`].
     #[must_use]
-    pub fn new() -> Self {
-        Self::default()
+    pub const fn new() -> Self {
+        Self {
+            broadcast: false,
+            read: false,
+            write_without_response: false,
+            write: false,
+            notify: false,
+            indicate: false,
+            authenticated_signed_writes: false,
+            extended_properties: false,
+            ignored: None,
+        }
     }
 
     /// Sets the "broadcast" property.
@@ -62,10 +73,13 @@ impl CharacteristicProperties {
     }
 
     /// Sets the "notify" property.
+    ///
+    /// The "notify" and "indicate" properties cannot be set at the same time:
+    /// if "indicate" is already set, this property is ignored.
     #[must_use]
-    pub fn notify(mut self) -> Self {
+    pub const fn notify(mut self) -> Self {
         if self.indicate {
-            warn!("Cannot set notify and indicate at the same time. Ignoring notify.");
+            self.ignored = Some("notify");
             return self;
         }
 
@@ -74,10 +88,13 @@ impl CharacteristicProperties {
     }
 
     /// Sets the "indicate" property.
+    ///
+    /// The "notify" and "indicate" properties cannot be set at the same time:
+    /// if "notify" is already set, this property is ignored.
     #[must_use]
-    pub fn indicate(mut self) -> Self {
+    pub const fn indicate(mut self) -> Self {
         if self.notify {
-            warn!("Cannot set notify and indicate at the same time. Ignoring indicate.");
+            self.ignored = Some("indicate");
             return self;
         }
 
@@ -0,0 +1,198 @@
+//! A simple framing for messages longer than a single attribute value.
+//!
+//! Each chunk starts with a one-byte sequence number. The first chunk of a message has
+//! sequence number zero, and continues with the total length of the message as a 32-bit
+//! little-endian integer. The following chunks count from one to 255, and then wrap around to one.
+//! The rest of every chunk is message content.
+
+/// The length of the header of the first chunk of a message: sequence number and message length.
+const FIRST_HEADER_LENGTH: usize = 5;
+
+/// The default maximum length of the messages accepted by a [`Reassembler`].
+pub const DEFAULT_MAX_LENGTH: usize = 4096;
+
+/// An error encountered while reassembling a chunked message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkError {
+    /// The chunk has no sequence number.
+    Empty,
+    /// The first chunk of a message does not contain the message length.
+    MissingLength,
+    /// The chunk does not follow the previous one.
+    UnexpectedSequence {
+        /// The sequence number of the chunk that was expected.
+        expected: u8,
+        /// The sequence number of the chunk that was received.
+        received: u8,
+    },
+    /// The message is longer than the allowed maximum length, or than its declared length.
+    TooLong,
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty chunk"),
+            Self::MissingLength => write!(f, "missing message length in first chunk"),
+            Self::UnexpectedSequence { expected, received } => write!(
+                f,
+                "unexpected chunk sequence number {received}, expected {expected}"
+            ),
+            Self::TooLong => write!(f, "message too long"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Splits a message into chunks of at most `chunk_size` bytes.
+///
+/// # Panics
+///
+/// Panics if `chunk_size` cannot contain the header of the first chunk and at least one byte,
+/// or if the message is longer than `u32::MAX` bytes.
+#[must_use]
+pub fn split_message(message: &[u8], chunk_size: usize) -> Vec<Vec<u8>> {
+    assert!(
+        chunk_size > FIRST_HEADER_LENGTH,
+        "Chunk size must be at least {} bytes.",
+        FIRST_HEADER_LENGTH + 1
+    );
+
+    let length = u32::try_from(message.len()).expect("Message is too long to be chunked.");
+    let (first, rest) = message.split_at(message.len().min(chunk_size - FIRST_HEADER_LENGTH));
+
+    let mut first_chunk = vec![0];
+    first_chunk.extend_from_slice(&length.to_le_bytes());
+    first_chunk.extend_from_slice(first);
+
+    let mut chunks = vec![first_chunk];
+    for (index, data) in rest.chunks(chunk_size - 1).enumerate() {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut chunk = vec![(index % 255) as u8 + 1];
+        chunk.extend_from_slice(data);
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// Reassembles the messages split by [`split_message`].
+///
+/// Messages are limited to [`DEFAULT_MAX_LENGTH`] bytes by default,
+/// so that a peer cannot exhaust the memory by declaring a huge message.
+#[derive(Debug, Clone)]
+pub struct Reassembler {
+    buffer: Vec<u8>,
+    expected_length: Option<usize>,
+    next_sequence: u8,
+    max_length: Option<usize>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            expected_length: None,
+            next_sequence: 0,
+            max_length: Some(DEFAULT_MAX_LENGTH),
+        }
+    }
+}
+
+impl Reassembler {
+    /// Creates a new [`Reassembler`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum length of the accepted messages.
+    ///
+    /// Longer messages are rejected as soon as their first chunk is received.
+    #[must_use]
+    pub const fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Accepts messages of any length, up to the 4 GiB the framing can declare.
+    ///
+    /// Only use it with trusted peers: the declared length is not checked against the memory.
+    #[must_use]
+    pub const fn unbounded(mut self) -> Self {
+        self.max_length = None;
+        self
+    }
+
+    /// Feeds a chunk into the [`Reassembler`].
+    ///
+    /// Returns the complete message when its last chunk is received.
+    /// A first chunk discards any partially received message.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ChunkError`] if the chunk is malformed or out of order.
+    /// The partially received message is discarded in that case.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Option<Vec<u8>>, ChunkError> {
+        let result = self.try_push(chunk);
+
+        if !matches!(result, Ok(None)) {
+            self.reset();
+        }
+
+        result
+    }
+
+    fn try_push(&mut self, chunk: &[u8]) -> Result<Option<Vec<u8>>, ChunkError> {
+        let Some((&sequence, mut data)) = chunk.split_first() else {
+            return Err(ChunkError::Empty);
+        };
+
+        if sequence == 0 {
+            let Some(length) = data.get(..4) else {
+                return Err(ChunkError::MissingLength);
+            };
+
+            let mut length_bytes = [0; 4];
+            length_bytes.copy_from_slice(length);
+            let length = u32::from_le_bytes(length_bytes) as usize;
+
+            if self
+                .max_length
+                .is_some_and(|max_length| length > max_length)
+            {
+                return Err(ChunkError::TooLong);
+            }
+
+            self.buffer.clear();
+            self.expected_length = Some(length);
+            data = &data[4..];
+        } else if self.expected_length.is_none() || sequence != self.next_sequence {
+            return Err(ChunkError::UnexpectedSequence {
+                expected: self.next_sequence,
+                received: sequence,
+            });
+        }
+
+        let expected_length = self.expected_length.unwrap_or_default();
+        if self.buffer.len() + data.len() > expected_length {
+            return Err(ChunkError::TooLong);
+        }
+
+        self.buffer.extend_from_slice(data);
+        self.next_sequence = if sequence == 255 { 1 } else { sequence + 1 };
+
+        if self.buffer.len() == expected_length {
+            Ok(Some(std::mem::take(&mut self.buffer)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.expected_length = None;
+        self.next_sequence = 0;
+    }
+}
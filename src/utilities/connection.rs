@@ -1,14 +1,85 @@
+#[cfg(feature = "client")]
+use esp_idf_sys::{
+    esp_ble_gattc_cb_param_t_gattc_disconnect_evt_param,
+    esp_ble_gattc_cb_param_t_gattc_open_evt_param,
+};
 use esp_idf_sys::{
     esp_ble_gatts_cb_param_t_gatts_connect_evt_param,
     esp_ble_gatts_cb_param_t_gatts_disconnect_evt_param,
+    esp_ble_gatts_cb_param_t_gatts_read_evt_param, esp_ble_gatts_cb_param_t_gatts_write_evt_param,
+    ESP_GATT_DEF_BLE_MTU_SIZE,
 };
+use parking_lot::Mutex;
 
+/// Represents a connection with a remote device: a GATT client, or a GATT server
+/// the [GATT client](crate::gatt_client) connected to.
 #[derive(Debug, Copy, Clone)]
-pub(crate) struct Connection {
+pub struct Connection {
     pub(crate) id: u16,
     #[cfg(esp_idf_version_major = "4")]
     pub(crate) is_slave: bool,
     pub(crate) remote_bda: [u8; 6],
+    pub(crate) mtu: u16,
+}
+
+/// The ATT MTU of a connection until the devices negotiate a larger one.
+#[allow(clippy::cast_possible_truncation)]
+const DEFAULT_MTU: u16 = ESP_GATT_DEF_BLE_MTU_SIZE as u16;
+
+/// The MTUs negotiated on the open connections, by connection identifier,
+/// so that the connections built from any later event carry them.
+static NEGOTIATED_MTUS: Mutex<Vec<(u16, u16)>> = Mutex::new(Vec::new());
+
+/// Returns the MTU negotiated on a connection, or the default one.
+fn negotiated_mtu(id: u16) -> u16 {
+    NEGOTIATED_MTUS
+        .lock()
+        .iter()
+        .find(|(connection, _)| *connection == id)
+        .map_or(DEFAULT_MTU, |(_, mtu)| *mtu)
+}
+
+/// Records the MTU negotiated on a connection.
+#[cfg(any(feature = "server", feature = "client"))]
+pub(crate) fn record_mtu(id: u16, mtu: u16) {
+    let mut mtus = NEGOTIATED_MTUS.lock();
+    mtus.retain(|(connection, _)| *connection != id);
+    mtus.push((id, mtu));
+}
+
+/// Forgets the MTU of a closed connection.
+#[cfg(any(feature = "server", feature = "client"))]
+pub(crate) fn forget_mtu(id: u16) {
+    NEGOTIATED_MTUS
+        .lock()
+        .retain(|(connection, _)| *connection != id);
+}
+
+impl Connection {
+    /// Returns the connection identifier assigned by the Bluetooth stack.
+    #[must_use]
+    pub const fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Returns the Bluetooth device address of the remote device.
+    #[must_use]
+    pub const fn remote_bda(&self) -> [u8; 6] {
+        self.remote_bda
+    }
+
+    /// Returns the ATT MTU of the connection, in bytes: 23 bytes until the devices
+    /// negotiate a larger one.
+    ///
+    /// A notification or an indication carries up to `mtu() - 3` bytes of value.
+    ///
+    /// This is the MTU as of when this [`Connection`] was obtained: the connections
+    /// passed to the callbacks after the negotiation, starting with the MTU change event,
+    /// carry the negotiated MTU.
+    #[must_use]
+    pub const fn mtu(&self) -> u16 {
+        self.mtu
+    }
 }
 
 impl From<esp_ble_gatts_cb_param_t_gatts_connect_evt_param> for Connection {
@@ -18,6 +89,7 @@ impl From<esp_ble_gatts_cb_param_t_gatts_connect_evt_param> for Connection {
             #[cfg(esp_idf_version_major = "4")]
             is_slave: param.link_role == 1,
             remote_bda: param.remote_bda,
+            mtu: negotiated_mtu(param.conn_id),
         }
     }
 }
@@ -29,6 +101,60 @@ impl From<esp_ble_gatts_cb_param_t_gatts_disconnect_evt_param> for Connection {
             #[cfg(esp_idf_version_major = "4")]
             is_slave: param.link_role == 1,
             remote_bda: param.remote_bda,
+            mtu: negotiated_mtu(param.conn_id),
+        }
+    }
+}
+
+impl From<esp_ble_gatts_cb_param_t_gatts_read_evt_param> for Connection {
+    fn from(param: esp_ble_gatts_cb_param_t_gatts_read_evt_param) -> Self {
+        Self {
+            id: param.conn_id,
+            // Read requests are only received by the GATT server, which acts as a slave.
+            #[cfg(esp_idf_version_major = "4")]
+            is_slave: true,
+            remote_bda: param.bda,
+            mtu: negotiated_mtu(param.conn_id),
+        }
+    }
+}
+
+impl From<esp_ble_gatts_cb_param_t_gatts_write_evt_param> for Connection {
+    fn from(param: esp_ble_gatts_cb_param_t_gatts_write_evt_param) -> Self {
+        Self {
+            id: param.conn_id,
+            // Write requests are only received by the GATT server, which acts as a slave.
+            #[cfg(esp_idf_version_major = "4")]
+            is_slave: true,
+            remote_bda: param.bda,
+            mtu: negotiated_mtu(param.conn_id),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<esp_ble_gattc_cb_param_t_gattc_open_evt_param> for Connection {
+    fn from(param: esp_ble_gattc_cb_param_t_gattc_open_evt_param) -> Self {
+        Self {
+            id: param.conn_id,
+            // Connections opened by the GATT client are always initiated as master.
+            #[cfg(esp_idf_version_major = "4")]
+            is_slave: false,
+            remote_bda: param.remote_bda,
+            mtu: negotiated_mtu(param.conn_id),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<esp_ble_gattc_cb_param_t_gattc_disconnect_evt_param> for Connection {
+    fn from(param: esp_ble_gattc_cb_param_t_gattc_disconnect_evt_param) -> Self {
+        Self {
+            id: param.conn_id,
+            #[cfg(esp_idf_version_major = "4")]
+            is_slave: false,
+            remote_bda: param.remote_bda,
+            mtu: negotiated_mtu(param.conn_id),
         }
     }
 }
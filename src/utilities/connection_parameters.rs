@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use esp_idf_sys::esp_gatt_conn_params_t;
+
+/// The parameters of a connection, set by the central device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionParameters {
+    /// The connection interval, in units of 1.25 ms.
+    pub(crate) interval: u16,
+    /// The number of connection events the peripheral can skip.
+    pub(crate) latency: u16,
+    /// The supervision timeout, in units of 10 ms.
+    pub(crate) timeout: u16,
+}
+
+impl ConnectionParameters {
+    /// Returns the connection interval, the time between two connection events.
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        Duration::from_micros(u64::from(self.interval) * 1250)
+    }
+
+    /// Returns the peripheral latency, the number of connection events the peripheral can skip.
+    #[must_use]
+    pub const fn latency(&self) -> u16 {
+        self.latency
+    }
+
+    /// Returns the supervision timeout, after which a silent link is considered lost.
+    #[must_use]
+    pub fn supervision_timeout(&self) -> Duration {
+        Duration::from_millis(u64::from(self.timeout) * 10)
+    }
+}
+
+impl From<esp_gatt_conn_params_t> for ConnectionParameters {
+    fn from(parameters: esp_gatt_conn_params_t) -> Self {
+        Self {
+            interval: parameters.interval,
+            latency: parameters.latency,
+            timeout: parameters.timeout,
+        }
+    }
+}
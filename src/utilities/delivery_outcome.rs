@@ -0,0 +1,24 @@
+/// The outcome of a value change sent through the delivery queue of a [`Characteristic`].
+///
+/// [`Characteristic`]: crate::gatt_server::Characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    /// The notification was handed to the controller by the Bluetooth stack.
+    ///
+    /// Notifications are not acknowledged by the client, so this is the best guarantee available.
+    Sent,
+    /// The notification was accepted by the Bluetooth stack, which did not report in time
+    /// whether it was handed to the controller.
+    Unknown,
+    /// The client confirmed the indication.
+    Confirmed,
+    /// The value could not be delivered, even after retrying.
+    Failed,
+    /// The connection was closed before the value could be delivered.
+    Disconnected,
+    /// The value was dropped from a full delivery queue before being sent,
+    /// see [`GattServer::delivery_queue_capacity`].
+    ///
+    /// [`GattServer::delivery_queue_capacity`]: crate::gatt_server::GattServer::delivery_queue_capacity
+    Dropped,
+}
@@ -0,0 +1,133 @@
+/// A type that can be encoded into a GATT attribute value.
+///
+/// Numbers are encoded in little-endian byte order, as mandated by the Bluetooth specification.
+/// Strings are encoded as UTF-8, without a terminator. Booleans are encoded as a single byte.
+/// Arrays, slices and vectors are encoded as the concatenation of their elements.
+///
+/// This trait is accepted by [`Characteristic::set_value`] and [`Descriptor::set_value`],
+/// so that `characteristic.set_value(23.5f32)` just works.
+///
+/// [`Characteristic::set_value`]: crate::gatt_server::Characteristic::set_value
+/// [`Descriptor::set_value`]: crate::gatt_server::Descriptor::set_value
+pub trait ToGattValue {
+    /// Appends the encoded value to the given buffer.
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>);
+
+    /// Returns the encoded value.
+    fn to_gatt_value(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        self.write_gatt_value(&mut buffer);
+        buffer
+    }
+}
+
+/// A type that can be decoded from a GATT attribute value.
+///
+/// The encoding is the same one used by [`ToGattValue`].
+pub trait FromGattValue: Sized {
+    /// Decodes a value from the given bytes.
+    ///
+    /// Returns `None` if the bytes do not represent a valid value of this type.
+    fn from_gatt_value(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_gatt_value_for_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl ToGattValue for $t {
+                fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+                    buffer.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl FromGattValue for $t {
+                fn from_gatt_value(bytes: &[u8]) -> Option<Self> {
+                    Some(Self::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_gatt_value_for_numbers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl ToGattValue for bool {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        buffer.push(u8::from(*self));
+    }
+}
+
+impl FromGattValue for bool {
+    fn from_gatt_value(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [value] => Some(*value != 0),
+            _ => None,
+        }
+    }
+}
+
+impl ToGattValue for str {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl ToGattValue for String {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        self.as_str().write_gatt_value(buffer);
+    }
+}
+
+impl FromGattValue for String {
+    fn from_gatt_value(bytes: &[u8]) -> Option<Self> {
+        Self::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl<T: ToGattValue> ToGattValue for [T] {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        for item in self {
+            item.write_gatt_value(buffer);
+        }
+    }
+}
+
+impl<T: ToGattValue, const N: usize> ToGattValue for [T; N] {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        self.as_slice().write_gatt_value(buffer);
+    }
+}
+
+impl<T: ToGattValue> ToGattValue for Vec<T> {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        self.as_slice().write_gatt_value(buffer);
+    }
+}
+
+impl<T: ToGattValue + ?Sized> ToGattValue for &T {
+    fn write_gatt_value(&self, buffer: &mut Vec<u8>) {
+        (**self).write_gatt_value(buffer);
+    }
+}
+
+impl FromGattValue for Vec<u8> {
+    fn from_gatt_value(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl<T: FromGattValue, const N: usize> FromGattValue for [T; N] {
+    /// Decodes an array by splitting the bytes into `N` equally sized elements.
+    fn from_gatt_value(bytes: &[u8]) -> Option<Self> {
+        if N == 0 || bytes.is_empty() || bytes.len() % N != 0 {
+            return None;
+        }
+
+        let items: Option<Vec<T>> = bytes
+            .chunks_exact(bytes.len() / N)
+            .map(T::from_gatt_value)
+            .collect();
+
+        items?.try_into().ok()
+    }
+}
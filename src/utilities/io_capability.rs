@@ -0,0 +1,34 @@
+use esp_idf_sys::{
+    esp_ble_io_cap_t, ESP_IO_CAP_IN, ESP_IO_CAP_IO, ESP_IO_CAP_KBDISP, ESP_IO_CAP_NONE,
+    ESP_IO_CAP_OUT,
+};
+
+/// The input and output capabilities of a device, which select the pairing method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IoCapability {
+    /// The device can display a passkey.
+    DisplayOnly,
+    /// The device can display a number, and the user can confirm it with yes or no.
+    DisplayYesNo,
+    /// The user can enter a passkey on the device.
+    KeyboardOnly,
+    /// The device has no input nor output: the pairing is "Just Works",
+    /// without protection against man-in-the-middle attacks.
+    #[default]
+    NoInputNoOutput,
+    /// The device can display a passkey, and the user can enter one.
+    KeyboardDisplay,
+}
+
+impl From<IoCapability> for esp_ble_io_cap_t {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from(capability: IoCapability) -> Self {
+        (match capability {
+            IoCapability::DisplayOnly => ESP_IO_CAP_OUT,
+            IoCapability::DisplayYesNo => ESP_IO_CAP_IO,
+            IoCapability::KeyboardOnly => ESP_IO_CAP_IN,
+            IoCapability::NoInputNoOutput => ESP_IO_CAP_NONE,
+            IoCapability::KeyboardDisplay => ESP_IO_CAP_KBDISP,
+        }) as Self
+    }
+}
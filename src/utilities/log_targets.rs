@@ -0,0 +1,23 @@
+//! The targets of the log messages of the crate.
+//!
+//! Each subsystem logs to its own target, so that its verbosity can be raised on its own,
+//! for example with `EspLogger::set_target_level` of `esp-idf-svc`:
+//!
+//! ```ignore
+//! EspLogger.set_target_level(bluedroid::utilities::log_targets::GAP, LevelFilter::Trace)?;
+//! ```
+
+/// The GATT server: registration of the attribute table, reads and writes.
+pub const GATTS: &str = "bluedroid::gatts";
+
+/// The GATT client: connections to remote GATT servers, discovery, reads and writes.
+pub const GATTC: &str = "bluedroid::gattc";
+
+/// The GAP: advertisement, scan response and connection parameters.
+pub const GAP: &str = "bluedroid::gap";
+
+/// The non-volatile storage of the attribute values, such as the CCCDs.
+pub const NVS: &str = "bluedroid::storage";
+
+/// The notifications and indications sent to the clients.
+pub const NOTIFY: &str = "bluedroid::notify";
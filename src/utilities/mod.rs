@@ -1,15 +1,40 @@
 //! This module contains useful structs and macros for the crate.
 
-// Leaky box: internally useful for ffi C interfacing.
-pub(crate) mod leaky_box;
-
 // Utilities: private.
+#[cfg(feature = "server")]
 mod attribute_control;
+#[cfg(feature = "server")]
 pub(crate) use attribute_control::AttributeControl;
 
-// Connection: private.
+// Connection: public.
 mod connection;
-pub(crate) use connection::Connection;
+pub use connection::Connection;
+#[cfg(any(feature = "server", feature = "client"))]
+pub(crate) use connection::{forget_mtu, record_mtu};
+
+// Connection parameters: public.
+mod connection_parameters;
+pub use connection_parameters::ConnectionParameters;
+
+// Address types: public.
+mod address_type;
+pub use address_type::AddressType;
+
+// Physical layers: public.
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+mod phy;
+#[cfg(esp_idf_bt_ble_50_features_supported)]
+pub use phy::Phy;
+
+// Input and output capabilities: public.
+#[cfg(feature = "security")]
+mod io_capability;
+#[cfg(feature = "security")]
+pub use io_capability::IoCapability;
+
+// Attribute operations: public.
+mod attribute_operation;
+pub use attribute_operation::AttributeOperation;
 
 // BLE identifiers: public.
 mod ble_uuid;
@@ -26,3 +51,44 @@ pub use characteristic_properties::CharacteristicProperties;
 // Attribute permissions: public.
 mod attribute_permissions;
 pub use attribute_permissions::AttributePermissions;
+
+// Notification mode: public.
+mod notification_mode;
+pub use notification_mode::NotificationMode;
+
+// Characteristic presentation formats: public.
+mod presentation_format;
+pub use presentation_format::PresentationFormat;
+
+// Privilege levels: public.
+mod privilege_level;
+pub use privilege_level::PrivilegeLevel;
+
+// Delivery outcomes: public.
+mod delivery_outcome;
+pub use delivery_outcome::DeliveryOutcome;
+
+// Chunked messages: public.
+pub mod chunked;
+
+// Bluetooth SIG assigned numbers: public.
+pub mod sig;
+
+// Log targets: public.
+pub mod log_targets;
+
+// GATT value conversions: public.
+mod gatt_value;
+pub use gatt_value::{FromGattValue, ToGattValue};
+
+// Payload codecs: public.
+mod payload_codec;
+#[cfg(feature = "cbor")]
+pub use payload_codec::Cbor;
+#[cfg(feature = "json")]
+pub use payload_codec::Json;
+#[cfg(feature = "postcard")]
+pub use payload_codec::Postcard;
+#[cfg(feature = "prost")]
+pub use payload_codec::Protobuf;
+pub use payload_codec::{GattValueCodec, PayloadCodec};
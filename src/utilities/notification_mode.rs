@@ -0,0 +1,21 @@
+/// Defines which clients are notified when the value of a [`Characteristic`] changes.
+///
+/// The notification mode is applied every time [`Characteristic::set_value`] is called
+/// on a registered characteristic that has the "notify" or "indicate" property.
+///
+/// [`Characteristic`]: crate::gatt_server::Characteristic
+/// [`Characteristic::set_value`]: crate::gatt_server::Characteristic::set_value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotificationMode {
+    /// Value changes are never notified or indicated implicitly.
+    Disabled,
+    /// Value changes are notified or indicated to every active connection,
+    /// regardless of the content of their Client Characteristic Configuration Descriptor.
+    AllConnections,
+    /// Value changes are notified or indicated only to the connections that enabled them
+    /// in their Client Characteristic Configuration Descriptor.
+    ///
+    /// This is the default mode.
+    #[default]
+    Subscribers,
+}
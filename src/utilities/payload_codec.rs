@@ -0,0 +1,133 @@
+#[cfg(any(
+    feature = "postcard",
+    feature = "cbor",
+    feature = "json",
+    feature = "prost"
+))]
+use {crate::utilities::log_targets::GATTS, log::warn};
+
+use crate::utilities::{FromGattValue, ToGattValue};
+
+/// A wire format for the values of characteristics.
+///
+/// A codec is selected by type, for example with [`Characteristic::set_encoded_value`],
+/// so that the wire format can be changed without touching the characteristic definitions.
+///
+/// The following codecs are provided:
+///
+/// - [`GattValueCodec`], the encoding of [`ToGattValue`] and [`FromGattValue`].
+/// - [`Postcard`], with the `postcard` feature.
+/// - [`Cbor`], with the `cbor` feature, using `minicbor`.
+/// - [`Json`], with the `json` feature.
+/// - [`Protobuf`], with the `prost` feature.
+///
+/// [`Characteristic::set_encoded_value`]: crate::gatt_server::Characteristic::set_encoded_value
+pub trait PayloadCodec<T> {
+    /// Encodes a value.
+    ///
+    /// Returns `None` if the value cannot be encoded.
+    fn encode(value: &T) -> Option<Vec<u8>>;
+
+    /// Decodes a value from the given bytes.
+    ///
+    /// Returns `None` if the bytes do not represent a valid value of type `T`.
+    fn decode(bytes: &[u8]) -> Option<T>;
+}
+
+/// The codec of [`ToGattValue`] and [`FromGattValue`]: numbers in little-endian byte order,
+/// strings in UTF-8, as mandated by the Bluetooth specification.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GattValueCodec;
+
+impl<T: ToGattValue + FromGattValue> PayloadCodec<T> for GattValueCodec {
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        Some(value.to_gatt_value())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        T::from_gatt_value(bytes)
+    }
+}
+
+/// The [postcard](https://docs.rs/postcard) codec, for any `serde` type.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> PayloadCodec<T> for Postcard {
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        postcard::to_allocvec(value)
+            .map_err(|error| warn!(target: GATTS, "Cannot encode postcard value: {}.", error))
+            .ok()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        postcard::from_bytes(bytes)
+            .map_err(|error| warn!(target: GATTS, "Cannot decode postcard value: {}.", error))
+            .ok()
+    }
+}
+
+/// The CBOR codec, for any [minicbor](https://docs.rs/minicbor) type.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl<T: minicbor::Encode<()> + for<'b> minicbor::Decode<'b, ()>> PayloadCodec<T> for Cbor {
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        minicbor::to_vec(value)
+            .map_err(|error| warn!(target: GATTS, "Cannot encode CBOR value: {}.", error))
+            .ok()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        minicbor::decode(bytes)
+            .map_err(|error| warn!(target: GATTS, "Cannot decode CBOR value: {}.", error))
+            .ok()
+    }
+}
+
+/// The JSON codec, for any `serde` type.
+///
+/// JSON is verbose: mind the maximum length of the values.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> PayloadCodec<T> for Json {
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        serde_json::to_vec(value)
+            .map_err(|error| warn!(target: GATTS, "Cannot encode JSON value: {}.", error))
+            .ok()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        serde_json::from_slice(bytes)
+            .map_err(|error| warn!(target: GATTS, "Cannot decode JSON value: {}.", error))
+            .ok()
+    }
+}
+
+/// The protobuf codec, for any [prost](https://docs.rs/prost) message.
+///
+/// Messages longer than a single attribute value can be sent over a
+/// [`ChunkedChannel`](crate::gatt_server::ChunkedChannel), which carries their length.
+#[cfg(feature = "prost")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protobuf;
+
+#[cfg(feature = "prost")]
+impl<T: prost::Message + Default> PayloadCodec<T> for Protobuf {
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        Some(value.encode_to_vec())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        T::decode(bytes)
+            .map_err(|error| warn!(target: GATTS, "Cannot decode protobuf message: {}.", error))
+            .ok()
+    }
+}
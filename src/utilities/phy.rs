@@ -0,0 +1,55 @@
+#[cfg(feature = "server")]
+use esp_idf_sys::{
+    esp_ble_gap_phy_mask_t, ESP_BLE_GAP_PHY_1M_PREF_MASK, ESP_BLE_GAP_PHY_2M_PREF_MASK,
+    ESP_BLE_GAP_PHY_CODED_PREF_MASK,
+};
+use esp_idf_sys::{
+    esp_ble_gap_phy_t, ESP_BLE_GAP_PHY_1M, ESP_BLE_GAP_PHY_2M, ESP_BLE_GAP_PHY_CODED,
+};
+
+/// A physical layer of Bluetooth LE 5.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Phy {
+    /// The 1 Mbit/s physical layer, supported by every device.
+    #[default]
+    Le1M,
+    /// The 2 Mbit/s physical layer, for a higher throughput.
+    Le2M,
+    /// The coded physical layer, for a longer range at a lower throughput.
+    LeCoded,
+}
+
+impl Phy {
+    /// Returns the preference mask of the physical layer, as expected by the PHY update requests.
+    #[cfg(feature = "server")]
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) const fn mask(self) -> esp_ble_gap_phy_mask_t {
+        (match self {
+            Self::Le1M => ESP_BLE_GAP_PHY_1M_PREF_MASK,
+            Self::Le2M => ESP_BLE_GAP_PHY_2M_PREF_MASK,
+            Self::LeCoded => ESP_BLE_GAP_PHY_CODED_PREF_MASK,
+        }) as esp_ble_gap_phy_mask_t
+    }
+
+    /// Returns the physical layer reported by the Bluetooth stack, or `None` if it is unknown.
+    #[cfg(feature = "server")]
+    pub(crate) fn from_raw(phy: esp_ble_gap_phy_t) -> Option<Self> {
+        match u32::from(phy) {
+            ESP_BLE_GAP_PHY_1M => Some(Self::Le1M),
+            ESP_BLE_GAP_PHY_2M => Some(Self::Le2M),
+            ESP_BLE_GAP_PHY_CODED => Some(Self::LeCoded),
+            _ => None,
+        }
+    }
+}
+
+impl From<Phy> for esp_ble_gap_phy_t {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from(phy: Phy) -> Self {
+        (match phy {
+            Phy::Le1M => ESP_BLE_GAP_PHY_1M,
+            Phy::Le2M => ESP_BLE_GAP_PHY_2M,
+            Phy::LeCoded => ESP_BLE_GAP_PHY_CODED,
+        }) as Self
+    }
+}
@@ -0,0 +1,89 @@
+/// The value of a Characteristic Presentation Format descriptor,
+/// describing how a client should display the value of a characteristic.
+///
+/// The value is shown as `value × 10^exponent`, in the given unit.
+/// Formats and units are the assigned numbers of the Bluetooth SIG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PresentationFormat {
+    format: u8,
+    exponent: i8,
+    unit: u16,
+    description: u16,
+}
+
+impl PresentationFormat {
+    /// A boolean.
+    pub const BOOLEAN: u8 = 0x01;
+    /// An unsigned 8-bit integer.
+    pub const UINT8: u8 = 0x04;
+    /// An unsigned 16-bit integer.
+    pub const UINT16: u8 = 0x06;
+    /// An unsigned 32-bit integer.
+    pub const UINT32: u8 = 0x08;
+    /// A signed 8-bit integer.
+    pub const SINT8: u8 = 0x0C;
+    /// A signed 16-bit integer.
+    pub const SINT16: u8 = 0x0E;
+    /// A signed 32-bit integer.
+    pub const SINT32: u8 = 0x10;
+    /// An IEEE-754 32-bit floating point number.
+    pub const FLOAT32: u8 = 0x14;
+    /// A UTF-8 string.
+    pub const UTF8: u8 = 0x19;
+
+    /// The unitless unit.
+    pub const UNITLESS: u16 = 0x2700;
+
+    /// Creates a new unitless [`PresentationFormat`] with the given format and no exponent.
+    #[must_use]
+    pub const fn new(format: u8) -> Self {
+        Self {
+            format,
+            exponent: 0,
+            unit: Self::UNITLESS,
+            description: 0,
+        }
+    }
+
+    /// Sets the base 10 exponent applied to the value.
+    #[must_use]
+    pub const fn exponent(mut self, exponent: i8) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    /// Sets the unit of the value, such as `0x272F` for degrees Celsius.
+    #[must_use]
+    pub const fn unit(mut self, unit: u16) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets the description of the value, from the Bluetooth SIG namespace.
+    ///
+    /// This tells several characteristics with the same UUID apart, such as "first" or "inside".
+    #[must_use]
+    pub const fn description(mut self, description: u16) -> Self {
+        self.description = description;
+        self
+    }
+
+    /// Returns the value of the descriptor, as sent to clients.
+    #[must_use]
+    pub const fn to_bytes(&self) -> [u8; 7] {
+        let unit = self.unit.to_le_bytes();
+        let description = self.description.to_le_bytes();
+
+        // The namespace is always the Bluetooth SIG one.
+        #[allow(clippy::cast_sign_loss)]
+        [
+            self.format,
+            self.exponent as u8,
+            unit[0],
+            unit[1],
+            0x01,
+            description[0],
+            description[1],
+        ]
+    }
+}
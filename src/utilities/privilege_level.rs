@@ -0,0 +1,13 @@
+/// The privilege level of a connection, checked against the level required by a characteristic.
+///
+/// Levels are ordered: a connection can access the characteristics requiring its level or a lower one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum PrivilegeLevel {
+    /// The level of every connection, until the application grants it another one.
+    #[default]
+    Anonymous,
+    /// A client allowed to use the device, but not to administer it.
+    Guest,
+    /// The owner of the device.
+    Owner,
+}
@@ -0,0 +1,297 @@
+//! Bluetooth SIG assigned numbers.
+//!
+//! These lists were copied from the Bluetooth SIG assigned numbers document, and only contain
+//! the numbers that are relevant to a GATT server running on an ESP32.
+//! The names of the identifiers are the ones displayed by [`BleUuid::name`], so that user code
+//! and the logs agree on what an attribute is.
+
+use crate::utilities::BleUuid;
+
+/// Declares a module of assigned 16-bit identifiers, along with the table of their names.
+macro_rules! assigned_numbers {
+    ($(#[$meta:meta])* $module:ident: $type:ty = $constructor:path {
+        $($constant:ident = $value:literal => $name:literal,)*
+    }) => {
+        $(#[$meta])*
+        pub mod $module {
+            #[allow(unused_imports)]
+            use crate::utilities::BleUuid;
+
+            $(
+                #[doc = $name]
+                #[allow(clippy::doc_markdown)]
+                pub const $constant: $type = $constructor($value);
+            )*
+
+            /// The names of the assigned numbers of this module.
+            pub(crate) const NAMES: &[(u16, &str)] = &[$(($value, $name)),*];
+
+            /// Returns the name of an assigned number of this module.
+            #[must_use]
+            pub fn name(value: u16) -> Option<&'static str> {
+                NAMES
+                    .iter()
+                    .find(|(assigned, _)| *assigned == value)
+                    .map(|(_, name)| *name)
+            }
+        }
+    };
+}
+
+/// Returns its argument, to declare plain numbers with [`assigned_numbers`].
+const fn identity(value: u16) -> u16 {
+    value
+}
+
+assigned_numbers! {
+    /// The identifiers of the standard GATT services.
+    services: BleUuid = BleUuid::from_uuid16 {
+        GENERIC_ACCESS = 0x1800 => "Generic Access",
+        GENERIC_ATTRIBUTE = 0x1801 => "Generic Attribute",
+        IMMEDIATE_ALERT = 0x1802 => "Immediate Alert",
+        LINK_LOSS = 0x1803 => "Link Loss",
+        TX_POWER = 0x1804 => "Tx Power",
+        CURRENT_TIME = 0x1805 => "Current Time",
+        REFERENCE_TIME_UPDATE = 0x1806 => "Reference Time Update",
+        NEXT_DST_CHANGE = 0x1807 => "Next DST Change",
+        GLUCOSE = 0x1808 => "Glucose",
+        HEALTH_THERMOMETER = 0x1809 => "Health Thermometer",
+        DEVICE_INFORMATION = 0x180A => "Device Information",
+        HEART_RATE = 0x180D => "Heart Rate",
+        PHONE_ALERT_STATUS = 0x180E => "Phone Alert Status",
+        BATTERY = 0x180F => "Battery",
+        BLOOD_PRESSURE = 0x1810 => "Blood Pressure",
+        ALERT_NOTIFICATION = 0x1811 => "Alert Notification",
+        HUMAN_INTERFACE_DEVICE = 0x1812 => "Human Interface Device",
+        SCAN_PARAMETERS = 0x1813 => "Scan Parameters",
+        RUNNING_SPEED_AND_CADENCE = 0x1814 => "Running Speed and Cadence",
+        AUTOMATION_IO = 0x1815 => "Automation IO",
+        CYCLING_SPEED_AND_CADENCE = 0x1816 => "Cycling Speed and Cadence",
+        CYCLING_POWER = 0x1818 => "Cycling Power",
+        LOCATION_AND_NAVIGATION = 0x1819 => "Location and Navigation",
+        ENVIRONMENTAL_SENSING = 0x181A => "Environmental Sensing",
+        BODY_COMPOSITION = 0x181B => "Body Composition",
+        USER_DATA = 0x181C => "User Data",
+        WEIGHT_SCALE = 0x181D => "Weight Scale",
+        BOND_MANAGEMENT = 0x181E => "Bond Management",
+        CONTINUOUS_GLUCOSE_MONITORING = 0x181F => "Continuous Glucose Monitoring",
+        INTERNET_PROTOCOL_SUPPORT = 0x1820 => "Internet Protocol Support",
+        INDOOR_POSITIONING = 0x1821 => "Indoor Positioning",
+        PULSE_OXIMETER = 0x1822 => "Pulse Oximeter",
+        HTTP_PROXY = 0x1823 => "HTTP Proxy",
+        TRANSPORT_DISCOVERY = 0x1824 => "Transport Discovery",
+        OBJECT_TRANSFER = 0x1825 => "Object Transfer",
+        FITNESS_MACHINE = 0x1826 => "Fitness Machine",
+        MESH_PROVISIONING = 0x1827 => "Mesh Provisioning",
+        MESH_PROXY = 0x1828 => "Mesh Proxy",
+        RECONNECTION_CONFIGURATION = 0x1829 => "Reconnection Configuration",
+        INSULIN_DELIVERY = 0x183A => "Insulin Delivery",
+        BINARY_SENSOR = 0x183B => "Binary Sensor",
+        EMERGENCY_CONFIGURATION = 0x183C => "Emergency Configuration",
+        PHYSICAL_ACTIVITY_MONITOR = 0x183E => "Physical Activity Monitor",
+        AUDIO_INPUT_CONTROL = 0x1843 => "Audio Input Control",
+        VOLUME_CONTROL = 0x1844 => "Volume Control",
+        DEVICE_TIME = 0x1847 => "Device Time",
+        CONSTANT_TONE_EXTENSION = 0x184A => "Constant Tone Extension",
+        ELECTRONIC_SHELF_LABEL = 0x1857 => "Electronic Shelf Label",
+    }
+}
+
+assigned_numbers! {
+    /// The identifiers of the standard GATT characteristics.
+    characteristics: BleUuid = BleUuid::from_uuid16 {
+        DEVICE_NAME = 0x2A00 => "Device Name",
+        APPEARANCE = 0x2A01 => "Appearance",
+        PERIPHERAL_PRIVACY_FLAG = 0x2A02 => "Peripheral Privacy Flag",
+        RECONNECTION_ADDRESS = 0x2A03 => "Reconnection Address",
+        PERIPHERAL_PREFERRED_CONNECTION_PARAMETERS = 0x2A04 => "Peripheral Preferred Connection Parameters",
+        SERVICE_CHANGED = 0x2A05 => "Service Changed",
+        ALERT_LEVEL = 0x2A06 => "Alert Level",
+        TX_POWER_LEVEL = 0x2A07 => "Tx Power Level",
+        DATE_TIME = 0x2A08 => "Date Time",
+        DAY_OF_WEEK = 0x2A09 => "Day of Week",
+        DAY_DATE_TIME = 0x2A0A => "Day Date Time",
+        EXACT_TIME_256 = 0x2A0C => "Exact Time 256",
+        DST_OFFSET = 0x2A0D => "DST Offset",
+        TIME_ZONE = 0x2A0E => "Time Zone",
+        LOCAL_TIME_INFORMATION = 0x2A0F => "Local Time Information",
+        TIME_WITH_DST = 0x2A11 => "Time with DST",
+        TIME_ACCURACY = 0x2A12 => "Time Accuracy",
+        TIME_SOURCE = 0x2A13 => "Time Source",
+        REFERENCE_TIME_INFORMATION = 0x2A14 => "Reference Time Information",
+        TIME_UPDATE_CONTROL_POINT = 0x2A16 => "Time Update Control Point",
+        TIME_UPDATE_STATE = 0x2A17 => "Time Update State",
+        GLUCOSE_MEASUREMENT = 0x2A18 => "Glucose Measurement",
+        BATTERY_LEVEL = 0x2A19 => "Battery Level",
+        TEMPERATURE_MEASUREMENT = 0x2A1C => "Temperature Measurement",
+        TEMPERATURE_TYPE = 0x2A1D => "Temperature Type",
+        INTERMEDIATE_TEMPERATURE = 0x2A1E => "Intermediate Temperature",
+        MEASUREMENT_INTERVAL = 0x2A21 => "Measurement Interval",
+        BOOT_KEYBOARD_INPUT_REPORT = 0x2A22 => "Boot Keyboard Input Report",
+        SYSTEM_ID = 0x2A23 => "System ID",
+        MODEL_NUMBER_STRING = 0x2A24 => "Model Number String",
+        SERIAL_NUMBER_STRING = 0x2A25 => "Serial Number String",
+        FIRMWARE_REVISION_STRING = 0x2A26 => "Firmware Revision String",
+        HARDWARE_REVISION_STRING = 0x2A27 => "Hardware Revision String",
+        SOFTWARE_REVISION_STRING = 0x2A28 => "Software Revision String",
+        MANUFACTURER_NAME_STRING = 0x2A29 => "Manufacturer Name String",
+        IEEE_11073_20601_REGULATORY_CERTIFICATION_DATA_LIST = 0x2A2A => "IEEE 11073-20601 Regulatory Certification Data List",
+        CURRENT_TIME = 0x2A2B => "Current Time",
+        SCAN_REFRESH = 0x2A31 => "Scan Refresh",
+        BOOT_KEYBOARD_OUTPUT_REPORT = 0x2A32 => "Boot Keyboard Output Report",
+        BOOT_MOUSE_INPUT_REPORT = 0x2A33 => "Boot Mouse Input Report",
+        GLUCOSE_MEASUREMENT_CONTEXT = 0x2A34 => "Glucose Measurement Context",
+        BLOOD_PRESSURE_MEASUREMENT = 0x2A35 => "Blood Pressure Measurement",
+        INTERMEDIATE_CUFF_PRESSURE = 0x2A36 => "Intermediate Cuff Pressure",
+        HEART_RATE_MEASUREMENT = 0x2A37 => "Heart Rate Measurement",
+        BODY_SENSOR_LOCATION = 0x2A38 => "Body Sensor Location",
+        HEART_RATE_CONTROL_POINT = 0x2A39 => "Heart Rate Control Point",
+        ALERT_STATUS = 0x2A3F => "Alert Status",
+        RINGER_CONTROL_POINT = 0x2A40 => "Ringer Control Point",
+        RINGER_SETTING = 0x2A41 => "Ringer Setting",
+        ALERT_CATEGORY_ID_BIT_MASK = 0x2A42 => "Alert Category ID Bit Mask",
+        ALERT_CATEGORY_ID = 0x2A43 => "Alert Category ID",
+        ALERT_NOTIFICATION_CONTROL_POINT = 0x2A44 => "Alert Notification Control Point",
+        UNREAD_ALERT_STATUS = 0x2A45 => "Unread Alert Status",
+        NEW_ALERT = 0x2A46 => "New Alert",
+        SUPPORTED_NEW_ALERT_CATEGORY = 0x2A47 => "Supported New Alert Category",
+        SUPPORTED_UNREAD_ALERT_CATEGORY = 0x2A48 => "Supported Unread Alert Category",
+        BLOOD_PRESSURE_FEATURE = 0x2A49 => "Blood Pressure Feature",
+        HID_INFORMATION = 0x2A4A => "HID Information",
+        REPORT_MAP = 0x2A4B => "Report Map",
+        HID_CONTROL_POINT = 0x2A4C => "HID Control Point",
+        REPORT = 0x2A4D => "Report",
+        PROTOCOL_MODE = 0x2A4E => "Protocol Mode",
+        SCAN_INTERVAL_WINDOW = 0x2A4F => "Scan Interval Window",
+        PNP_ID = 0x2A50 => "PnP ID",
+        GLUCOSE_FEATURE = 0x2A51 => "Glucose Feature",
+        RECORD_ACCESS_CONTROL_POINT = 0x2A52 => "Record Access Control Point",
+        RSC_MEASUREMENT = 0x2A53 => "RSC Measurement",
+        RSC_FEATURE = 0x2A54 => "RSC Feature",
+        SC_CONTROL_POINT = 0x2A55 => "SC Control Point",
+        CSC_MEASUREMENT = 0x2A5B => "CSC Measurement",
+        CSC_FEATURE = 0x2A5C => "CSC Feature",
+        SENSOR_LOCATION = 0x2A5D => "Sensor Location",
+        PLX_SPOT_CHECK_MEASUREMENT = 0x2A5E => "PLX Spot-Check Measurement",
+        PLX_CONTINUOUS_MEASUREMENT = 0x2A5F => "PLX Continuous Measurement",
+        PLX_FEATURES = 0x2A60 => "PLX Features",
+        CYCLING_POWER_MEASUREMENT = 0x2A63 => "Cycling Power Measurement",
+        CYCLING_POWER_VECTOR = 0x2A64 => "Cycling Power Vector",
+        CYCLING_POWER_FEATURE = 0x2A65 => "Cycling Power Feature",
+        CYCLING_POWER_CONTROL_POINT = 0x2A66 => "Cycling Power Control Point",
+        LOCATION_AND_SPEED = 0x2A67 => "Location and Speed",
+        NAVIGATION = 0x2A68 => "Navigation",
+        POSITION_QUALITY = 0x2A69 => "Position Quality",
+        LN_FEATURE = 0x2A6A => "LN Feature",
+        LN_CONTROL_POINT = 0x2A6B => "LN Control Point",
+        ELEVATION = 0x2A6C => "Elevation",
+        PRESSURE = 0x2A6D => "Pressure",
+        TEMPERATURE = 0x2A6E => "Temperature",
+        HUMIDITY = 0x2A6F => "Humidity",
+        TRUE_WIND_SPEED = 0x2A70 => "True Wind Speed",
+        TRUE_WIND_DIRECTION = 0x2A71 => "True Wind Direction",
+        UV_INDEX = 0x2A76 => "UV Index",
+        IRRADIANCE = 0x2A77 => "Irradiance",
+        RAINFALL = 0x2A78 => "Rainfall",
+        DEW_POINT = 0x2A7B => "Dew Point",
+        WEIGHT = 0x2A98 => "Weight",
+        WEIGHT_MEASUREMENT = 0x2A9D => "Weight Measurement",
+        WEIGHT_SCALE_FEATURE = 0x2A9E => "Weight Scale Feature",
+        CENTRAL_ADDRESS_RESOLUTION = 0x2AA6 => "Central Address Resolution",
+        CGM_MEASUREMENT = 0x2AA7 => "CGM Measurement",
+        RESOLVABLE_PRIVATE_ADDRESS_ONLY = 0x2AC9 => "Resolvable Private Address Only",
+        FITNESS_MACHINE_FEATURE = 0x2ACC => "Fitness Machine Feature",
+        TREADMILL_DATA = 0x2ACD => "Treadmill Data",
+        INDOOR_BIKE_DATA = 0x2AD2 => "Indoor Bike Data",
+        FITNESS_MACHINE_CONTROL_POINT = 0x2AD9 => "Fitness Machine Control Point",
+        FITNESS_MACHINE_STATUS = 0x2ADA => "Fitness Machine Status",
+        CLIENT_SUPPORTED_FEATURES = 0x2B29 => "Client Supported Features",
+        DATABASE_HASH = 0x2B2A => "Database Hash",
+        SERVER_SUPPORTED_FEATURES = 0x2B3A => "Server Supported Features",
+    }
+}
+
+assigned_numbers! {
+    /// The identifiers of the standard GATT descriptors.
+    descriptors: BleUuid = BleUuid::from_uuid16 {
+        CHARACTERISTIC_EXTENDED_PROPERTIES = 0x2900 => "Characteristic Extended Properties",
+        CHARACTERISTIC_USER_DESCRIPTION = 0x2901 => "Characteristic User Description",
+        CLIENT_CHARACTERISTIC_CONFIGURATION = 0x2902 => "Client Characteristic Configuration",
+        SERVER_CHARACTERISTIC_CONFIGURATION = 0x2903 => "Server Characteristic Configuration",
+        CHARACTERISTIC_PRESENTATION_FORMAT = 0x2904 => "Characteristic Presentation Format",
+        CHARACTERISTIC_AGGREGATE_FORMAT = 0x2905 => "Characteristic Aggregate Format",
+        VALID_RANGE = 0x2906 => "Valid Range",
+        EXTERNAL_REPORT_REFERENCE = 0x2907 => "External Report Reference",
+        REPORT_REFERENCE = 0x2908 => "Report Reference",
+        NUMBER_OF_DIGITALS = 0x2909 => "Number of Digitals",
+        VALUE_TRIGGER_SETTING = 0x290A => "Value Trigger Setting",
+        ENVIRONMENTAL_SENSING_CONFIGURATION = 0x290B => "Environmental Sensing Configuration",
+        ENVIRONMENTAL_SENSING_MEASUREMENT = 0x290C => "Environmental Sensing Measurement",
+        ENVIRONMENTAL_SENSING_TRIGGER_SETTING = 0x290D => "Environmental Sensing Trigger Setting",
+        TIME_TRIGGER_SETTING = 0x290E => "Time Trigger Setting",
+        COMPLETE_BR_EDR_TRANSPORT_BLOCK_DATA = 0x290F => "Complete BR-EDR Transport Block Data",
+    }
+}
+
+assigned_numbers! {
+    /// The units of the Characteristic Presentation Format descriptor.
+    units: u16 = super::identity {
+        UNITLESS = 0x2700 => "unitless",
+        METRE = 0x2701 => "metre",
+        KILOGRAM = 0x2702 => "kilogram",
+        SECOND = 0x2703 => "second",
+        AMPERE = 0x2704 => "ampere",
+        KELVIN = 0x2705 => "kelvin",
+        MOLE = 0x2706 => "mole",
+        CANDELA = 0x2707 => "candela",
+        SQUARE_METRES = 0x2710 => "square metres",
+        CUBIC_METRES = 0x2711 => "cubic metres",
+        METRES_PER_SECOND = 0x2712 => "metres per second",
+        METRES_PER_SECOND_SQUARED = 0x2713 => "metres per second squared",
+        HERTZ = 0x2722 => "hertz",
+        NEWTON = 0x2723 => "newton",
+        PASCAL = 0x2724 => "pascal",
+        JOULE = 0x2725 => "joule",
+        WATT = 0x2726 => "watt",
+        COULOMB = 0x2727 => "coulomb",
+        VOLT = 0x2728 => "volt",
+        FARAD = 0x2729 => "farad",
+        OHM = 0x272A => "ohm",
+        SIEMENS = 0x272B => "siemens",
+        WEBER = 0x272C => "weber",
+        TESLA = 0x272D => "tesla",
+        HENRY = 0x272E => "henry",
+        CELSIUS = 0x272F => "degree Celsius",
+        LUMEN = 0x2730 => "lumen",
+        LUX = 0x2731 => "lux",
+        DEGREE = 0x2763 => "degree",
+        MINUTE = 0x2760 => "minute",
+        HOUR = 0x2761 => "hour",
+        DAY = 0x2762 => "day",
+        LITRE = 0x2767 => "litre",
+        FAHRENHEIT = 0x27AC => "degree Fahrenheit",
+        PERCENTAGE = 0x27AD => "percentage",
+        BEATS_PER_MINUTE = 0x27AF => "beats per minute",
+        DECIBEL = 0x27C3 => "decibel",
+        KILOWATT_HOUR = 0x27AB => "kilowatt hour",
+        MILLIMETRES_OF_MERCURY = 0x2781 => "millimetres of mercury",
+        KILOMETRES_PER_HOUR = 0x27A6 => "kilometres per hour",
+        MILLIMOLE_PER_LITRE = 0x27B2 => "millimole per litre",
+        MILLIGRAM_PER_DECILITRE = 0x27B1 => "milligram per decilitre",
+        PARTS_PER_MILLION = 0x27C4 => "parts per million",
+        AMPERE_HOURS = 0x27B0 => "ampere hours",
+    }
+}
+
+impl BleUuid {
+    /// Returns the assigned name of this identifier, if it is a standard service,
+    /// characteristic or descriptor identifier.
+    #[must_use]
+    pub fn name(&self) -> Option<&'static str> {
+        let uuid = self.as_uuid16()?;
+
+        services::name(uuid)
+            .or_else(|| characteristics::name(uuid))
+            .or_else(|| descriptors::name(uuid))
+    }
+}